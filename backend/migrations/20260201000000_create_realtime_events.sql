@@ -0,0 +1,18 @@
+-- Per-workspace counter handing out monotonically increasing event sequence numbers
+CREATE TABLE IF NOT EXISTS workspace_event_sequences (
+    workspace_id UUID PRIMARY KEY REFERENCES workspaces(id) ON DELETE CASCADE,
+    last_seq BIGINT NOT NULL DEFAULT 0
+);
+
+-- Every broadcast event, kept so reconnecting clients can replay what they missed
+CREATE TABLE IF NOT EXISTS realtime_events (
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    seq BIGINT NOT NULL,
+    event_type TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    topics TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, seq)
+);
+
+CREATE INDEX idx_realtime_events_created_at ON realtime_events(created_at);
@@ -0,0 +1,39 @@
+CREATE TYPE outbox_channel AS ENUM ('WebSocket', 'Webhook', 'Email');
+
+-- Side effects written in the same transaction as the domain change that caused them
+CREATE TABLE IF NOT EXISTS outbox (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    channel outbox_channel NOT NULL,
+    event_type TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    -- Webhook URL or email recipient, depending on the channel
+    target TEXT,
+    dedupe_key TEXT NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    delivered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (channel, dedupe_key)
+);
+
+CREATE INDEX idx_outbox_pending ON outbox(next_attempt_at) WHERE delivered_at IS NULL;
+
+CREATE TABLE IF NOT EXISTS webhook_endpoints (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_webhook_endpoints_updated_at
+BEFORE UPDATE ON webhook_endpoints
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+-- Lets a redelivered outbox row reuse the sequence number of its first delivery
+ALTER TABLE realtime_events ADD COLUMN dedupe_key TEXT;
+CREATE UNIQUE INDEX idx_realtime_events_dedupe_key ON realtime_events(workspace_id, dedupe_key);
@@ -0,0 +1,2 @@
+-- Emails such as magic links are sent before the user is in any workspace
+ALTER TABLE outbox ALTER COLUMN workspace_id DROP NOT NULL;
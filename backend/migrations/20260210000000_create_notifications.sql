@@ -0,0 +1,25 @@
+CREATE TYPE notification_type AS ENUM (
+    'SubstitutionOffer',
+    'SubstitutionUpdate',
+    'TimetablePublished',
+    'ConflictAssigned',
+    'AvailabilityReminder'
+);
+
+CREATE TABLE IF NOT EXISTS notifications (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    notification_type notification_type NOT NULL,
+    title TEXT NOT NULL,
+    body TEXT,
+    -- Entity the notification is about, e.g. a substitution
+    entity_id UUID,
+    link TEXT,
+    read_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_notifications_user ON notifications(workspace_id, user_id, created_at DESC);
+CREATE INDEX idx_notifications_unread ON notifications(workspace_id, user_id) WHERE read_at IS NULL;
+CREATE INDEX idx_notifications_created_at ON notifications(created_at);
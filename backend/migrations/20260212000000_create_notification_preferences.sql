@@ -0,0 +1,46 @@
+CREATE TYPE notification_delivery AS ENUM ('Immediate', 'Digest', 'Off');
+
+-- Users without a row for a type get immediate in-app and email delivery
+CREATE TABLE IF NOT EXISTS notification_preferences (
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    notification_type notification_type NOT NULL,
+    delivery notification_delivery NOT NULL DEFAULT 'Immediate',
+    in_app BOOLEAN NOT NULL DEFAULT true,
+    email BOOLEAN NOT NULL DEFAULT true,
+    webhook BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, user_id, notification_type)
+);
+
+CREATE TRIGGER update_notification_preferences_updated_at
+BEFORE UPDATE ON notification_preferences
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+CREATE TABLE IF NOT EXISTS notification_settings (
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- IANA time zone the digest time is expressed in
+    timezone TEXT NOT NULL,
+    digest_time TIME NOT NULL,
+    webhook_url TEXT,
+    last_digest_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, user_id)
+);
+
+CREATE TRIGGER update_notification_settings_updated_at
+BEFORE UPDATE ON notification_settings
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+-- Hidden from the inbox when the user turned the in-app channel off
+ALTER TABLE notifications ADD COLUMN in_app BOOLEAN NOT NULL DEFAULT true;
+-- Waiting for the next digest on these channels
+ALTER TABLE notifications ADD COLUMN digest_email BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE notifications ADD COLUMN digest_webhook BOOLEAN NOT NULL DEFAULT false;
+
+CREATE INDEX idx_notifications_digest ON notifications(workspace_id, user_id) WHERE digest_email OR digest_webhook;
@@ -0,0 +1,6 @@
+-- The timetable that was in force when this one was published; changes are
+-- reported against it
+ALTER TABLE published_timetables
+    ADD COLUMN previous_published_timetable_id UUID REFERENCES published_timetables(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_published_timetables_workspace ON published_timetables (workspace_id, published_at DESC);
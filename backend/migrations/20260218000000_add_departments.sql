@@ -0,0 +1,3 @@
+-- Departments, used to prefer colleagues from the same department as substitutes
+ALTER TABLE courses ADD COLUMN department TEXT;
+ALTER TABLE workspace_members ADD COLUMN department TEXT;
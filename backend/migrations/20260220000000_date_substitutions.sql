@@ -0,0 +1,24 @@
+-- A substitution covers a lesson on specific dates only; the weekly
+-- timetable entry itself never changes
+ALTER TABLE substitutions
+    ADD COLUMN starts_on DATE,
+    ADD COLUMN ends_on DATE;
+
+UPDATE substitutions SET starts_on = created_at::date, ends_on = created_at::date;
+
+ALTER TABLE substitutions
+    ALTER COLUMN starts_on SET NOT NULL,
+    ALTER COLUMN ends_on SET NOT NULL,
+    ADD CONSTRAINT substitutions_dates_check CHECK (ends_on >= starts_on);
+
+CREATE INDEX idx_substitutions_dates ON substitutions (timetable_entry_id, starts_on, ends_on);
+
+-- Secret links to a user's personal iCal feed
+CREATE TABLE calendar_feeds (
+    token TEXT PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_calendar_feeds_user ON calendar_feeds (workspace_id, user_id);
@@ -0,0 +1,29 @@
+-- Substitutions retracted before anyone covered them, e.g. because the
+-- absence they were requested for was cancelled
+ALTER TYPE substitution_status ADD VALUE IF NOT EXISTS 'Cancelled';
+
+CREATE TABLE IF NOT EXISTS absences (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    teacher_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL,
+    reason TEXT,
+    reported_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    cancelled_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT absences_dates_check CHECK (ends_on >= starts_on)
+);
+
+CREATE INDEX idx_absences_teacher ON absences (workspace_id, teacher_id, starts_on);
+
+CREATE TRIGGER update_absences_updated_at
+BEFORE UPDATE ON absences
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+-- The absence a substitution was requested for, if any
+ALTER TABLE substitutions ADD COLUMN absence_id UUID REFERENCES absences(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_substitutions_absence ON substitutions (absence_id) WHERE absence_id IS NOT NULL;
@@ -0,0 +1,23 @@
+-- Waiting: queued in a cascade, not sent yet. Pending: sent and open until
+-- expires_at. The rest are final.
+CREATE TYPE substitution_offer_status AS ENUM ('Waiting', 'Pending', 'Accepted', 'Declined', 'Expired', 'Withdrawn');
+
+-- Offers of a substitution to specific teachers, either all at once or one
+-- after the other in order of position
+CREATE TABLE IF NOT EXISTS substitution_offers (
+    id UUID PRIMARY KEY,
+    substitution_id UUID NOT NULL REFERENCES substitutions(id) ON DELETE CASCADE,
+    teacher_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    position INT NOT NULL,
+    status substitution_offer_status NOT NULL DEFAULT 'Waiting',
+    timeout_minutes INT NOT NULL CHECK (timeout_minutes > 0),
+    sent_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ,
+    responded_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_substitution_offers_substitution ON substitution_offers (substitution_id, position);
+CREATE INDEX idx_substitution_offers_expiry ON substitution_offers (expires_at) WHERE status = 'Pending';
+CREATE UNIQUE INDEX idx_substitution_offers_open ON substitution_offers (substitution_id, teacher_id)
+    WHERE status IN ('Waiting', 'Pending');
@@ -0,0 +1,30 @@
+CREATE TYPE lesson_exception_kind AS ENUM ('Cancellation', 'RoomChange', 'ExtraLesson');
+
+-- One-off changes on a single date, on top of the timetable published for
+-- that date. Course, teacher and times are copied from the lesson so the
+-- change reads on its own; room_id is the new room of a room change or the
+-- room of an extra lesson.
+CREATE TABLE IF NOT EXISTS lesson_exceptions (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    published_timetable_id UUID NOT NULL REFERENCES published_timetables(id) ON DELETE CASCADE,
+    date DATE NOT NULL,
+    kind lesson_exception_kind NOT NULL,
+    timetable_entry_id UUID REFERENCES timetable_entries(id) ON DELETE CASCADE,
+    course_id UUID NOT NULL REFERENCES courses(id) ON DELETE CASCADE,
+    teacher_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    room_id UUID REFERENCES rooms(id) ON DELETE CASCADE,
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    reason TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT lesson_exceptions_entry_check CHECK ((kind = 'ExtraLesson') = (timetable_entry_id IS NULL)),
+    CONSTRAINT lesson_exceptions_room_check CHECK ((kind = 'Cancellation') = (room_id IS NULL)),
+    CONSTRAINT lesson_exceptions_times_check CHECK (end_time > start_time)
+);
+
+CREATE INDEX idx_lesson_exceptions_date ON lesson_exceptions (workspace_id, date);
+-- A lesson is either cancelled or moved on a given date, not both
+CREATE UNIQUE INDEX idx_lesson_exceptions_entry ON lesson_exceptions (timetable_entry_id, date)
+    WHERE timetable_entry_id IS NOT NULL;
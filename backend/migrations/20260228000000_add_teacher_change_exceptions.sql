@@ -0,0 +1,7 @@
+-- A lesson taught by another teacher on a single date, e.g. after two
+-- teachers swapped lessons. teacher_id is the teacher for that date; the
+-- room stays as published.
+ALTER TYPE lesson_exception_kind ADD VALUE IF NOT EXISTS 'TeacherChange';
+
+-- Lesson swaps proposed, answered or decided
+ALTER TYPE notification_type ADD VALUE IF NOT EXISTS 'LessonSwap';
@@ -0,0 +1,40 @@
+ALTER TABLE lesson_exceptions DROP CONSTRAINT lesson_exceptions_room_check;
+ALTER TABLE lesson_exceptions ADD CONSTRAINT lesson_exceptions_room_check
+    CHECK ((kind IN ('Cancellation', 'TeacherChange')) = (room_id IS NULL));
+
+CREATE TYPE lesson_swap_status AS ENUM ('Proposed', 'Accepted', 'Approved', 'Declined', 'Rejected', 'Cancelled');
+
+-- A teacher's proposal to swap one of their lessons on a date with a
+-- colleague's lesson on a date. Once the colleague accepts and a
+-- coordinator approves, each lesson gets a TeacherChange exception, kept
+-- here so the swap can be undone.
+CREATE TABLE IF NOT EXISTS lesson_swaps (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    requester_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    requester_entry_id UUID NOT NULL REFERENCES timetable_entries(id) ON DELETE CASCADE,
+    requester_date DATE NOT NULL,
+    colleague_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    colleague_entry_id UUID NOT NULL REFERENCES timetable_entries(id) ON DELETE CASCADE,
+    colleague_date DATE NOT NULL,
+    status lesson_swap_status NOT NULL DEFAULT 'Proposed',
+    reason TEXT,
+    decided_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    decision_note TEXT,
+    requester_exception_id UUID REFERENCES lesson_exceptions(id) ON DELETE SET NULL,
+    colleague_exception_id UUID REFERENCES lesson_exceptions(id) ON DELETE SET NULL,
+    responded_at TIMESTAMPTZ,
+    decided_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT lesson_swaps_teachers_check CHECK (requester_id <> colleague_id)
+);
+
+CREATE INDEX idx_lesson_swaps_status ON lesson_swaps (workspace_id, status);
+CREATE INDEX idx_lesson_swaps_requester ON lesson_swaps (requester_id);
+CREATE INDEX idx_lesson_swaps_colleague ON lesson_swaps (colleague_id);
+
+CREATE TRIGGER update_lesson_swaps_updated_at
+BEFORE UPDATE ON lesson_swaps
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
@@ -0,0 +1,4 @@
+-- Weekly contract hours of a member, used to put substitution cover in
+-- proportion to how much they are employed
+ALTER TABLE workspace_members ADD COLUMN contract_hours DOUBLE PRECISION
+    CONSTRAINT workspace_members_contract_hours_check CHECK (contract_hours > 0 AND contract_hours <= 80);
@@ -0,0 +1,41 @@
+-- Windows that could never be matched
+DELETE FROM availability WHERE day_of_week NOT BETWEEN 0 AND 6 OR start_time >= end_time;
+
+ALTER TABLE availability
+    ADD CONSTRAINT availability_day_of_week_check CHECK (day_of_week BETWEEN 0 AND 6),
+    ADD CONSTRAINT availability_times_check CHECK (start_time < end_time);
+
+-- Merge repeated submissions: windows of a teacher on the same day with the
+-- same preference that overlap or touch become one, kept on the earliest row.
+WITH ordered AS (
+    SELECT id, workspace_id, teacher_id, day_of_week, is_preferred, start_time, end_time, created_at,
+        MAX(end_time) OVER (
+            PARTITION BY workspace_id, teacher_id, day_of_week, is_preferred
+            ORDER BY start_time, end_time
+            ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING
+        ) AS previous_end
+    FROM availability
+),
+islands AS (
+    SELECT *, SUM(CASE WHEN previous_end >= start_time THEN 0 ELSE 1 END) OVER (
+            PARTITION BY workspace_id, teacher_id, day_of_week, is_preferred
+            ORDER BY start_time, end_time
+        ) AS island
+    FROM ordered
+),
+merged AS (
+    SELECT (ARRAY_AGG(id ORDER BY created_at, id))[1] AS keep_id,
+        ARRAY_AGG(id) AS ids, MIN(start_time) AS start_time, MAX(end_time) AS end_time
+    FROM islands
+    GROUP BY workspace_id, teacher_id, day_of_week, is_preferred, island
+    HAVING COUNT(*) > 1
+),
+removed AS (
+    DELETE FROM availability a
+    USING merged m
+    WHERE a.id = ANY(m.ids) AND a.id <> m.keep_id
+)
+UPDATE availability a
+SET start_time = m.start_time, end_time = m.end_time
+FROM merged m
+WHERE a.id = m.keep_id;
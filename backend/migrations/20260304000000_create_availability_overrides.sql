@@ -0,0 +1,67 @@
+CREATE TYPE availability_override_kind AS ENUM ('Unavailable', 'Available');
+
+-- Dated changes on top of a teacher's weekly availability: away, or
+-- available outside the pattern, from starts_on through ends_on. A
+-- day_of_week limits it to that weekday of the range; without times it
+-- applies to the whole day.
+CREATE TABLE IF NOT EXISTS availability_overrides (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    teacher_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind availability_override_kind NOT NULL,
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL,
+    day_of_week INT,
+    start_time TIME,
+    end_time TIME,
+    reason TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT availability_overrides_dates_check CHECK (ends_on >= starts_on),
+    CONSTRAINT availability_overrides_day_of_week_check CHECK (day_of_week BETWEEN 0 AND 6),
+    CONSTRAINT availability_overrides_times_check CHECK (
+        (start_time IS NULL AND end_time IS NULL) OR start_time < end_time
+    )
+);
+
+CREATE INDEX idx_availability_overrides_teacher ON availability_overrides (workspace_id, teacher_id, starts_on, ends_on);
+
+CREATE TRIGGER update_availability_overrides_updated_at
+BEFORE UPDATE ON availability_overrides
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+-- Whether a teacher can teach from p_start to p_end on p_date: no
+-- unavailability override touches that time, and either an extra
+-- availability override or a weekly window covers it. Teachers without a
+-- weekly pattern count as available outside their unavailability.
+CREATE OR REPLACE FUNCTION teacher_available(
+    p_workspace_id UUID, p_teacher_id UUID, p_date DATE, p_start TIME, p_end TIME
+) RETURNS BOOLEAN
+LANGUAGE sql STABLE AS $$
+    WITH overrides AS (
+        SELECT kind, start_time, end_time
+        FROM availability_overrides
+        WHERE workspace_id = p_workspace_id AND teacher_id = p_teacher_id
+            AND p_date BETWEEN starts_on AND ends_on
+            AND (day_of_week IS NULL OR day_of_week = EXTRACT(DOW FROM p_date)::int)
+    )
+    SELECT NOT EXISTS (
+            SELECT 1 FROM overrides
+            WHERE kind = 'Unavailable' AND (start_time IS NULL OR (start_time < p_end AND end_time > p_start))
+        )
+        AND (
+            EXISTS (
+                SELECT 1 FROM overrides
+                WHERE kind = 'Available' AND (start_time IS NULL OR (start_time <= p_start AND end_time >= p_end))
+            )
+            OR NOT EXISTS (SELECT 1 FROM availability WHERE workspace_id = p_workspace_id AND teacher_id = p_teacher_id)
+            OR EXISTS (
+                SELECT 1 FROM availability
+                WHERE workspace_id = p_workspace_id AND teacher_id = p_teacher_id
+                    AND day_of_week = EXTRACT(DOW FROM p_date)::int
+                    AND start_time <= p_start AND end_time >= p_end
+            )
+        )
+$$;
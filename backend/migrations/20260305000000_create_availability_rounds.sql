@@ -0,0 +1,54 @@
+CREATE TYPE availability_round_status AS ENUM ('Open', 'Closed');
+
+-- A call for teachers to submit their availability for a term, matched to
+-- drafts by term and year. Closing a round freezes the availability of
+-- every teacher into availability_round_windows, which drafting then uses.
+CREATE TABLE IF NOT EXISTS availability_rounds (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    term TEXT NOT NULL,
+    year INT NOT NULL,
+    deadline TIMESTAMPTZ NOT NULL,
+    remind_hours_before INT NOT NULL DEFAULT 48 CHECK (remind_hours_before > 0),
+    status availability_round_status NOT NULL DEFAULT 'Open',
+    reminded_at TIMESTAMPTZ,
+    closed_at TIMESTAMPTZ,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_availability_rounds_open ON availability_rounds (workspace_id, term, year) WHERE status = 'Open';
+CREATE INDEX idx_availability_rounds_deadline ON availability_rounds (deadline) WHERE status = 'Open';
+
+CREATE TRIGGER update_availability_rounds_updated_at
+BEFORE UPDATE ON availability_rounds
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+-- A teacher's submission locks their weekly availability while the round
+-- is open.
+CREATE TABLE IF NOT EXISTS availability_submissions (
+    round_id UUID NOT NULL REFERENCES availability_rounds(id) ON DELETE CASCADE,
+    teacher_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    submitted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (round_id, teacher_id)
+);
+
+CREATE INDEX idx_availability_submissions_teacher ON availability_submissions (teacher_id);
+
+CREATE TABLE IF NOT EXISTS availability_round_windows (
+    round_id UUID NOT NULL REFERENCES availability_rounds(id) ON DELETE CASCADE,
+    id UUID NOT NULL,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    teacher_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    day_of_week INT NOT NULL,
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    is_preferred BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (round_id, id)
+);
+
+CREATE INDEX idx_availability_round_windows_teacher ON availability_round_windows (round_id, teacher_id);
@@ -0,0 +1,36 @@
+CREATE TYPE teacher_preference_kind AS ENUM ('TimeWindow', 'DayOff', 'MaxGaps', 'MaxConsecutive');
+
+-- Soft scheduling wishes of a teacher, weighted from -10 to 10. A time
+-- window with a positive weight is preferred and one with a negative weight
+-- avoided; the other kinds only take positive weights. day_of_week limits
+-- a preference to that day, 0 being Sunday.
+CREATE TABLE IF NOT EXISTS teacher_preferences (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    teacher_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind teacher_preference_kind NOT NULL,
+    weight DOUBLE PRECISION NOT NULL,
+    day_of_week INT,
+    start_time TIME,
+    end_time TIME,
+    max_count INT,
+    note TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT teacher_preferences_weight_check CHECK (weight BETWEEN -10 AND 10 AND weight <> 0),
+    CONSTRAINT teacher_preferences_day_of_week_check CHECK (day_of_week BETWEEN 0 AND 6),
+    CONSTRAINT teacher_preferences_kind_check CHECK (
+        CASE kind
+            WHEN 'TimeWindow' THEN start_time < end_time AND max_count IS NULL
+            WHEN 'DayOff' THEN day_of_week IS NOT NULL AND start_time IS NULL AND end_time IS NULL AND max_count IS NULL AND weight > 0
+            ELSE start_time IS NULL AND end_time IS NULL AND max_count >= 0 AND weight > 0
+        END
+    )
+);
+
+CREATE INDEX idx_teacher_preferences_teacher ON teacher_preferences (workspace_id, teacher_id);
+
+CREATE TRIGGER update_teacher_preferences_updated_at
+BEFORE UPDATE ON teacher_preferences
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
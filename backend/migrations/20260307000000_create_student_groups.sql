@@ -0,0 +1,61 @@
+-- Student groups: who attends a lesson
+CREATE TYPE student_group_kind AS ENUM ('YearGroup', 'Class', 'Subgroup');
+
+CREATE TABLE student_groups (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    kind student_group_kind NOT NULL,
+    -- The group this one is part of: a class in a year group, a subgroup in a class
+    parent_id UUID REFERENCES student_groups(id) ON DELETE CASCADE,
+    size INTEGER CHECK (size >= 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (workspace_id, name),
+    CONSTRAINT student_groups_parent_check CHECK (parent_id <> id),
+    CONSTRAINT student_groups_kind_check CHECK (
+        CASE kind
+            WHEN 'YearGroup' THEN parent_id IS NULL
+            WHEN 'Subgroup' THEN parent_id IS NOT NULL
+            ELSE TRUE
+        END
+    )
+);
+
+CREATE INDEX idx_student_groups_parent ON student_groups (parent_id);
+
+CREATE TRIGGER update_student_groups_updated_at
+BEFORE UPDATE ON student_groups
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+-- The groups a course is taught to
+CREATE TABLE course_student_groups (
+    course_id UUID NOT NULL REFERENCES courses(id) ON DELETE CASCADE,
+    student_group_id UUID NOT NULL REFERENCES student_groups(id) ON DELETE CASCADE,
+    PRIMARY KEY (course_id, student_group_id)
+);
+
+CREATE INDEX idx_course_student_groups_group ON course_student_groups (student_group_id);
+
+-- Groups of a single draft lesson, replacing those of its course
+CREATE TABLE draft_entry_student_groups (
+    draft_entry_id UUID NOT NULL REFERENCES draft_entries(id) ON DELETE CASCADE,
+    student_group_id UUID NOT NULL REFERENCES student_groups(id) ON DELETE CASCADE,
+    PRIMARY KEY (draft_entry_id, student_group_id)
+);
+
+CREATE INDEX idx_draft_entry_student_groups_group ON draft_entry_student_groups (student_group_id);
+
+-- Who attends each draft lesson: its own groups, or its course's when it has none
+CREATE VIEW draft_entry_groups AS
+SELECT de.id AS draft_entry_id, de.draft_timetable_id, g.student_group_id
+FROM draft_entries de
+JOIN draft_entry_student_groups g ON g.draft_entry_id = de.id
+UNION ALL
+SELECT de.id, de.draft_timetable_id, g.student_group_id
+FROM draft_entries de
+JOIN course_student_groups g ON g.course_id = de.course_id
+WHERE NOT EXISTS (SELECT 1 FROM draft_entry_student_groups o WHERE o.draft_entry_id = de.id);
+
+ALTER TABLE conflicts ADD COLUMN student_group_id UUID REFERENCES student_groups(id) ON DELETE CASCADE;
@@ -0,0 +1,54 @@
+-- Roster of students; a student may have a login of their own
+CREATE TABLE students (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    student_number TEXT NOT NULL,
+    name TEXT NOT NULL,
+    email TEXT,
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    -- Their class; they attend its lessons and those of the groups it is part of
+    student_group_id UUID REFERENCES student_groups(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (workspace_id, student_number)
+);
+
+CREATE INDEX idx_students_user ON students (user_id);
+CREATE INDEX idx_students_group ON students (student_group_id);
+
+CREATE TRIGGER update_students_updated_at
+BEFORE UPDATE ON students
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+-- Parallel sections of a course that students choose between
+CREATE TABLE course_sections (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    course_id UUID NOT NULL REFERENCES courses(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (course_id, name),
+    UNIQUE (id, course_id)
+);
+
+CREATE TRIGGER update_course_sections_updated_at
+BEFORE UPDATE ON course_sections
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+CREATE TABLE enrollments (
+    student_id UUID NOT NULL REFERENCES students(id) ON DELETE CASCADE,
+    course_section_id UUID NOT NULL REFERENCES course_sections(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (student_id, course_section_id)
+);
+
+CREATE INDEX idx_enrollments_section ON enrollments (course_section_id);
+
+-- The section a draft lesson is for; lessons without one are for every section of the course
+ALTER TABLE draft_entries
+    ADD COLUMN course_section_id UUID,
+    ADD CONSTRAINT draft_entries_course_section_fkey FOREIGN KEY (course_section_id, course_id)
+        REFERENCES course_sections (id, course_id) ON DELETE SET NULL (course_section_id);
@@ -0,0 +1,7 @@
+-- Expected number of students of a course, when not known from groups or enrolments
+ALTER TABLE courses ADD COLUMN expected_size INTEGER
+    CONSTRAINT courses_expected_size_check CHECK (expected_size >= 0);
+
+-- How far, in percent, a lesson may go over the capacity of its room before it is a conflict
+ALTER TABLE workspaces ADD COLUMN room_capacity_tolerance INTEGER NOT NULL DEFAULT 0
+    CONSTRAINT workspaces_room_capacity_tolerance_check CHECK (room_capacity_tolerance BETWEEN 0 AND 100);
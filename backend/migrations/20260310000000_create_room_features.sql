@@ -0,0 +1,44 @@
+-- Catalogue of room features: lab, projector, piano, wheelchair access
+CREATE TABLE room_features (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (workspace_id, name)
+);
+
+CREATE TRIGGER update_room_features_updated_at
+BEFORE UPDATE ON room_features
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+-- The features each room has
+CREATE TABLE room_feature_assignments (
+    room_id UUID NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+    room_feature_id UUID NOT NULL REFERENCES room_features(id) ON DELETE CASCADE,
+    PRIMARY KEY (room_id, room_feature_id)
+);
+
+CREATE INDEX idx_room_feature_assignments_feature ON room_feature_assignments (room_feature_id);
+
+-- Required features must be in the room; preferred ones only rank suggestions
+CREATE TYPE room_feature_need AS ENUM ('Required', 'Preferred');
+
+CREATE TABLE course_room_features (
+    course_id UUID NOT NULL REFERENCES courses(id) ON DELETE CASCADE,
+    room_feature_id UUID NOT NULL REFERENCES room_features(id) ON DELETE CASCADE,
+    need room_feature_need NOT NULL,
+    PRIMARY KEY (course_id, room_feature_id)
+);
+
+CREATE INDEX idx_course_room_features_feature ON course_room_features (room_feature_id);
+
+CREATE TABLE student_group_room_features (
+    student_group_id UUID NOT NULL REFERENCES student_groups(id) ON DELETE CASCADE,
+    room_feature_id UUID NOT NULL REFERENCES room_features(id) ON DELETE CASCADE,
+    need room_feature_need NOT NULL,
+    PRIMARY KEY (student_group_id, room_feature_id)
+);
+
+CREATE INDEX idx_student_group_room_features_feature ON student_group_room_features (room_feature_id);
@@ -0,0 +1,20 @@
+-- Which teachers may teach which courses, and how keen they are to
+CREATE TYPE qualification_preference AS ENUM ('Preferred', 'Neutral', 'Reluctant');
+
+CREATE TABLE teacher_qualifications (
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    teacher_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    course_id UUID NOT NULL REFERENCES courses(id) ON DELETE CASCADE,
+    preference qualification_preference NOT NULL DEFAULT 'Neutral',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (teacher_id, course_id)
+);
+
+CREATE INDEX idx_teacher_qualifications_course ON teacher_qualifications (course_id);
+CREATE INDEX idx_teacher_qualifications_workspace ON teacher_qualifications (workspace_id);
+
+CREATE TRIGGER update_teacher_qualifications_updated_at
+BEFORE UPDATE ON teacher_qualifications
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
@@ -0,0 +1,47 @@
+-- Whether the teacher has a reported absence, not cancelled, on any date
+-- from p_from through p_to that falls on the weekday (0 = Sunday).
+CREATE OR REPLACE FUNCTION teacher_absent(
+    p_workspace_id UUID, p_teacher_id UUID, p_from DATE, p_to DATE, p_day_of_week INT
+) RETURNS BOOLEAN
+LANGUAGE sql STABLE AS $$
+    SELECT EXISTS (
+        SELECT 1
+        FROM absences a
+        CROSS JOIN generate_series(GREATEST(a.starts_on, p_from), LEAST(a.ends_on, p_to), INTERVAL '1 day') day
+        WHERE a.workspace_id = p_workspace_id AND a.teacher_id = p_teacher_id AND a.cancelled_at IS NULL
+            AND a.starts_on <= p_to AND a.ends_on >= p_from
+            AND EXTRACT(DOW FROM day)::int = p_day_of_week
+    )
+$$;
+
+-- As before, but a teacher is never available on a day they are absent
+CREATE OR REPLACE FUNCTION teacher_available(
+    p_workspace_id UUID, p_teacher_id UUID, p_date DATE, p_start TIME, p_end TIME
+) RETURNS BOOLEAN
+LANGUAGE sql STABLE AS $$
+    WITH overrides AS (
+        SELECT kind, start_time, end_time
+        FROM availability_overrides
+        WHERE workspace_id = p_workspace_id AND teacher_id = p_teacher_id
+            AND p_date BETWEEN starts_on AND ends_on
+            AND (day_of_week IS NULL OR day_of_week = EXTRACT(DOW FROM p_date)::int)
+    )
+    SELECT NOT teacher_absent(p_workspace_id, p_teacher_id, p_date, p_date, EXTRACT(DOW FROM p_date)::int)
+        AND NOT EXISTS (
+            SELECT 1 FROM overrides
+            WHERE kind = 'Unavailable' AND (start_time IS NULL OR (start_time < p_end AND end_time > p_start))
+        )
+        AND (
+            EXISTS (
+                SELECT 1 FROM overrides
+                WHERE kind = 'Available' AND (start_time IS NULL OR (start_time <= p_start AND end_time >= p_end))
+            )
+            OR NOT EXISTS (SELECT 1 FROM availability WHERE workspace_id = p_workspace_id AND teacher_id = p_teacher_id)
+            OR EXISTS (
+                SELECT 1 FROM availability
+                WHERE workspace_id = p_workspace_id AND teacher_id = p_teacher_id
+                    AND day_of_week = EXTRACT(DOW FROM p_date)::int
+                    AND start_time <= p_start AND end_time >= p_end
+            )
+        )
+$$;
@@ -0,0 +1,7 @@
+-- Each endpoint signs its deliveries with its own secret
+ALTER TABLE webhook_endpoints ADD COLUMN secret TEXT;
+UPDATE webhook_endpoints SET secret = replace(gen_random_uuid()::text || gen_random_uuid()::text, '-', '');
+ALTER TABLE webhook_endpoints ALTER COLUMN secret SET NOT NULL;
+
+-- The endpoint a webhook message goes to, for its secret; personal webhooks have none
+ALTER TABLE outbox ADD COLUMN webhook_endpoint_id UUID REFERENCES webhook_endpoints(id) ON DELETE CASCADE;
@@ -0,0 +1,32 @@
+CREATE TYPE conflict_kind AS ENUM (
+    'TeacherDoubleBooking', 'RoomDoubleBooking', 'GroupDoubleBooking', 'RoomCapacity', 'RoomFeatures',
+    'Qualification', 'Availability', 'DatedAvailability'
+);
+
+ALTER TABLE conflicts ADD COLUMN kind conflict_kind;
+UPDATE conflicts SET kind = CASE
+    WHEN description LIKE 'Teacher is double-booked%' THEN 'TeacherDoubleBooking'
+    WHEN description LIKE 'Room is double-booked%' THEN 'RoomDoubleBooking'
+    WHEN description LIKE 'Group % is double-booked%' THEN 'GroupDoubleBooking'
+    WHEN description LIKE 'Room % holds %' THEN 'RoomCapacity'
+    WHEN description LIKE 'Room % lacks %' THEN 'RoomFeatures'
+    WHEN description LIKE 'Teacher is not qualified%' THEN 'Qualification'
+    WHEN description LIKE 'Teacher is not available during%' THEN 'Availability'
+    ELSE 'DatedAvailability'
+END::conflict_kind;
+ALTER TABLE conflicts ALTER COLUMN kind SET NOT NULL;
+
+-- Every detection run used to store its conflicts again. Keep one of each,
+-- preferring a decision already taken on it.
+DELETE FROM conflicts c
+USING conflicts keep
+WHERE keep.draft_timetable_id = c.draft_timetable_id AND keep.kind = c.kind
+    AND keep.teacher_id IS NOT DISTINCT FROM c.teacher_id
+    AND keep.room_id IS NOT DISTINCT FROM c.room_id
+    AND keep.student_group_id IS NOT DISTINCT FROM c.student_group_id
+    AND keep.time_slot_id IS NOT DISTINCT FROM c.time_slot_id
+    AND (keep.status <> 'Open', c.created_at, c.id) > (c.status <> 'Open', keep.created_at, keep.id);
+
+-- A conflict is stored once per draft, kind, teacher, room, group and slot
+CREATE UNIQUE INDEX idx_conflicts_unique ON conflicts (draft_timetable_id, kind, teacher_id, room_id, student_group_id, time_slot_id)
+    NULLS NOT DISTINCT;
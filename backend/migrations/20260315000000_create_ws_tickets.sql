@@ -0,0 +1,9 @@
+-- Single-use tickets for opening /ws, kept here so any replica can redeem
+-- a ticket issued by another
+CREATE TABLE IF NOT EXISTS ws_tickets (
+    ticket TEXT PRIMARY KEY,
+    claims JSONB NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_ws_tickets_expires_at ON ws_tickets (expires_at);
@@ -0,0 +1,25 @@
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::IntoResponse,
+};
+use crate::error::AppError;
+use crate::AppState;
+
+/// Serves a personal iCal feed. Calendar apps cannot log in, so the secret
+/// token in the URL is the only credential.
+pub async fn feed_handler(
+    State(state): State<AppState>,
+    Path(feed): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let token = feed.strip_suffix(".ics").ok_or(AppError::NotFound)?;
+    let body = state.occurrences.feed_calendar(token).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (header::CACHE_CONTROL, "private, max-age=300"),
+        ],
+        body,
+    ))
+}
@@ -1,17 +1,26 @@
 pub mod health;
 pub mod auth;
+pub mod calendar;
+pub mod reports;
 
-use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
-use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use async_graphql::http::{playground_source, GraphQLPlaygroundConfig, ALL_WEBSOCKET_PROTOCOLS};
+use async_graphql::Data;
+use async_graphql_axum::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
 use axum::{
+    extract::{State, WebSocketUpgrade},
     handler::Handler,
     http::HeaderMap,
-    routing::get,
+    response::IntoResponse,
+    routing::{get, post},
     Extension, Router,
 };
+use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::error::AppError;
 use crate::graphql::{AppSchema, WorkspaceContext};
+use crate::middleware::auth::decode_claims;
+use crate::service::WorkspaceService;
 use crate::AppState;
 
 pub fn router(
@@ -31,7 +40,14 @@ pub fn router(
             "/graphql",
             get(graphql_playground).post(graphql_handler.layer(auth_middleware.clone())),
         )
-        .route("/ws", get(crate::ws::ws_handler).layer(auth_middleware))
+        .route("/graphql/ws", get(graphql_ws_handler))
+        .route("/calendar/{feed}", get(calendar::feed_handler))
+        .route(
+            "/reports/substitution-stats.csv",
+            get(reports::substitution_stats_handler).layer(auth_middleware.clone()),
+        )
+        .route("/ws", get(crate::ws::ws_handler))
+        .route("/ws/ticket", post(crate::ws::ticket::ticket_handler).layer(auth_middleware))
         .layer(Extension(schema))
         .with_state(state)
 }
@@ -58,6 +74,103 @@ async fn graphql_handler(
     schema.execute(request).await.into()
 }
 
+// Browsers cannot set headers on WebSocket upgrades, so graphql-ws clients
+// authenticate through the `connection_init` payload instead.
+async fn graphql_ws_handler(
+    Extension(schema): Extension<AppSchema>,
+    State(state): State<AppState>,
+    protocol: GraphQLProtocol,
+    upgrade: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let workspace_service = schema
+        .data::<Arc<WorkspaceService>>()
+        .expect("WorkspaceService not found in schema data")
+        .clone();
+
+    upgrade
+        .protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |stream| {
+            GraphQLWebSocket::new(stream, schema, protocol)
+                .on_connection_init(move |payload| async move {
+                    connection_data(payload, &state.config.jwt_secret, &workspace_service).await
+                })
+                .serve()
+        })
+}
+
+/// Checks the JWT in a `connection_init` payload, accepting members of the
+/// token's workspace only, as `/ws` does.
+async fn connection_data(
+    payload: serde_json::Value,
+    jwt_secret: &str,
+    workspace_service: &WorkspaceService,
+) -> async_graphql::Result<Data> {
+    let token = payload
+        .get("Authorization")
+        .or_else(|| payload.get("authorization"))
+        .and_then(|v| v.as_str())
+        .map(|v| v.strip_prefix("Bearer ").unwrap_or(v))
+        .ok_or(AppError::Unauthorized)?;
+
+    let claims = decode_claims(token, jwt_secret)?;
+    if workspace_service.get_member_role(claims.workspace_id, claims.sub).await?.is_none() {
+        return Err(AppError::Forbidden("Not a member of this workspace".to_string()).into());
+    }
+
+    let mut data = Data::default();
+    data.insert(WorkspaceContext {
+        workspace_id: Some(claims.workspace_id),
+    });
+    data.insert(claims);
+    Ok(data)
+}
+
 async fn graphql_playground() -> impl axum::response::IntoResponse {
-    axum::response::Html(playground_source(GraphQLPlaygroundConfig::new("/graphql")))
+    axum::response::Html(playground_source(
+        GraphQLPlaygroundConfig::new("/graphql").subscription_endpoint("/graphql/ws"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::models::WorkspaceRole;
+    use crate::test_support::{self, JWT_SECRET};
+
+    fn payload(token: &str) -> serde_json::Value {
+        json!({ "Authorization": format!("Bearer {}", token) })
+    }
+
+    #[sqlx::test]
+    async fn connection_init_accepts_members(pool: PgPool) {
+        let workspaces = test_support::workspace_service(&pool);
+        let workspace_id = test_support::workspace(&pool).await;
+        let viewer = test_support::member(&pool, workspace_id, WorkspaceRole::Viewer).await;
+        let token = test_support::token(&test_support::claims(viewer, workspace_id));
+
+        assert!(connection_data(payload(&token), JWT_SECRET, &workspaces).await.is_ok());
+    }
+
+    #[sqlx::test]
+    async fn connection_init_refuses_non_members(pool: PgPool) {
+        let workspaces = test_support::workspace_service(&pool);
+        let workspace_id = test_support::workspace(&pool).await;
+        let other_workspace = test_support::workspace(&pool).await;
+        let outsider = test_support::member(&pool, other_workspace, WorkspaceRole::Owner).await;
+        let token = test_support::token(&test_support::claims(outsider, workspace_id));
+
+        let err = connection_data(payload(&token), JWT_SECRET, &workspaces).await.err().unwrap();
+        assert_eq!(err.message, "Forbidden: Not a member of this workspace");
+    }
+
+    #[sqlx::test]
+    async fn connection_init_refuses_bad_tokens(pool: PgPool) {
+        let workspaces = test_support::workspace_service(&pool);
+
+        assert!(connection_data(json!({}), JWT_SECRET, &workspaces).await.is_err());
+        assert!(connection_data(payload("not-a-jwt"), JWT_SECRET, &workspaces).await.is_err());
+    }
 }
@@ -0,0 +1,55 @@
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::IntoResponse,
+    Extension,
+};
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use crate::error::AppError;
+use crate::graphql::AppSchema;
+use crate::service::auth::Claims;
+use crate::service::WorkspaceService;
+use crate::AppState;
+
+#[derive(Deserialize)]
+pub struct PeriodParams {
+    from: NaiveDate,
+    to: NaiveDate,
+}
+
+/// Downloads the substitution figures of every teacher for a period as a
+/// spreadsheet-friendly CSV file.
+pub async fn substitution_stats_handler(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(schema): Extension<AppSchema>,
+    Query(period): Query<PeriodParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let workspace_service = schema
+        .data::<Arc<WorkspaceService>>()
+        .expect("WorkspaceService not found in schema data");
+    let role = workspace_service.get_member_role(claims.workspace_id, claims.sub).await?;
+    if !role.is_some_and(|role| role.can_edit()) {
+        return Err(AppError::Forbidden("Only workspace owners and editors can export substitution statistics".to_string()));
+    }
+
+    let body = state
+        .substitution_stats
+        .export_csv(claims.workspace_id, period.from, period.to)
+        .await?;
+    let disposition = format!(
+        "attachment; filename=\"substitutions-{}-{}.csv\"",
+        period.from, period.to
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    ))
+}
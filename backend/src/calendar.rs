@@ -0,0 +1,118 @@
+//! Minimal iCalendar (RFC 5545) output for lessons.
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+/// 0 = Sunday, as stored in `time_slots.day_of_week`.
+pub fn weekday_name(day_of_week: i32) -> &'static str {
+    const DAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+    DAYS.get(day_of_week as usize).copied().unwrap_or("unknown day")
+}
+
+/// The first date on or after `from` that falls on `day_of_week`.
+pub fn first_occurrence(from: NaiveDate, day_of_week: i32) -> NaiveDate {
+    let offset = (day_of_week - from.weekday().num_days_from_sunday() as i32).rem_euclid(7);
+    from + chrono::Duration::days(offset as i64)
+}
+
+#[derive(Debug, Clone)]
+pub struct CalendarEvent {
+    /// Stays the same for every version of the event, so calendar apps
+    /// update or cancel it instead of adding a copy.
+    pub uid: String,
+    pub summary: String,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// Repeats weekly up to and including this date.
+    pub repeat_until: Option<NaiveDate>,
+    pub cancelled: bool,
+}
+
+impl CalendarEvent {
+    /// A lesson held every week on `day_of_week` between `from` and `until`.
+    pub fn weekly(
+        uid: String,
+        summary: String,
+        day_of_week: i32,
+        start_time: NaiveTime,
+        end_time: NaiveTime,
+        from: NaiveDate,
+        until: NaiveDate,
+    ) -> Self {
+        let date = first_occurrence(from, day_of_week);
+        Self {
+            uid,
+            summary,
+            location: None,
+            description: None,
+            start: date.and_time(start_time),
+            end: date.and_time(end_time),
+            repeat_until: Some(until),
+            cancelled: false,
+        }
+    }
+}
+
+/// Renders a calendar. Times are written as floating local times, i.e. in
+/// the school's own time zone.
+pub fn to_ics(name: &str, events: &[CalendarEvent], stamp: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Nullslot//Timetable//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+    ];
+
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("DTSTAMP:{}", stamp.format("%Y%m%dT%H%M%SZ")));
+        lines.push(format!("DTSTART:{}", event.start.format("%Y%m%dT%H%M%S")));
+        lines.push(format!("DTEND:{}", event.end.format("%Y%m%dT%H%M%S")));
+        if let Some(until) = event.repeat_until {
+            lines.push(format!("RRULE:FREQ=WEEKLY;UNTIL={}T235959", until.format("%Y%m%d")));
+        }
+        lines.push(format!("SUMMARY:{}", escape_text(&event.summary)));
+        if let Some(location) = &event.location {
+            lines.push(format!("LOCATION:{}", escape_text(location)));
+        }
+        if let Some(description) = &event.description {
+            lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+        }
+        lines.push(format!("STATUS:{}", if event.cancelled { "CANCELLED" } else { "CONFIRMED" }));
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = String::new();
+    for line in lines {
+        fold_line(&line, &mut out);
+    }
+    out
+}
+
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Content lines are limited to 75 octets; longer ones continue on the next
+/// line after a space.
+fn fold_line(line: &str, out: &mut String) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
@@ -1,4 +1,30 @@
 use std::env;
+use chrono::NaiveTime;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BroadcastBackendKind {
+    /// Events stay inside this process; for single-node deployments and tests.
+    Memory,
+    /// Events fan out to every instance through Postgres LISTEN/NOTIFY.
+    Postgres,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MailTransportKind {
+    /// Sends through the SMTP server configured with `SMTP_*`.
+    Smtp,
+    /// Writes `.eml` files into `MAIL_DIR`; for development.
+    File,
+    /// Keeps sent messages in memory; for tests.
+    Memory,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    None,
+    StartTls,
+    Tls,
+}
 
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -8,6 +34,22 @@ pub struct Config {
     pub oidc_redirect_uri: String,
     pub oidc_frontend_redirect_url: String,
     pub jwt_secret: String,
+    pub event_retention_days: i64,
+    pub notification_retention_days: i64,
+    pub digest_time: NaiveTime,
+    pub digest_timezone: String,
+    pub broadcast_backend: BroadcastBackendKind,
+    pub frontend_url: String,
+    pub public_url: String,
+    pub mail_transport: MailTransportKind,
+    pub mail_from: String,
+    pub mail_dir: String,
+    pub mail_locale: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub smtp_security: SmtpSecurity,
 }
 
 impl Config {
@@ -19,6 +61,45 @@ impl Config {
             oidc_redirect_uri: env::var("OIDC_REDIRECT_URI").expect("OIDC_REDIRECT_URI must be set"),
             oidc_frontend_redirect_url: env::var("OIDC_FRONTEND_REDIRECT_URL").unwrap_or_else(|_| "http://localhost:5173/oidc-callback".to_string()),
             jwt_secret: env::var("JWT_SECRET").unwrap_or_else(|_| "default_secret".to_string()),
+            event_retention_days: env::var("EVENT_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(7),
+            notification_retention_days: env::var("NOTIFICATION_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(90),
+            digest_time: env::var("DIGEST_TIME")
+                .ok()
+                .and_then(|v| NaiveTime::parse_from_str(&v, "%H:%M").ok())
+                .unwrap_or(NaiveTime::from_hms_opt(7, 0, 0).unwrap()),
+            digest_timezone: env::var("DIGEST_TIMEZONE").unwrap_or_else(|_| "UTC".to_string()),
+            broadcast_backend: match env::var("BROADCAST_BACKEND").as_deref() {
+                Ok("postgres") => BroadcastBackendKind::Postgres,
+                _ => BroadcastBackendKind::Memory,
+            },
+            frontend_url: env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:5173".to_string()),
+            public_url: env::var("PUBLIC_URL").unwrap_or_else(|_| "http://localhost:8080".to_string()),
+            mail_transport: match env::var("MAIL_TRANSPORT").as_deref() {
+                Ok("smtp") => MailTransportKind::Smtp,
+                Ok("memory") => MailTransportKind::Memory,
+                _ => MailTransportKind::File,
+            },
+            mail_from: env::var("MAIL_FROM").unwrap_or_else(|_| "Nullslot <no-reply@nullslot.local>".to_string()),
+            mail_dir: env::var("MAIL_DIR").unwrap_or_else(|_| "mailbox".to_string()),
+            mail_locale: env::var("MAIL_LOCALE").unwrap_or_else(|_| "en".to_string()),
+            smtp_host: env::var("SMTP_HOST").unwrap_or_else(|_| "localhost".to_string()),
+            smtp_port: env::var("SMTP_PORT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1025),
+            smtp_username: env::var("SMTP_USERNAME").ok(),
+            smtp_password: env::var("SMTP_PASSWORD").ok(),
+            smtp_security: match env::var("SMTP_SECURITY").as_deref() {
+                Ok("starttls") => SmtpSecurity::StartTls,
+                Ok("tls") => SmtpSecurity::Tls,
+                _ => SmtpSecurity::None,
+            },
         }
     }
 }
@@ -1,12 +1,46 @@
 pub mod query;
 pub mod mutation;
 pub mod schema;
+pub mod subscription;
 pub mod types;
 
 pub use schema::{create_schema, AppSchema};
+
+use std::sync::Arc;
+
+use async_graphql::{Context, ErrorExtensions, Result};
 use uuid::Uuid;
 
+use crate::error::AppError;
+use crate::models::WorkspaceRole;
+use crate::service::auth::Claims;
+use crate::service::WorkspaceService;
+
 #[derive(Debug, Clone, Copy)]
 pub struct WorkspaceContext {
     pub workspace_id: Option<Uuid>,
 }
+
+/// The caller's claims and role, for members of the workspace in their token.
+pub(crate) async fn require_workspace_member<'a>(ctx: &Context<'a>) -> Result<(&'a Claims, WorkspaceRole)> {
+    let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+    let workspace_service = ctx.data::<Arc<WorkspaceService>>()?;
+    match workspace_service.get_member_role(claims.workspace_id, claims.sub).await? {
+        Some(role) => Ok((claims, role)),
+        None => Err(AppError::Forbidden("You are not a member of this workspace".to_string()).into()),
+    }
+}
+
+pub(crate) async fn require_workspace_editor<'a>(ctx: &Context<'a>) -> Result<&'a Claims> {
+    match require_workspace_member(ctx).await? {
+        (claims, role) if role.can_edit() => Ok(claims),
+        _ => Err(AppError::Forbidden("Only workspace owners and editors can do this".to_string()).into()),
+    }
+}
+
+pub(crate) async fn require_workspace_owner<'a>(ctx: &Context<'a>) -> Result<&'a Claims> {
+    match require_workspace_member(ctx).await? {
+        (claims, WorkspaceRole::Owner) => Ok(claims),
+        _ => Err(AppError::Forbidden("Only workspace owners can do this".to_string()).into()),
+    }
+}
@@ -1,11 +1,14 @@
 use std::sync::Arc;
 
 use async_graphql::{Context, ErrorExtensions, InputObject, Object, Result};
-use chrono::NaiveTime;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use uuid::Uuid;
-use crate::models::{Resource, Token, Course, Room, TimeSlot, TimetableEntry, Substitution, User, UserRole};
+use crate::models::{Absence, QualificationImport, QualificationPreference, TeacherQualification, AvailabilityRound, AvailabilitySubmission, LessonException, LessonSwap, OfferMode, ReportedAbsence, Resource, SubstitutionOffer, Token, Course, Room, TimeSlot, TimetableEntry, Substitution, UserRole, CreatedWebhookEndpoint, Notification,
+    NotificationDelivery, NotificationPreference, NotificationSettings, NotificationType};
 use crate::graphql::types::{
-    Availability, AvailabilityInput, DraftTimetable, DraftTimetableInput, Conflict,
+    TeacherPreference, TeacherPreferenceInput, StudentGroup, StudentGroupInput, Student, StudentInput, CourseSection, EnrollmentImport,
+    RoomFeature, RoomFeatureRequirement, RoomFeatureRequirementInput,
+    Availability, AvailabilityInput, AvailabilityOverride, AvailabilityOverrideInput, AvailabilityWindowInput, DraftTimetable, DraftTimetableInput, Conflict,
     RequestMagicLinkInput, LoginWithMagicLinkInput, LoginPayload,
     CreateWorkspaceInput, CreateInviteInput, AcceptInviteInput, Workspace
 };
@@ -14,11 +17,13 @@ use crate::service::auth::Claims;
 use crate::service::{
     UserService, ResourceService, CourseService, RoomService,
     TimeSlotService, TimetableEntryService, SubstitutionService,
-    AvailabilityService, ConflictService, DraftTimetableService,
+    AvailabilityService, AvailabilityRoundService, ConflictService, TeacherPreferenceService, TeacherQualificationService, StudentGroupService, EnrollmentService, RoomFeatureService, DraftTimetableService,
     DraftEntryService, PublishedTimetableService, AuthService,
-    workspace::WorkspaceService
+    OutboxService, NotificationService, OccurrenceService, LessonExceptionService, LessonSwapService,
+    workspace::WorkspaceService, lesson_exceptions::ExtraLesson, lesson_swaps::SwapProposal,
 };
 use crate::error::AppError;
+use crate::graphql::{require_workspace_editor, require_workspace_member, require_workspace_owner};
 
 pub struct Mutation;
 
@@ -42,6 +47,10 @@ pub struct CreateCourseInput {
     pub code: String,
     pub name: String,
     pub description: Option<String>,
+    pub department: Option<String>,
+    /// Number of students expected, checked against room capacity when
+    /// the course has no groups or enrolments.
+    pub expected_size: Option<i32>,
 }
 
 #[derive(InputObject)]
@@ -50,6 +59,8 @@ pub struct UpdateCourseInput {
     pub code: Option<String>,
     pub name: Option<String>,
     pub description: Option<String>,
+    pub department: Option<String>,
+    pub expected_size: Option<i32>,
 }
 
 #[derive(InputObject)]
@@ -97,6 +108,54 @@ pub struct UpdateTimetableEntryInput {
     pub teacher_id: Option<Uuid>,
 }
 
+#[derive(InputObject)]
+pub struct ExtraLessonInput {
+    pub date: NaiveDate,
+    pub course_id: Uuid,
+    pub teacher_id: Uuid,
+    pub room_id: Uuid,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub reason: Option<String>,
+}
+
+#[derive(InputObject)]
+pub struct ProposeLessonSwapInput {
+    /// The caller's lesson to hand over.
+    pub timetable_entry_id: Uuid,
+    pub date: NaiveDate,
+    /// The colleague's lesson to take over.
+    pub colleague_entry_id: Uuid,
+    pub colleague_date: NaiveDate,
+    pub reason: Option<String>,
+}
+
+#[derive(InputObject)]
+pub struct OpenAvailabilityRoundInput {
+    pub term: String,
+    pub year: i32,
+    pub deadline: DateTime<Utc>,
+    /// Hours before the deadline the reminder goes out; 48 by default.
+    pub remind_hours_before: Option<i32>,
+}
+
+#[derive(InputObject)]
+pub struct NotificationPreferenceInput {
+    pub notification_type: NotificationType,
+    pub delivery: NotificationDelivery,
+    pub in_app: bool,
+    pub email: bool,
+    pub webhook: bool,
+}
+
+#[derive(InputObject)]
+pub struct NotificationSettingsInput {
+    /// IANA time zone, e.g. `Europe/Paris`.
+    pub timezone: String,
+    pub digest_time: NaiveTime,
+    pub webhook_url: Option<String>,
+}
+
 #[Object]
 impl Mutation {
     async fn register(&self, ctx: &Context<'_>, input: RegisterInput) -> Result<Token> {
@@ -129,12 +188,12 @@ impl Mutation {
     async fn create_course(&self, ctx: &Context<'_>, input: CreateCourseInput) -> Result<Course> {
         let service = ctx.data::<CourseService>()?;
         let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
-        Ok(service.create_course(claims.workspace_id, input.code, input.name, input.description).await?)
+        Ok(service.create_course(claims.workspace_id, input.code, input.name, input.description, input.department, input.expected_size).await?)
     }
 
     async fn update_course(&self, ctx: &Context<'_>, input: UpdateCourseInput) -> Result<Course> {
         let service = ctx.data::<CourseService>()?;
-        Ok(service.update_course(input.id, input.code, input.name, input.description).await?)
+        Ok(service.update_course(input.id, input.code, input.name, input.description, input.department, input.expected_size).await?)
     }
 
     async fn delete_course(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
@@ -179,60 +238,530 @@ impl Mutation {
 
     async fn create_timetable_entry(&self, ctx: &Context<'_>, input: CreateTimetableEntryInput) -> Result<TimetableEntry> {
         let service = ctx.data::<TimetableEntryService>()?;
-        Ok(service.create_timetable_entry(input.course_id, input.room_id, input.time_slot_id, input.teacher_id).await?)
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.create_timetable_entry(claims.workspace_id, input.course_id, input.room_id, input.time_slot_id, input.teacher_id).await?)
     }
 
     async fn update_timetable_entry(&self, ctx: &Context<'_>, input: UpdateTimetableEntryInput) -> Result<TimetableEntry> {
         let service = ctx.data::<TimetableEntryService>()?;
-        Ok(service.update_timetable_entry(input.id, input.course_id, input.room_id, input.time_slot_id, input.teacher_id).await?)
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.update_timetable_entry(claims.workspace_id, input.id, input.course_id, input.room_id, input.time_slot_id, input.teacher_id).await?)
     }
 
     async fn delete_timetable_entry(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
         let service = ctx.data::<TimetableEntryService>()?;
-        service.delete_timetable_entry(id).await?;
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        service.delete_timetable_entry(claims.workspace_id, id).await?;
         Ok(true)
     }
 
-    async fn request_substitution(&self, ctx: &Context<'_>, timetable_entry_id: Uuid) -> Result<Substitution> {
-        let user = ctx.data::<Option<User>>()?.as_ref().ok_or(AppError::Unauthorized)?;
-        if user.role != UserRole::Admin {
-            return Err(AppError::Forbidden("Only admins can request substitutions".to_string()).into());
-        }
+    /// Requests a substitute for the lesson on `startsOn`, or for every
+    /// occurrence through `endsOn`.
+    async fn request_substitution(
+        &self,
+        ctx: &Context<'_>,
+        timetable_entry_id: Uuid,
+        starts_on: NaiveDate,
+        ends_on: Option<NaiveDate>,
+    ) -> Result<Substitution> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<SubstitutionService>()?;
+        Ok(service.request_substitution(claims.workspace_id, timetable_entry_id, starts_on, ends_on).await?)
+    }
+
+    /// Records that a teacher is away from `from` through `to` and requests
+    /// a substitute for each of their lessons on those days.
+    async fn report_absence(
+        &self,
+        ctx: &Context<'_>,
+        teacher_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+        reason: Option<String>,
+    ) -> Result<ReportedAbsence> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<SubstitutionService>()?;
+        Ok(service.report_absence(claims.workspace_id, claims.sub, teacher_id, from, to, reason).await?)
+    }
 
+    /// Cancels an absence and retracts its substitutions nobody has
+    /// accepted yet.
+    async fn cancel_absence(&self, ctx: &Context<'_>, absence_id: Uuid) -> Result<Absence> {
+        let claims = require_workspace_editor(ctx).await?;
         let service = ctx.data::<SubstitutionService>()?;
-        Ok(service.request_substitution(timetable_entry_id).await?)
+        Ok(service.cancel_absence(claims.workspace_id, absence_id).await?)
     }
 
     async fn accept_substitution(&self, ctx: &Context<'_>, substitution_id: Uuid) -> Result<Substitution> {
-        let user = ctx.data::<Option<User>>()?.as_ref().ok_or(AppError::Unauthorized)?;
-        if user.role != UserRole::Teacher {
+        let (claims, _) = require_workspace_member(ctx).await?;
+        if claims.role != UserRole::Teacher {
             return Err(AppError::Forbidden("Only teachers can accept substitutions".to_string()).into());
         }
 
         let service = ctx.data::<SubstitutionService>()?;
-        Ok(service.accept_substitution(substitution_id, user.id).await?)
+        Ok(service.accept_substitution(claims.workspace_id, substitution_id, claims.sub).await?)
+    }
+
+    /// Offers a pending substitution to specific teachers, all at once or
+    /// one after the other. Without `teacherIds` a cascade goes through the
+    /// best ranked candidates. Unanswered offers expire after
+    /// `timeoutMinutes` (default 60).
+    async fn offer_substitution(
+        &self,
+        ctx: &Context<'_>,
+        substitution_id: Uuid,
+        teacher_ids: Option<Vec<Uuid>>,
+        mode: OfferMode,
+        timeout_minutes: Option<i32>,
+    ) -> Result<Vec<SubstitutionOffer>> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<SubstitutionService>()?;
+        Ok(service
+            .offer_substitution(claims.workspace_id, substitution_id, teacher_ids, mode, timeout_minutes)
+            .await?)
+    }
+
+    async fn decline_substitution_offer(&self, ctx: &Context<'_>, substitution_id: Uuid) -> Result<SubstitutionOffer> {
+        let (claims, _) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<SubstitutionService>()?;
+        Ok(service.decline_offer(claims.workspace_id, substitution_id, claims.sub).await?)
     }
 
     async fn reject_substitution(&self, ctx: &Context<'_>, substitution_id: Uuid) -> Result<Substitution> {
-        let _user = ctx.data::<Option<User>>()?.as_ref().ok_or(AppError::Unauthorized)?;
-        // Based on instructions, anyone authorized (Admin or Teacher) might be able to reject?
-        // Usually it's the admin or the teacher who was assigned.
-        // The prompt says: "Add a rejectSubstitution(substitution_id: UUID) mutation." without specifying role restriction, 
-        // but typically it's also restricted.
-        
+        let claims = require_workspace_editor(ctx).await?;
         let service = ctx.data::<SubstitutionService>()?;
-        Ok(service.reject_substitution(substitution_id).await?)
+        Ok(service.reject_substitution(claims.workspace_id, substitution_id).await?)
+    }
+
+    /// Cancels a lesson on a single date.
+    async fn cancel_lesson(
+        &self,
+        ctx: &Context<'_>,
+        timetable_entry_id: Uuid,
+        date: NaiveDate,
+        reason: Option<String>,
+    ) -> Result<LessonException> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<LessonExceptionService>>()?;
+        Ok(service.cancel_lesson(claims.workspace_id, claims.sub, timetable_entry_id, date, reason).await?)
+    }
+
+    /// Moves a lesson to another room for a single date.
+    async fn change_lesson_room(
+        &self,
+        ctx: &Context<'_>,
+        timetable_entry_id: Uuid,
+        date: NaiveDate,
+        room_id: Uuid,
+        reason: Option<String>,
+    ) -> Result<LessonException> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<LessonExceptionService>>()?;
+        Ok(service.change_room(claims.workspace_id, claims.sub, timetable_entry_id, date, room_id, reason).await?)
+    }
+
+    async fn add_extra_lesson(&self, ctx: &Context<'_>, input: ExtraLessonInput) -> Result<LessonException> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<LessonExceptionService>>()?;
+        Ok(service
+            .add_extra_lesson(claims.workspace_id, claims.sub, ExtraLesson {
+                date: input.date,
+                course_id: input.course_id,
+                teacher_id: input.teacher_id,
+                room_id: input.room_id,
+                start_time: input.start_time,
+                end_time: input.end_time,
+                reason: input.reason,
+            })
+            .await?)
+    }
+
+    /// Undoes a cancellation, room change or extra lesson. Teacher changes
+    /// are undone by cancelling their lesson swap.
+    async fn delete_lesson_exception(&self, ctx: &Context<'_>, id: Uuid) -> Result<LessonException> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<LessonExceptionService>>()?;
+        Ok(service.delete_exception(claims.workspace_id, id).await?)
+    }
+
+    /// Proposes swapping one of the caller's lessons with a colleague's.
+    async fn propose_lesson_swap(&self, ctx: &Context<'_>, input: ProposeLessonSwapInput) -> Result<LessonSwap> {
+        let (claims, _) = require_workspace_member(ctx).await?;
+        if claims.role != UserRole::Teacher {
+            return Err(AppError::Forbidden("Only teachers can propose lesson swaps".to_string()).into());
+        }
+
+        let service = ctx.data::<Arc<LessonSwapService>>()?;
+        Ok(service
+            .propose(claims.workspace_id, claims.sub, SwapProposal {
+                timetable_entry_id: input.timetable_entry_id,
+                date: input.date,
+                colleague_entry_id: input.colleague_entry_id,
+                colleague_date: input.colleague_date,
+                reason: input.reason,
+            })
+            .await?)
+    }
+
+    async fn accept_lesson_swap(&self, ctx: &Context<'_>, id: Uuid) -> Result<LessonSwap> {
+        let (claims, _) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<Arc<LessonSwapService>>()?;
+        Ok(service.respond(claims.workspace_id, id, claims.sub, true).await?)
+    }
+
+    async fn decline_lesson_swap(&self, ctx: &Context<'_>, id: Uuid) -> Result<LessonSwap> {
+        let (claims, _) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<Arc<LessonSwapService>>()?;
+        Ok(service.respond(claims.workspace_id, id, claims.sub, false).await?)
+    }
+
+    /// Approves an accepted swap and applies it to both lessons.
+    async fn approve_lesson_swap(&self, ctx: &Context<'_>, id: Uuid, note: Option<String>) -> Result<LessonSwap> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<LessonSwapService>>()?;
+        Ok(service.approve(claims.workspace_id, id, claims.sub, note).await?)
+    }
+
+    async fn reject_lesson_swap(&self, ctx: &Context<'_>, id: Uuid, note: Option<String>) -> Result<LessonSwap> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<LessonSwapService>>()?;
+        Ok(service.reject(claims.workspace_id, id, claims.sub, note).await?)
+    }
+
+    /// Withdraws a swap before it is approved, or, for admins, undoes an
+    /// approved one.
+    async fn cancel_lesson_swap(&self, ctx: &Context<'_>, id: Uuid) -> Result<LessonSwap> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<Arc<LessonSwapService>>()?;
+        Ok(service.cancel(claims.workspace_id, id, claims.sub, role.can_edit()).await?)
     }
 
     async fn add_availability(&self, ctx: &Context<'_>, input: AvailabilityInput) -> Result<Availability> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        if !role.can_edit() && claims.sub != input.teacher_id {
+            return Err(AppError::Forbidden("Teachers can only change their own availability".to_string()).into());
+        }
         let service = ctx.data::<Arc<AvailabilityService>>()?;
-        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
         Ok(service.submit_availability(claims.workspace_id, input).await?)
     }
 
+    async fn update_availability(&self, ctx: &Context<'_>, id: Uuid, input: AvailabilityWindowInput) -> Result<Availability> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<Arc<AvailabilityService>>()?;
+        Ok(service.update_availability(claims.workspace_id, id, claims.sub, role.can_edit(), input).await?)
+    }
+
+    async fn delete_availability(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<Arc<AvailabilityService>>()?;
+        service.delete_availability(claims.workspace_id, id, claims.sub, role.can_edit()).await?;
+        Ok(true)
+    }
+
+    /// Marks a teacher as away, or as available outside their weekly
+    /// pattern, for a date or a range.
+    async fn add_availability_override(&self, ctx: &Context<'_>, input: AvailabilityOverrideInput) -> Result<AvailabilityOverride> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        if !role.can_edit() && claims.sub != input.teacher_id {
+            return Err(AppError::Forbidden("Teachers can only change their own availability".to_string()).into());
+        }
+        let service = ctx.data::<Arc<AvailabilityService>>()?;
+        Ok(service.add_override(claims.workspace_id, claims.sub, input).await?)
+    }
+
+    async fn delete_availability_override(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<Arc<AvailabilityService>>()?;
+        service.delete_override(claims.workspace_id, id, claims.sub, role.can_edit()).await?;
+        Ok(true)
+    }
+
+    /// Replaces a teacher's whole weekly availability with `windows`.
+    async fn set_weekly_availability(
+        &self,
+        ctx: &Context<'_>,
+        teacher_id: Uuid,
+        windows: Vec<AvailabilityWindowInput>,
+    ) -> Result<Vec<Availability>> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        if !role.can_edit() && claims.sub != teacher_id {
+            return Err(AppError::Forbidden("Teachers can only change their own availability".to_string()).into());
+        }
+        let service = ctx.data::<Arc<AvailabilityService>>()?;
+        Ok(service.set_weekly_availability(claims.workspace_id, teacher_id, windows).await?)
+    }
+
+    /// Adds a weighted scheduling wish for a teacher.
+    async fn create_teacher_preference(
+        &self,
+        ctx: &Context<'_>,
+        teacher_id: Uuid,
+        input: TeacherPreferenceInput,
+    ) -> Result<TeacherPreference> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        if !role.can_edit() && claims.sub != teacher_id {
+            return Err(AppError::Forbidden("Teachers can only change their own preferences".to_string()).into());
+        }
+        let service = ctx.data::<Arc<TeacherPreferenceService>>()?;
+        Ok(service.create_preference(claims.workspace_id, teacher_id, input).await?)
+    }
+
+    async fn update_teacher_preference(&self, ctx: &Context<'_>, id: Uuid, input: TeacherPreferenceInput) -> Result<TeacherPreference> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<Arc<TeacherPreferenceService>>()?;
+        Ok(service.update_preference(claims.workspace_id, id, claims.sub, role.can_edit(), input).await?)
+    }
+
+    async fn delete_teacher_preference(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<Arc<TeacherPreferenceService>>()?;
+        service.delete_preference(claims.workspace_id, id, claims.sub, role.can_edit()).await?;
+        Ok(true)
+    }
+
+    /// Asks every teacher to submit their availability for a term by the
+    /// deadline.
+    async fn open_availability_round(&self, ctx: &Context<'_>, input: OpenAvailabilityRoundInput) -> Result<AvailabilityRound> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<AvailabilityRoundService>>()?;
+        Ok(service
+            .open_round(claims.workspace_id, claims.sub, input.term, input.year, input.deadline, input.remind_hours_before)
+            .await?)
+    }
+
+    /// Submits the caller's weekly availability for the round, locking it
+    /// until the round closes.
+    async fn submit_availability(&self, ctx: &Context<'_>, round_id: Uuid) -> Result<AvailabilitySubmission> {
+        let (claims, _) = require_workspace_member(ctx).await?;
+        if claims.role != UserRole::Teacher {
+            return Err(AppError::Forbidden("Only teachers submit availability".to_string()).into());
+        }
+        let service = ctx.data::<Arc<AvailabilityRoundService>>()?;
+        Ok(service.submit(claims.workspace_id, round_id, claims.sub).await?)
+    }
+
+    /// Lets a teacher change their submitted availability again.
+    async fn unlock_availability_submission(&self, ctx: &Context<'_>, round_id: Uuid, teacher_id: Uuid) -> Result<bool> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<AvailabilityRoundService>>()?;
+        service.unlock(claims.workspace_id, round_id, teacher_id).await?;
+        Ok(true)
+    }
+
+    /// Reminds the teachers who have not submitted yet and returns how many
+    /// were reminded.
+    async fn remind_availability_round(&self, ctx: &Context<'_>, id: Uuid) -> Result<i32> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<AvailabilityRoundService>>()?;
+        Ok(service.remind(claims.workspace_id, id).await? as i32)
+    }
+
+    /// Closes the round before its deadline and freezes everyone's
+    /// availability for drafting.
+    async fn close_availability_round(&self, ctx: &Context<'_>, id: Uuid) -> Result<AvailabilityRound> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<AvailabilityRoundService>>()?;
+        Ok(service.close(claims.workspace_id, id).await?)
+    }
+
+    async fn create_student_group(&self, ctx: &Context<'_>, input: StudentGroupInput) -> Result<StudentGroup> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<StudentGroupService>>()?;
+        Ok(service.create_group(claims.workspace_id, input).await?)
+    }
+
+    async fn update_student_group(&self, ctx: &Context<'_>, id: Uuid, input: StudentGroupInput) -> Result<StudentGroup> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<StudentGroupService>>()?;
+        Ok(service.update_group(claims.workspace_id, id, input).await?)
+    }
+
+    /// Deletes a group together with the groups within it.
+    async fn delete_student_group(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<StudentGroupService>>()?;
+        service.delete_group(claims.workspace_id, id).await?;
+        Ok(true)
+    }
+
+    /// Replaces the groups a course is taught to.
+    async fn set_course_student_groups(
+        &self,
+        ctx: &Context<'_>,
+        course_id: Uuid,
+        student_group_ids: Vec<Uuid>,
+    ) -> Result<Vec<StudentGroup>> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<StudentGroupService>>()?;
+        Ok(service.set_course_groups(claims.workspace_id, course_id, student_group_ids).await?)
+    }
+
+    /// Sets the groups of one draft lesson in place of its course's; an
+    /// empty list goes back to the course's groups.
+    async fn set_draft_entry_student_groups(
+        &self,
+        ctx: &Context<'_>,
+        draft_entry_id: Uuid,
+        student_group_ids: Vec<Uuid>,
+    ) -> Result<bool> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<DraftEntryService>>()?;
+        service.set_entry_groups(claims.workspace_id, draft_entry_id, student_group_ids).await?;
+        Ok(true)
+    }
+
+    async fn create_student(&self, ctx: &Context<'_>, input: StudentInput) -> Result<Student> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        Ok(service.create_student(claims.workspace_id, input).await?)
+    }
+
+    async fn update_student(&self, ctx: &Context<'_>, id: Uuid, input: StudentInput) -> Result<Student> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        Ok(service.update_student(claims.workspace_id, id, input).await?)
+    }
+
+    async fn delete_student(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        service.delete_student(claims.workspace_id, id).await?;
+        Ok(true)
+    }
+
+    async fn create_course_section(&self, ctx: &Context<'_>, course_id: Uuid, name: String) -> Result<CourseSection> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        Ok(service.create_section(claims.workspace_id, course_id, name).await?)
+    }
+
+    async fn rename_course_section(&self, ctx: &Context<'_>, id: Uuid, name: String) -> Result<CourseSection> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        Ok(service.rename_section(claims.workspace_id, id, name).await?)
+    }
+
+    /// Deletes a section with its enrolments; its draft lessons become
+    /// lessons for the whole course.
+    async fn delete_course_section(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        service.delete_section(claims.workspace_id, id).await?;
+        Ok(true)
+    }
+
+    /// Enrols a student in a section, moving them out of any other section
+    /// of the same course.
+    async fn enroll_student(&self, ctx: &Context<'_>, student_id: Uuid, course_section_id: Uuid) -> Result<bool> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        service.enroll(claims.workspace_id, student_id, course_section_id).await?;
+        Ok(true)
+    }
+
+    async fn unenroll_student(&self, ctx: &Context<'_>, student_id: Uuid, course_section_id: Uuid) -> Result<bool> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        service.unenroll(claims.workspace_id, student_id, course_section_id).await?;
+        Ok(true)
+    }
+
+    /// Imports enrolments from CSV text with a header row naming the
+    /// `student_number`, `name`, `email`, `course_code` and `section`
+    /// columns. Nothing is imported when a line is wrong.
+    async fn import_enrollments(&self, ctx: &Context<'_>, csv: String) -> Result<EnrollmentImport> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        Ok(service.import_csv(claims.workspace_id, &csv).await?)
+    }
+
+    async fn create_room_feature(&self, ctx: &Context<'_>, name: String) -> Result<RoomFeature> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<RoomFeatureService>>()?;
+        Ok(service.create_feature(claims.workspace_id, name).await?)
+    }
+
+    async fn rename_room_feature(&self, ctx: &Context<'_>, id: Uuid, name: String) -> Result<RoomFeature> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<RoomFeatureService>>()?;
+        Ok(service.rename_feature(claims.workspace_id, id, name).await?)
+    }
+
+    /// Deletes a feature from the catalogue and from every room, course
+    /// and group.
+    async fn delete_room_feature(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<RoomFeatureService>>()?;
+        service.delete_feature(claims.workspace_id, id).await?;
+        Ok(true)
+    }
+
+    /// Replaces the features a room has.
+    async fn set_room_features(&self, ctx: &Context<'_>, room_id: Uuid, room_feature_ids: Vec<Uuid>) -> Result<Vec<RoomFeature>> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<RoomFeatureService>>()?;
+        Ok(service.set_room_features(claims.workspace_id, room_id, room_feature_ids).await?)
+    }
+
+    /// Replaces the features a course needs in the rooms of its lessons.
+    async fn set_course_room_features(
+        &self,
+        ctx: &Context<'_>,
+        course_id: Uuid,
+        requirements: Vec<RoomFeatureRequirementInput>,
+    ) -> Result<Vec<RoomFeatureRequirement>> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<RoomFeatureService>>()?;
+        Ok(service.set_course_requirements(claims.workspace_id, course_id, requirements).await?)
+    }
+
+    /// Replaces the features a group needs in the rooms of its lessons,
+    /// including lessons of the groups it is part of.
+    async fn set_student_group_room_features(
+        &self,
+        ctx: &Context<'_>,
+        student_group_id: Uuid,
+        requirements: Vec<RoomFeatureRequirementInput>,
+    ) -> Result<Vec<RoomFeatureRequirement>> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<RoomFeatureService>>()?;
+        Ok(service.set_group_requirements(claims.workspace_id, student_group_id, requirements).await?)
+    }
+
+    /// Qualifies a teacher for a course, or changes how keen they are on
+    /// it. The preference defaults to `NEUTRAL`.
+    async fn set_teacher_qualification(
+        &self,
+        ctx: &Context<'_>,
+        teacher_id: Uuid,
+        course_id: Uuid,
+        preference: Option<QualificationPreference>,
+    ) -> Result<TeacherQualification> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<TeacherQualificationService>>()?;
+        Ok(service.set_qualification(claims.workspace_id, teacher_id, course_id, preference).await?)
+    }
+
+    async fn remove_teacher_qualification(&self, ctx: &Context<'_>, teacher_id: Uuid, course_id: Uuid) -> Result<bool> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<TeacherQualificationService>>()?;
+        service.remove_qualification(claims.workspace_id, teacher_id, course_id).await?;
+        Ok(true)
+    }
+
+    /// Imports qualifications from CSV text with a header naming the
+    /// `teacher_email`, `course_code` and optionally `preference` columns.
+    /// Nothing is imported when any line is wrong.
+    async fn import_teacher_qualifications(&self, ctx: &Context<'_>, csv: String) -> Result<QualificationImport> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<TeacherQualificationService>>()?;
+        Ok(service.import_csv(claims.workspace_id, &csv).await?)
+    }
+
     async fn resolve_conflict(&self, ctx: &Context<'_>, conflict_id: Uuid, status: ConflictStatus) -> Result<Conflict> {
         let service = ctx.data::<Arc<ConflictService>>()?;
-        Ok(service.resolve_conflict(conflict_id, status).await?)
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.resolve_conflict(claims.workspace_id, conflict_id, status).await?)
     }
 
     async fn save_draft_timetable(&self, ctx: &Context<'_>, input: DraftTimetableInput) -> Result<DraftTimetable> {
@@ -243,7 +772,7 @@ impl Mutation {
         let entries = input.entries.clone();
         let draft = draft_service.create_draft(claims.workspace_id, input.name, input.term, input.year).await?;
         
-        entry_service.add_entries_to_draft(draft.id, entries).await?;
+        entry_service.add_entries_to_draft(claims.workspace_id, draft.id, entries).await?;
         
         Ok(draft)
     }
@@ -287,4 +816,738 @@ impl Mutation {
         service.accept_invite(claims.sub, input.token).await?;
         Ok(true)
     }
+
+    async fn mark_notification_read(&self, ctx: &Context<'_>, id: Uuid) -> Result<Notification> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<NotificationService>>()?;
+        Ok(service.mark_read(claims.workspace_id, claims.sub, id).await?)
+    }
+
+    /// Marks every notification of the caller as read and returns how many
+    /// were unread.
+    async fn mark_all_read(&self, ctx: &Context<'_>) -> Result<i64> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<NotificationService>>()?;
+        Ok(service.mark_all_read(claims.workspace_id, claims.sub).await? as i64)
+    }
+
+    /// Sets how the caller receives one type of notification in the current
+    /// workspace.
+    async fn set_notification_preference(
+        &self,
+        ctx: &Context<'_>,
+        input: NotificationPreferenceInput,
+    ) -> Result<NotificationPreference> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<NotificationService>>()?;
+        Ok(service.set_preference(NotificationPreference {
+            workspace_id: claims.workspace_id,
+            user_id: claims.sub,
+            notification_type: input.notification_type,
+            delivery: input.delivery,
+            in_app: input.in_app,
+            email: input.email,
+            webhook: input.webhook,
+        }).await?)
+    }
+
+    async fn update_notification_settings(
+        &self,
+        ctx: &Context<'_>,
+        input: NotificationSettingsInput,
+    ) -> Result<NotificationSettings> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<NotificationService>>()?;
+        Ok(service
+            .update_settings(claims.workspace_id, claims.sub, input.timezone, input.digest_time, input.webhook_url)
+            .await?)
+    }
+
+    /// Sets the department of a workspace member; pass no department to
+    /// clear it.
+    async fn set_member_department(&self, ctx: &Context<'_>, user_id: Uuid, department: Option<String>) -> Result<bool> {
+        let claims = require_workspace_owner(ctx).await?;
+        let service = ctx.data::<Arc<WorkspaceService>>()?;
+        service.set_member_department(claims.workspace_id, user_id, department).await?;
+        Ok(true)
+    }
+
+    /// Sets the weekly contract hours of a workspace member, used to weigh
+    /// how much cover they take on; pass no hours to clear them.
+    async fn set_member_contract_hours(&self, ctx: &Context<'_>, user_id: Uuid, contract_hours: Option<f64>) -> Result<bool> {
+        let claims = require_workspace_owner(ctx).await?;
+        let service = ctx.data::<Arc<WorkspaceService>>()?;
+        service.set_member_contract_hours(claims.workspace_id, user_id, contract_hours).await?;
+        Ok(true)
+    }
+
+    /// Lets lessons exceed the capacity of their room by up to `percent`
+    /// before conflict detection reports them.
+    async fn set_room_capacity_tolerance(&self, ctx: &Context<'_>, percent: i32) -> Result<Workspace> {
+        let claims = require_workspace_owner(ctx).await?;
+        let service = ctx.data::<Arc<WorkspaceService>>()?;
+        Ok(service.set_room_capacity_tolerance(claims.workspace_id, percent).await?)
+    }
+
+    /// Returns a secret URL serving the caller's lessons as an iCal feed.
+    async fn create_calendar_feed(&self, ctx: &Context<'_>) -> Result<String> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<OccurrenceService>>()?;
+        Ok(service.create_feed(claims.workspace_id, claims.sub).await?)
+    }
+
+    /// Disables every feed URL of the caller and returns how many there were.
+    async fn revoke_calendar_feeds(&self, ctx: &Context<'_>) -> Result<i64> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<OccurrenceService>>()?;
+        Ok(service.revoke_feeds(claims.workspace_id, claims.sub).await? as i64)
+    }
+
+    async fn create_webhook_endpoint(&self, ctx: &Context<'_>, url: String) -> Result<CreatedWebhookEndpoint> {
+        let claims = require_workspace_owner(ctx).await?;
+        let service = ctx.data::<Arc<OutboxService>>()?;
+        Ok(service.create_webhook_endpoint(claims.workspace_id, url).await?)
+    }
+
+    async fn delete_webhook_endpoint(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
+        let claims = require_workspace_owner(ctx).await?;
+        let service = ctx.data::<Arc<OutboxService>>()?;
+        Ok(service.delete_webhook_endpoint(claims.workspace_id, id).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::Request;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::models::WorkspaceRole;
+    use crate::test_support;
+
+    #[sqlx::test]
+    async fn created_webhook_endpoints_come_with_their_secret(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let owner = test_support::member(&pool, workspace_id, WorkspaceRole::Owner).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::outbox(&pool))
+            .finish();
+        let mutation = r#"mutation { createWebhookEndpoint(url: "https://93.184.216.34/hooks") { id url secret } }"#;
+
+        let response = schema
+            .execute(Request::new(mutation).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners can do this"]);
+
+        let response = schema
+            .execute(Request::new(mutation).data(test_support::claims(owner, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        let created = response.data.into_json().unwrap();
+        let secret: String = sqlx::query_scalar("SELECT secret FROM webhook_endpoints")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(created["createWebhookEndpoint"]["secret"], secret.as_str());
+    }
+
+    #[sqlx::test]
+    async fn only_editors_request_substitutions(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let teacher = test_support::teacher(&pool, workspace_id).await;
+        let outsider = test_support::user(&pool).await;
+        let entry_id = test_support::lesson(&pool, workspace_id, teacher, 1).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::substitution_service(&pool))
+            .finish();
+        let mutation = format!(
+            r#"mutation {{ requestSubstitution(timetableEntryId: "{}", startsOn: "2026-11-02") {{ status }} }}"#,
+            entry_id
+        );
+
+        let response = schema
+            .execute(Request::new(&mutation).data(test_support::claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema
+            .execute(Request::new(&mutation).data(test_support::claims(outsider, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: You are not a member of this workspace"]);
+
+        let response = schema
+            .execute(Request::new(&mutation).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({ "requestSubstitution": { "status": "PENDING" } })
+        );
+    }
+
+    #[sqlx::test]
+    async fn only_editors_report_and_cancel_absences(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let teacher = test_support::teacher(&pool, workspace_id).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::substitution_service(&pool))
+            .finish();
+        let report = format!(
+            r#"mutation {{ reportAbsence(teacherId: "{}", from: "2026-11-02", to: "2026-11-03") {{ absence {{ id reportedBy }} }} }}"#,
+            teacher
+        );
+
+        let response = schema
+            .execute(Request::new(&report).data(test_support::claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema
+            .execute(Request::new(&report).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        let absence = response.data.into_json().unwrap()["reportAbsence"]["absence"].clone();
+        assert_eq!(absence["reportedBy"], editor.to_string());
+
+        let cancel = format!(r#"mutation {{ cancelAbsence(absenceId: {}) {{ cancelledAt }} }}"#, absence["id"]);
+        let response = schema
+            .execute(Request::new(&cancel).data(test_support::claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema
+            .execute(Request::new(&cancel).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        assert!(!response.data.into_json().unwrap()["cancelAbsence"]["cancelledAt"].is_null());
+    }
+
+    async fn requested_substitution(pool: &PgPool, workspace_id: Uuid, absent_teacher: Uuid) -> Uuid {
+        let entry_id = test_support::lesson(pool, workspace_id, absent_teacher, 1).await;
+        let monday = NaiveDate::from_ymd_opt(2026, 11, 2).unwrap();
+        test_support::substitution_service(pool)
+            .request_substitution(workspace_id, entry_id, monday, None)
+            .await
+            .unwrap()
+            .id
+    }
+
+    #[sqlx::test]
+    async fn substitutions_go_to_the_first_teacher_to_accept(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let absent = test_support::teacher(&pool, workspace_id).await;
+        let first = test_support::teacher(&pool, workspace_id).await;
+        let second = test_support::teacher(&pool, workspace_id).await;
+        let viewer = test_support::member(&pool, workspace_id, WorkspaceRole::Viewer).await;
+        let substitution_id = requested_substitution(&pool, workspace_id, absent).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::substitution_service(&pool))
+            .finish();
+        let accept = format!(
+            r#"mutation {{ acceptSubstitution(substitutionId: "{}") {{ status substitutingTeacherId }} }}"#,
+            substitution_id
+        );
+
+        let response = schema
+            .execute(Request::new(&accept).data(test_support::claims(viewer, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only teachers can accept substitutions"]);
+
+        let response = schema
+            .execute(Request::new(&accept).data(test_support::teacher_claims(first, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({ "acceptSubstitution": { "status": "ACCEPTED", "substitutingTeacherId": first } })
+        );
+
+        let response = schema
+            .execute(Request::new(&accept).data(test_support::teacher_claims(second, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Conflict: This substitution is no longer open"]);
+    }
+
+    #[sqlx::test]
+    async fn declined_cascade_offers_move_on_to_the_next_teacher(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let absent = test_support::teacher(&pool, workspace_id).await;
+        let first = test_support::teacher(&pool, workspace_id).await;
+        let second = test_support::teacher(&pool, workspace_id).await;
+        let substitution_id = requested_substitution(&pool, workspace_id, absent).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::substitution_service(&pool))
+            .finish();
+        let offer = format!(
+            r#"mutation {{ offerSubstitution(substitutionId: "{}", teacherIds: ["{}", "{}"], mode: CASCADE) {{ teacherId }} }}"#,
+            substitution_id, first, second
+        );
+        let accept = format!(r#"mutation {{ acceptSubstitution(substitutionId: "{}") {{ status }} }}"#, substitution_id);
+        let decline = format!(
+            r#"mutation {{ declineSubstitutionOffer(substitutionId: "{}") {{ status }} }}"#,
+            substitution_id
+        );
+
+        let response = schema
+            .execute(Request::new(&offer).data(test_support::teacher_claims(first, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema
+            .execute(Request::new(&offer).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+
+        let response = schema
+            .execute(Request::new(&accept).data(test_support::teacher_claims(second, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: This substitution is not offered to you"]);
+
+        let response = schema
+            .execute(Request::new(&decline).data(test_support::teacher_claims(first, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({ "declineSubstitutionOffer": { "status": "DECLINED" } })
+        );
+
+        let response = schema
+            .execute(Request::new(&accept).data(test_support::teacher_claims(second, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({ "acceptSubstitution": { "status": "ACCEPTED" } })
+        );
+    }
+
+    #[sqlx::test]
+    async fn only_editors_reject_substitutions(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let absent = test_support::teacher(&pool, workspace_id).await;
+        let substitution_id = requested_substitution(&pool, workspace_id, absent).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::substitution_service(&pool))
+            .finish();
+        let reject = format!(r#"mutation {{ rejectSubstitution(substitutionId: "{}") {{ status }} }}"#, substitution_id);
+
+        let response = schema
+            .execute(Request::new(&reject).data(test_support::claims(absent, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema
+            .execute(Request::new(&reject).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({ "rejectSubstitution": { "status": "REJECTED" } })
+        );
+    }
+
+    #[sqlx::test]
+    async fn only_editors_change_single_lessons(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let teacher = test_support::teacher(&pool, workspace_id).await;
+        let entry_id = test_support::lesson(&pool, workspace_id, teacher, 1).await;
+        test_support::publish(&pool, workspace_id, &[entry_id]).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::lesson_exception_service(&pool))
+            .finish();
+        let cancel = format!(
+            r#"mutation {{ cancelLesson(timetableEntryId: "{}", date: "2026-11-02") {{ kind createdBy }} }}"#,
+            entry_id
+        );
+
+        let response = schema
+            .execute(Request::new(&cancel).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema
+            .execute(Request::new(&cancel).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({ "cancelLesson": { "kind": "CANCELLATION", "createdBy": editor } })
+        );
+    }
+
+    #[sqlx::test]
+    async fn lesson_swaps_need_the_colleague_and_an_editor(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let requester = test_support::teacher(&pool, workspace_id).await;
+        let colleague = test_support::teacher(&pool, workspace_id).await;
+        let monday = test_support::lesson(&pool, workspace_id, requester, 1).await;
+        let tuesday = test_support::lesson(&pool, workspace_id, colleague, 2).await;
+        test_support::publish(&pool, workspace_id, &[monday, tuesday]).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::lesson_swap_service(&pool))
+            .finish();
+        let propose = format!(
+            r#"mutation {{ proposeLessonSwap(input: {{ timetableEntryId: "{}", date: "2026-11-02", colleagueEntryId: "{}", colleagueDate: "2026-11-03" }}) {{ id }} }}"#,
+            monday, tuesday
+        );
+
+        let response = schema
+            .execute(Request::new(&propose).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only teachers can propose lesson swaps"]);
+
+        let response = schema
+            .execute(Request::new(&propose).data(test_support::teacher_claims(requester, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        let swap_id = response.data.into_json().unwrap()["proposeLessonSwap"]["id"].clone();
+
+        let accept = format!(r#"mutation {{ acceptLessonSwap(id: {}) {{ status }} }}"#, swap_id);
+        let response = schema
+            .execute(Request::new(&accept).data(test_support::teacher_claims(colleague, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+
+        let approve = format!(r#"mutation {{ approveLessonSwap(id: {}) {{ status }} }}"#, swap_id);
+        let response = schema
+            .execute(Request::new(&approve).data(test_support::teacher_claims(requester, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema
+            .execute(Request::new(&approve).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({ "approveLessonSwap": { "status": "APPROVED" } })
+        );
+
+        // Only editors may undo an approved swap
+        let cancel = format!(r#"mutation {{ cancelLessonSwap(id: {}) {{ status }} }}"#, swap_id);
+        let response = schema
+            .execute(Request::new(&cancel).data(test_support::teacher_claims(requester, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only admins can undo an approved swap"]);
+
+        let response = schema
+            .execute(Request::new(&cancel).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+    }
+
+    #[sqlx::test]
+    async fn teachers_only_change_their_own_availability(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let teacher = test_support::teacher(&pool, workspace_id).await;
+        let colleague = test_support::teacher(&pool, workspace_id).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::availability_service(&pool))
+            .finish();
+        let set = |teacher_id: Uuid| {
+            format!(
+                r#"mutation {{ setWeeklyAvailability(teacherId: "{}", windows: [{{ dayOfWeek: 1, startTime: "08:00:00", endTime: "12:00:00", isPreferred: false }}]) {{ id }} }}"#,
+                teacher_id
+            )
+        };
+
+        let response = schema
+            .execute(Request::new(set(teacher)).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+
+        let response = schema
+            .execute(Request::new(set(colleague)).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Teachers can only change their own availability"]);
+
+        let response = schema
+            .execute(Request::new(set(colleague)).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        let window_id = response.data.into_json().unwrap()["setWeeklyAvailability"][0]["id"].clone();
+
+        let delete = format!("mutation {{ deleteAvailability(id: {}) }}", window_id);
+        let response = schema
+            .execute(Request::new(&delete).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Teachers can only change their own availability"]);
+
+        let response = schema
+            .execute(Request::new(&delete).data(test_support::teacher_claims(colleague, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+    }
+
+    #[sqlx::test]
+    async fn teachers_only_change_their_own_availability_overrides(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let teacher = test_support::teacher(&pool, workspace_id).await;
+        let colleague = test_support::teacher(&pool, workspace_id).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::availability_service(&pool))
+            .finish();
+        let add = format!(
+            r#"mutation {{ addAvailabilityOverride(input: {{ teacherId: "{}", kind: UNAVAILABLE, startsOn: "2026-11-02" }}) {{ id createdBy }} }}"#,
+            colleague
+        );
+
+        let response = schema
+            .execute(Request::new(&add).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Teachers can only change their own availability"]);
+
+        let response = schema
+            .execute(Request::new(&add).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        let created = response.data.into_json().unwrap()["addAvailabilityOverride"].clone();
+        assert_eq!(created["createdBy"], editor.to_string());
+
+        let delete = format!("mutation {{ deleteAvailabilityOverride(id: {}) }}", created["id"]);
+        let response = schema
+            .execute(Request::new(&delete).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Teachers can only change their own availability"]);
+
+        let response = schema
+            .execute(Request::new(&delete).data(test_support::teacher_claims(colleague, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+    }
+
+    #[sqlx::test]
+    async fn editors_run_availability_rounds_and_teachers_submit(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let viewer = test_support::member(&pool, workspace_id, WorkspaceRole::Viewer).await;
+        let teacher = test_support::teacher(&pool, workspace_id).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::availability_round_service(&pool))
+            .finish();
+        let open = r#"mutation { openAvailabilityRound(input: { term: "Autumn", year: 2026, deadline: "2026-12-01T12:00:00Z" }) { id } }"#;
+
+        let response = schema.execute(Request::new(open).data(test_support::claims(viewer, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema.execute(Request::new(open).data(test_support::claims(editor, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        let round_id = response.data.into_json().unwrap()["openAvailabilityRound"]["id"].clone();
+
+        let submit = format!("mutation {{ submitAvailability(roundId: {}) {{ teacherId }} }}", round_id);
+        let response = schema.execute(Request::new(&submit).data(test_support::claims(editor, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only teachers submit availability"]);
+
+        let response = schema
+            .execute(Request::new(&submit).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+
+        let unlock = format!(r#"mutation {{ unlockAvailabilitySubmission(roundId: {}, teacherId: "{}") }}"#, round_id, teacher);
+        let response = schema
+            .execute(Request::new(&unlock).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema.execute(Request::new(&unlock).data(test_support::claims(editor, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+    }
+
+    #[sqlx::test]
+    async fn teachers_only_manage_their_own_preferences(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let teacher = test_support::teacher(&pool, workspace_id).await;
+        let colleague = test_support::teacher(&pool, workspace_id).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::teacher_preference_service(&pool))
+            .finish();
+        let create = |teacher_id: Uuid| {
+            format!(
+                r#"mutation {{ createTeacherPreference(teacherId: "{}", input: {{ kind: DAY_OFF, weight: 5, dayOfWeek: 5 }}) {{ id }} }}"#,
+                teacher_id
+            )
+        };
+
+        let response = schema
+            .execute(Request::new(create(colleague)).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Teachers can only change their own preferences"]);
+
+        let response = schema
+            .execute(Request::new(create(colleague)).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        let response = schema
+            .execute(Request::new(create(teacher)).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+
+        // Teachers only see their own, whichever teacher they ask for
+        let list = format!(r#"{{ teacherPreferences(teacherId: "{}") {{ teacherId }} }}"#, colleague);
+        let response = schema
+            .execute(Request::new(&list).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        let listed = response.data.into_json().unwrap()["teacherPreferences"].clone();
+        assert_eq!(listed, serde_json::json!([{ "teacherId": teacher.to_string() }]));
+
+        let response = schema
+            .execute(Request::new("{ teacherPreferences { teacherId } }").data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(response.data.into_json().unwrap()["teacherPreferences"].as_array().unwrap().len(), 2);
+
+        let scores = r#"{ preferenceScores(draftTimetableId: "00000000-0000-0000-0000-000000000000") { teacherId } }"#;
+        let response = schema
+            .execute(Request::new(scores).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+    }
+
+    #[sqlx::test]
+    async fn only_editors_manage_student_groups(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let viewer = test_support::member(&pool, workspace_id, WorkspaceRole::Viewer).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::student_group_service(&pool))
+            .finish();
+        let create = r#"mutation { createStudentGroup(input: { name: "7a", kind: CLASS }) { id } }"#;
+
+        let response = schema.execute(Request::new(create).data(test_support::claims(viewer, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema.execute(Request::new(create).data(test_support::claims(editor, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        let group_id = response.data.into_json().unwrap()["createStudentGroup"]["id"].clone();
+
+        let delete = format!("mutation {{ deleteStudentGroup(id: {}) }}", group_id);
+        let response = schema.execute(Request::new(&delete).data(test_support::claims(viewer, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema.execute(Request::new(&delete).data(test_support::claims(editor, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+    }
+
+    #[sqlx::test]
+    async fn only_editors_manage_students_and_students_see_their_own_timetable(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let student = test_support::member(&pool, workspace_id, WorkspaceRole::Viewer).await;
+        let classmate = test_support::member(&pool, workspace_id, WorkspaceRole::Viewer).await;
+        let teacher = test_support::teacher(&pool, workspace_id).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::enrollment_service(&pool))
+            .finish();
+        let create = format!(
+            r#"mutation {{ createStudent(input: {{ studentNumber: "1001", name: "Ada", userId: "{}" }}) {{ id }} }}"#,
+            student
+        );
+
+        let response = schema.execute(Request::new(&create).data(test_support::claims(student, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema.execute(Request::new(&create).data(test_support::claims(editor, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        let student_id = response.data.into_json().unwrap()["createStudent"]["id"].clone();
+
+        let roster = "{ students { id } }";
+        let response = schema.execute(Request::new(roster).data(test_support::claims(student, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only staff can see the roster"]);
+        let response = schema
+            .execute(Request::new(roster).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+
+        let timetable = format!("{{ studentTimetable(studentId: {}) {{ courseId }} }}", student_id);
+        let response = schema.execute(Request::new(&timetable).data(test_support::claims(classmate, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Students can only see their own timetable"]);
+        let response = schema.execute(Request::new(&timetable).data(test_support::claims(student, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+    }
+
+    #[sqlx::test]
+    async fn only_editors_manage_room_features(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let teacher = test_support::teacher(&pool, workspace_id).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::room_feature_service(&pool))
+            .finish();
+        let create = r#"mutation { createRoomFeature(name: "Projector") { id } }"#;
+
+        let response = schema
+            .execute(Request::new(create).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema.execute(Request::new(create).data(test_support::claims(editor, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        let feature_id = response.data.into_json().unwrap()["createRoomFeature"]["id"].clone();
+
+        let rename = format!(r#"mutation {{ renameRoomFeature(id: {}, name: "Beamer") {{ name }} }}"#, feature_id);
+        let response = schema
+            .execute(Request::new(&rename).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema.execute(Request::new(&rename).data(test_support::claims(editor, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+    }
+
+    #[sqlx::test]
+    async fn only_editors_manage_qualifications(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let teacher = test_support::teacher(&pool, workspace_id).await;
+        let course_id = test_support::course(&pool, workspace_id).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::teacher_qualification_service(&pool))
+            .finish();
+        let set = format!(
+            r#"mutation {{ setTeacherQualification(teacherId: "{}", courseId: "{}", preference: PREFERRED) {{ preference }} }}"#,
+            teacher, course_id
+        );
+        let remove = format!(r#"mutation {{ removeTeacherQualification(teacherId: "{}", courseId: "{}") }}"#, teacher, course_id);
+
+        // Not even for themselves
+        let response = schema.execute(Request::new(&set).data(test_support::teacher_claims(teacher, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema.execute(Request::new(&set).data(test_support::claims(editor, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+
+        let response = schema
+            .execute(Request::new(&remove).data(test_support::teacher_claims(teacher, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners and editors can do this"]);
+
+        let response = schema.execute(Request::new(&remove).data(test_support::claims(editor, workspace_id))).await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+    }
 }
@@ -2,19 +2,21 @@ use std::sync::Arc;
 
 use async_graphql::{Context, Object, Result};
 use uuid::Uuid;
-use chrono::NaiveDate;
-use crate::models::{User, Resource, Course, Room, TimeSlot, TimetableEntry, Substitution, snapshot::TimetableSnapshot};
+use chrono::{DateTime, NaiveDate, Utc};
+use crate::models::{Absence, TeacherPreference, TeacherQualification, TeacherPreferenceScore, StudentGroup, PublishedLesson, Student, CourseSection, Enrollment, EnrollmentClash, StudentLesson, RoomSuggestion, RoomFeature, RoomFeatureRequirement, AvailabilityRound, AvailabilityRoundStatus, AvailabilitySubmission, DailyChange, LessonSwap, LessonSwapStatus, SubstitutionOffer, TeacherSubstitutionStats, User, UserRole, Resource, Course, Room, TimeSlot, TimetableEntry, Substitution, WebhookEndpoint, Notification, NotificationPreference, NotificationSettings, TimetableChanges, SubstitutionCandidate, LessonOccurrence, snapshot::TimetableSnapshot};
 use crate::graphql::types::{
-    Availability, Conflict, DraftTimetable, PublishedTimetable, Workspace
+    Availability, AvailabilityOverride, AvailabilityWindow, Conflict, DraftTimetable, PublishedTimetable, Workspace
 };
 use crate::service::{
     UserService, ResourceService, CourseService, RoomService,
     TimeSlotService, TimetableEntryService, SubstitutionService,
-    SnapshotService, AvailabilityService, ConflictService,
+    SnapshotService, AvailabilityService, AvailabilityRoundService, ConflictService, TeacherPreferenceService, TeacherQualificationService, StudentGroupService, EnrollmentService, RoomFeatureService,
     DraftTimetableService, PublishedTimetableService, WorkspaceService,
+    OutboxService, NotificationService, OccurrenceService, LessonExceptionService, LessonSwapService, SubstitutionStatsService,
     auth::Claims
 };
 use crate::error::AppError;
+use crate::graphql::{require_workspace_editor, require_workspace_member, require_workspace_owner};
 use async_graphql::ErrorExtensions;
 
 pub struct Query;
@@ -100,19 +102,292 @@ impl Query {
         Ok(service.get_substitution(id).await?)
     }
 
+    /// The changes board for a date: cancelled and moved lessons, extra
+    /// lessons, teacher changes and substitutions, in order of start time.
+    async fn daily_changes(&self, ctx: &Context<'_>, date: NaiveDate) -> Result<Vec<DailyChange>> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<LessonExceptionService>>()?;
+        Ok(service.daily_changes(claims.workspace_id, date).await?)
+    }
+
+    /// Lesson swaps, newest first. Teachers only see swaps they are part of.
+    async fn lesson_swaps(&self, ctx: &Context<'_>, status: Option<LessonSwapStatus>) -> Result<Vec<LessonSwap>> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<Arc<LessonSwapService>>()?;
+        let teacher_id = (!role.can_edit()).then_some(claims.sub);
+        Ok(service.get_swaps(claims.workspace_id, status, teacher_id).await?)
+    }
+
+    /// Targeted offers made for the substitution, oldest first.
+    async fn substitution_offers(&self, ctx: &Context<'_>, substitution_id: Uuid) -> Result<Vec<SubstitutionOffer>> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<SubstitutionService>()?;
+        Ok(service.get_offers(claims.workspace_id, substitution_id).await?)
+    }
+
+    /// Per-teacher substitution figures for the lessons from `from` through
+    /// `to`. Teachers only get their own figures.
+    async fn substitution_stats(
+        &self,
+        ctx: &Context<'_>,
+        from: NaiveDate,
+        to: NaiveDate,
+        teacher_id: Option<Uuid>,
+    ) -> Result<Vec<TeacherSubstitutionStats>> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<Arc<SubstitutionStatsService>>()?;
+        let teacher_id = if role.can_edit() { teacher_id } else { Some(claims.sub) };
+        Ok(service.get_stats(claims.workspace_id, from, to, teacher_id).await?)
+    }
+
+    /// Absences overlapping the range, including cancelled ones.
+    async fn absences(
+        &self,
+        ctx: &Context<'_>,
+        from: NaiveDate,
+        to: NaiveDate,
+        teacher_id: Option<Uuid>,
+    ) -> Result<Vec<Absence>> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<SubstitutionService>()?;
+        Ok(service.get_absences(claims.workspace_id, from, to, teacher_id).await?)
+    }
+
+    /// Teachers free to cover the substitution, best suited first, with the
+    /// reasons behind each score. With `qualifiedOnly`, teachers not
+    /// qualified for a course that has qualified teachers are left out.
+    async fn substitution_candidates(
+        &self,
+        ctx: &Context<'_>,
+        substitution_id: Uuid,
+        qualified_only: Option<bool>,
+    ) -> Result<Vec<SubstitutionCandidate>> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<SubstitutionService>()?;
+        Ok(service.get_candidates(claims.workspace_id, substitution_id, qualified_only.unwrap_or(false)).await?)
+    }
+
+    /// Lessons on each date between `from` and `to`, with the substitute for
+    /// that date where there is one. Filter by teacher (lessons they teach or
+    /// cover) or by room.
+    async fn lesson_occurrences(
+        &self,
+        ctx: &Context<'_>,
+        from: NaiveDate,
+        to: NaiveDate,
+        teacher_id: Option<Uuid>,
+        room_id: Option<Uuid>,
+    ) -> Result<Vec<LessonOccurrence>> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<OccurrenceService>>()?;
+        Ok(service.get_occurrences(claims.workspace_id, from, to, teacher_id, room_id).await?)
+    }
+
+    /// The caller's own lessons between `from` and `to`.
+    async fn my_lesson_occurrences(&self, ctx: &Context<'_>, from: NaiveDate, to: NaiveDate) -> Result<Vec<LessonOccurrence>> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<OccurrenceService>>()?;
+        Ok(service.get_occurrences(claims.workspace_id, from, to, Some(claims.sub), None).await?)
+    }
+
     async fn timetable_snapshot(&self, ctx: &Context<'_>) -> Result<TimetableSnapshot> {
         let service = ctx.data::<SnapshotService>()?;
         Ok(service.get_timetable_snapshot().await?)
     }
 
-    async fn availability(&self, ctx: &Context<'_>, teacher_id: Uuid, date: NaiveDate) -> Result<Vec<Availability>> {
+    /// When the teacher is available on the date, with their overrides
+    /// applied to the weekly pattern.
+    async fn availability(&self, ctx: &Context<'_>, teacher_id: Uuid, date: NaiveDate) -> Result<Vec<AvailabilityWindow>> {
         let service = ctx.data::<Arc<AvailabilityService>>()?;
-        Ok(service.get_availability(teacher_id, date).await?)
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.get_availability(claims.workspace_id, teacher_id, date).await?)
+    }
+
+    /// Availability overrides touching the period, of one teacher or all.
+    async fn availability_overrides(
+        &self,
+        ctx: &Context<'_>,
+        from: NaiveDate,
+        to: NaiveDate,
+        teacher_id: Option<Uuid>,
+    ) -> Result<Vec<AvailabilityOverride>> {
+        let service = ctx.data::<Arc<AvailabilityService>>()?;
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.get_overrides(claims.workspace_id, teacher_id, from, to).await?)
+    }
+
+    /// Scheduling preferences of one teacher or, for admins, of everyone;
+    /// teachers only see their own.
+    async fn teacher_preferences(&self, ctx: &Context<'_>, teacher_id: Option<Uuid>) -> Result<Vec<TeacherPreference>> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<Arc<TeacherPreferenceService>>()?;
+        let teacher_id = if role.can_edit() { teacher_id } else { Some(claims.sub) };
+        Ok(service.get_preferences(claims.workspace_id, teacher_id).await?)
+    }
+
+    /// Soft-constraint scores of a draft: how far each teacher's lessons go
+    /// against their preferences, worst first.
+    async fn preference_scores(&self, ctx: &Context<'_>, draft_timetable_id: Uuid) -> Result<Vec<TeacherPreferenceScore>> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<ConflictService>>()?;
+        Ok(service.get_preference_scores(claims.workspace_id, draft_timetable_id).await?)
+    }
+
+    async fn availability_rounds(&self, ctx: &Context<'_>, status: Option<AvailabilityRoundStatus>) -> Result<Vec<AvailabilityRound>> {
+        let service = ctx.data::<Arc<AvailabilityRoundService>>()?;
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.get_rounds(claims.workspace_id, status).await?)
+    }
+
+    /// Every teacher with their submission for the round; `submitted:
+    /// false` lists who has not submitted yet.
+    async fn availability_submissions(
+        &self,
+        ctx: &Context<'_>,
+        round_id: Uuid,
+        submitted: Option<bool>,
+    ) -> Result<Vec<AvailabilitySubmission>> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<AvailabilityRoundService>>()?;
+        Ok(service.get_submissions(claims.workspace_id, round_id, submitted).await?)
+    }
+
+    /// A teacher's whole weekly availability, by day and start time.
+    async fn weekly_availability(&self, ctx: &Context<'_>, teacher_id: Uuid) -> Result<Vec<Availability>> {
+        let service = ctx.data::<Arc<AvailabilityService>>()?;
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.get_weekly_availability(claims.workspace_id, teacher_id).await?)
+    }
+
+    async fn student_groups(&self, ctx: &Context<'_>) -> Result<Vec<StudentGroup>> {
+        let service = ctx.data::<Arc<StudentGroupService>>()?;
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.get_groups(claims.workspace_id).await?)
+    }
+
+    async fn course_student_groups(&self, ctx: &Context<'_>, course_id: Uuid) -> Result<Vec<StudentGroup>> {
+        let service = ctx.data::<Arc<StudentGroupService>>()?;
+        Ok(service.get_course_groups(course_id).await?)
+    }
+
+    /// A group's weekly lessons in a draft, or in the timetable in force
+    /// today. Lessons of the groups it is part of and of the groups within
+    /// it are included.
+    async fn student_group_timetable(
+        &self,
+        ctx: &Context<'_>,
+        student_group_id: Uuid,
+        draft_timetable_id: Option<Uuid>,
+    ) -> Result<Vec<PublishedLesson>> {
+        let service = ctx.data::<Arc<StudentGroupService>>()?;
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.get_timetable(claims.workspace_id, student_group_id, draft_timetable_id).await?)
+    }
+
+    /// The roster, optionally only the students of a class. Not visible to
+    /// students.
+    async fn students(&self, ctx: &Context<'_>, student_group_id: Option<Uuid>) -> Result<Vec<Student>> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        if !role.can_edit() && claims.role == UserRole::User {
+            return Err(AppError::Forbidden("Only staff can see the roster".to_string()).into());
+        }
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        Ok(service.get_students(claims.workspace_id, student_group_id).await?)
+    }
+
+    async fn course_sections(&self, ctx: &Context<'_>, course_id: Option<Uuid>) -> Result<Vec<CourseSection>> {
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.get_sections(claims.workspace_id, course_id).await?)
+    }
+
+    async fn enrollments(
+        &self,
+        ctx: &Context<'_>,
+        student_id: Option<Uuid>,
+        course_section_id: Option<Uuid>,
+    ) -> Result<Vec<Enrollment>> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        if !role.can_edit() && claims.role == UserRole::User {
+            return Err(AppError::Forbidden("Only staff can see enrolments".to_string()).into());
+        }
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        Ok(service.get_enrollments(claims.workspace_id, student_id, course_section_id).await?)
+    }
+
+    /// A student's weekly lessons in a draft, or in the timetable in force
+    /// today. Students can only see their own.
+    async fn student_timetable(
+        &self,
+        ctx: &Context<'_>,
+        student_id: Uuid,
+        draft_timetable_id: Option<Uuid>,
+    ) -> Result<Vec<StudentLesson>> {
+        let (claims, role) = require_workspace_member(ctx).await?;
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        if !role.can_edit() && claims.role == UserRole::User {
+            let student = service.get_student(claims.workspace_id, student_id).await?;
+            if student.and_then(|s| s.user_id) != Some(claims.sub) {
+                return Err(AppError::Forbidden("Students can only see their own timetable".to_string()).into());
+            }
+        }
+        Ok(service.get_timetable(claims.workspace_id, student_id, draft_timetable_id).await?)
+    }
+
+    /// Students with two lessons at overlapping times in a draft, or in the
+    /// timetable in force today, one entry per pair of lessons.
+    async fn enrollment_clashes(&self, ctx: &Context<'_>, draft_timetable_id: Option<Uuid>) -> Result<Vec<EnrollmentClash>> {
+        let claims = require_workspace_editor(ctx).await?;
+        let service = ctx.data::<Arc<EnrollmentService>>()?;
+        Ok(service.get_clashes(claims.workspace_id, draft_timetable_id).await?)
+    }
+
+    /// The workspace's room features, or only those of a room.
+    async fn room_features(&self, ctx: &Context<'_>, room_id: Option<Uuid>) -> Result<Vec<RoomFeature>> {
+        let service = ctx.data::<Arc<RoomFeatureService>>()?;
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.get_features(claims.workspace_id, room_id).await?)
+    }
+
+    async fn course_room_features(&self, ctx: &Context<'_>, course_id: Uuid) -> Result<Vec<RoomFeatureRequirement>> {
+        let service = ctx.data::<Arc<RoomFeatureService>>()?;
+        Ok(service.get_course_requirements(course_id).await?)
+    }
+
+    async fn student_group_room_features(&self, ctx: &Context<'_>, student_group_id: Uuid) -> Result<Vec<RoomFeatureRequirement>> {
+        let service = ctx.data::<Arc<RoomFeatureService>>()?;
+        Ok(service.get_group_requirements(student_group_id).await?)
+    }
+
+    /// Who may teach what, optionally for one teacher or one course.
+    async fn teacher_qualifications(
+        &self,
+        ctx: &Context<'_>,
+        teacher_id: Option<Uuid>,
+        course_id: Option<Uuid>,
+    ) -> Result<Vec<TeacherQualification>> {
+        let service = ctx.data::<Arc<TeacherQualificationService>>()?;
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.get_qualifications(claims.workspace_id, teacher_id, course_id).await?)
     }
 
     async fn conflicts(&self, ctx: &Context<'_>, draft_timetable_id: Uuid) -> Result<Vec<Conflict>> {
         let service = ctx.data::<Arc<ConflictService>>()?;
-        Ok(service.get_conflicts(draft_timetable_id).await?)
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.get_conflicts(claims.workspace_id, draft_timetable_id).await?)
+    }
+
+    /// Free rooms at the time of a draft lesson that hold all its
+    /// students, smallest first.
+    async fn room_suggestions(
+        &self,
+        ctx: &Context<'_>,
+        draft_timetable_id: Uuid,
+        draft_entry_id: Uuid,
+    ) -> Result<Vec<RoomSuggestion>> {
+        let service = ctx.data::<Arc<ConflictService>>()?;
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        Ok(service.get_room_suggestions(claims.workspace_id, draft_timetable_id, draft_entry_id).await?)
     }
 
     async fn draft_timetable(&self, ctx: &Context<'_>, id: Uuid) -> Result<Option<DraftTimetable>> {
@@ -126,6 +401,14 @@ impl Query {
         Ok(service.get_published_timetable(id).await?)
     }
 
+    /// Lessons added, removed or moved by a published timetable, per teacher
+    /// and per room.
+    async fn timetable_changes(&self, ctx: &Context<'_>, published_timetable_id: Uuid) -> Result<TimetableChanges> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<PublishedTimetableService>()?;
+        Ok(service.get_changes(claims.workspace_id, published_timetable_id).await?)
+    }
+
     async fn latest_published_timetable(&self, ctx: &Context<'_>) -> Result<Option<PublishedTimetable>> {
         let service = ctx.data::<PublishedTimetableService>()?;
         Ok(service.get_latest_published_timetable().await?)
@@ -136,4 +419,154 @@ impl Query {
         let service = ctx.data::<Arc<WorkspaceService>>()?;
         Ok(service.get_user_workspaces(claims.sub).await?)
     }
+
+    async fn my_notifications(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = false)] unread_only: bool,
+        before: Option<DateTime<Utc>>,
+        #[graphql(default = 50)] limit: i64,
+    ) -> Result<Vec<Notification>> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<NotificationService>>()?;
+        Ok(service.get_notifications(claims.workspace_id, claims.sub, unread_only, before, limit).await?)
+    }
+
+    async fn unread_notification_count(&self, ctx: &Context<'_>) -> Result<i64> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<NotificationService>>()?;
+        Ok(service.get_unread_count(claims.workspace_id, claims.sub).await?)
+    }
+
+    /// How the caller receives each notification type in the current workspace.
+    async fn my_notification_preferences(&self, ctx: &Context<'_>) -> Result<Vec<NotificationPreference>> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<NotificationService>>()?;
+        Ok(service.get_preferences(claims.workspace_id, claims.sub).await?)
+    }
+
+    async fn my_notification_settings(&self, ctx: &Context<'_>) -> Result<NotificationSettings> {
+        let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+        let service = ctx.data::<Arc<NotificationService>>()?;
+        Ok(service.get_settings(claims.workspace_id, claims.sub).await?)
+    }
+
+    async fn webhook_endpoints(&self, ctx: &Context<'_>) -> Result<Vec<WebhookEndpoint>> {
+        let claims = require_workspace_owner(ctx).await?;
+        let service = ctx.data::<Arc<OutboxService>>()?;
+        Ok(service.get_webhook_endpoints(claims.workspace_id).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::Request;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::models::WorkspaceRole;
+    use crate::service::lesson_swaps::SwapProposal;
+    use crate::test_support;
+
+    #[sqlx::test]
+    async fn only_owners_list_webhook_endpoints(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let owner = test_support::member(&pool, workspace_id, WorkspaceRole::Owner).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let outbox = test_support::outbox(&pool);
+        outbox
+            .create_webhook_endpoint(workspace_id, "https://93.184.216.34/hooks".to_string())
+            .await
+            .unwrap();
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(outbox)
+            .finish();
+        let query = "{ webhookEndpoints { url } }";
+
+        let response = schema
+            .execute(Request::new(query).data(test_support::claims(owner, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), Vec::<String>::new());
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({ "webhookEndpoints": [{ "url": "https://93.184.216.34/hooks" }] })
+        );
+
+        let response = schema
+            .execute(Request::new(query).data(test_support::claims(editor, workspace_id)))
+            .await;
+        assert_eq!(test_support::errors(&response), ["Forbidden: Only workspace owners can do this"]);
+
+        // The secret is only handed out when the endpoint is created
+        let response = schema
+            .execute(Request::new("{ webhookEndpoints { secret } }").data(test_support::claims(owner, workspace_id)))
+            .await;
+        assert!(!response.errors.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn teachers_only_see_their_own_lesson_swaps(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let requester = test_support::teacher(&pool, workspace_id).await;
+        let colleague = test_support::teacher(&pool, workspace_id).await;
+        let bystander = test_support::teacher(&pool, workspace_id).await;
+        let monday = test_support::lesson(&pool, workspace_id, requester, 1).await;
+        let tuesday = test_support::lesson(&pool, workspace_id, colleague, 2).await;
+        test_support::publish(&pool, workspace_id, &[monday, tuesday]).await;
+        let swaps = test_support::lesson_swap_service(&pool);
+        swaps
+            .propose(workspace_id, requester, SwapProposal {
+                timetable_entry_id: monday,
+                date: NaiveDate::from_ymd_opt(2026, 11, 2).unwrap(),
+                colleague_entry_id: tuesday,
+                colleague_date: NaiveDate::from_ymd_opt(2026, 11, 3).unwrap(),
+                reason: None,
+            })
+            .await
+            .unwrap();
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(swaps)
+            .finish();
+        let query = "{ lessonSwaps { status } }";
+
+        for (user_id, expected) in [(editor, 1), (requester, 1), (colleague, 1), (bystander, 0)] {
+            let response = schema
+                .execute(Request::new(query).data(test_support::claims(user_id, workspace_id)))
+                .await;
+            assert_eq!(test_support::errors(&response), Vec::<String>::new());
+            let listed = response.data.into_json().unwrap()["lessonSwaps"].as_array().unwrap().len();
+            assert_eq!(listed, expected);
+        }
+    }
+
+    #[sqlx::test]
+    async fn teachers_only_see_their_own_substitution_stats(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let editor = test_support::member(&pool, workspace_id, WorkspaceRole::Editor).await;
+        let teacher = test_support::teacher(&pool, workspace_id).await;
+        let colleague = test_support::teacher(&pool, workspace_id).await;
+        let schema = test_support::schema()
+            .data(test_support::workspace_service(&pool))
+            .data(test_support::substitution_stats_service(&pool))
+            .finish();
+        let query = format!(
+            r#"{{ substitutionStats(from: "2026-11-01", to: "2026-11-30", teacherId: "{}") {{ teacherId }} }}"#,
+            colleague
+        );
+
+        for (claims, expected) in [
+            (test_support::claims(editor, workspace_id), colleague),
+            (test_support::teacher_claims(teacher, workspace_id), teacher),
+        ] {
+            let response = schema.execute(Request::new(&query).data(claims)).await;
+            assert_eq!(test_support::errors(&response), Vec::<String>::new());
+            assert_eq!(
+                response.data.into_json().unwrap(),
+                serde_json::json!({ "substitutionStats": [{ "teacherId": expected }] })
+            );
+        }
+    }
 }
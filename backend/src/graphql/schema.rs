@@ -1,30 +1,37 @@
-use async_graphql::{EmptySubscription, Schema};
+use async_graphql::Schema;
 use std::sync::Arc;
 use crate::config::Config;
 use crate::oidc::OidcClient;
 use crate::ws::Broadcaster;
 use crate::graphql::query::Query;
 use crate::graphql::mutation::Mutation;
+use crate::graphql::subscription::Subscription;
 use crate::repository::{
     UserRepository, ResourceRepository, CourseRepository, RoomRepository,
-    TimeSlotRepository, TimetableEntryRepository, SubstitutionRepository,
-    AvailabilityRepository, ConflictRepository, DraftTimetableRepository,
+    TimeSlotRepository, TimetableEntryRepository, SubstitutionRepository, AbsenceRepository, LessonExceptionRepository,
+    LessonSwapRepository,
+    AvailabilityRepository, AvailabilityRoundRepository, ConflictRepository, DraftTimetableRepository,
     PublishedTimetableRepository, DraftEntryRepository, AuthRepository,
-    WorkspaceRepository
+    WorkspaceRepository, NotificationRepository, TeacherPreferenceRepository, TeacherQualificationRepository, StudentGroupRepository,
+    EnrollmentRepository, RoomFeatureRepository,
 };
 use crate::service::{
     UserService, ResourceService, CourseService, RoomService,
-    TimeSlotService, TimetableEntryService, SubstitutionService,
-    NotificationService, SnapshotService, AvailabilityService,
+    TimeSlotService, TimetableEntryService, SubstitutionService, LessonExceptionService, LessonSwapService,
+    NotificationService, OccurrenceService, SubstitutionStatsService, SnapshotService, AvailabilityService, AvailabilityRoundService,
     ConflictService, DraftTimetableService, PublishedTimetableService,
-    DraftEntryService, AuthService, WorkspaceService
+    DraftEntryService, AuthService, WorkspaceService, OutboxService, TeacherPreferenceService, TeacherQualificationService, StudentGroupService,
+    EnrollmentService, RoomFeatureService,
 };
 
-pub type AppSchema = Schema<Query, Mutation, EmptySubscription>;
+pub type AppSchema = Schema<Query, Mutation, Subscription>;
 
 pub fn create_schema(
     pool: sqlx::PgPool,
     broadcaster: Arc<Broadcaster>,
+    outbox: Arc<OutboxService>,
+    occurrences: Arc<OccurrenceService>,
+    substitution_stats: Arc<SubstitutionStatsService>,
     config: Arc<Config>,
     oidc_client: Arc<OidcClient>,
 ) -> AppSchema {
@@ -49,13 +56,32 @@ pub fn create_schema(
     let room_service = RoomService::new(room_repo.clone());
     let time_slot_service = TimeSlotService::new(time_slot_repo.clone());
     let timetable_entry_service =
-        TimetableEntryService::new(timetable_entry_repo.clone(), broadcaster.clone());
-    let notification_service = NotificationService::new();
+        TimetableEntryService::new(timetable_entry_repo.clone(), outbox.clone());
+    let notification_service = Arc::new(NotificationService::new(
+        NotificationRepository::new(pool.clone()),
+        user_repo.clone(),
+        outbox.clone(),
+        config.clone(),
+    ));
     let substitution_service = SubstitutionService::new(
         substitution_repo.clone(),
+        AbsenceRepository::new(pool.clone()),
+        outbox.clone(),
         notification_service.clone(),
-        broadcaster.clone(),
+        substitution_stats.clone(),
     );
+    let lesson_exception_repo = LessonExceptionRepository::new(pool.clone());
+    let lesson_exception_service = Arc::new(LessonExceptionService::new(
+        lesson_exception_repo.clone(),
+        outbox.clone(),
+    ));
+    let lesson_swap_service = Arc::new(LessonSwapService::new(
+        LessonSwapRepository::new(pool.clone()),
+        lesson_exception_repo,
+        lesson_exception_service.clone(),
+        outbox.clone(),
+        notification_service.clone(),
+    ));
     let snapshot_service = SnapshotService::new(
         course_repo,
         room_repo,
@@ -63,31 +89,63 @@ pub fn create_schema(
         timetable_entry_repo,
         user_repo.clone(),
     );
-    let availability_service = Arc::new(AvailabilityService::new(availability_repo));
-    let draft_entry_service = Arc::new(DraftEntryService::new(draft_entry_repo));
+    let availability_service = Arc::new(AvailabilityService::new(availability_repo.clone()));
+    let availability_round_service = Arc::new(AvailabilityRoundService::new(
+        AvailabilityRoundRepository::new(pool.clone()),
+        availability_repo,
+        outbox.clone(),
+        notification_service.clone(),
+    ));
+    let draft_entry_service = Arc::new(DraftEntryService::new(
+        draft_entry_repo,
+        StudentGroupRepository::new(pool.clone()),
+        conflict_repo.clone(),
+        outbox.clone(),
+    ));
     let conflict_service = Arc::new(ConflictService::new(
         conflict_repo,
         draft_entry_service.clone(),
         availability_service.clone(),
         time_slot_repo,
+        TeacherPreferenceRepository::new(pool.clone()),
+        StudentGroupRepository::new(pool.clone()),
+        outbox.clone(),
+    ));
+    let teacher_preference_service = Arc::new(TeacherPreferenceService::new(TeacherPreferenceRepository::new(pool.clone())));
+    let teacher_qualification_service = Arc::new(TeacherQualificationService::new(TeacherQualificationRepository::new(pool.clone())));
+    let student_group_service = Arc::new(StudentGroupService::new(StudentGroupRepository::new(pool.clone())));
+    let enrollment_service = Arc::new(EnrollmentService::new(
+        EnrollmentRepository::new(pool.clone()),
+        StudentGroupRepository::new(pool.clone()),
     ));
-    let draft_timetable_service = Arc::new(DraftTimetableService::new(draft_timetable_repo));
+    let room_feature_service = Arc::new(RoomFeatureService::new(RoomFeatureRepository::new(pool.clone())));
+    let draft_timetable_service = Arc::new(DraftTimetableService::new(draft_timetable_repo, outbox.clone()));
     let published_timetable_service = PublishedTimetableService::new(
         published_timetable_repo,
         draft_timetable_service.clone(),
         conflict_service.clone(),
+        outbox.clone(),
+        notification_service.clone(),
     );
-    let workspace_service = Arc::new(WorkspaceService::new(workspace_repo));
+    let workspace_service = Arc::new(WorkspaceService::new(
+        workspace_repo,
+        user_repo.clone(),
+        outbox.clone(),
+        config.clone(),
+    ));
     let auth_service = AuthService::new(
         auth_repo,
         user_repo.clone(),
         config,
         oidc_client,
         workspace_service.clone(),
+        outbox.clone(),
     );
 
-    Schema::build(Query, Mutation, EmptySubscription)
+    Schema::build(Query, Mutation, Subscription)
         .data(pool)
+        .data(broadcaster)
+        .data(outbox)
         .data(user_service)
         .data(resource_service)
         .data(course_service)
@@ -96,12 +154,22 @@ pub fn create_schema(
         .data(timetable_entry_service)
         .data(notification_service)
         .data(substitution_service)
+        .data(lesson_exception_service)
+        .data(lesson_swap_service)
         .data(snapshot_service)
         .data(availability_service)
+        .data(availability_round_service)
         .data(conflict_service)
+        .data(teacher_preference_service)
+        .data(teacher_qualification_service)
+        .data(student_group_service)
+        .data(enrollment_service)
+        .data(room_feature_service)
         .data(draft_timetable_service)
         .data(draft_entry_service)
         .data(published_timetable_service)
+        .data(occurrences)
+        .data(substitution_stats)
         .data(workspace_service)
         .data(auth_service)
         .finish()
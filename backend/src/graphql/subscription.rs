@@ -0,0 +1,361 @@
+use std::sync::Arc;
+
+use async_graphql::futures_util::stream::{self, Stream};
+use async_graphql::{Context, Enum, Error, ErrorExtensions, Result, SimpleObject, Subscription as SubscriptionObject};
+use serde::Deserialize;
+use tokio::sync::broadcast;
+use chrono::NaiveDate;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::conflicts::ConflictStatus;
+use crate::models::{LessonExceptionKind, LessonSwapStatus};
+use crate::service::auth::Claims;
+use crate::ws::{self, Broadcaster, WebSocketMessage};
+
+pub struct Subscription;
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimetableEntryChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+#[derive(SimpleObject, Debug, Clone)]
+pub struct TimetableEntryChanged {
+    pub kind: TimetableEntryChangeKind,
+    pub timetable_entry_id: Uuid,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubstitutionEventKind {
+    Requested,
+    Accepted,
+    Rejected,
+    Cancelled,
+    Offered,
+    OffersExpired,
+}
+
+#[derive(SimpleObject, Debug, Clone)]
+pub struct SubstitutionEvent {
+    pub kind: SubstitutionEventKind,
+    pub substitution_id: Uuid,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LessonExceptionEventKind {
+    Created,
+    Deleted,
+}
+
+#[derive(SimpleObject, Debug, Clone)]
+pub struct LessonExceptionEvent {
+    pub kind: LessonExceptionEventKind,
+    pub exception_id: Uuid,
+    pub exception_kind: LessonExceptionKind,
+    pub date: NaiveDate,
+    pub timetable_entry_id: Option<Uuid>,
+}
+
+#[derive(SimpleObject, Debug, Clone)]
+pub struct LessonSwapEvent {
+    pub swap_id: Uuid,
+    pub status: LessonSwapStatus,
+}
+
+#[derive(SimpleObject, Debug, Clone)]
+pub struct DraftUpdated {
+    pub draft_timetable_id: Uuid,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictEventKind {
+    Opened,
+    Resolved,
+}
+
+#[derive(SimpleObject, Debug, Clone)]
+pub struct ConflictEvent {
+    pub kind: ConflictEventKind,
+    pub conflict_id: Uuid,
+    pub draft_timetable_id: Uuid,
+    pub status: Option<ConflictStatus>,
+}
+
+#[derive(SimpleObject, Debug, Clone)]
+pub struct UnreadNotifications {
+    pub unread_count: i64,
+    /// Set when the count changed because of a new notification.
+    pub notification_id: Option<Uuid>,
+}
+
+#[derive(SimpleObject, Debug, Clone)]
+pub struct TimetablePublished {
+    pub published_timetable_id: Uuid,
+    pub draft_timetable_id: Uuid,
+}
+
+// Payload shapes produced by the services when broadcasting.
+#[derive(Deserialize)]
+struct IdPayload {
+    id: Uuid,
+}
+
+#[derive(Deserialize)]
+struct LessonExceptionPayload {
+    id: Uuid,
+    date: NaiveDate,
+    kind: LessonExceptionKind,
+    timetable_entry_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+struct LessonSwapPayload {
+    id: Uuid,
+    status: LessonSwapStatus,
+}
+
+#[derive(Deserialize)]
+struct ConflictPayload {
+    id: Uuid,
+    draft_timetable_id: Uuid,
+    status: Option<ConflictStatus>,
+}
+
+#[derive(Deserialize)]
+struct UnreadPayload {
+    unread_count: i64,
+    notification_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+struct PublishedPayload {
+    id: Uuid,
+    draft_timetable_id: Uuid,
+}
+
+/// Streams the events of the caller's workspace that are visible to the
+/// caller, as far as `select` turns them into items. A subscriber that falls
+/// so far behind that events were dropped gets a `RESYNC_REQUIRED` error in
+/// their place and should refetch; the stream then carries on.
+fn workspace_events<T, F>(ctx: &Context<'_>, select: F) -> Result<impl Stream<Item = Result<T>> + use<T, F>>
+where
+    T: Send + 'static,
+    F: Fn(WebSocketMessage) -> Option<T> + Send + 'static,
+{
+    let claims = ctx.data::<Claims>().map_err(|_| AppError::Unauthorized.extend())?;
+    let broadcaster = ctx.data::<Arc<Broadcaster>>()?;
+
+    let user_id = claims.sub;
+
+    let events = stream::unfold((broadcaster.subscribe(claims.workspace_id), select), move |(mut rx, select)| async move {
+        loop {
+            match rx.recv().await {
+                Ok(msg) if msg.is_visible_to(user_id) => {
+                    if let Some(item) = select(msg) {
+                        return Some((Ok(item), (rx, select)));
+                    }
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("GraphQL subscriber lagged, skipped {} events", skipped);
+                    return Some((Err(resync_required(skipped)), (rx, select)));
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(events)
+}
+
+fn resync_required(skipped: u64) -> Error {
+    Error::new(format!("{} events were missed; refetch to catch up", skipped)).extend_with(|_, e| {
+        e.set("code", ws::RESYNC_REQUIRED);
+        e.set("skipped", skipped);
+    })
+}
+
+#[SubscriptionObject]
+impl Subscription {
+    async fn timetable_entry_changed(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = Result<TimetableEntryChanged>>> {
+        workspace_events(ctx, |msg| {
+            let kind = match msg.event_type.as_str() {
+                ws::TIMETABLE_CREATED => TimetableEntryChangeKind::Created,
+                ws::TIMETABLE_UPDATED => TimetableEntryChangeKind::Updated,
+                ws::TIMETABLE_DELETED => TimetableEntryChangeKind::Deleted,
+                _ => return None,
+            };
+            let payload: IdPayload = serde_json::from_value(msg.payload).ok()?;
+            Some(TimetableEntryChanged {
+                kind,
+                timetable_entry_id: payload.id,
+            })
+        })
+    }
+
+    async fn substitution_events(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = Result<SubstitutionEvent>>> {
+        workspace_events(ctx, |msg| {
+            let kind = match msg.event_type.as_str() {
+                ws::SUBSTITUTION_REQUESTED => SubstitutionEventKind::Requested,
+                ws::SUBSTITUTION_ACCEPTED => SubstitutionEventKind::Accepted,
+                ws::SUBSTITUTION_REJECTED => SubstitutionEventKind::Rejected,
+                ws::SUBSTITUTION_CANCELLED => SubstitutionEventKind::Cancelled,
+                ws::SUBSTITUTION_OFFERED => SubstitutionEventKind::Offered,
+                ws::SUBSTITUTION_OFFERS_EXPIRED => SubstitutionEventKind::OffersExpired,
+                _ => return None,
+            };
+            let payload: IdPayload = serde_json::from_value(msg.payload).ok()?;
+            Some(SubstitutionEvent {
+                kind,
+                substitution_id: payload.id,
+            })
+        })
+    }
+
+    /// Cancellations, room changes and extra lessons as they are made or
+    /// undone, optionally only those for one date.
+    async fn lesson_exception_events(&self, ctx: &Context<'_>, date: Option<NaiveDate>) -> Result<impl Stream<Item = Result<LessonExceptionEvent>>> {
+        workspace_events(ctx, move |msg| {
+            let kind = match msg.event_type.as_str() {
+                ws::LESSON_EXCEPTION_CREATED => LessonExceptionEventKind::Created,
+                ws::LESSON_EXCEPTION_DELETED => LessonExceptionEventKind::Deleted,
+                _ => return None,
+            };
+            let payload: LessonExceptionPayload = serde_json::from_value(msg.payload).ok()?;
+            if date.is_some_and(|date| date != payload.date) {
+                return None;
+            }
+            Some(LessonExceptionEvent {
+                kind,
+                exception_id: payload.id,
+                exception_kind: payload.kind,
+                date: payload.date,
+                timetable_entry_id: payload.timetable_entry_id,
+            })
+        })
+    }
+
+    /// Lesson swaps as they are proposed, answered and decided.
+    async fn lesson_swap_events(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = Result<LessonSwapEvent>>> {
+        workspace_events(ctx, |msg| {
+            if msg.event_type != ws::LESSON_SWAP_PROPOSED && msg.event_type != ws::LESSON_SWAP_UPDATED {
+                return None;
+            }
+            let payload: LessonSwapPayload = serde_json::from_value(msg.payload).ok()?;
+            Some(LessonSwapEvent {
+                swap_id: payload.id,
+                status: payload.status,
+            })
+        })
+    }
+
+    async fn draft_updated(&self, ctx: &Context<'_>, draft_timetable_id: Option<Uuid>) -> Result<impl Stream<Item = Result<DraftUpdated>>> {
+        workspace_events(ctx, move |msg| {
+            if msg.event_type != ws::DRAFT_UPDATED {
+                return None;
+            }
+            let payload: IdPayload = serde_json::from_value(msg.payload).ok()?;
+            if draft_timetable_id.is_some_and(|id| id != payload.id) {
+                return None;
+            }
+            Some(DraftUpdated {
+                draft_timetable_id: payload.id,
+            })
+        })
+    }
+
+    async fn conflict_events(&self, ctx: &Context<'_>, draft_timetable_id: Option<Uuid>) -> Result<impl Stream<Item = Result<ConflictEvent>>> {
+        workspace_events(ctx, move |msg| {
+            let kind = match msg.event_type.as_str() {
+                ws::CONFLICT_OPENED => ConflictEventKind::Opened,
+                ws::CONFLICT_RESOLVED => ConflictEventKind::Resolved,
+                _ => return None,
+            };
+            let payload: ConflictPayload = serde_json::from_value(msg.payload).ok()?;
+            if draft_timetable_id.is_some_and(|id| id != payload.draft_timetable_id) {
+                return None;
+            }
+            Some(ConflictEvent {
+                kind,
+                conflict_id: payload.id,
+                draft_timetable_id: payload.draft_timetable_id,
+                status: payload.status,
+            })
+        })
+    }
+
+    async fn timetable_published(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = Result<TimetablePublished>>> {
+        workspace_events(ctx, |msg| {
+            if msg.event_type != ws::TIMETABLE_PUBLISHED {
+                return None;
+            }
+            let payload: PublishedPayload = serde_json::from_value(msg.payload).ok()?;
+            Some(TimetablePublished {
+                published_timetable_id: payload.id,
+                draft_timetable_id: payload.draft_timetable_id,
+            })
+        })
+    }
+
+    /// The caller's unread notification count whenever it changes.
+    async fn unread_notifications(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = Result<UnreadNotifications>>> {
+        workspace_events(ctx, |msg| {
+            if msg.event_type != ws::NOTIFICATIONS_UNREAD {
+                return None;
+            }
+            let payload: UnreadPayload = serde_json::from_value(msg.payload).ok()?;
+            Some(UnreadNotifications {
+                unread_count: payload.unread_count,
+                notification_id: payload.notification_id,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_graphql::futures_util::StreamExt;
+    use async_graphql::{Request, Value};
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support;
+
+    #[tokio::test]
+    async fn lagging_subscribers_are_told_to_resync() {
+        let workspace_id = Uuid::new_v4();
+        let broadcaster = Arc::new(Broadcaster::new(2));
+        let schema = test_support::schema().data(broadcaster.clone()).finish();
+        let request = Request::new("subscription { draftUpdated { draftTimetableId } }")
+            .data(test_support::claims(Uuid::new_v4(), workspace_id));
+        let mut responses = schema.execute_stream(request);
+
+        // Polling once runs the resolver, which subscribes to the workspace.
+        assert!(tokio::time::timeout(Duration::from_millis(50), responses.next()).await.is_err());
+
+        let drafts: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        for id in &drafts {
+            broadcaster
+                .broadcast(WebSocketMessage::new(workspace_id, ws::DRAFT_UPDATED, json!({ "id": id })))
+                .await;
+        }
+
+        let lagged = responses.next().await.unwrap();
+        let extensions = lagged.errors[0].extensions.as_ref().unwrap();
+        assert_eq!(extensions.get("code"), Some(&Value::from(ws::RESYNC_REQUIRED)));
+        assert_eq!(extensions.get("skipped"), Some(&Value::from(3)));
+
+        // The stream carries on with the events that were kept.
+        let next = responses.next().await.unwrap();
+        assert!(next.errors.is_empty());
+        assert_eq!(
+            next.data.into_json().unwrap(),
+            json!({ "draftUpdated": { "draftTimetableId": drafts[3] } })
+        );
+    }
+}
@@ -1,12 +1,16 @@
 use async_graphql::{InputObject, SimpleObject};
 use uuid::Uuid;
-use chrono::NaiveTime;
+use chrono::{NaiveDate, NaiveTime};
 pub use crate::models::User;
-pub use crate::models::availability::Availability;
+pub use crate::models::availability::{Availability, AvailabilityOverride, AvailabilityOverrideKind, AvailabilityWindow};
 pub use crate::models::conflicts::{Conflict, ConflictStatus, ConflictSeverity};
 pub use crate::models::draft_timetables::{DraftTimetable, DraftTimetableStatus};
 pub use crate::models::published_timetables::PublishedTimetable;
 pub use crate::models::draft_entries::DraftEntry;
+pub use crate::models::teacher_preferences::{TeacherPreference, TeacherPreferenceKind};
+pub use crate::models::student_groups::{StudentGroup, StudentGroupKind};
+pub use crate::models::room_features::{RoomFeature, RoomFeatureNeed, RoomFeatureRequirement};
+pub use crate::models::enrollments::{CourseSection, Enrollment, EnrollmentClash, EnrollmentImport, Student, StudentLesson};
 pub use crate::models::workspace::{Workspace, WorkspaceMember, WorkspaceInvite, WorkspaceRole};
 
 #[derive(InputObject, Clone)]
@@ -18,6 +22,47 @@ pub struct AvailabilityInput {
     pub is_preferred: bool,
 }
 
+/// One window of a weekly availability pattern.
+#[derive(InputObject, Clone, Copy)]
+pub struct AvailabilityWindowInput {
+    /// 0 is Sunday, 6 is Saturday.
+    pub day_of_week: i32,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub is_preferred: bool,
+}
+
+/// A dated change to a teacher's weekly availability.
+#[derive(InputObject, Clone)]
+pub struct AvailabilityOverrideInput {
+    pub teacher_id: Uuid,
+    pub kind: AvailabilityOverrideKind,
+    pub starts_on: NaiveDate,
+    /// Defaults to `starts_on`.
+    pub ends_on: Option<NaiveDate>,
+    /// Only this weekday of the range, 0 being Sunday.
+    pub day_of_week: Option<i32>,
+    /// Leave both times out for the whole day.
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    pub reason: Option<String>,
+}
+
+/// A weighted scheduling wish; which fields apply depends on the kind.
+#[derive(InputObject, Clone)]
+pub struct TeacherPreferenceInput {
+    pub kind: TeacherPreferenceKind,
+    /// From -10 to 10; negative only for time windows to avoid.
+    pub weight: f64,
+    pub day_of_week: Option<i32>,
+    /// The window, for time windows.
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    /// The limit, for maximum gaps and consecutive lessons.
+    pub max_count: Option<i32>,
+    pub note: Option<String>,
+}
+
 #[derive(InputObject, Clone)]
 pub struct DraftTimetableInput {
     pub name: String,
@@ -34,6 +79,37 @@ pub struct DraftEntryInput {
     pub teacher_id: Uuid,
     pub room_id: Uuid,
     pub time_slot_id: Uuid,
+    /// The section the lesson is for, when the course has several.
+    pub course_section_id: Option<Uuid>,
+    /// The groups attending this lesson, when not those of its course.
+    pub student_group_ids: Option<Vec<Uuid>>,
+}
+
+#[derive(InputObject, Clone)]
+pub struct StudentInput {
+    pub student_number: String,
+    pub name: String,
+    pub email: Option<String>,
+    /// The student's own login, if they have one.
+    pub user_id: Option<Uuid>,
+    /// Their class.
+    pub student_group_id: Option<Uuid>,
+}
+
+#[derive(InputObject, Clone)]
+pub struct StudentGroupInput {
+    pub name: String,
+    pub kind: StudentGroupKind,
+    /// The group this one is part of; required for subgroups and not
+    /// allowed for year groups.
+    pub parent_id: Option<Uuid>,
+    pub size: Option<i32>,
+}
+
+#[derive(InputObject, Clone)]
+pub struct RoomFeatureRequirementInput {
+    pub room_feature_id: Uuid,
+    pub need: RoomFeatureNeed,
 }
 
 #[derive(InputObject)]
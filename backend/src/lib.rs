@@ -8,17 +8,25 @@ pub mod graphql;
 pub mod service;
 pub mod repository;
 pub mod middleware;
+pub mod mail;
+pub mod calendar;
+#[cfg(test)]
+mod test_support;
 
 pub use error::{AppError, AppResult};
 
 use std::sync::Arc;
 use crate::config::Config;
 use crate::oidc::OidcClient;
-use crate::ws::Broadcaster;
+use crate::service::{OccurrenceService, SubstitutionStatsService};
+use crate::ws::{Broadcaster, TicketStore};
 
 #[derive(Clone)]
 pub struct AppState {
     pub broadcaster: Arc<Broadcaster>,
     pub config: Arc<Config>,
     pub oidc_client: Arc<OidcClient>,
+    pub tickets: Arc<TicketStore>,
+    pub occurrences: Arc<OccurrenceService>,
+    pub substitution_stats: Arc<SubstitutionStatsService>,
 }
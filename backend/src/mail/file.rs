@@ -0,0 +1,40 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::message::Mailbox;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use super::{Email, Mailer};
+
+/// Writes every email as an `.eml` file into a directory, which most mail
+/// clients can open. Stands in for SMTP during development.
+pub struct FileMailer {
+    dir: PathBuf,
+    from: Mailbox,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>, from: Mailbox) -> Self {
+        Self { dir: dir.into(), from }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> anyhow::Result<()> {
+        let message = email.to_message(&self.from)?;
+
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("Failed to create mail directory {}", self.dir.display()))?;
+
+        let path = self.dir.join(format!("{}-{}.eml", Utc::now().format("%Y%m%dT%H%M%S"), Uuid::new_v4()));
+        tokio::fs::write(&path, message.formatted())
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        tracing::info!("Email \"{}\" to {} written to {}", email.subject, email.to, path.display());
+        Ok(())
+    }
+}
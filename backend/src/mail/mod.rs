@@ -0,0 +1,115 @@
+pub mod file;
+pub mod memory;
+pub mod sender;
+pub mod smtp;
+pub mod templates;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment as AttachmentPart, Mailbox, MultiPart};
+use lettre::Message;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::config::{Config, MailTransportKind};
+
+pub use file::FileMailer;
+pub use memory::MemoryMailer;
+pub use sender::EmailSender;
+pub use smtp::SmtpMailer;
+pub use templates::{EmailTemplate, Templates};
+pub(crate) use templates::escape_html;
+
+/// A rendered email, ready to be handed to a [`Mailer`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+}
+
+/// A file sent along with an email, e.g. a calendar invite.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    pub content: String,
+}
+
+/// An email waiting in the outbox; rendered only when it is sent, so a
+/// template fix also applies to messages still being retried.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedEmail {
+    pub template: EmailTemplate,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    pub vars: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+}
+
+impl QueuedEmail {
+    pub fn new(template: EmailTemplate, vars: serde_json::Value) -> Self {
+        Self {
+            template,
+            locale: None,
+            vars,
+            attachments: Vec::new(),
+        }
+    }
+
+    pub fn with_attachment(mut self, attachment: Attachment) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> anyhow::Result<()>;
+}
+
+impl Email {
+    /// Builds the MIME message with both a text and an HTML part, followed by
+    /// any attachments.
+    pub fn to_message(&self, from: &Mailbox) -> anyhow::Result<Message> {
+        let to: Mailbox = self.to.parse().with_context(|| format!("Invalid recipient {}", self.to))?;
+
+        let body = MultiPart::alternative_plain_html(self.text.clone(), self.html.clone());
+        let body = if self.attachments.is_empty() {
+            body
+        } else {
+            let mut mixed = MultiPart::mixed().multipart(body);
+            for attachment in &self.attachments {
+                let content_type = ContentType::parse(&attachment.content_type)
+                    .with_context(|| format!("Invalid content type {}", attachment.content_type))?;
+                mixed = mixed.singlepart(
+                    AttachmentPart::new(attachment.filename.clone()).body(attachment.content.clone(), content_type),
+                );
+            }
+            mixed
+        };
+
+        Message::builder()
+            .from(from.clone())
+            .to(to)
+            .subject(&self.subject)
+            .multipart(body)
+            .context("Failed to build email")
+    }
+}
+
+/// Builds the mailer selected by `MAIL_TRANSPORT`.
+pub fn from_config(config: &Config) -> anyhow::Result<Arc<dyn Mailer>> {
+    let from: Mailbox = config.mail_from.parse().context("MAIL_FROM is not a valid mailbox")?;
+
+    Ok(match config.mail_transport {
+        MailTransportKind::Smtp => Arc::new(SmtpMailer::new(config, from)?),
+        MailTransportKind::File => Arc::new(FileMailer::new(&config.mail_dir, from)),
+        MailTransportKind::Memory => Arc::new(MemoryMailer::new()),
+    })
+}
@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use super::{Mailer, QueuedEmail, Templates};
+use crate::models::OutboxMessage;
+
+/// Renders and sends the emails queued in the outbox.
+#[derive(Clone)]
+pub struct EmailSender {
+    mailer: Arc<dyn Mailer>,
+    templates: Templates,
+}
+
+impl EmailSender {
+    pub fn new(mailer: Arc<dyn Mailer>, templates: Templates) -> Self {
+        Self { mailer, templates }
+    }
+
+    pub async fn deliver(&self, msg: &OutboxMessage) -> anyhow::Result<()> {
+        let to = msg
+            .target
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Email {} has no recipient", msg.id))?;
+        let queued: QueuedEmail = serde_json::from_value(msg.payload.clone())
+            .map_err(|e| anyhow::anyhow!("Malformed queued email: {}", e))?;
+
+        let email = self.templates.render(to, &queued)?;
+        self.mailer.send(&email).await?;
+
+        tracing::debug!("Sent {} email to {}", queued.template.name(), to);
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{Email, QueuedEmail};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailTemplate {
+    MagicLink,
+    WorkspaceInvite,
+    SubstitutionRequested,
+    SubstitutionAccepted,
+    SubstitutionRejected,
+    TimetableChanged,
+    Notification,
+    NotificationDigest,
+}
+
+impl EmailTemplate {
+    pub fn name(self) -> &'static str {
+        match self {
+            EmailTemplate::MagicLink => "magic_link",
+            EmailTemplate::WorkspaceInvite => "workspace_invite",
+            EmailTemplate::SubstitutionRequested => "substitution_requested",
+            EmailTemplate::SubstitutionAccepted => "substitution_accepted",
+            EmailTemplate::SubstitutionRejected => "substitution_rejected",
+            EmailTemplate::TimetableChanged => "timetable_changed",
+            EmailTemplate::Notification => "notification",
+            EmailTemplate::NotificationDigest => "notification_digest",
+        }
+    }
+}
+
+macro_rules! template {
+    ($locale:literal, $name:literal) => {
+        (
+            $locale,
+            $name,
+            include_str!(concat!("../../templates/email/", $locale, "/", $name, ".txt")),
+            include_str!(concat!("../../templates/email/", $locale, "/", $name, ".html")),
+        )
+    };
+}
+
+// (locale, template, text, html). The first line of the text template is the
+// subject, written as `Subject: ...`.
+const TEMPLATES: &[(&str, &str, &str, &str)] = &[
+    template!("en", "magic_link"),
+    template!("en", "workspace_invite"),
+    template!("en", "substitution_requested"),
+    template!("en", "substitution_accepted"),
+    template!("en", "substitution_rejected"),
+    template!("en", "timetable_changed"),
+    template!("en", "notification"),
+    template!("en", "notification_digest"),
+    template!("fr", "magic_link"),
+    template!("fr", "workspace_invite"),
+    template!("fr", "substitution_requested"),
+    template!("fr", "substitution_accepted"),
+    template!("fr", "substitution_rejected"),
+    template!("fr", "timetable_changed"),
+    template!("fr", "notification"),
+    template!("fr", "notification_digest"),
+];
+
+const FALLBACK_LOCALE: &str = "en";
+
+const WEEKDAYS: &[(&str, [&str; 7])] = &[
+    ("en", ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"]),
+    ("fr", ["dimanche", "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi"]),
+];
+
+/// Renders the built-in email templates. Templates use `{{ name }}`
+/// placeholders; values are HTML-escaped in the HTML part unless written as
+/// `{{{ name }}}`, which is meant for markup the caller escaped already.
+#[derive(Clone)]
+pub struct Templates {
+    default_locale: String,
+}
+
+impl Templates {
+    pub fn new(default_locale: impl Into<String>) -> Self {
+        Self {
+            default_locale: default_locale.into(),
+        }
+    }
+
+    pub fn render(&self, to: &str, email: &QueuedEmail) -> anyhow::Result<Email> {
+        let locale = email.locale.as_deref().unwrap_or(&self.default_locale);
+        let (text, html) = find(locale, email.template.name())
+            .or_else(|| find(FALLBACK_LOCALE, email.template.name()))
+            .with_context(|| format!("No template named {}", email.template.name()))?;
+
+        let mut vars = email.vars.clone();
+        add_weekday(&mut vars, locale);
+
+        let text = render(text, &vars, false)?;
+        let (subject, body) = text
+            .strip_prefix("Subject: ")
+            .and_then(|rest| rest.split_once('\n'))
+            .with_context(|| format!("Template {} has no subject line", email.template.name()))?;
+
+        Ok(Email {
+            to: to.to_string(),
+            subject: subject.trim().to_string(),
+            text: body.trim_start().to_string(),
+            html: render(html, &vars, true)?,
+            attachments: email.attachments.clone(),
+        })
+    }
+}
+
+fn find(locale: &str, name: &str) -> Option<(&'static str, &'static str)> {
+    // "fr-CA" falls back to "fr"
+    let language = locale.split(['-', '_']).next().unwrap_or(locale);
+    TEMPLATES
+        .iter()
+        .find(|(l, n, _, _)| (*l == locale || *l == language) && *n == name)
+        .map(|(_, _, text, html)| (*text, *html))
+}
+
+/// Exposes `day_of_week` (0 = Sunday) as a localised `weekday` variable.
+fn add_weekday(vars: &mut Value, locale: &str) {
+    let Some(day) = vars.get("day_of_week").and_then(Value::as_u64) else {
+        return;
+    };
+    let language = locale.split(['-', '_']).next().unwrap_or(locale);
+    let names = WEEKDAYS
+        .iter()
+        .find(|(l, _)| *l == language)
+        .unwrap_or(&WEEKDAYS[0])
+        .1;
+
+    if let (Some(name), Some(map)) = (names.get(day as usize), vars.as_object_mut()) {
+        map.insert("weekday".to_string(), Value::String(name.to_string()));
+    }
+}
+
+fn render(template: &str, vars: &Value, escape: bool) -> anyhow::Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let raw = rest[start + 2..].starts_with('{');
+        let (open, close) = if raw { (3, "}}}") } else { (2, "}}") };
+        let after = &rest[start + open..];
+        let end = after.find(close).context("Unclosed placeholder in email template")?;
+        let name = after[..end].trim();
+
+        let value = match vars.get(name) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Null) | None => bail!("Missing email template variable {}", name),
+            Some(other) => other.to_string(),
+        };
+        if escape && !raw {
+            out.push_str(&escape_html(&value));
+        } else {
+            out.push_str(&value);
+        }
+
+        rest = &after[end + close.len()..];
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+pub(crate) fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
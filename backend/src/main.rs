@@ -1,4 +1,4 @@
-use backend::{api, config::Config, graphql, oidc, ws::Broadcaster, AppState};
+use backend::{api, mail::{self, EmailSender, Templates}, config::{BroadcastBackendKind, Config}, graphql, oidc, repository::{AbsenceRepository, AvailabilityRepository, AvailabilityRoundRepository, NotificationRepository, OccurrenceRepository, OutboxRepository, RealtimeEventRepository, SubstitutionRepository, SubstitutionStatsRepository, TicketRepository, UserRepository}, service::{availability_rounds, notifications, outbox, substitutions, AvailabilityRoundService, NotificationService, OccurrenceService, OutboxService, SubstitutionService, SubstitutionStatsService}, ws::{self, Broadcaster, PostgresBackend, TicketStore}, AppState};
 use sqlx::postgres::PgPoolOptions;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -34,20 +34,71 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("Database migrations completed successfully");
 
-    // Create Broadcaster for WebSockets
-    let broadcaster = Arc::new(Broadcaster::new(1024));
+    // Create Broadcaster for WebSockets, persisting events so clients can resume
+    let event_store = RealtimeEventRepository::new(pool.clone());
+    ws::spawn_retention_task(event_store.clone(), config.event_retention_days);
+    let mut broadcaster = Broadcaster::new(1024).with_store(event_store);
+    if config.broadcast_backend == BroadcastBackendKind::Postgres {
+        broadcaster = broadcaster.with_backend(PostgresBackend::new(pool.clone()));
+    }
+    let broadcaster = Arc::new(broadcaster);
+    if config.broadcast_backend == BroadcastBackendKind::Postgres {
+        ws::backend::spawn_postgres_listener(pool.clone(), broadcaster.clone());
+    }
+
+    // Deliver side effects recorded in the outbox
+    let outbox = Arc::new(OutboxService::new(OutboxRepository::new(pool.clone())));
+    let mailer = mail::from_config(&config)?;
+    let emails = EmailSender::new(mailer, Templates::new(config.mail_locale.clone()));
+    outbox::spawn_dispatcher(outbox.clone(), broadcaster.clone(), emails);
+    notifications::spawn_retention_task(NotificationRepository::new(pool.clone()), config.notification_retention_days);
+    let notification_service = Arc::new(NotificationService::new(
+        NotificationRepository::new(pool.clone()),
+        UserRepository::new(pool.clone()),
+        outbox.clone(),
+        config.clone(),
+    ));
+    notifications::spawn_digest_task(notification_service.clone());
+    availability_rounds::spawn_round_task(Arc::new(AvailabilityRoundService::new(
+        AvailabilityRoundRepository::new(pool.clone()),
+        AvailabilityRepository::new(pool.clone()),
+        outbox.clone(),
+        notification_service.clone(),
+    )));
+    let substitution_stats = Arc::new(SubstitutionStatsService::new(SubstitutionStatsRepository::new(pool.clone())));
+    substitutions::spawn_offer_expiry_task(Arc::new(SubstitutionService::new(
+        SubstitutionRepository::new(pool.clone()),
+        AbsenceRepository::new(pool.clone()),
+        outbox.clone(),
+        notification_service,
+        substitution_stats.clone(),
+    )));
 
     // Discover OIDC client
     let oidc_client = Arc::new(oidc::discover_oidc_client(&config).await?);
 
+    let occurrences = Arc::new(OccurrenceService::new(OccurrenceRepository::new(pool.clone()), config.clone()));
+    let tickets = Arc::new(TicketStore::new(TicketRepository::new(pool.clone())));
+
     // Create GraphQL schema
-    let schema = graphql::create_schema(pool, broadcaster.clone(), config.clone(), oidc_client.clone());
+    let schema = graphql::create_schema(
+        pool,
+        broadcaster.clone(),
+        outbox,
+        occurrences.clone(),
+        substitution_stats.clone(),
+        config.clone(),
+        oidc_client.clone(),
+    );
 
     // Create AppState
     let state = AppState {
         broadcaster: broadcaster.clone(),
         config: config.clone(),
         oidc_client,
+        tickets,
+        occurrences,
+        substitution_stats,
     };
 
     // Setup router
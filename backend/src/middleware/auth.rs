@@ -25,8 +25,15 @@ pub async fn auth(
         _ => return Err(AppError::Unauthorized),
     };
 
-    let secret = &state.config.jwt_secret;
+    let claims = decode_claims(token, &state.config.jwt_secret)?;
 
+    // Insert claims into request extensions so they can be accessed by GraphQL resolvers
+    req.extensions_mut().insert(claims);
+
+    Ok(next.run(req).await)
+}
+
+pub fn decode_claims(token: &str, secret: &str) -> Result<Claims, AppError> {
     let token_data = decode::<Claims>(
         token,
         &DecodingKey::from_secret(secret.as_bytes()),
@@ -37,8 +44,5 @@ pub async fn auth(
         AppError::Unauthorized
     })?;
 
-    // Insert claims into request extensions so they can be accessed by GraphQL resolvers
-    req.extensions_mut().insert(token_data.claims);
-
-    Ok(next.run(req).await)
+    Ok(token_data.claims)
 }
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+use super::Substitution;
+
+#[derive(Debug, Serialize, Deserialize, Clone, async_graphql::SimpleObject)]
+pub struct Absence {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub teacher_id: Uuid,
+    /// First day of the absence.
+    pub starts_on: NaiveDate,
+    /// Last day of the absence, inclusive.
+    pub ends_on: NaiveDate,
+    pub reason: Option<String>,
+    pub reported_by: Option<Uuid>,
+    /// Set once the absence is cancelled; its open substitutions are then
+    /// retracted.
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A lesson of the published timetable that falls into an absence.
+#[derive(Debug, Clone)]
+pub struct AbsentLesson {
+    pub date: NaiveDate,
+    /// The weekly timetable entry for the lesson; substitutions refer to it.
+    pub timetable_entry_id: Option<Uuid>,
+    pub course_name: String,
+    pub start_time: NaiveTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, async_graphql::SimpleObject)]
+pub struct ReportedAbsence {
+    pub absence: Absence,
+    /// One pending substitution per lesson missed.
+    pub substitutions: Vec<Substitution>,
+    /// Lessons already covered by another substitution, which were left as
+    /// they are.
+    pub already_covered: i32,
+    /// Published lessons without a matching weekly timetable entry, for
+    /// which no substitution could be requested.
+    pub unmatched_lessons: i32,
+}
@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use chrono::{DateTime, Utc, NaiveTime};
+use chrono::{DateTime, Utc, NaiveDate, NaiveTime};
 use sqlx::FromRow;
+use async_graphql::{Enum, SimpleObject};
 
 #[derive(Debug, Serialize, Deserialize, Clone, FromRow, async_graphql::SimpleObject)]
 pub struct Availability {
@@ -15,3 +16,44 @@ pub struct Availability {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "availability_override_kind")]
+pub enum AvailabilityOverrideKind {
+    /// Away, whatever the weekly pattern says.
+    Unavailable,
+    /// Available in addition to the weekly pattern.
+    Available,
+}
+
+/// A dated change to a teacher's weekly availability.
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject)]
+pub struct AvailabilityOverride {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub teacher_id: Uuid,
+    pub kind: AvailabilityOverrideKind,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    /// Limits the override to this weekday of the range, 0 being Sunday.
+    pub day_of_week: Option<i32>,
+    /// Without times the override covers the whole day.
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    pub reason: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A window in which a teacher is available on a date, after applying
+/// the overrides to the weekly pattern.
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject)]
+pub struct AvailabilityWindow {
+    pub date: NaiveDate,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub is_preferred: bool,
+    /// Set when the window comes from an extra availability override.
+    pub override_id: Option<Uuid>,
+}
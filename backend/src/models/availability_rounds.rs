@@ -0,0 +1,39 @@
+use async_graphql::{Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "availability_round_status")]
+pub enum AvailabilityRoundStatus {
+    Open,
+    Closed,
+}
+
+/// A call for teachers to submit their availability for a term by a
+/// deadline.
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject)]
+pub struct AvailabilityRound {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub term: String,
+    pub year: i32,
+    pub deadline: DateTime<Utc>,
+    /// How long before the deadline the automatic reminder goes out.
+    pub remind_hours_before: i32,
+    pub status: AvailabilityRoundStatus,
+    pub reminded_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Whether a teacher has submitted their availability for a round.
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject)]
+pub struct AvailabilitySubmission {
+    pub round_id: Uuid,
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    pub submitted_at: Option<DateTime<Utc>>,
+}
@@ -1,7 +1,7 @@
 use async_graphql::Enum;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use sqlx::FromRow;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
@@ -12,6 +12,20 @@ pub enum ConflictStatus {
     Ignored,
 }
 
+/// Which rule found a conflict.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "conflict_kind")]
+pub enum ConflictKind {
+    TeacherDoubleBooking,
+    RoomDoubleBooking,
+    GroupDoubleBooking,
+    RoomCapacity,
+    RoomFeatures,
+    Qualification,
+    Availability,
+    DatedAvailability,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum)]
 pub enum ConflictSeverity {
     Low,
@@ -23,11 +37,64 @@ pub enum ConflictSeverity {
 pub struct Conflict {
     pub id: Uuid,
     pub draft_timetable_id: Uuid,
+    pub kind: ConflictKind,
     pub description: String,
     pub teacher_id: Option<Uuid>,
     pub room_id: Option<Uuid>,
     pub time_slot_id: Option<Uuid>,
+    pub student_group_id: Option<Uuid>,
     pub status: ConflictStatus,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
+
+impl Conflict {
+    /// What makes a conflict the same one when it is detected again.
+    pub fn key(&self) -> (ConflictKind, Option<Uuid>, Option<Uuid>, Option<Uuid>, Option<Uuid>) {
+        (self.kind, self.teacher_id, self.room_id, self.student_group_id, self.time_slot_id)
+    }
+}
+
+/// How many students a draft lesson has and how many its room holds.
+#[derive(Debug, Clone, FromRow)]
+pub struct LessonSize {
+    pub draft_entry_id: Uuid,
+    pub course_id: Uuid,
+    pub room_id: Uuid,
+    pub room_name: String,
+    pub capacity: i32,
+    pub time_slot_id: Uuid,
+    /// Zero when nothing tells how many students attend.
+    pub size: i32,
+    pub tolerance: i32,
+}
+
+impl LessonSize {
+    /// Whether the lesson exceeds its room by more than the tolerance.
+    pub fn overflows(&self) -> bool {
+        self.size as i64 * 100 > self.capacity as i64 * (100 + self.tolerance as i64)
+    }
+}
+
+/// A room free at the time of a lesson that holds its students and has
+/// the features it requires.
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, async_graphql::SimpleObject)]
+pub struct RoomSuggestion {
+    pub room_id: Uuid,
+    pub room_name: String,
+    pub capacity: i32,
+    /// How many of the lesson's preferred features the room has.
+    pub preferred_features: i32,
+}
+
+/// A lesson of a published draft whose teacher is unavailable on some of
+/// its dates, counting lesson exceptions.
+#[derive(Debug, Clone, FromRow)]
+pub struct UnavailableLesson {
+    pub teacher_id: Uuid,
+    /// Empty for extra lessons.
+    pub time_slot_id: Option<Uuid>,
+    pub course_id: Uuid,
+    pub first_date: NaiveDate,
+    pub dates: i64,
+}
@@ -11,6 +11,9 @@ pub struct DraftEntry {
     pub teacher_id: Uuid,
     pub room_id: Uuid,
     pub time_slot_id: Uuid,
+    /// The section the lesson is for; without one it is for every section
+    /// of the course.
+    pub course_section_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -0,0 +1,67 @@
+use async_graphql::SimpleObject;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+use crate::models::PublishedLesson;
+
+/// A student on the roster, with or without a login of their own.
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, SimpleObject)]
+pub struct Student {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    /// The school's own number for the student; imports match on it.
+    pub student_number: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub user_id: Option<Uuid>,
+    /// Their class, whose lessons they attend.
+    pub student_group_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// One of the parallel sections of a course, such as an elective taught
+/// to two sets at different times.
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, SimpleObject)]
+pub struct CourseSection {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub course_id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, SimpleObject)]
+pub struct Enrollment {
+    pub student_id: Uuid,
+    pub course_section_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A weekly lesson of a student, with the section they attend it in.
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, SimpleObject)]
+pub struct StudentLesson {
+    #[sqlx(flatten)]
+    #[graphql(flatten)]
+    pub lesson: PublishedLesson,
+    pub course_section_id: Option<Uuid>,
+    pub section_name: Option<String>,
+}
+
+/// Two lessons of a student at overlapping times.
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject)]
+pub struct EnrollmentClash {
+    pub student_id: Uuid,
+    pub student_name: String,
+    pub first: StudentLesson,
+    pub second: StudentLesson,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject)]
+pub struct EnrollmentImport {
+    pub students_created: i32,
+    pub enrollments_created: i32,
+}
@@ -0,0 +1,126 @@
+use async_graphql::{Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+use super::SubstitutionStatus;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "lesson_exception_kind")]
+pub enum LessonExceptionKind {
+    Cancellation,
+    RoomChange,
+    ExtraLesson,
+    TeacherChange,
+}
+
+/// A one-off change on a single date, on top of the timetable published
+/// for that date.
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject)]
+pub struct LessonException {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub published_timetable_id: Uuid,
+    pub date: NaiveDate,
+    pub kind: LessonExceptionKind,
+    /// The lesson changed; empty for extra lessons.
+    pub timetable_entry_id: Option<Uuid>,
+    pub course_id: Uuid,
+    /// The teacher of the lesson, or who teaches it instead after a
+    /// teacher change.
+    pub teacher_id: Uuid,
+    /// The new room of a room change, or the room of an extra lesson.
+    pub room_id: Option<Uuid>,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub reason: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A weekly lesson being changed, checked against the published timetable.
+#[derive(Debug, Clone)]
+pub struct ExceptionLesson {
+    pub course_id: Uuid,
+    pub teacher_id: Uuid,
+    pub room_id: Uuid,
+    pub day_of_week: i32,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    /// The timetable published for the date has this lesson.
+    pub is_published: bool,
+}
+
+/// An exception with the names shown on the changes board.
+#[derive(Debug, Clone)]
+pub struct ExceptionChange {
+    pub id: Uuid,
+    pub kind: LessonExceptionKind,
+    pub timetable_entry_id: Option<Uuid>,
+    pub course_id: Uuid,
+    pub course_name: String,
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    /// Who normally teaches the lesson; empty for extra lessons.
+    pub scheduled_teacher_id: Option<Uuid>,
+    pub scheduled_teacher_name: Option<String>,
+    /// Where the lesson is normally held; empty for extra lessons.
+    pub scheduled_room_id: Option<Uuid>,
+    pub scheduled_room_name: Option<String>,
+    pub room_id: Option<Uuid>,
+    pub room_name: Option<String>,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub reason: Option<String>,
+}
+
+/// A pending or accepted substitution on the board's date.
+#[derive(Debug, Clone)]
+pub struct SubstitutionChange {
+    pub id: Uuid,
+    pub timetable_entry_id: Uuid,
+    pub status: SubstitutionStatus,
+    pub course_id: Uuid,
+    pub course_name: String,
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    pub room_id: Uuid,
+    pub room_name: String,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub substitute_id: Option<Uuid>,
+    pub substitute_name: Option<String>,
+}
+
+/// One line of the daily changes board: a lesson that differs from the
+/// timetable on that date, with everything that changed about it.
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject)]
+pub struct DailyChange {
+    /// Empty for extra lessons.
+    pub timetable_entry_id: Option<Uuid>,
+    pub course_id: Uuid,
+    pub course_name: String,
+    /// The teacher in the timetable, or of the extra lesson.
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    /// Where the lesson is normally held, or where the extra lesson is.
+    pub room_id: Option<Uuid>,
+    pub room_name: Option<String>,
+    pub cancelled: bool,
+    pub extra_lesson: bool,
+    /// Set when the lesson moves to another room for the day.
+    pub new_room_id: Option<Uuid>,
+    pub new_room_name: Option<String>,
+    /// Set when another teacher takes the lesson for the day, e.g. after a
+    /// swap.
+    pub new_teacher_id: Option<Uuid>,
+    pub new_teacher_name: Option<String>,
+    pub substitution_id: Option<Uuid>,
+    pub substitution_status: Option<SubstitutionStatus>,
+    pub substitute_id: Option<Uuid>,
+    pub substitute_name: Option<String>,
+    pub exception_ids: Vec<Uuid>,
+    pub reason: Option<String>,
+}
@@ -0,0 +1,55 @@
+use async_graphql::{Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "lesson_swap_status")]
+pub enum LessonSwapStatus {
+    /// Waiting for the colleague to answer.
+    Proposed,
+    /// The colleague agreed; waiting for a coordinator.
+    Accepted,
+    /// Applied to the timetable as teacher changes.
+    Approved,
+    Declined,
+    Rejected,
+    Cancelled,
+}
+
+/// A teacher's proposal to swap one of their lessons with a colleague's.
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject)]
+pub struct LessonSwap {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub requester_id: Uuid,
+    /// The requester's lesson, which the colleague takes over.
+    pub requester_entry_id: Uuid,
+    pub requester_date: NaiveDate,
+    pub colleague_id: Uuid,
+    /// The colleague's lesson, which the requester takes over.
+    pub colleague_entry_id: Uuid,
+    pub colleague_date: NaiveDate,
+    pub status: LessonSwapStatus,
+    pub reason: Option<String>,
+    pub decided_by: Option<Uuid>,
+    pub decision_note: Option<String>,
+    /// The teacher changes applied on approval.
+    pub requester_exception_id: Option<Uuid>,
+    pub colleague_exception_id: Option<Uuid>,
+    pub responded_at: Option<DateTime<Utc>>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Names and times of both lessons of a swap, for notifications.
+#[derive(Debug, Clone)]
+pub struct SwapSummary {
+    pub requester_name: String,
+    pub requester_course: String,
+    pub requester_start: NaiveTime,
+    pub colleague_name: String,
+    pub colleague_course: String,
+    pub colleague_start: NaiveTime,
+}
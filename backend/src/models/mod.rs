@@ -1,22 +1,63 @@
+pub mod absences;
 pub mod availability;
+pub mod availability_rounds;
 pub mod conflicts;
 pub mod draft_entries;
 pub mod draft_timetables;
+pub mod enrollments;
+pub mod lesson_exceptions;
+pub mod lesson_swaps;
 pub mod magic_link;
+pub mod notifications;
+pub mod occurrences;
+pub mod outbox;
 pub mod published_timetables;
+pub mod realtime_events;
+pub mod room_features;
 pub mod snapshot;
+pub mod student_groups;
+pub mod substitution_candidates;
+pub mod substitution_offers;
+pub mod substitution_stats;
+pub mod teacher_preferences;
+pub mod teacher_qualifications;
 pub mod workspace;
 use async_graphql::Enum;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use chrono::{DateTime, Utc, NaiveTime};
+use chrono::{DateTime, Utc, NaiveDate, NaiveTime};
 
-pub use availability::Availability;
-pub use conflicts::{Conflict, ConflictSeverity, ConflictStatus};
+pub use absences::{Absence, AbsentLesson, ReportedAbsence};
+pub use availability::{Availability, AvailabilityOverride, AvailabilityOverrideKind, AvailabilityWindow};
+pub use availability_rounds::{AvailabilityRound, AvailabilityRoundStatus, AvailabilitySubmission};
+pub use conflicts::{Conflict, ConflictKind, ConflictSeverity, ConflictStatus, LessonSize, RoomSuggestion, UnavailableLesson};
 pub use draft_entries::DraftEntry;
 pub use draft_timetables::{DraftTimetable, DraftTimetableStatus};
+pub use enrollments::{CourseSection, Enrollment, EnrollmentClash, EnrollmentImport, Student, StudentLesson};
+pub use lesson_exceptions::{
+    DailyChange, ExceptionChange, ExceptionLesson, LessonException, LessonExceptionKind, SubstitutionChange,
+};
+pub use lesson_swaps::{LessonSwap, LessonSwapStatus, SwapSummary};
 pub use magic_link::MagicLink;
-pub use published_timetables::PublishedTimetable;
+pub use notifications::{
+    Notification, NotificationDelivery, NotificationPreference, NotificationSettings, NotificationType,
+};
+pub use occurrences::{CalendarFeed, DatedException, DatedSubstitution, LessonOccurrence, WeeklyLesson};
+pub use outbox::{CreatedWebhookEndpoint, OutboxChannel, OutboxMessage, WebhookEndpoint};
+pub use published_timetables::{
+    LessonChange, LessonChangeKind, PublishedLesson, PublishedTimetable, RoomTimetableChanges, TeacherTimetableChanges,
+    TimetableChanges,
+};
+pub use realtime_events::RealtimeEvent;
+pub use room_features::{LessonFeature, RoomFeature, RoomFeatureNeed, RoomFeatureRequirement};
+pub use student_groups::{DraftEntryGroup, StudentGroup, StudentGroupKind};
+pub use substitution_candidates::{CandidateFacts, ScoreComponent, SubstitutionCandidate};
+pub use substitution_offers::{ExpiredOffer, OfferMode, SubstitutionOffer, SubstitutionOfferStatus};
+pub use substitution_stats::TeacherSubstitutionStats;
+pub use teacher_preferences::{
+    PreferenceViolation, ScheduledLesson, TeacherPreference, TeacherPreferenceKind, TeacherPreferenceScore,
+};
+pub use teacher_qualifications::{QualificationImport, QualificationPreference, TeacherQualification};
 pub use workspace::{Workspace, WorkspaceInvite, WorkspaceMember, WorkspaceRole};
 
 #[derive(Debug, Serialize, Deserialize, Clone, async_graphql::SimpleObject)]
@@ -38,6 +79,10 @@ pub struct Course {
     pub code: String,
     pub name: String,
     pub description: Option<String>,
+    pub department: Option<String>,
+    /// Number of students expected, for courses without groups or
+    /// enrolments to count.
+    pub expected_size: Option<i32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -88,6 +133,8 @@ pub enum SubstitutionStatus {
     Pending,
     Accepted,
     Rejected,
+    /// Withdrawn before anyone accepted it.
+    Cancelled,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, async_graphql::SimpleObject)]
@@ -96,10 +143,30 @@ pub struct Substitution {
     pub timetable_entry_id: Uuid,
     pub substituting_teacher_id: Option<Uuid>,
     pub status: SubstitutionStatus,
+    /// First date of the lesson covered.
+    pub starts_on: NaiveDate,
+    /// Last date covered; the same as `starts_on` for a single lesson.
+    pub ends_on: NaiveDate,
+    /// The absence the substitution was requested for, if any.
+    pub absence_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// The lesson a substitution is about, as shown in notifications.
+#[derive(Debug, Clone)]
+pub struct SubstitutionLesson {
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    pub substitute_name: Option<String>,
+    pub course_name: String,
+    pub day_of_week: i32,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, async_graphql::SimpleObject)]
 pub struct Token {
     pub access_token: String,
@@ -0,0 +1,98 @@
+use async_graphql::Enum;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, NaiveTime, Utc};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Enum, sqlx::Type)]
+#[sqlx(type_name = "notification_type")]
+pub enum NotificationType {
+    /// A lesson needs a substitute the user could cover.
+    SubstitutionOffer,
+    /// A substitution for one of the user's lessons changed status.
+    SubstitutionUpdate,
+    TimetablePublished,
+    ConflictAssigned,
+    AvailabilityReminder,
+    /// A lesson swap the user is part of was proposed, answered or decided.
+    LessonSwap,
+}
+
+impl NotificationType {
+    pub const ALL: [NotificationType; 6] = [
+        NotificationType::SubstitutionOffer,
+        NotificationType::SubstitutionUpdate,
+        NotificationType::TimetablePublished,
+        NotificationType::ConflictAssigned,
+        NotificationType::AvailabilityReminder,
+        NotificationType::LessonSwap,
+    ];
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, async_graphql::SimpleObject)]
+pub struct Notification {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub user_id: Uuid,
+    pub notification_type: NotificationType,
+    pub title: String,
+    pub body: Option<String>,
+    pub entity_id: Option<Uuid>,
+    pub link: Option<String>,
+    pub read_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub in_app: bool,
+    #[graphql(skip)]
+    pub digest_email: bool,
+    #[graphql(skip)]
+    pub digest_webhook: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "notification_delivery")]
+pub enum NotificationDelivery {
+    Immediate,
+    /// Shown in-app right away; email and webhook wait for the daily digest.
+    Digest,
+    Off,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, async_graphql::SimpleObject)]
+pub struct NotificationPreference {
+    pub workspace_id: Uuid,
+    pub user_id: Uuid,
+    pub notification_type: NotificationType,
+    pub delivery: NotificationDelivery,
+    pub in_app: bool,
+    pub email: bool,
+    pub webhook: bool,
+}
+
+impl NotificationPreference {
+    /// What users get for types they never configured.
+    pub fn default_for(workspace_id: Uuid, user_id: Uuid, notification_type: NotificationType) -> Self {
+        Self {
+            workspace_id,
+            user_id,
+            notification_type,
+            delivery: NotificationDelivery::Immediate,
+            in_app: true,
+            email: true,
+            webhook: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, async_graphql::SimpleObject)]
+pub struct NotificationSettings {
+    pub workspace_id: Uuid,
+    pub user_id: Uuid,
+    /// IANA time zone, e.g. `Europe/Paris`.
+    pub timezone: String,
+    /// Local time at which the daily digest goes out.
+    pub digest_time: NaiveTime,
+    /// Receives notifications routed to the webhook channel.
+    pub webhook_url: Option<String>,
+    pub last_digest_at: Option<DateTime<Utc>>,
+}
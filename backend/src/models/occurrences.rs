@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+use super::{LessonExceptionKind, SubstitutionStatus};
+
+/// A weekly timetable entry with the names needed to show it.
+#[derive(Debug, Clone)]
+pub struct WeeklyLesson {
+    pub timetable_entry_id: Uuid,
+    pub course_id: Uuid,
+    pub course_name: String,
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    pub room_id: Uuid,
+    pub room_name: String,
+    pub day_of_week: i32,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+}
+
+/// A substitution with the name of the substitute, if any.
+#[derive(Debug, Clone)]
+pub struct DatedSubstitution {
+    pub id: Uuid,
+    pub timetable_entry_id: Uuid,
+    pub status: SubstitutionStatus,
+    pub substitute_id: Option<Uuid>,
+    pub substitute_name: Option<String>,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+}
+
+/// A one-off change to the lessons of a date, with the names needed to
+/// show it.
+#[derive(Debug, Clone)]
+pub struct DatedException {
+    pub id: Uuid,
+    pub date: NaiveDate,
+    pub kind: LessonExceptionKind,
+    pub timetable_entry_id: Option<Uuid>,
+    pub course_id: Uuid,
+    pub course_name: String,
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    pub room_id: Option<Uuid>,
+    pub room_name: Option<String>,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+}
+
+/// A lesson on a concrete date, with the substitution and the lesson
+/// exception for that date if there are any.
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+pub struct LessonOccurrence {
+    pub date: NaiveDate,
+    /// The weekly lesson; empty for extra lessons.
+    pub timetable_entry_id: Option<Uuid>,
+    pub course_id: Uuid,
+    pub course_name: String,
+    /// The teacher in the weekly timetable, or the one taking the lesson over
+    /// on this date after a swap.
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    pub room_id: Uuid,
+    pub room_name: String,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub substitution_id: Option<Uuid>,
+    pub substitution_status: Option<SubstitutionStatus>,
+    /// Who teaches the lesson on this date instead, once accepted.
+    pub substitute_id: Option<Uuid>,
+    pub substitute_name: Option<String>,
+    /// The room change, teacher change or extra lesson behind this occurrence,
+    /// if any.
+    pub lesson_exception_id: Option<Uuid>,
+    pub exception_kind: Option<LessonExceptionKind>,
+}
+
+impl LessonOccurrence {
+    /// The teacher actually teaching on this date.
+    pub fn taught_by(&self) -> Uuid {
+        self.substitute_id.unwrap_or(self.teacher_id)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CalendarFeed {
+    pub token: String,
+    pub workspace_id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,53 @@
+use async_graphql::Enum;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "outbox_channel")]
+pub enum OutboxChannel {
+    WebSocket,
+    Webhook,
+    Email,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct OutboxMessage {
+    pub id: Uuid,
+    pub workspace_id: Option<Uuid>,
+    pub channel: OutboxChannel,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub target: Option<String>,
+    pub webhook_endpoint_id: Option<Uuid>,
+    pub dedupe_key: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, async_graphql::SimpleObject)]
+pub struct WebhookEndpoint {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub url: String,
+    /// Only handed out once, by `CreatedWebhookEndpoint`.
+    #[graphql(skip)]
+    pub secret: String,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A newly registered endpoint along with its secret, which is not shown
+/// again afterwards.
+#[derive(Debug, Clone, async_graphql::SimpleObject)]
+pub struct CreatedWebhookEndpoint {
+    #[graphql(flatten)]
+    pub endpoint: WebhookEndpoint,
+    /// Key for the `X-Nullslot-Signature` header of each delivery.
+    pub secret: String,
+}
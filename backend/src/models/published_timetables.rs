@@ -1,6 +1,7 @@
+use async_graphql::Enum;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use chrono::{DateTime, Utc, NaiveDate};
+use chrono::{DateTime, Utc, NaiveDate, NaiveTime};
 use sqlx::FromRow;
 
 #[derive(Debug, Serialize, Deserialize, Clone, FromRow, async_graphql::SimpleObject)]
@@ -13,4 +14,72 @@ pub struct PublishedTimetable {
     pub valid_to: NaiveDate,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// The timetable in force when this one was published.
+    pub previous_published_timetable_id: Option<Uuid>,
+}
+
+/// One weekly lesson of a published timetable.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow, async_graphql::SimpleObject)]
+pub struct PublishedLesson {
+    pub course_id: Uuid,
+    pub course_name: String,
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    pub room_id: Uuid,
+    pub room_name: String,
+    pub time_slot_id: Uuid,
+    pub day_of_week: i32,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+}
+
+impl PublishedLesson {
+    /// Whether both are the same lesson held in the same place at the same time.
+    pub fn is_same_as(&self, other: &PublishedLesson) -> bool {
+        self.course_id == other.course_id
+            && self.teacher_id == other.teacher_id
+            && self.room_id == other.room_id
+            && self.time_slot_id == other.time_slot_id
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum LessonChangeKind {
+    Added,
+    Removed,
+    /// Same course at another time, in another room or with another teacher.
+    Moved,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, async_graphql::SimpleObject)]
+pub struct LessonChange {
+    pub kind: LessonChangeKind,
+    /// The lesson as it is now; for removed lessons, as it was.
+    pub lesson: PublishedLesson,
+    /// Where a moved lesson used to be.
+    pub previous: Option<PublishedLesson>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, async_graphql::SimpleObject)]
+pub struct TeacherTimetableChanges {
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    pub changes: Vec<LessonChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, async_graphql::SimpleObject)]
+pub struct RoomTimetableChanges {
+    pub room_id: Uuid,
+    pub room_name: String,
+    pub changes: Vec<LessonChange>,
+}
+
+/// What a published timetable changed compared to the one it replaced. Only
+/// teachers and rooms with changes are listed.
+#[derive(Debug, Serialize, Deserialize, Clone, async_graphql::SimpleObject)]
+pub struct TimetableChanges {
+    pub published_timetable_id: Uuid,
+    pub previous_published_timetable_id: Option<Uuid>,
+    pub teachers: Vec<TeacherTimetableChanges>,
+    pub rooms: Vec<RoomTimetableChanges>,
 }
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct RealtimeEvent {
+    pub workspace_id: Uuid,
+    pub seq: i64,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub topics: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
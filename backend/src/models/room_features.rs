@@ -0,0 +1,49 @@
+use async_graphql::{Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "room_feature_need")]
+pub enum RoomFeatureNeed {
+    /// A room without the feature is a conflict.
+    Required,
+    /// Rooms with the feature come first among suggestions.
+    Preferred,
+}
+
+impl sqlx::postgres::PgHasArrayType for RoomFeatureNeed {
+    fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("_room_feature_need")
+    }
+}
+
+/// Something a room can offer, such as a lab bench or wheelchair access.
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, SimpleObject)]
+pub struct RoomFeature {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A feature a course or student group needs in the rooms of its lessons.
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, SimpleObject)]
+pub struct RoomFeatureRequirement {
+    pub room_feature_id: Uuid,
+    pub name: String,
+    pub need: RoomFeatureNeed,
+}
+
+/// A feature a draft lesson needs, through its course or the groups
+/// attending it, and whether its room has it.
+#[derive(Debug, Clone, FromRow)]
+pub struct LessonFeature {
+    pub draft_entry_id: Uuid,
+    pub room_feature_id: Uuid,
+    pub name: String,
+    pub required: bool,
+    pub in_room: bool,
+}
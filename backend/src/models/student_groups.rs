@@ -0,0 +1,38 @@
+use async_graphql::{Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "student_group_kind")]
+pub enum StudentGroupKind {
+    /// All classes of a year; never part of another group.
+    YearGroup,
+    Class,
+    /// Part of a class, such as a language set; always has a parent.
+    Subgroup,
+}
+
+/// Students who attend lessons together.
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, SimpleObject)]
+pub struct StudentGroup {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    pub kind: StudentGroupKind,
+    /// The group this one is part of. Lessons of a group are also lessons
+    /// of every group within it.
+    pub parent_id: Option<Uuid>,
+    pub size: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A group attending a draft lesson, either assigned to the lesson itself
+/// or to its course.
+#[derive(Debug, Clone, Copy, FromRow)]
+pub struct DraftEntryGroup {
+    pub draft_entry_id: Uuid,
+    pub student_group_id: Uuid,
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use async_graphql::SimpleObject;
+
+use super::{QualificationPreference, TeacherSubstitutionStats};
+
+/// What the ranking knows about a teacher who could cover a substitution.
+#[derive(Debug, Clone)]
+pub struct CandidateFacts {
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    /// Teaches or covers another lesson at an overlapping time.
+    pub is_busy: bool,
+    /// Has a weekly pattern, or overrides on the lesson's dates.
+    pub has_availability: bool,
+    /// Available for the lesson on each of its dates, overrides included.
+    pub is_available: bool,
+    /// The covering window is marked as preferred.
+    pub is_preferred: bool,
+    /// How keen they are on the lesson's course, when qualified for it.
+    pub qualification: Option<QualificationPreference>,
+    /// Anyone is qualified for the course; otherwise it is open to all.
+    pub course_has_qualifications: bool,
+    pub same_department: bool,
+}
+
+/// One criterion's contribution to a candidate's score.
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct ScoreComponent {
+    pub criterion: String,
+    pub points: f64,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct SubstitutionCandidate {
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    /// Sum of the breakdown; higher is better.
+    pub score: f64,
+    pub breakdown: Vec<ScoreComponent>,
+    /// The substitution figures behind the load criterion.
+    pub fairness: Option<TeacherSubstitutionStats>,
+}
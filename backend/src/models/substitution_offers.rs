@@ -0,0 +1,51 @@
+use async_graphql::{Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+/// How a substitution is offered to the chosen teachers.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum OfferMode {
+    /// Everyone at once; the first to accept gets it.
+    All,
+    /// One teacher at a time, in the given order, moving on when an offer
+    /// is declined or times out.
+    Cascade,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "substitution_offer_status")]
+pub enum SubstitutionOfferStatus {
+    /// Queued in a cascade and not sent yet.
+    Waiting,
+    /// Sent and open until `expires_at`.
+    Pending,
+    Accepted,
+    Declined,
+    Expired,
+    /// Closed because someone else accepted or the substitution ended.
+    Withdrawn,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject)]
+pub struct SubstitutionOffer {
+    pub id: Uuid,
+    pub substitution_id: Uuid,
+    pub teacher_id: Uuid,
+    /// Order within a cascade; offers sent all at once share position 0.
+    pub position: i32,
+    pub status: SubstitutionOfferStatus,
+    pub timeout_minutes: i32,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub responded_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An offer that timed out, with the workspace it belongs to.
+#[derive(Debug, Clone)]
+pub struct ExpiredOffer {
+    pub workspace_id: Uuid,
+    pub substitution_id: Uuid,
+    pub teacher_id: Uuid,
+}
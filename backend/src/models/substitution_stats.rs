@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use async_graphql::SimpleObject;
+
+/// How much a teacher was covered for and covered for others over a
+/// period. Lessons are counted per date, so a substitution over a week
+/// counts each lesson of that week.
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct TeacherSubstitutionStats {
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    /// Lessons of the teacher's for which a substitute was requested.
+    pub requested: i64,
+    /// Lessons of colleagues the teacher covered.
+    pub covered: i64,
+    /// Offers to cover that the teacher turned down.
+    pub declined: i64,
+    /// Hours of the lessons the teacher covered.
+    pub cover_hours: f64,
+    /// Contracted hours over the period, from the weekly contract hours.
+    pub contract_hours: Option<f64>,
+    /// Cover hours as a share of contracted hours, from 0 to 1.
+    pub cover_share: Option<f64>,
+}
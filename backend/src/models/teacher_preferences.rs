@@ -0,0 +1,71 @@
+use async_graphql::{Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, NaiveTime, Utc};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "teacher_preference_kind")]
+pub enum TeacherPreferenceKind {
+    /// Teach within a time window (positive weight) or avoid it (negative).
+    TimeWindow,
+    /// No lessons on a day.
+    DayOff,
+    /// At most `max_count` free periods between lessons a day.
+    MaxGaps,
+    /// At most `max_count` lessons in a row.
+    MaxConsecutive,
+}
+
+/// A weighted scheduling wish of a teacher, scored as a soft constraint.
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject)]
+pub struct TeacherPreference {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub teacher_id: Uuid,
+    pub kind: TeacherPreferenceKind,
+    /// From -10 to 10; only time windows take negative weights, which mark
+    /// them as times to avoid.
+    pub weight: f64,
+    /// Limits the preference to one day, 0 being Sunday; required for days
+    /// off.
+    pub day_of_week: Option<i32>,
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    pub max_count: Option<i32>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A weekly lesson as far as preferences are concerned.
+#[derive(Debug, Clone)]
+pub struct ScheduledLesson {
+    pub teacher_id: Uuid,
+    pub time_slot_id: Uuid,
+    pub day_of_week: i32,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+}
+
+/// How far a timetable goes against one preference.
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct PreferenceViolation {
+    pub preference_id: Uuid,
+    pub kind: TeacherPreferenceKind,
+    /// The preference's weight times how often it is broken.
+    pub penalty: f64,
+    pub detail: String,
+    /// The lesson breaking it, for time windows.
+    pub time_slot_id: Option<Uuid>,
+    pub day_of_week: Option<i32>,
+}
+
+/// The soft-constraint score of a teacher's lessons in a draft.
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct TeacherPreferenceScore {
+    pub teacher_id: Uuid,
+    pub teacher_name: String,
+    /// Sum of the violation penalties; 0 when every preference is met.
+    pub penalty: f64,
+    pub violations: Vec<PreferenceViolation>,
+}
@@ -0,0 +1,33 @@
+use async_graphql::{Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "qualification_preference")]
+pub enum QualificationPreference {
+    /// Would like to teach the course.
+    Preferred,
+    Neutral,
+    /// Can teach the course but would rather not.
+    Reluctant,
+}
+
+/// A teacher allowed to teach a course. Courses nobody is qualified for
+/// are open to every teacher.
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow, SimpleObject)]
+pub struct TeacherQualification {
+    pub workspace_id: Uuid,
+    pub teacher_id: Uuid,
+    pub course_id: Uuid,
+    pub preference: QualificationPreference,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject)]
+pub struct QualificationImport {
+    pub qualifications_created: i32,
+    pub qualifications_updated: i32,
+}
@@ -11,11 +11,22 @@ pub enum WorkspaceRole {
     Viewer,
 }
 
+impl WorkspaceRole {
+    /// Owners and editors run the workspace's timetabling; viewers only act
+    /// for themselves.
+    pub fn can_edit(self) -> bool {
+        matches!(self, WorkspaceRole::Owner | WorkspaceRole::Editor)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, SimpleObject, sqlx::FromRow)]
 pub struct Workspace {
     pub id: Uuid,
     pub name: String,
     pub domain_restriction: Option<String>,
+    /// How far, in percent, a lesson may exceed the capacity of its room
+    /// before it is reported as a conflict.
+    pub room_capacity_tolerance: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -25,6 +36,9 @@ pub struct WorkspaceMember {
     pub workspace_id: Uuid,
     pub user_id: Uuid,
     pub role: WorkspaceRole,
+    pub department: Option<String>,
+    /// Contracted teaching hours per week.
+    pub contract_hours: Option<f64>,
     pub joined_at: DateTime<Utc>,
 }
 
@@ -0,0 +1,216 @@
+use chrono::NaiveDate;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::error::AppResult;
+use crate::models::{Absence, AbsentLesson};
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn create(&self, conn: &mut PgConnection, absence: Absence) -> AppResult<Absence> {
+        let absence = sqlx::query_as!(
+            Absence,
+            r#"
+            INSERT INTO absences (id, workspace_id, teacher_id, starts_on, ends_on, reason, reported_by, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id, workspace_id, teacher_id, starts_on, ends_on, reason, reported_by, cancelled_at, created_at, updated_at
+            "#,
+            absence.id,
+            absence.workspace_id,
+            absence.teacher_id,
+            absence.starts_on,
+            absence.ends_on,
+            absence.reason,
+            absence.reported_by,
+            absence.created_at,
+            absence.updated_at
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(absence)
+    }
+
+    pub async fn find_by_id(&self, workspace_id: Uuid, id: Uuid) -> AppResult<Option<Absence>> {
+        let absence = sqlx::query_as!(
+            Absence,
+            r#"
+            SELECT id, workspace_id, teacher_id, starts_on, ends_on, reason, reported_by, cancelled_at, created_at, updated_at
+            FROM absences
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(absence)
+    }
+
+    /// Absences overlapping the range, including cancelled ones.
+    pub async fn find_in_range(
+        &self,
+        workspace_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+        teacher_id: Option<Uuid>,
+    ) -> AppResult<Vec<Absence>> {
+        let absences = sqlx::query_as!(
+            Absence,
+            r#"
+            SELECT id, workspace_id, teacher_id, starts_on, ends_on, reason, reported_by, cancelled_at, created_at, updated_at
+            FROM absences
+            WHERE workspace_id = $1 AND starts_on <= $3 AND ends_on >= $2
+                AND ($4::uuid IS NULL OR teacher_id = $4)
+            ORDER BY starts_on, created_at
+            "#,
+            workspace_id,
+            from,
+            to,
+            teacher_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(absences)
+    }
+
+    /// Marks the absence as cancelled unless it already is. Returns the
+    /// updated absence, or `None` when there was nothing to cancel.
+    pub async fn cancel(&self, conn: &mut PgConnection, workspace_id: Uuid, id: Uuid) -> AppResult<Option<Absence>> {
+        let absence = sqlx::query_as!(
+            Absence,
+            r#"
+            UPDATE absences
+            SET cancelled_at = NOW()
+            WHERE workspace_id = $1 AND id = $2 AND cancelled_at IS NULL
+            RETURNING id, workspace_id, teacher_id, starts_on, ends_on, reason, reported_by, cancelled_at, created_at, updated_at
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(absence)
+    }
+
+    /// The name of a teacher who is a member of the workspace.
+    pub async fn find_teacher_name(&self, conn: &mut PgConnection, workspace_id: Uuid, teacher_id: Uuid) -> AppResult<Option<String>> {
+        let name = sqlx::query_scalar!(
+            r#"
+            SELECT u.username
+            FROM workspace_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.workspace_id = $1 AND m.user_id = $2 AND u.role = 'Teacher'
+            "#,
+            workspace_id,
+            teacher_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(name)
+    }
+
+    /// The teacher's lessons on each date of the range, taken from the
+    /// timetable published for that date. Each lesson is matched to the
+    /// weekly timetable entry with the same course, teacher and time slot,
+    /// preferring one in the same room.
+    pub async fn find_lessons(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        teacher_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> AppResult<Vec<AbsentLesson>> {
+        let lessons = sqlx::query_as!(
+            AbsentLesson,
+            r#"
+            WITH days AS (
+                SELECT d::date AS date
+                FROM generate_series($3::date, $4::date, INTERVAL '1 day') d
+            )
+            SELECT lesson.date AS "date!", lesson.timetable_entry_id AS "timetable_entry_id?",
+                lesson.course_name AS "course_name!", lesson.start_time AS "start_time!"
+            FROM (
+                SELECT days.date, te.id AS timetable_entry_id, c.name AS course_name, ts.start_time
+                FROM days
+                JOIN LATERAL (
+                    SELECT pt.draft_timetable_id
+                    FROM published_timetables pt
+                    WHERE pt.workspace_id = $1 AND pt.valid_from <= days.date AND pt.valid_to >= days.date
+                    ORDER BY pt.published_at DESC
+                    LIMIT 1
+                ) pt ON true
+                JOIN draft_entries de ON de.draft_timetable_id = pt.draft_timetable_id AND de.teacher_id = $2
+                JOIN time_slots ts ON ts.id = de.time_slot_id AND ts.day_of_week = EXTRACT(DOW FROM days.date)::int
+                JOIN courses c ON c.id = de.course_id
+                LEFT JOIN LATERAL (
+                    SELECT e.id
+                    FROM timetable_entries e
+                    WHERE e.course_id = de.course_id AND e.teacher_id = de.teacher_id AND e.time_slot_id = de.time_slot_id
+                    ORDER BY e.room_id = de.room_id DESC
+                    LIMIT 1
+                ) te ON true
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM lesson_exceptions x
+                    WHERE x.timetable_entry_id = te.id AND x.date = days.date AND x.kind IN ('Cancellation', 'TeacherChange')
+                )
+                UNION ALL
+                -- Lessons taken over from another teacher, and extra lessons, which
+                -- have no weekly entry to cover
+                SELECT x.date, x.timetable_entry_id, c.name, x.start_time
+                FROM lesson_exceptions x
+                JOIN courses c ON c.id = x.course_id
+                WHERE x.workspace_id = $1 AND x.teacher_id = $2 AND x.kind IN ('ExtraLesson', 'TeacherChange')
+                    AND x.date BETWEEN $3 AND $4
+            ) lesson
+            ORDER BY lesson.date, lesson.start_time, lesson.course_name
+            "#,
+            workspace_id,
+            teacher_id,
+            from,
+            to
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(lessons)
+    }
+
+    /// Every other teacher of the workspace, i.e. who can be asked to cover,
+    /// except those away themselves for the whole absence.
+    pub async fn find_pool(&self, conn: &mut PgConnection, absence: &Absence) -> AppResult<Vec<Uuid>> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            SELECT u.id
+            FROM workspace_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.workspace_id = $1 AND u.role = 'Teacher' AND u.id <> $2
+                AND NOT EXISTS (
+                    SELECT 1 FROM absences a
+                    WHERE a.workspace_id = $1 AND a.teacher_id = u.id AND a.cancelled_at IS NULL
+                        AND a.starts_on <= $3 AND a.ends_on >= $4
+                )
+            "#,
+            absence.workspace_id,
+            absence.teacher_id,
+            absence.starts_on,
+            absence.ends_on
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(ids)
+    }
+}
@@ -1,4 +1,4 @@
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 use crate::error::AppResult;
 use crate::models::magic_link::MagicLink;
 
@@ -12,7 +12,7 @@ impl AuthRepository {
         Self { db_pool }
     }
 
-    pub async fn create_magic_link(&self, magic_link: MagicLink) -> AppResult<MagicLink> {
+    pub async fn create_magic_link(&self, conn: &mut PgConnection, magic_link: MagicLink) -> AppResult<MagicLink> {
         let row = sqlx::query_as!(
             MagicLink,
             r#"
@@ -26,7 +26,7 @@ impl AuthRepository {
             magic_link.used,
             magic_link.created_at
         )
-        .fetch_one(&self.db_pool)
+        .fetch_one(conn)
         .await?;
 
         Ok(row)
@@ -1,8 +1,8 @@
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 use chrono::{NaiveDate, Datelike};
 use crate::error::AppResult;
-use crate::models::availability::Availability;
+use crate::models::availability::{Availability, AvailabilityOverride, AvailabilityOverrideKind};
 
 #[derive(Clone)]
 pub struct Repository {
@@ -14,7 +14,52 @@ impl Repository {
         Self { db_pool }
     }
 
-    pub async fn create(&self, availability: Availability) -> AppResult<Availability> {
+    pub async fn begin(&self) -> AppResult<Transaction<'static, Postgres>> {
+        Ok(self.db_pool.begin().await?)
+    }
+
+    /// Locks the membership of a teacher so that concurrent changes to
+    /// their availability run one after the other. Returns false if they
+    /// are not a member of the workspace.
+    pub async fn lock_teacher(&self, conn: &mut PgConnection, workspace_id: Uuid, teacher_id: Uuid) -> AppResult<bool> {
+        let row = sqlx::query_scalar!(
+            r#"
+            SELECT 1 AS "one!"
+            FROM workspace_members
+            WHERE workspace_id = $1 AND user_id = $2
+            FOR UPDATE
+            "#,
+            workspace_id,
+            teacher_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(row.is_some())
+    }
+
+    /// Whether the teacher has submitted their availability for a round
+    /// that is still open.
+    pub async fn is_locked(&self, conn: &mut PgConnection, workspace_id: Uuid, teacher_id: Uuid) -> AppResult<bool> {
+        let locked = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM availability_submissions s
+                JOIN availability_rounds r ON r.id = s.round_id
+                WHERE r.workspace_id = $1 AND s.teacher_id = $2 AND r.status = 'Open'
+            ) AS "locked!"
+            "#,
+            workspace_id,
+            teacher_id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(locked)
+    }
+
+    pub async fn create(&self, conn: &mut PgConnection, availability: Availability) -> AppResult<Availability> {
         let row = sqlx::query_as!(
             Availability,
             r#"
@@ -32,13 +77,126 @@ impl Repository {
             availability.created_at,
             availability.updated_at
         )
-        .fetch_one(&self.db_pool)
+        .fetch_one(conn)
         .await?;
 
         Ok(row)
     }
 
-    pub async fn get_by_teacher_and_date(&self, teacher_id: Uuid, date: NaiveDate) -> AppResult<Vec<Availability>> {
+    pub async fn update(&self, conn: &mut PgConnection, availability: &Availability) -> AppResult<Availability> {
+        let row = sqlx::query_as!(
+            Availability,
+            r#"
+            UPDATE availability
+            SET day_of_week = $2, start_time = $3, end_time = $4, is_preferred = $5
+            WHERE id = $1
+            RETURNING id, workspace_id, teacher_id, day_of_week, start_time, end_time, is_preferred, created_at, updated_at
+            "#,
+            availability.id,
+            availability.day_of_week,
+            availability.start_time,
+            availability.end_time,
+            availability.is_preferred
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn delete(&self, conn: &mut PgConnection, ids: &[Uuid]) -> AppResult<()> {
+        sqlx::query!("DELETE FROM availability WHERE id = ANY($1)", ids)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_by_teacher(&self, conn: &mut PgConnection, workspace_id: Uuid, teacher_id: Uuid) -> AppResult<()> {
+        sqlx::query!(
+            "DELETE FROM availability WHERE workspace_id = $1 AND teacher_id = $2",
+            workspace_id,
+            teacher_id
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_id(&self, workspace_id: Uuid, id: Uuid) -> AppResult<Option<Availability>> {
+        let row = sqlx::query_as!(
+            Availability,
+            r#"
+            SELECT id, workspace_id, teacher_id, day_of_week, start_time, end_time, is_preferred, created_at, updated_at
+            FROM availability
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    /// The weekly pattern of a teacher, by day and start time.
+    pub async fn find_by_teacher(&self, conn: &mut PgConnection, workspace_id: Uuid, teacher_id: Uuid) -> AppResult<Vec<Availability>> {
+        let rows = sqlx::query_as!(
+            Availability,
+            r#"
+            SELECT id, workspace_id, teacher_id, day_of_week, start_time, end_time, is_preferred, created_at, updated_at
+            FROM availability
+            WHERE workspace_id = $1 AND teacher_id = $2
+            ORDER BY day_of_week, start_time
+            "#,
+            workspace_id,
+            teacher_id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// The weekly pattern a draft is built against: as frozen by the last
+    /// closed availability round for the draft's term and year, or the
+    /// current one when there is none.
+    pub async fn find_for_draft(&self, workspace_id: Uuid, draft_timetable_id: Uuid, teacher_id: Uuid) -> AppResult<Vec<Availability>> {
+        let rows = sqlx::query_as!(
+            Availability,
+            r#"
+            WITH round AS (
+                SELECT r.id
+                FROM availability_rounds r
+                JOIN draft_timetables d ON d.workspace_id = r.workspace_id AND d.term = r.term AND d.year = r.year
+                WHERE r.workspace_id = $1 AND d.id = $2 AND r.status = 'Closed'
+                ORDER BY r.closed_at DESC
+                LIMIT 1
+            )
+            SELECT w.id AS "id!", w.workspace_id AS "workspace_id!", w.teacher_id AS "teacher_id!", w.day_of_week AS "day_of_week!",
+                w.start_time AS "start_time!", w.end_time AS "end_time!", w.is_preferred AS "is_preferred!",
+                w.created_at AS "created_at!", w.updated_at AS "updated_at!"
+            FROM availability_round_windows w
+            JOIN round ON round.id = w.round_id
+            WHERE w.teacher_id = $3
+            UNION ALL
+            SELECT a.id, a.workspace_id, a.teacher_id, a.day_of_week, a.start_time, a.end_time, a.is_preferred, a.created_at, a.updated_at
+            FROM availability a
+            WHERE a.workspace_id = $1 AND a.teacher_id = $3 AND NOT EXISTS (SELECT 1 FROM round)
+            "#,
+            workspace_id,
+            draft_timetable_id,
+            teacher_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    pub async fn get_by_teacher_and_date(&self, workspace_id: Uuid, teacher_id: Uuid, date: NaiveDate) -> AppResult<Vec<Availability>> {
         let day_of_week = date.weekday().num_days_from_sunday() as i32;
 
         let rows = sqlx::query_as!(
@@ -46,8 +204,10 @@ impl Repository {
             r#"
             SELECT id, workspace_id, teacher_id, day_of_week, start_time, end_time, is_preferred, created_at, updated_at
             FROM availability
-            WHERE teacher_id = $1 AND day_of_week = $2
+            WHERE workspace_id = $1 AND teacher_id = $2 AND day_of_week = $3
+            ORDER BY start_time
             "#,
+            workspace_id,
             teacher_id,
             day_of_week
         )
@@ -56,4 +216,102 @@ impl Repository {
 
         Ok(rows)
     }
+
+    /// Whether the teacher has any weekly window at all.
+    pub async fn has_weekly_pattern(&self, workspace_id: Uuid, teacher_id: Uuid) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM availability WHERE workspace_id = $1 AND teacher_id = $2) AS "exists!""#,
+            workspace_id,
+            teacher_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    pub async fn create_override(&self, conn: &mut PgConnection, item: AvailabilityOverride) -> AppResult<AvailabilityOverride> {
+        let row = sqlx::query_as!(
+            AvailabilityOverride,
+            r#"
+            INSERT INTO availability_overrides
+                (id, workspace_id, teacher_id, kind, starts_on, ends_on, day_of_week, start_time, end_time, reason, created_by, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            RETURNING id, workspace_id, teacher_id, kind as "kind: AvailabilityOverrideKind", starts_on, ends_on, day_of_week,
+                start_time, end_time, reason, created_by, created_at, updated_at
+            "#,
+            item.id,
+            item.workspace_id,
+            item.teacher_id,
+            item.kind as AvailabilityOverrideKind,
+            item.starts_on,
+            item.ends_on,
+            item.day_of_week,
+            item.start_time,
+            item.end_time,
+            item.reason,
+            item.created_by,
+            item.created_at,
+            item.updated_at
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn find_override(&self, workspace_id: Uuid, id: Uuid) -> AppResult<Option<AvailabilityOverride>> {
+        let row = sqlx::query_as!(
+            AvailabilityOverride,
+            r#"
+            SELECT id, workspace_id, teacher_id, kind as "kind: AvailabilityOverrideKind", starts_on, ends_on, day_of_week,
+                start_time, end_time, reason, created_by, created_at, updated_at
+            FROM availability_overrides
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn delete_override(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query!("DELETE FROM availability_overrides WHERE id = $1", id)
+            .execute(&self.db_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Overrides of a teacher, or of every teacher, touching the period.
+    pub async fn find_overrides(
+        &self,
+        workspace_id: Uuid,
+        teacher_id: Option<Uuid>,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> AppResult<Vec<AvailabilityOverride>> {
+        let rows = sqlx::query_as!(
+            AvailabilityOverride,
+            r#"
+            SELECT id, workspace_id, teacher_id, kind as "kind: AvailabilityOverrideKind", starts_on, ends_on, day_of_week,
+                start_time, end_time, reason, created_by, created_at, updated_at
+            FROM availability_overrides
+            WHERE workspace_id = $1 AND ($2::uuid IS NULL OR teacher_id = $2)
+                AND starts_on <= $4 AND ends_on >= $3
+            ORDER BY starts_on, start_time NULLS FIRST
+            "#,
+            workspace_id,
+            teacher_id,
+            from,
+            to
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows)
+    }
 }
@@ -0,0 +1,259 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::error::AppResult;
+use crate::models::{AvailabilityRound, AvailabilityRoundStatus, AvailabilitySubmission};
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn create(&self, conn: &mut PgConnection, round: AvailabilityRound) -> AppResult<AvailabilityRound> {
+        let round = sqlx::query_as!(
+            AvailabilityRound,
+            r#"
+            INSERT INTO availability_rounds (id, workspace_id, term, year, deadline, remind_hours_before, status, created_by, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING id, workspace_id, term, year, deadline, remind_hours_before, status as "status: AvailabilityRoundStatus",
+                reminded_at, closed_at, created_by, created_at, updated_at
+            "#,
+            round.id,
+            round.workspace_id,
+            round.term,
+            round.year,
+            round.deadline,
+            round.remind_hours_before,
+            round.status as AvailabilityRoundStatus,
+            round.created_by,
+            round.created_at,
+            round.updated_at
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(round)
+    }
+
+    /// Loads the round and locks it until the transaction ends, so that
+    /// submissions cannot slip in while it closes.
+    pub async fn lock(&self, conn: &mut PgConnection, workspace_id: Uuid, id: Uuid) -> AppResult<Option<AvailabilityRound>> {
+        let round = sqlx::query_as!(
+            AvailabilityRound,
+            r#"
+            SELECT id, workspace_id, term, year, deadline, remind_hours_before, status as "status: AvailabilityRoundStatus",
+                reminded_at, closed_at, created_by, created_at, updated_at
+            FROM availability_rounds
+            WHERE workspace_id = $1 AND id = $2
+            FOR UPDATE
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(round)
+    }
+
+    pub async fn find(&self, workspace_id: Uuid, id: Uuid) -> AppResult<Option<AvailabilityRound>> {
+        let round = sqlx::query_as!(
+            AvailabilityRound,
+            r#"
+            SELECT id, workspace_id, term, year, deadline, remind_hours_before, status as "status: AvailabilityRoundStatus",
+                reminded_at, closed_at, created_by, created_at, updated_at
+            FROM availability_rounds
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(round)
+    }
+
+    pub async fn is_open_for(&self, conn: &mut PgConnection, workspace_id: Uuid, term: &str, year: i32) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM availability_rounds
+                WHERE workspace_id = $1 AND term = $2 AND year = $3 AND status = 'Open'
+            ) AS "exists!"
+            "#,
+            workspace_id,
+            term,
+            year
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(exists)
+    }
+
+    pub async fn find_all(&self, workspace_id: Uuid, status: Option<AvailabilityRoundStatus>) -> AppResult<Vec<AvailabilityRound>> {
+        let rounds = sqlx::query_as!(
+            AvailabilityRound,
+            r#"
+            SELECT id, workspace_id, term, year, deadline, remind_hours_before, status as "status: AvailabilityRoundStatus",
+                reminded_at, closed_at, created_by, created_at, updated_at
+            FROM availability_rounds
+            WHERE workspace_id = $1 AND ($2::availability_round_status IS NULL OR status = $2)
+            ORDER BY deadline DESC
+            "#,
+            workspace_id,
+            status as Option<AvailabilityRoundStatus>
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rounds)
+    }
+
+    /// Open rounds whose reminder is due but not yet sent.
+    pub async fn find_due_reminders(&self, now: DateTime<Utc>) -> AppResult<Vec<AvailabilityRound>> {
+        let rounds = sqlx::query_as!(
+            AvailabilityRound,
+            r#"
+            SELECT id, workspace_id, term, year, deadline, remind_hours_before, status as "status: AvailabilityRoundStatus",
+                reminded_at, closed_at, created_by, created_at, updated_at
+            FROM availability_rounds
+            WHERE status = 'Open' AND reminded_at IS NULL
+                AND deadline - make_interval(hours => remind_hours_before) <= $1 AND deadline > $1
+            "#,
+            now
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rounds)
+    }
+
+    /// Open rounds whose deadline has passed.
+    pub async fn find_overdue(&self, now: DateTime<Utc>) -> AppResult<Vec<AvailabilityRound>> {
+        let rounds = sqlx::query_as!(
+            AvailabilityRound,
+            r#"
+            SELECT id, workspace_id, term, year, deadline, remind_hours_before, status as "status: AvailabilityRoundStatus",
+                reminded_at, closed_at, created_by, created_at, updated_at
+            FROM availability_rounds
+            WHERE status = 'Open' AND deadline <= $1
+            "#,
+            now
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rounds)
+    }
+
+    pub async fn mark_reminded(&self, conn: &mut PgConnection, id: Uuid) -> AppResult<()> {
+        sqlx::query!("UPDATE availability_rounds SET reminded_at = NOW() WHERE id = $1", id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Closes the round and freezes the weekly availability of every
+    /// teacher of the workspace.
+    pub async fn close(&self, conn: &mut PgConnection, round: &AvailabilityRound) -> AppResult<AvailabilityRound> {
+        sqlx::query!(
+            r#"
+            INSERT INTO availability_round_windows
+                (round_id, id, workspace_id, teacher_id, day_of_week, start_time, end_time, is_preferred, created_at, updated_at)
+            SELECT $1, a.id, a.workspace_id, a.teacher_id, a.day_of_week, a.start_time, a.end_time, a.is_preferred,
+                a.created_at, a.updated_at
+            FROM availability a
+            JOIN workspace_members m ON m.workspace_id = a.workspace_id AND m.user_id = a.teacher_id
+            JOIN users u ON u.id = a.teacher_id
+            WHERE a.workspace_id = $2 AND u.role = 'Teacher'
+            "#,
+            round.id,
+            round.workspace_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        let round = sqlx::query_as!(
+            AvailabilityRound,
+            r#"
+            UPDATE availability_rounds
+            SET status = 'Closed', closed_at = NOW()
+            WHERE id = $1
+            RETURNING id, workspace_id, term, year, deadline, remind_hours_before, status as "status: AvailabilityRoundStatus",
+                reminded_at, closed_at, created_by, created_at, updated_at
+            "#,
+            round.id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(round)
+    }
+
+    pub async fn submit(&self, conn: &mut PgConnection, round_id: Uuid, teacher_id: Uuid) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO availability_submissions (round_id, teacher_id)
+            VALUES ($1, $2)
+            ON CONFLICT (round_id, teacher_id) DO NOTHING
+            "#,
+            round_id,
+            teacher_id
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns false if the teacher had not submitted.
+    pub async fn unlock(&self, conn: &mut PgConnection, round_id: Uuid, teacher_id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM availability_submissions WHERE round_id = $1 AND teacher_id = $2",
+            round_id,
+            teacher_id
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Every teacher of the workspace with their submission for the
+    /// round, optionally only those who have or have not submitted.
+    pub async fn find_submissions(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        round_id: Uuid,
+        submitted: Option<bool>,
+    ) -> AppResult<Vec<AvailabilitySubmission>> {
+        let submissions = sqlx::query_as!(
+            AvailabilitySubmission,
+            r#"
+            SELECT $2::uuid AS "round_id!", u.id AS teacher_id, u.username AS teacher_name, s.submitted_at AS "submitted_at?"
+            FROM workspace_members m
+            JOIN users u ON u.id = m.user_id
+            LEFT JOIN availability_submissions s ON s.round_id = $2 AND s.teacher_id = u.id
+            WHERE m.workspace_id = $1 AND u.role = 'Teacher'
+                AND ($3::bool IS NULL OR (s.teacher_id IS NOT NULL) = $3)
+            ORDER BY u.username
+            "#,
+            workspace_id,
+            round_id,
+            submitted
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(submissions)
+    }
+}
@@ -1,7 +1,8 @@
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 use uuid::Uuid;
 use crate::error::AppResult;
-use crate::models::conflicts::{Conflict, ConflictStatus};
+use crate::models::conflicts::{Conflict, ConflictKind, ConflictStatus, LessonSize, RoomSuggestion, UnavailableLesson};
+use crate::models::LessonFeature;
 
 #[derive(Clone)]
 pub struct Repository {
@@ -13,25 +14,61 @@ impl Repository {
         Self { db_pool }
     }
 
-    pub async fn create(&self, conflict: Conflict) -> AppResult<Conflict> {
+    /// Stores a conflict unless the same one is already open or ignored
+    /// for the draft; a resolved one that is found again is reopened.
+    /// Returns it only when it is new or reopened.
+    pub async fn create_if_new(&self, conn: &mut PgConnection, conflict: Conflict) -> AppResult<Option<Conflict>> {
         let result = sqlx::query_as!(
             Conflict,
             r#"
-            INSERT INTO conflicts (id, draft_timetable_id, description, teacher_id, room_id, time_slot_id, status, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-            RETURNING id, draft_timetable_id, description, teacher_id, room_id, time_slot_id, status as "status: ConflictStatus", created_at, updated_at
+            INSERT INTO conflicts (id, draft_timetable_id, kind, description, teacher_id, room_id, time_slot_id, student_group_id, status, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            ON CONFLICT (draft_timetable_id, kind, teacher_id, room_id, student_group_id, time_slot_id) DO UPDATE
+                SET status = 'Open', description = EXCLUDED.description, updated_at = NOW()
+                WHERE conflicts.status = 'Resolved'
+            RETURNING id, draft_timetable_id, kind as "kind: ConflictKind", description, teacher_id, room_id, time_slot_id, student_group_id, status as "status: ConflictStatus", created_at, updated_at
             "#,
             conflict.id,
             conflict.draft_timetable_id,
+            conflict.kind as ConflictKind,
             conflict.description,
             conflict.teacher_id,
             conflict.room_id,
             conflict.time_slot_id,
+            conflict.student_group_id,
             conflict.status as ConflictStatus,
             conflict.created_at,
             conflict.updated_at
         )
-        .fetch_one(&self.db_pool)
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Resolves the open conflict of a kind for a room and slot, if any.
+    pub async fn resolve_open(
+        &self,
+        conn: &mut PgConnection,
+        draft_timetable_id: Uuid,
+        kind: ConflictKind,
+        room_id: Uuid,
+        time_slot_id: Uuid,
+    ) -> AppResult<Option<Conflict>> {
+        let result = sqlx::query_as!(
+            Conflict,
+            r#"
+            UPDATE conflicts
+            SET status = 'Resolved', updated_at = NOW()
+            WHERE draft_timetable_id = $1 AND kind = $2 AND room_id = $3 AND time_slot_id = $4 AND status = 'Open'
+            RETURNING id, draft_timetable_id, kind as "kind: ConflictKind", description, teacher_id, room_id, time_slot_id, student_group_id, status as "status: ConflictStatus", created_at, updated_at
+            "#,
+            draft_timetable_id,
+            kind as ConflictKind,
+            room_id,
+            time_slot_id
+        )
+        .fetch_optional(conn)
         .await?;
 
         Ok(result)
@@ -41,7 +78,7 @@ impl Repository {
         let conflicts = sqlx::query_as!(
             Conflict,
             r#"
-            SELECT id, draft_timetable_id, description, teacher_id, room_id, time_slot_id, status as "status: ConflictStatus", created_at, updated_at
+            SELECT id, draft_timetable_id, kind as "kind: ConflictKind", description, teacher_id, room_id, time_slot_id, student_group_id, status as "status: ConflictStatus", created_at, updated_at
             FROM conflicts
             WHERE draft_timetable_id = $1
             "#,
@@ -53,21 +90,257 @@ impl Repository {
         Ok(conflicts)
     }
 
-    pub async fn update_status(&self, id: Uuid, status: ConflictStatus) -> AppResult<Conflict> {
+    pub async fn update_status(&self, conn: &mut PgConnection, id: Uuid, status: ConflictStatus) -> AppResult<Conflict> {
         let result = sqlx::query_as!(
             Conflict,
             r#"
             UPDATE conflicts
             SET status = $2, updated_at = NOW()
             WHERE id = $1
-            RETURNING id, draft_timetable_id, description, teacher_id, room_id, time_slot_id, status as "status: ConflictStatus", created_at, updated_at
+            RETURNING id, draft_timetable_id, kind as "kind: ConflictKind", description, teacher_id, room_id, time_slot_id, student_group_id, status as "status: ConflictStatus", created_at, updated_at
             "#,
             id,
             status as ConflictStatus
         )
-        .fetch_one(&self.db_pool)
+        .fetch_one(conn)
         .await?;
 
         Ok(result)
     }
+
+    /// The size of every lesson of a draft: the students enrolled in its
+    /// section, else the size of the groups attending it, else the
+    /// expected size of its course, else the students enrolled in the
+    /// course. A group without a size counts the students in it and in the
+    /// groups within it.
+    pub async fn find_lesson_sizes(&self, workspace_id: Uuid, draft_timetable_id: Uuid) -> AppResult<Vec<LessonSize>> {
+        let sizes = sqlx::query_as!(
+            LessonSize,
+            r#"
+            WITH RECURSIVE group_tree (root_id, student_group_id) AS (
+                SELECT id, id FROM student_groups WHERE workspace_id = $1
+                UNION ALL
+                SELECT t.root_id, g.id
+                FROM group_tree t
+                JOIN student_groups g ON g.parent_id = t.student_group_id
+            ),
+            group_sizes AS (
+                SELECT g.id, COALESCE(g.size, (
+                    SELECT COUNT(*)::int
+                    FROM group_tree t
+                    JOIN students s ON s.student_group_id = t.student_group_id
+                    WHERE t.root_id = g.id
+                )) AS size
+                FROM student_groups g
+                WHERE g.workspace_id = $1
+            )
+            SELECT de.id AS draft_entry_id, de.course_id, de.room_id, r.name AS room_name, r.capacity,
+                de.time_slot_id, w.room_capacity_tolerance AS tolerance,
+                COALESCE(CASE
+                    WHEN de.course_section_id IS NOT NULL THEN
+                        (SELECT COUNT(*)::int FROM enrollments e WHERE e.course_section_id = de.course_section_id)
+                    WHEN EXISTS (SELECT 1 FROM draft_entry_groups g WHERE g.draft_entry_id = de.id) THEN
+                        (SELECT SUM(gs.size)::int
+                         FROM draft_entry_groups g
+                         JOIN group_sizes gs ON gs.id = g.student_group_id
+                         WHERE g.draft_entry_id = de.id)
+                    WHEN c.expected_size IS NOT NULL THEN c.expected_size
+                    ELSE
+                        (SELECT COUNT(*)::int
+                         FROM enrollments e
+                         JOIN course_sections cs ON cs.id = e.course_section_id
+                         WHERE cs.course_id = de.course_id)
+                END, 0) AS "size!"
+            FROM draft_entries de
+            JOIN draft_timetables d ON d.id = de.draft_timetable_id
+            JOIN workspaces w ON w.id = d.workspace_id
+            JOIN courses c ON c.id = de.course_id
+            JOIN rooms r ON r.id = de.room_id
+            WHERE d.workspace_id = $1 AND d.id = $2
+            "#,
+            workspace_id,
+            draft_timetable_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(sizes)
+    }
+
+    /// Lessons of a draft given to a teacher not qualified for the course.
+    /// Courses nobody is qualified for are open to every teacher.
+    pub async fn find_unqualified_entries(&self, workspace_id: Uuid, draft_timetable_id: Uuid) -> AppResult<Vec<Uuid>> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            SELECT de.id
+            FROM draft_entries de
+            JOIN draft_timetables d ON d.id = de.draft_timetable_id
+            WHERE d.workspace_id = $1 AND d.id = $2
+                AND EXISTS (SELECT 1 FROM teacher_qualifications q WHERE q.course_id = de.course_id)
+                AND NOT EXISTS (
+                    SELECT 1 FROM teacher_qualifications q
+                    WHERE q.course_id = de.course_id AND q.teacher_id = de.teacher_id
+                )
+            "#,
+            workspace_id,
+            draft_timetable_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Upcoming lessons of a published draft whose teacher is not available
+    /// on their date, one row per lesson. Cancelled lessons and lessons
+    /// handed to another teacher are left out; teacher changes and extra
+    /// lessons count for the teacher who takes them.
+    pub async fn find_unavailable_lessons(&self, workspace_id: Uuid, draft_timetable_id: Uuid) -> AppResult<Vec<UnavailableLesson>> {
+        let lessons = sqlx::query_as!(
+            UnavailableLesson,
+            r#"
+            WITH pt AS (
+                SELECT id, GREATEST(valid_from, CURRENT_DATE) AS valid_from, valid_to
+                FROM published_timetables
+                WHERE workspace_id = $1 AND draft_timetable_id = $2
+            ),
+            lesson AS (
+                SELECT day::date AS date, de.teacher_id, de.time_slot_id, de.course_id, ts.start_time, ts.end_time
+                FROM pt
+                CROSS JOIN generate_series(pt.valid_from, pt.valid_to, INTERVAL '1 day') day
+                JOIN draft_entries de ON de.draft_timetable_id = $2
+                JOIN time_slots ts ON ts.id = de.time_slot_id AND ts.day_of_week = EXTRACT(DOW FROM day)::int
+                WHERE NOT EXISTS (
+                    SELECT 1
+                    FROM lesson_exceptions x
+                    JOIN timetable_entries e ON e.id = x.timetable_entry_id
+                    WHERE x.published_timetable_id = pt.id AND x.date = day::date
+                        AND x.kind IN ('Cancellation', 'TeacherChange')
+                        AND e.course_id = de.course_id AND e.teacher_id = de.teacher_id AND e.time_slot_id = de.time_slot_id
+                )
+                UNION ALL
+                SELECT x.date, x.teacher_id, e.time_slot_id, x.course_id, x.start_time, x.end_time
+                FROM pt
+                JOIN lesson_exceptions x ON x.published_timetable_id = pt.id
+                LEFT JOIN timetable_entries e ON e.id = x.timetable_entry_id
+                WHERE x.kind IN ('ExtraLesson', 'TeacherChange') AND x.date >= pt.valid_from
+            )
+            SELECT teacher_id AS "teacher_id!", time_slot_id, course_id AS "course_id!",
+                MIN(date) AS "first_date!", COUNT(*) AS "dates!"
+            FROM lesson
+            WHERE NOT teacher_available($1, teacher_id, date, start_time, end_time)
+            GROUP BY teacher_id, time_slot_id, course_id
+            ORDER BY MIN(date)
+            "#,
+            workspace_id,
+            draft_timetable_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(lessons)
+    }
+
+    /// The features each lesson of a draft needs: those of its course and
+    /// those of the groups attending it and of the groups within them. A
+    /// feature is required when any of them requires it.
+    pub async fn find_lesson_features(&self, workspace_id: Uuid, draft_timetable_id: Uuid) -> AppResult<Vec<LessonFeature>> {
+        let features = sqlx::query_as!(
+            LessonFeature,
+            r#"
+            WITH RECURSIVE group_tree (root_id, student_group_id) AS (
+                SELECT id, id FROM student_groups WHERE workspace_id = $1
+                UNION ALL
+                SELECT t.root_id, g.id
+                FROM group_tree t
+                JOIN student_groups g ON g.parent_id = t.student_group_id
+            ),
+            needs AS (
+                SELECT de.id AS draft_entry_id, de.room_id, cf.room_feature_id, cf.need
+                FROM draft_entries de
+                JOIN course_room_features cf ON cf.course_id = de.course_id
+                WHERE de.draft_timetable_id = $2
+                UNION ALL
+                SELECT de.id, de.room_id, gf.room_feature_id, gf.need
+                FROM draft_entries de
+                JOIN draft_entry_groups g ON g.draft_entry_id = de.id
+                JOIN group_tree t ON t.root_id = g.student_group_id
+                JOIN student_group_room_features gf ON gf.student_group_id = t.student_group_id
+                WHERE de.draft_timetable_id = $2
+            )
+            SELECT n.draft_entry_id AS "draft_entry_id!", n.room_feature_id AS "room_feature_id!", f.name,
+                BOOL_OR(n.need = 'Required') AS "required!",
+                EXISTS (
+                    SELECT 1 FROM room_feature_assignments a
+                    WHERE a.room_id = n.room_id AND a.room_feature_id = n.room_feature_id
+                ) AS "in_room!"
+            FROM needs n
+            JOIN room_features f ON f.id = n.room_feature_id
+            WHERE f.workspace_id = $1
+            GROUP BY n.draft_entry_id, n.room_id, n.room_feature_id, f.name
+            ORDER BY f.name
+            "#,
+            workspace_id,
+            draft_timetable_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(features)
+    }
+
+    /// Rooms holding all the lesson's students and having every required
+    /// feature that no other lesson of the draft uses at a time overlapping
+    /// the lesson's. Rooms with more of the preferred features come first,
+    /// then the smallest.
+    pub async fn find_free_rooms(
+        &self,
+        workspace_id: Uuid,
+        draft_timetable_id: Uuid,
+        lesson: &LessonSize,
+        required: &[Uuid],
+        preferred: &[Uuid],
+    ) -> AppResult<Vec<RoomSuggestion>> {
+        let rooms = sqlx::query_as!(
+            RoomSuggestion,
+            r#"
+            SELECT r.id AS room_id, r.name AS room_name, r.capacity,
+                (SELECT COUNT(*)::int FROM room_feature_assignments a
+                 WHERE a.room_id = r.id AND a.room_feature_id = ANY($7)) AS "preferred_features!"
+            FROM rooms r
+            JOIN time_slots slot ON slot.id = $3 AND slot.workspace_id = r.workspace_id
+            WHERE r.workspace_id = $1 AND r.capacity >= $5
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM draft_entries de
+                    JOIN time_slots ts ON ts.id = de.time_slot_id
+                    WHERE de.draft_timetable_id = $2
+                        AND de.id <> $4
+                        AND de.room_id = r.id
+                        AND ts.day_of_week = slot.day_of_week
+                        AND ts.start_time < slot.end_time
+                        AND slot.start_time < ts.end_time
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM unnest($6::uuid[]) AS f (id)
+                    WHERE NOT EXISTS (
+                        SELECT 1 FROM room_feature_assignments a
+                        WHERE a.room_id = r.id AND a.room_feature_id = f.id
+                    )
+                )
+            ORDER BY 4 DESC, r.capacity, r.name
+            "#,
+            workspace_id,
+            draft_timetable_id,
+            lesson.time_slot_id,
+            lesson.draft_entry_id,
+            lesson.size,
+            required,
+            preferred
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rooms)
+    }
 }
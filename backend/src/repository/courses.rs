@@ -16,14 +16,16 @@ impl CourseRepository {
     pub async fn create(&self, course: Course) -> AppResult<Course> {
         sqlx::query!(
             r#"
-            INSERT INTO courses (id, workspace_id, code, name, description, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            INSERT INTO courses (id, workspace_id, code, name, description, department, expected_size, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             "#,
             course.id,
             course.workspace_id,
             course.code,
             course.name,
             course.description,
+            course.department,
+            course.expected_size,
             course.created_at,
             course.updated_at
         )
@@ -37,7 +39,7 @@ impl CourseRepository {
         let course = sqlx::query_as!(
             Course,
             r#"
-            SELECT id, workspace_id, code, name, description, created_at, updated_at
+            SELECT id, workspace_id, code, name, description, department, expected_size, created_at, updated_at
             FROM courses
             WHERE id = $1
             "#,
@@ -53,7 +55,7 @@ impl CourseRepository {
         let courses = sqlx::query_as!(
             Course,
             r#"
-            SELECT id, workspace_id, code, name, description, created_at, updated_at
+            SELECT id, workspace_id, code, name, description, department, expected_size, created_at, updated_at
             FROM courses
             ORDER BY code ASC
             "#
@@ -68,13 +70,15 @@ impl CourseRepository {
         sqlx::query!(
             r#"
             UPDATE courses
-            SET code = $2, name = $3, description = $4, updated_at = NOW()
+            SET code = $2, name = $3, description = $4, department = $5, expected_size = $6, updated_at = NOW()
             WHERE id = $1
             "#,
             course.id,
             course.code,
             course.name,
-            course.description
+            course.description,
+            course.department,
+            course.expected_size
         )
         .execute(&self.pool)
         .await?;
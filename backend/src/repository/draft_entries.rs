@@ -1,4 +1,4 @@
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 use uuid::Uuid;
 use crate::error::AppResult;
 use crate::models::draft_entries::DraftEntry;
@@ -13,18 +13,16 @@ impl Repository {
         Self { db_pool }
     }
 
-    pub async fn create_many(&self, entries: Vec<DraftEntry>) -> AppResult<Vec<DraftEntry>> {
-        let mut tx = self.db_pool.begin().await?;
-
+    pub async fn create_many(&self, conn: &mut PgConnection, entries: Vec<DraftEntry>) -> AppResult<Vec<DraftEntry>> {
         let mut created_entries = Vec::with_capacity(entries.len());
 
         for entry in entries {
             let created = sqlx::query_as!(
                 DraftEntry,
                 r#"
-                INSERT INTO draft_entries (id, draft_timetable_id, course_id, teacher_id, room_id, time_slot_id, created_at, updated_at)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-                RETURNING id, draft_timetable_id, course_id, teacher_id, room_id, time_slot_id, created_at, updated_at
+                INSERT INTO draft_entries (id, draft_timetable_id, course_id, teacher_id, room_id, time_slot_id, course_section_id, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                RETURNING id, draft_timetable_id, course_id, teacher_id, room_id, time_slot_id, course_section_id, created_at, updated_at
                 "#,
                 entry.id,
                 entry.draft_timetable_id,
@@ -32,16 +30,15 @@ impl Repository {
                 entry.teacher_id,
                 entry.room_id,
                 entry.time_slot_id,
+                entry.course_section_id,
                 entry.created_at,
                 entry.updated_at
             )
-            .fetch_one(&mut *tx)
+            .fetch_one(&mut *conn)
             .await?;
             created_entries.push(created);
         }
 
-        tx.commit().await?;
-
         Ok(created_entries)
     }
 
@@ -49,7 +46,7 @@ impl Repository {
         let entries = sqlx::query_as!(
             DraftEntry,
             r#"
-            SELECT id, draft_timetable_id, course_id, teacher_id, room_id, time_slot_id, created_at, updated_at
+            SELECT id, draft_timetable_id, course_id, teacher_id, room_id, time_slot_id, course_section_id, created_at, updated_at
             FROM draft_entries
             WHERE draft_timetable_id = $1
             "#,
@@ -60,4 +57,42 @@ impl Repository {
 
         Ok(entries)
     }
+
+    /// The draft of a lesson, if the lesson belongs to the workspace.
+    pub async fn find_draft_timetable_id(&self, workspace_id: Uuid, draft_entry_id: Uuid) -> AppResult<Option<Uuid>> {
+        let id = sqlx::query_scalar!(
+            r#"
+            SELECT de.draft_timetable_id
+            FROM draft_entries de
+            JOIN draft_timetables d ON d.id = de.draft_timetable_id
+            WHERE d.workspace_id = $1 AND de.id = $2
+            "#,
+            workspace_id,
+            draft_entry_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Whether every section belongs to the course paired with it and to
+    /// the workspace.
+    pub async fn sections_match_courses(&self, workspace_id: Uuid, section_ids: &[Uuid], course_ids: &[Uuid]) -> AppResult<bool> {
+        let matching = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM unnest($2::uuid[], $3::uuid[]) AS p(section_id, course_id)
+            JOIN course_sections cs ON cs.id = p.section_id AND cs.course_id = p.course_id
+            WHERE cs.workspace_id = $1
+            "#,
+            workspace_id,
+            section_ids,
+            course_ids
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(matching == section_ids.len() as i64)
+    }
 }
@@ -1,4 +1,4 @@
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 use uuid::Uuid;
 use crate::error::AppResult;
 use crate::models::draft_timetables::{DraftTimetable, DraftTimetableStatus};
@@ -54,7 +54,7 @@ impl Repository {
         Ok(row)
     }
 
-    pub async fn update_status(&self, workspace_id: Uuid, id: Uuid, status: DraftTimetableStatus) -> AppResult<DraftTimetable> {
+    pub async fn update_status(&self, conn: &mut PgConnection, workspace_id: Uuid, id: Uuid, status: DraftTimetableStatus) -> AppResult<DraftTimetable> {
         let row = sqlx::query_as!(
             DraftTimetable,
             r#"
@@ -67,7 +67,7 @@ impl Repository {
             id,
             workspace_id
         )
-        .fetch_one(&self.db_pool)
+        .fetch_one(conn)
         .await?;
 
         Ok(row)
@@ -0,0 +1,420 @@
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+use crate::error::AppResult;
+use crate::models::{CourseSection, Enrollment, Student, StudentLesson};
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+/// A weekly lesson of a draft together with a student attending it.
+#[derive(FromRow)]
+pub struct StudentLessonRow {
+    pub student_id: Uuid,
+    pub student_name: String,
+    #[sqlx(flatten)]
+    pub lesson: StudentLesson,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn begin(&self) -> AppResult<Transaction<'static, Postgres>> {
+        Ok(self.db_pool.begin().await?)
+    }
+
+    pub async fn create_student(&self, conn: &mut PgConnection, student: Student) -> AppResult<Student> {
+        let student = sqlx::query_as!(
+            Student,
+            r#"
+            INSERT INTO students (id, workspace_id, student_number, name, email, user_id, student_group_id, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id, workspace_id, student_number, name, email, user_id, student_group_id, created_at, updated_at
+            "#,
+            student.id,
+            student.workspace_id,
+            student.student_number,
+            student.name,
+            student.email,
+            student.user_id,
+            student.student_group_id,
+            student.created_at,
+            student.updated_at
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(student)
+    }
+
+    pub async fn update_student(&self, student: &Student) -> AppResult<Student> {
+        let student = sqlx::query_as!(
+            Student,
+            r#"
+            UPDATE students
+            SET student_number = $2, name = $3, email = $4, user_id = $5, student_group_id = $6
+            WHERE id = $1
+            RETURNING id, workspace_id, student_number, name, email, user_id, student_group_id, created_at, updated_at
+            "#,
+            student.id,
+            student.student_number,
+            student.name,
+            student.email,
+            student.user_id,
+            student.student_group_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(student)
+    }
+
+    pub async fn delete_student(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query!("DELETE FROM students WHERE id = $1", id)
+            .execute(&self.db_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_student(&self, workspace_id: Uuid, id: Uuid) -> AppResult<Option<Student>> {
+        let student = sqlx::query_as!(
+            Student,
+            r#"
+            SELECT id, workspace_id, student_number, name, email, user_id, student_group_id, created_at, updated_at
+            FROM students
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(student)
+    }
+
+    pub async fn find_student_by_number(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        student_number: &str,
+    ) -> AppResult<Option<Student>> {
+        let student = sqlx::query_as!(
+            Student,
+            r#"
+            SELECT id, workspace_id, student_number, name, email, user_id, student_group_id, created_at, updated_at
+            FROM students
+            WHERE workspace_id = $1 AND student_number = $2
+            "#,
+            workspace_id,
+            student_number
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(student)
+    }
+
+    /// The workspace's students, optionally only those of a class.
+    pub async fn find_students(&self, workspace_id: Uuid, student_group_id: Option<Uuid>) -> AppResult<Vec<Student>> {
+        let students = sqlx::query_as!(
+            Student,
+            r#"
+            SELECT id, workspace_id, student_number, name, email, user_id, student_group_id, created_at, updated_at
+            FROM students
+            WHERE workspace_id = $1 AND ($2::uuid IS NULL OR student_group_id = $2)
+            ORDER BY name, student_number
+            "#,
+            workspace_id,
+            student_group_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(students)
+    }
+
+    /// The member of the workspace with the email, to link a student to
+    /// their login.
+    pub async fn find_member_by_email(&self, conn: &mut PgConnection, workspace_id: Uuid, email: &str) -> AppResult<Option<Uuid>> {
+        let user_id = sqlx::query_scalar!(
+            r#"
+            SELECT u.id
+            FROM users u
+            JOIN workspace_members wm ON wm.user_id = u.id
+            WHERE wm.workspace_id = $1 AND lower(u.email) = lower($2)
+            "#,
+            workspace_id,
+            email
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    pub async fn create_section(&self, section: CourseSection) -> AppResult<CourseSection> {
+        let section = sqlx::query_as!(
+            CourseSection,
+            r#"
+            INSERT INTO course_sections (id, workspace_id, course_id, name, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, workspace_id, course_id, name, created_at, updated_at
+            "#,
+            section.id,
+            section.workspace_id,
+            section.course_id,
+            section.name,
+            section.created_at,
+            section.updated_at
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(section)
+    }
+
+    pub async fn rename_section(&self, id: Uuid, name: &str) -> AppResult<CourseSection> {
+        let section = sqlx::query_as!(
+            CourseSection,
+            r#"
+            UPDATE course_sections
+            SET name = $2
+            WHERE id = $1
+            RETURNING id, workspace_id, course_id, name, created_at, updated_at
+            "#,
+            id,
+            name
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(section)
+    }
+
+    pub async fn delete_section(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query!("DELETE FROM course_sections WHERE id = $1", id)
+            .execute(&self.db_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_section(&self, workspace_id: Uuid, id: Uuid) -> AppResult<Option<CourseSection>> {
+        let section = sqlx::query_as!(
+            CourseSection,
+            r#"
+            SELECT id, workspace_id, course_id, name, created_at, updated_at
+            FROM course_sections
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(section)
+    }
+
+    /// Looks a section up by its course code and name, as written in an
+    /// enrolment import.
+    pub async fn find_section_by_code(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        course_code: &str,
+        name: &str,
+    ) -> AppResult<Option<CourseSection>> {
+        let section = sqlx::query_as!(
+            CourseSection,
+            r#"
+            SELECT cs.id, cs.workspace_id, cs.course_id, cs.name, cs.created_at, cs.updated_at
+            FROM course_sections cs
+            JOIN courses c ON c.id = cs.course_id
+            WHERE cs.workspace_id = $1 AND c.code = $2 AND cs.name = $3
+            "#,
+            workspace_id,
+            course_code,
+            name
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(section)
+    }
+
+    pub async fn find_sections(&self, workspace_id: Uuid, course_id: Option<Uuid>) -> AppResult<Vec<CourseSection>> {
+        let sections = sqlx::query_as!(
+            CourseSection,
+            r#"
+            SELECT id, workspace_id, course_id, name, created_at, updated_at
+            FROM course_sections
+            WHERE workspace_id = $1 AND ($2::uuid IS NULL OR course_id = $2)
+            ORDER BY course_id, name
+            "#,
+            workspace_id,
+            course_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(sections)
+    }
+
+    pub async fn course_in_workspace(&self, workspace_id: Uuid, course_id: Uuid) -> AppResult<bool> {
+        let found = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM courses WHERE workspace_id = $1 AND id = $2) AS "found!""#,
+            workspace_id,
+            course_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(found)
+    }
+
+    /// Enrols the student in the section, taking them out of any other
+    /// section of the same course. Returns false if they already were.
+    pub async fn enroll(&self, conn: &mut PgConnection, student_id: Uuid, course_section_id: Uuid) -> AppResult<bool> {
+        sqlx::query!(
+            r#"
+            DELETE FROM enrollments e
+            USING course_sections cs, course_sections target
+            WHERE e.student_id = $1
+                AND cs.id = e.course_section_id
+                AND target.id = $2
+                AND cs.course_id = target.course_id
+                AND cs.id <> target.id
+            "#,
+            student_id,
+            course_section_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO enrollments (student_id, course_section_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            student_id,
+            course_section_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn unenroll(&self, student_id: Uuid, course_section_id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM enrollments WHERE student_id = $1 AND course_section_id = $2",
+            student_id,
+            course_section_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn find_enrollments(
+        &self,
+        workspace_id: Uuid,
+        student_id: Option<Uuid>,
+        course_section_id: Option<Uuid>,
+    ) -> AppResult<Vec<Enrollment>> {
+        let enrollments = sqlx::query_as!(
+            Enrollment,
+            r#"
+            SELECT e.student_id, e.course_section_id, e.created_at
+            FROM enrollments e
+            JOIN students s ON s.id = e.student_id
+            WHERE s.workspace_id = $1
+                AND ($2::uuid IS NULL OR e.student_id = $2)
+                AND ($3::uuid IS NULL OR e.course_section_id = $3)
+            ORDER BY s.name, e.created_at
+            "#,
+            workspace_id,
+            student_id,
+            course_section_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(enrollments)
+    }
+
+    /// The lessons of a draft each student attends: those of the sections
+    /// they are enrolled in, lessons of their courses given to every
+    /// section, and lessons of their class and the groups it is part of.
+    /// Without a student, every student of the workspace.
+    pub async fn find_student_lessons(
+        &self,
+        workspace_id: Uuid,
+        draft_timetable_id: Uuid,
+        student_id: Option<Uuid>,
+    ) -> AppResult<Vec<StudentLessonRow>> {
+        let rows = sqlx::query_as::<_, StudentLessonRow>(
+            r#"
+            WITH RECURSIVE chain (student_id, student_group_id) AS (
+                SELECT id, student_group_id
+                FROM students
+                WHERE workspace_id = $1 AND student_group_id IS NOT NULL AND ($3::uuid IS NULL OR id = $3)
+                UNION
+                SELECT chain.student_id, g.parent_id
+                FROM chain
+                JOIN student_groups g ON g.id = chain.student_group_id
+                WHERE g.parent_id IS NOT NULL
+            )
+            SELECT s.id AS student_id, s.name AS student_name,
+                de.course_id, c.name AS course_name, de.teacher_id, u.username AS teacher_name,
+                de.room_id, r.name AS room_name, de.time_slot_id, ts.day_of_week, ts.start_time, ts.end_time,
+                de.course_section_id, cs.name AS section_name
+            FROM students s
+            JOIN draft_timetables d ON d.workspace_id = s.workspace_id AND d.id = $2
+            JOIN draft_entries de ON de.draft_timetable_id = d.id
+            JOIN courses c ON c.id = de.course_id
+            JOIN users u ON u.id = de.teacher_id
+            JOIN rooms r ON r.id = de.room_id
+            JOIN time_slots ts ON ts.id = de.time_slot_id
+            LEFT JOIN course_sections cs ON cs.id = de.course_section_id
+            WHERE s.workspace_id = $1
+                AND ($3::uuid IS NULL OR s.id = $3)
+                AND (
+                    EXISTS (
+                        SELECT 1
+                        FROM enrollments e
+                        JOIN course_sections es ON es.id = e.course_section_id
+                        WHERE e.student_id = s.id
+                            AND es.course_id = de.course_id
+                            AND (de.course_section_id IS NULL OR de.course_section_id = es.id)
+                    )
+                    OR (
+                        de.course_section_id IS NULL
+                        AND EXISTS (
+                            SELECT 1
+                            FROM draft_entry_groups g
+                            JOIN chain ON chain.student_group_id = g.student_group_id
+                            WHERE g.draft_entry_id = de.id AND chain.student_id = s.id
+                        )
+                    )
+                )
+            ORDER BY s.name, s.id, ts.day_of_week, ts.start_time, c.name
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(draft_timetable_id)
+        .bind(student_id)
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows)
+    }
+}
@@ -0,0 +1,383 @@
+use chrono::{NaiveDate, NaiveTime};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::error::AppResult;
+use crate::models::{
+    ExceptionChange, ExceptionLesson, LessonException, LessonExceptionKind, SubstitutionChange, SubstitutionStatus,
+};
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn create(&self, conn: &mut PgConnection, exception: LessonException) -> AppResult<LessonException> {
+        let exception = sqlx::query_as!(
+            LessonException,
+            r#"
+            INSERT INTO lesson_exceptions (
+                id, workspace_id, published_timetable_id, date, kind, timetable_entry_id, course_id, teacher_id,
+                room_id, start_time, end_time, reason, created_by, created_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            RETURNING id, workspace_id, published_timetable_id, date, kind as "kind: LessonExceptionKind",
+                timetable_entry_id, course_id, teacher_id, room_id, start_time, end_time, reason, created_by, created_at
+            "#,
+            exception.id,
+            exception.workspace_id,
+            exception.published_timetable_id,
+            exception.date,
+            exception.kind as LessonExceptionKind,
+            exception.timetable_entry_id,
+            exception.course_id,
+            exception.teacher_id,
+            exception.room_id,
+            exception.start_time,
+            exception.end_time,
+            exception.reason,
+            exception.created_by,
+            exception.created_at
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(exception)
+    }
+
+    pub async fn delete(&self, conn: &mut PgConnection, workspace_id: Uuid, id: Uuid) -> AppResult<Option<LessonException>> {
+        let exception = sqlx::query_as!(
+            LessonException,
+            r#"
+            DELETE FROM lesson_exceptions
+            WHERE workspace_id = $1 AND id = $2
+            RETURNING id, workspace_id, published_timetable_id, date, kind as "kind: LessonExceptionKind",
+                timetable_entry_id, course_id, teacher_id, room_id, start_time, end_time, reason, created_by, created_at
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(exception)
+    }
+
+    /// The most recently published timetable of the workspace valid on the
+    /// date.
+    pub async fn find_published_for(&self, conn: &mut PgConnection, workspace_id: Uuid, date: NaiveDate) -> AppResult<Option<Uuid>> {
+        let id = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM published_timetables
+            WHERE workspace_id = $1 AND valid_from <= $2 AND valid_to >= $2
+            ORDER BY published_at DESC
+            LIMIT 1
+            "#,
+            workspace_id,
+            date
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// A weekly timetable entry of the workspace, and whether the published
+    /// timetable has a lesson with the same course, teacher and time slot.
+    pub async fn find_lesson(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        timetable_entry_id: Uuid,
+        published_timetable_id: Uuid,
+    ) -> AppResult<Option<ExceptionLesson>> {
+        let lesson = sqlx::query_as!(
+            ExceptionLesson,
+            r#"
+            SELECT te.course_id, te.teacher_id, te.room_id, ts.day_of_week, ts.start_time, ts.end_time,
+                EXISTS (
+                    SELECT 1
+                    FROM published_timetables pt
+                    JOIN draft_entries de ON de.draft_timetable_id = pt.draft_timetable_id
+                    WHERE pt.id = $3 AND de.course_id = te.course_id AND de.teacher_id = te.teacher_id
+                        AND de.time_slot_id = te.time_slot_id
+                ) AS "is_published!"
+            FROM timetable_entries te
+            JOIN time_slots ts ON ts.id = te.time_slot_id
+            JOIN courses c ON c.id = te.course_id
+            WHERE te.id = $2 AND c.workspace_id = $1
+            "#,
+            workspace_id,
+            timetable_entry_id,
+            published_timetable_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(lesson)
+    }
+
+    pub async fn has_exception(&self, conn: &mut PgConnection, timetable_entry_id: Uuid, date: NaiveDate) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM lesson_exceptions WHERE timetable_entry_id = $1 AND date = $2
+            ) AS "exists!"
+            "#,
+            timetable_entry_id,
+            date
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(exists)
+    }
+
+    /// Whether the room is taken at that time on the date, by a weekly
+    /// lesson that has not been cancelled or moved away, or by a lesson
+    /// moved or added there for the day.
+    pub async fn is_room_busy(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        room_id: Uuid,
+        date: NaiveDate,
+        start_time: NaiveTime,
+        end_time: NaiveTime,
+    ) -> AppResult<bool> {
+        let busy = sqlx::query_scalar!(
+            r#"
+            SELECT (
+                EXISTS (
+                    SELECT 1
+                    FROM timetable_entries te
+                    JOIN time_slots ts ON ts.id = te.time_slot_id
+                    JOIN courses c ON c.id = te.course_id
+                    WHERE c.workspace_id = $1 AND te.room_id = $2
+                        AND ts.day_of_week = EXTRACT(DOW FROM $3::date)::int
+                        AND ts.start_time < $5 AND ts.end_time > $4
+                        AND NOT EXISTS (
+                            SELECT 1 FROM lesson_exceptions x
+                            WHERE x.timetable_entry_id = te.id AND x.date = $3 AND x.kind IN ('Cancellation', 'RoomChange')
+                        )
+                )
+                OR EXISTS (
+                    SELECT 1 FROM lesson_exceptions x
+                    WHERE x.workspace_id = $1 AND x.room_id = $2 AND x.date = $3
+                        AND x.start_time < $5 AND x.end_time > $4
+                )
+            ) AS "busy!"
+            "#,
+            workspace_id,
+            room_id,
+            date,
+            start_time,
+            end_time
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(busy)
+    }
+
+    /// How many lessons the teacher teaches at that time on the date: weekly
+    /// lessons that are neither cancelled nor handed to someone else,
+    /// lessons they cover, and extra lessons or lessons taken over for the
+    /// day.
+    pub async fn count_teacher_lessons(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        teacher_id: Uuid,
+        date: NaiveDate,
+        start_time: NaiveTime,
+        end_time: NaiveTime,
+    ) -> AppResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT (
+                (
+                    SELECT COUNT(*)
+                    FROM timetable_entries te
+                    JOIN time_slots ts ON ts.id = te.time_slot_id
+                    JOIN courses c ON c.id = te.course_id
+                    WHERE c.workspace_id = $1 AND te.teacher_id = $2
+                        AND ts.day_of_week = EXTRACT(DOW FROM $3::date)::int
+                        AND ts.start_time < $5 AND ts.end_time > $4
+                        AND NOT EXISTS (
+                            SELECT 1 FROM lesson_exceptions x
+                            WHERE x.timetable_entry_id = te.id AND x.date = $3
+                                AND x.kind IN ('Cancellation', 'TeacherChange')
+                        )
+                        AND NOT EXISTS (
+                            SELECT 1 FROM substitutions sub
+                            WHERE sub.timetable_entry_id = te.id AND sub.status = 'Accepted'
+                                AND sub.starts_on <= $3 AND sub.ends_on >= $3
+                        )
+                )
+                + (
+                    SELECT COUNT(*)
+                    FROM substitutions sub
+                    JOIN timetable_entries te ON te.id = sub.timetable_entry_id
+                    JOIN time_slots ts ON ts.id = te.time_slot_id
+                    JOIN courses c ON c.id = te.course_id
+                    WHERE c.workspace_id = $1 AND sub.substituting_teacher_id = $2 AND sub.status = 'Accepted'
+                        AND sub.starts_on <= $3 AND sub.ends_on >= $3
+                        AND ts.day_of_week = EXTRACT(DOW FROM $3::date)::int
+                        AND ts.start_time < $5 AND ts.end_time > $4
+                )
+                + (
+                    SELECT COUNT(*) FROM lesson_exceptions x
+                    WHERE x.workspace_id = $1 AND x.teacher_id = $2 AND x.date = $3
+                        AND x.kind IN ('ExtraLesson', 'TeacherChange')
+                        AND x.start_time < $5 AND x.end_time > $4
+                )
+            ) AS "count!"
+            "#,
+            workspace_id,
+            teacher_id,
+            date,
+            start_time,
+            end_time
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(count)
+    }
+
+    pub async fn room_exists(&self, conn: &mut PgConnection, workspace_id: Uuid, room_id: Uuid) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM rooms WHERE workspace_id = $1 AND id = $2) AS "exists!"
+            "#,
+            workspace_id,
+            room_id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(exists)
+    }
+
+    pub async fn course_exists(&self, conn: &mut PgConnection, workspace_id: Uuid, course_id: Uuid) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM courses WHERE workspace_id = $1 AND id = $2) AS "exists!"
+            "#,
+            workspace_id,
+            course_id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(exists)
+    }
+
+    pub async fn is_teacher(&self, conn: &mut PgConnection, workspace_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM workspace_members m
+                JOIN users u ON u.id = m.user_id
+                WHERE m.workspace_id = $1 AND m.user_id = $2 AND u.role = 'Teacher'
+            ) AS "exists!"
+            "#,
+            workspace_id,
+            user_id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(exists)
+    }
+
+    /// Whether the teacher is available at the time on the date, taking
+    /// their availability overrides into account. Teachers who never
+    /// submitted a weekly pattern count as available outside their
+    /// unavailability, as in the candidate search.
+    pub async fn is_available(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        teacher_id: Uuid,
+        date: NaiveDate,
+        start_time: NaiveTime,
+        end_time: NaiveTime,
+    ) -> AppResult<bool> {
+        let available = sqlx::query_scalar!(
+            r#"
+            SELECT teacher_available($1, $2, $3, $4, $5) AS "available!"
+            "#,
+            workspace_id,
+            teacher_id,
+            date,
+            start_time,
+            end_time
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(available)
+    }
+
+    pub async fn find_changes(&self, workspace_id: Uuid, date: NaiveDate) -> AppResult<Vec<ExceptionChange>> {
+        let changes = sqlx::query_as!(
+            ExceptionChange,
+            r#"
+            SELECT x.id, x.kind as "kind: LessonExceptionKind", x.timetable_entry_id, x.course_id, c.name AS course_name,
+                x.teacher_id, u.username AS teacher_name, te.teacher_id as "scheduled_teacher_id?",
+                su.username as "scheduled_teacher_name?", te.room_id as "scheduled_room_id?",
+                sr.name as "scheduled_room_name?", x.room_id, r.name as "room_name?", x.start_time, x.end_time, x.reason
+            FROM lesson_exceptions x
+            JOIN courses c ON c.id = x.course_id
+            JOIN users u ON u.id = x.teacher_id
+            LEFT JOIN timetable_entries te ON te.id = x.timetable_entry_id
+            LEFT JOIN users su ON su.id = te.teacher_id
+            LEFT JOIN rooms sr ON sr.id = te.room_id
+            LEFT JOIN rooms r ON r.id = x.room_id
+            WHERE x.workspace_id = $1 AND x.date = $2
+            "#,
+            workspace_id,
+            date
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(changes)
+    }
+
+    /// Pending and accepted substitutions for lessons held on the date.
+    pub async fn find_substitution_changes(&self, workspace_id: Uuid, date: NaiveDate) -> AppResult<Vec<SubstitutionChange>> {
+        let changes = sqlx::query_as!(
+            SubstitutionChange,
+            r#"
+            SELECT sub.id, sub.timetable_entry_id, sub.status as "status: SubstitutionStatus", te.course_id,
+                c.name AS course_name, te.teacher_id, u.username AS teacher_name, te.room_id, r.name AS room_name,
+                ts.start_time, ts.end_time, sub.substituting_teacher_id AS substitute_id,
+                s.username as "substitute_name?"
+            FROM substitutions sub
+            JOIN timetable_entries te ON te.id = sub.timetable_entry_id
+            JOIN time_slots ts ON ts.id = te.time_slot_id
+            JOIN courses c ON c.id = te.course_id
+            JOIN users u ON u.id = te.teacher_id
+            JOIN rooms r ON r.id = te.room_id
+            LEFT JOIN users s ON s.id = sub.substituting_teacher_id
+            WHERE c.workspace_id = $1 AND sub.status IN ('Pending', 'Accepted')
+                AND sub.starts_on <= $2 AND sub.ends_on >= $2
+                AND ts.day_of_week = EXTRACT(DOW FROM $2::date)::int
+            "#,
+            workspace_id,
+            date
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(changes)
+    }
+}
@@ -0,0 +1,210 @@
+use chrono::NaiveDate;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::error::AppResult;
+use crate::models::{LessonSwap, LessonSwapStatus, SwapSummary};
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn create(&self, conn: &mut PgConnection, swap: LessonSwap) -> AppResult<LessonSwap> {
+        let swap = sqlx::query_as!(
+            LessonSwap,
+            r#"
+            INSERT INTO lesson_swaps (
+                id, workspace_id, requester_id, requester_entry_id, requester_date, colleague_id, colleague_entry_id,
+                colleague_date, status, reason, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            RETURNING id, workspace_id, requester_id, requester_entry_id, requester_date, colleague_id, colleague_entry_id,
+                colleague_date, status as "status: LessonSwapStatus", reason, decided_by, decision_note,
+                requester_exception_id, colleague_exception_id, responded_at, decided_at, created_at, updated_at
+            "#,
+            swap.id,
+            swap.workspace_id,
+            swap.requester_id,
+            swap.requester_entry_id,
+            swap.requester_date,
+            swap.colleague_id,
+            swap.colleague_entry_id,
+            swap.colleague_date,
+            swap.status as LessonSwapStatus,
+            swap.reason,
+            swap.created_at,
+            swap.updated_at
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(swap)
+    }
+
+    /// Loads the swap and locks it until the transaction ends, so two
+    /// answers or decisions cannot both apply.
+    pub async fn lock(&self, conn: &mut PgConnection, workspace_id: Uuid, id: Uuid) -> AppResult<Option<LessonSwap>> {
+        let swap = sqlx::query_as!(
+            LessonSwap,
+            r#"
+            SELECT id, workspace_id, requester_id, requester_entry_id, requester_date, colleague_id, colleague_entry_id,
+                colleague_date, status as "status: LessonSwapStatus", reason, decided_by, decision_note,
+                requester_exception_id, colleague_exception_id, responded_at, decided_at, created_at, updated_at
+            FROM lesson_swaps
+            WHERE workspace_id = $1 AND id = $2
+            FOR UPDATE
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(swap)
+    }
+
+    /// Writes the answer, decision and applied exceptions of a swap.
+    pub async fn update(&self, conn: &mut PgConnection, swap: &LessonSwap) -> AppResult<LessonSwap> {
+        let swap = sqlx::query_as!(
+            LessonSwap,
+            r#"
+            UPDATE lesson_swaps
+            SET status = $2, decided_by = $3, decision_note = $4, requester_exception_id = $5,
+                colleague_exception_id = $6, responded_at = $7, decided_at = $8
+            WHERE id = $1
+            RETURNING id, workspace_id, requester_id, requester_entry_id, requester_date, colleague_id, colleague_entry_id,
+                colleague_date, status as "status: LessonSwapStatus", reason, decided_by, decision_note,
+                requester_exception_id, colleague_exception_id, responded_at, decided_at, created_at, updated_at
+            "#,
+            swap.id,
+            swap.status as LessonSwapStatus,
+            swap.decided_by,
+            swap.decision_note,
+            swap.requester_exception_id,
+            swap.colleague_exception_id,
+            swap.responded_at,
+            swap.decided_at
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(swap)
+    }
+
+    /// Swaps of the workspace, newest first, optionally only those in one
+    /// status or involving one teacher.
+    pub async fn find_all(
+        &self,
+        workspace_id: Uuid,
+        status: Option<LessonSwapStatus>,
+        teacher_id: Option<Uuid>,
+    ) -> AppResult<Vec<LessonSwap>> {
+        let swaps = sqlx::query_as!(
+            LessonSwap,
+            r#"
+            SELECT id, workspace_id, requester_id, requester_entry_id, requester_date, colleague_id, colleague_entry_id,
+                colleague_date, status as "status: LessonSwapStatus", reason, decided_by, decision_note,
+                requester_exception_id, colleague_exception_id, responded_at, decided_at, created_at, updated_at
+            FROM lesson_swaps
+            WHERE workspace_id = $1
+                AND ($2::lesson_swap_status IS NULL OR status = $2)
+                AND ($3::uuid IS NULL OR requester_id = $3 OR colleague_id = $3)
+            ORDER BY created_at DESC
+            "#,
+            workspace_id,
+            status as Option<LessonSwapStatus>,
+            teacher_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(swaps)
+    }
+
+    /// Whether the lesson on the date is part of a swap still waiting for
+    /// an answer or a decision.
+    pub async fn is_in_open_swap(&self, conn: &mut PgConnection, timetable_entry_id: Uuid, date: NaiveDate) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM lesson_swaps
+                WHERE status IN ('Proposed', 'Accepted')
+                    AND ((requester_entry_id = $1 AND requester_date = $2)
+                        OR (colleague_entry_id = $1 AND colleague_date = $2))
+            ) AS "exists!"
+            "#,
+            timetable_entry_id,
+            date
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(exists)
+    }
+
+    /// Whether a substitute is requested or found for the lesson on the
+    /// date.
+    pub async fn has_substitution(&self, conn: &mut PgConnection, timetable_entry_id: Uuid, date: NaiveDate) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM substitutions
+                WHERE timetable_entry_id = $1 AND status IN ('Pending', 'Accepted')
+                    AND starts_on <= $2 AND ends_on >= $2
+            ) AS "exists!"
+            "#,
+            timetable_entry_id,
+            date
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(exists)
+    }
+
+    pub async fn find_summary(&self, conn: &mut PgConnection, id: Uuid) -> AppResult<Option<SwapSummary>> {
+        let summary = sqlx::query_as!(
+            SwapSummary,
+            r#"
+            SELECT ru.username AS requester_name, rc.name AS requester_course, rts.start_time AS requester_start,
+                cu.username AS colleague_name, cc.name AS colleague_course, cts.start_time AS colleague_start
+            FROM lesson_swaps s
+            JOIN users ru ON ru.id = s.requester_id
+            JOIN timetable_entries rte ON rte.id = s.requester_entry_id
+            JOIN courses rc ON rc.id = rte.course_id
+            JOIN time_slots rts ON rts.id = rte.time_slot_id
+            JOIN users cu ON cu.id = s.colleague_id
+            JOIN timetable_entries cte ON cte.id = s.colleague_entry_id
+            JOIN courses cc ON cc.id = cte.course_id
+            JOIN time_slots cts ON cts.id = cte.time_slot_id
+            WHERE s.id = $1
+            "#,
+            id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(summary)
+    }
+
+    pub async fn find_admins(&self, conn: &mut PgConnection, workspace_id: Uuid) -> AppResult<Vec<Uuid>> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            SELECT u.id
+            FROM workspace_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.workspace_id = $1 AND u.role = 'Admin'
+            "#,
+            workspace_id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(ids)
+    }
+}
@@ -6,12 +6,27 @@ pub mod rooms;
 pub mod time_slots;
 pub mod timetable_entries;
 pub mod substitutions;
+pub mod substitution_stats;
+pub mod teacher_preferences;
+pub mod teacher_qualifications;
 pub mod availability;
+pub mod availability_rounds;
 pub mod conflicts;
 pub mod draft_timetables;
 pub mod published_timetables;
 pub mod draft_entries;
 pub mod workspace;
+pub mod realtime_events;
+pub mod outbox;
+pub mod notifications;
+pub mod occurrences;
+pub mod absences;
+pub mod lesson_exceptions;
+pub mod lesson_swaps;
+pub mod student_groups;
+pub mod enrollments;
+pub mod room_features;
+pub mod ws_tickets;
 
 pub use users::UserRepository;
 pub use resources::ResourceRepository;
@@ -20,10 +35,25 @@ pub use rooms::RoomRepository;
 pub use time_slots::TimeSlotRepository;
 pub use timetable_entries::TimetableEntryRepository;
 pub use substitutions::SubstitutionRepository;
+pub use substitution_stats::Repository as SubstitutionStatsRepository;
+pub use teacher_preferences::Repository as TeacherPreferenceRepository;
+pub use teacher_qualifications::Repository as TeacherQualificationRepository;
 pub use auth::AuthRepository;
 pub use availability::Repository as AvailabilityRepository;
+pub use availability_rounds::Repository as AvailabilityRoundRepository;
 pub use conflicts::Repository as ConflictRepository;
 pub use draft_timetables::Repository as DraftTimetableRepository;
 pub use published_timetables::Repository as PublishedTimetableRepository;
 pub use draft_entries::Repository as DraftEntryRepository;
 pub use workspace::WorkspaceRepository;
+pub use realtime_events::Repository as RealtimeEventRepository;
+pub use outbox::Repository as OutboxRepository;
+pub use notifications::Repository as NotificationRepository;
+pub use occurrences::Repository as OccurrenceRepository;
+pub use absences::Repository as AbsenceRepository;
+pub use lesson_exceptions::Repository as LessonExceptionRepository;
+pub use lesson_swaps::Repository as LessonSwapRepository;
+pub use student_groups::Repository as StudentGroupRepository;
+pub use enrollments::Repository as EnrollmentRepository;
+pub use room_features::Repository as RoomFeatureRepository;
+pub use ws_tickets::Repository as TicketRepository;
@@ -0,0 +1,365 @@
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use chrono::{DateTime, NaiveTime, Utc};
+use crate::error::AppResult;
+use crate::models::notifications::{
+    Notification, NotificationDelivery, NotificationPreference, NotificationSettings, NotificationType,
+};
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn create(&self, conn: &mut PgConnection, notification: Notification) -> AppResult<Notification> {
+        let row = sqlx::query_as!(
+            Notification,
+            r#"
+            INSERT INTO notifications (
+                id, workspace_id, user_id, notification_type, title, body, entity_id, link, read_at, created_at,
+                in_app, digest_email, digest_webhook
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            RETURNING id, workspace_id, user_id, notification_type as "notification_type: NotificationType", title, body,
+                entity_id, link, read_at, created_at, in_app, digest_email, digest_webhook
+            "#,
+            notification.id,
+            notification.workspace_id,
+            notification.user_id,
+            notification.notification_type as NotificationType,
+            notification.title,
+            notification.body,
+            notification.entity_id,
+            notification.link,
+            notification.read_at,
+            notification.created_at,
+            notification.in_app,
+            notification.digest_email,
+            notification.digest_webhook
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(row)
+    }
+
+    /// Newest first; `before` pages through older notifications.
+    pub async fn find_for_user(
+        &self,
+        workspace_id: Uuid,
+        user_id: Uuid,
+        unread_only: bool,
+        before: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> AppResult<Vec<Notification>> {
+        let rows = sqlx::query_as!(
+            Notification,
+            r#"
+            SELECT id, workspace_id, user_id, notification_type as "notification_type: NotificationType", title, body,
+                entity_id, link, read_at, created_at, in_app, digest_email, digest_webhook
+            FROM notifications
+            WHERE workspace_id = $1 AND user_id = $2 AND in_app
+                AND (NOT $3 OR read_at IS NULL)
+                AND ($4::timestamptz IS NULL OR created_at < $4)
+            ORDER BY created_at DESC
+            LIMIT $5
+            "#,
+            workspace_id,
+            user_id,
+            unread_only,
+            before,
+            limit
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    pub async fn count_unread(&self, conn: &mut PgConnection, workspace_id: Uuid, user_id: Uuid) -> AppResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!"
+            FROM notifications
+            WHERE workspace_id = $1 AND user_id = $2 AND in_app AND read_at IS NULL
+            "#,
+            workspace_id,
+            user_id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(count)
+    }
+
+    pub async fn mark_read(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> AppResult<Option<Notification>> {
+        let row = sqlx::query_as!(
+            Notification,
+            r#"
+            UPDATE notifications
+            SET read_at = COALESCE(read_at, NOW())
+            WHERE id = $1 AND workspace_id = $2 AND user_id = $3 AND in_app
+            RETURNING id, workspace_id, user_id, notification_type as "notification_type: NotificationType", title, body,
+                entity_id, link, read_at, created_at, in_app, digest_email, digest_webhook
+            "#,
+            id,
+            workspace_id,
+            user_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn mark_all_read(&self, conn: &mut PgConnection, workspace_id: Uuid, user_id: Uuid) -> AppResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE notifications
+            SET read_at = NOW()
+            WHERE workspace_id = $1 AND user_id = $2 AND in_app AND read_at IS NULL
+            "#,
+            workspace_id,
+            user_id
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn delete_older_than(&self, cutoff: DateTime<Utc>) -> AppResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM notifications
+            WHERE created_at < $1 AND NOT digest_email AND NOT digest_webhook
+            "#,
+            cutoff
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Users with notifications waiting for their digest.
+    pub async fn find_digest_recipients(&self) -> AppResult<Vec<(Uuid, Uuid)>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT DISTINCT workspace_id, user_id
+            FROM notifications
+            WHERE digest_email OR digest_webhook
+            "#
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| (row.workspace_id, row.user_id)).collect())
+    }
+
+    /// Returns the notifications waiting for the user's digest and clears
+    /// their digest flags.
+    pub async fn take_digest_items(&self, conn: &mut PgConnection, workspace_id: Uuid, user_id: Uuid) -> AppResult<Vec<Notification>> {
+        let rows = sqlx::query_as!(
+            Notification,
+            r#"
+            WITH pending AS (
+                SELECT id, digest_email, digest_webhook
+                FROM notifications
+                WHERE workspace_id = $1 AND user_id = $2 AND (digest_email OR digest_webhook)
+                FOR UPDATE
+            )
+            UPDATE notifications n
+            SET digest_email = false, digest_webhook = false
+            FROM pending
+            WHERE n.id = pending.id
+            RETURNING n.id, n.workspace_id, n.user_id, n.notification_type as "notification_type: NotificationType", n.title, n.body,
+                n.entity_id, n.link, n.read_at, n.created_at, n.in_app,
+                pending.digest_email as "digest_email!", pending.digest_webhook as "digest_webhook!"
+            "#,
+            workspace_id,
+            user_id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rows)
+    }
+
+    pub async fn find_preference(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        user_id: Uuid,
+        notification_type: NotificationType,
+    ) -> AppResult<Option<NotificationPreference>> {
+        let row = sqlx::query_as!(
+            NotificationPreference,
+            r#"
+            SELECT workspace_id, user_id, notification_type as "notification_type: NotificationType",
+                delivery as "delivery: NotificationDelivery", in_app, email, webhook
+            FROM notification_preferences
+            WHERE workspace_id = $1 AND user_id = $2 AND notification_type = $3
+            "#,
+            workspace_id,
+            user_id,
+            notification_type as NotificationType
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn find_preferences(&self, workspace_id: Uuid, user_id: Uuid) -> AppResult<Vec<NotificationPreference>> {
+        let rows = sqlx::query_as!(
+            NotificationPreference,
+            r#"
+            SELECT workspace_id, user_id, notification_type as "notification_type: NotificationType",
+                delivery as "delivery: NotificationDelivery", in_app, email, webhook
+            FROM notification_preferences
+            WHERE workspace_id = $1 AND user_id = $2
+            "#,
+            workspace_id,
+            user_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    pub async fn upsert_preference(&self, preference: NotificationPreference) -> AppResult<NotificationPreference> {
+        let row = sqlx::query_as!(
+            NotificationPreference,
+            r#"
+            INSERT INTO notification_preferences (workspace_id, user_id, notification_type, delivery, in_app, email, webhook)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (workspace_id, user_id, notification_type)
+            DO UPDATE SET delivery = EXCLUDED.delivery, in_app = EXCLUDED.in_app, email = EXCLUDED.email, webhook = EXCLUDED.webhook
+            RETURNING workspace_id, user_id, notification_type as "notification_type: NotificationType",
+                delivery as "delivery: NotificationDelivery", in_app, email, webhook
+            "#,
+            preference.workspace_id,
+            preference.user_id,
+            preference.notification_type as NotificationType,
+            preference.delivery as NotificationDelivery,
+            preference.in_app,
+            preference.email,
+            preference.webhook
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn find_settings(&self, conn: &mut PgConnection, workspace_id: Uuid, user_id: Uuid) -> AppResult<Option<NotificationSettings>> {
+        let row = sqlx::query_as!(
+            NotificationSettings,
+            r#"
+            SELECT workspace_id, user_id, timezone, digest_time, webhook_url, last_digest_at
+            FROM notification_settings
+            WHERE workspace_id = $1 AND user_id = $2
+            "#,
+            workspace_id,
+            user_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn upsert_settings(&self, settings: NotificationSettings) -> AppResult<NotificationSettings> {
+        let row = sqlx::query_as!(
+            NotificationSettings,
+            r#"
+            INSERT INTO notification_settings (workspace_id, user_id, timezone, digest_time, webhook_url)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (workspace_id, user_id)
+            DO UPDATE SET timezone = EXCLUDED.timezone, digest_time = EXCLUDED.digest_time, webhook_url = EXCLUDED.webhook_url
+            RETURNING workspace_id, user_id, timezone, digest_time, webhook_url, last_digest_at
+            "#,
+            settings.workspace_id,
+            settings.user_id,
+            settings.timezone,
+            settings.digest_time,
+            settings.webhook_url
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    /// Locks the user's settings for the rest of the transaction, creating
+    /// them with the given defaults first if needed, so that only one
+    /// instance sends a given digest.
+    pub async fn lock_settings(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        user_id: Uuid,
+        default_timezone: &str,
+        default_digest_time: NaiveTime,
+    ) -> AppResult<NotificationSettings> {
+        sqlx::query!(
+            r#"
+            INSERT INTO notification_settings (workspace_id, user_id, timezone, digest_time)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (workspace_id, user_id) DO NOTHING
+            "#,
+            workspace_id,
+            user_id,
+            default_timezone,
+            default_digest_time
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        let row = sqlx::query_as!(
+            NotificationSettings,
+            r#"
+            SELECT workspace_id, user_id, timezone, digest_time, webhook_url, last_digest_at
+            FROM notification_settings
+            WHERE workspace_id = $1 AND user_id = $2
+            FOR UPDATE
+            "#,
+            workspace_id,
+            user_id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn set_last_digest_at(&self, conn: &mut PgConnection, workspace_id: Uuid, user_id: Uuid, at: DateTime<Utc>) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE notification_settings
+            SET last_digest_at = $3
+            WHERE workspace_id = $1 AND user_id = $2
+            "#,
+            workspace_id,
+            user_id,
+            at
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use chrono::NaiveDate;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::error::AppResult;
+use crate::models::{CalendarFeed, DatedException, DatedSubstitution, LessonExceptionKind, SubstitutionStatus, WeeklyLesson};
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn find_weekly_lessons(&self, workspace_id: Uuid) -> AppResult<Vec<WeeklyLesson>> {
+        let lessons = sqlx::query_as!(
+            WeeklyLesson,
+            r#"
+            SELECT te.id AS timetable_entry_id, te.course_id, c.name AS course_name,
+                te.teacher_id, u.username AS teacher_name, te.room_id, r.name AS room_name,
+                ts.day_of_week, ts.start_time, ts.end_time
+            FROM timetable_entries te
+            JOIN courses c ON c.id = te.course_id
+            JOIN users u ON u.id = te.teacher_id
+            JOIN rooms r ON r.id = te.room_id
+            JOIN time_slots ts ON ts.id = te.time_slot_id
+            WHERE c.workspace_id = $1
+            ORDER BY ts.start_time, c.name
+            "#,
+            workspace_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(lessons)
+    }
+
+    /// Pending and accepted substitutions covering any date in the range.
+    pub async fn find_substitutions(&self, workspace_id: Uuid, from: NaiveDate, to: NaiveDate) -> AppResult<Vec<DatedSubstitution>> {
+        let substitutions = sqlx::query_as!(
+            DatedSubstitution,
+            r#"
+            SELECT sub.id, sub.timetable_entry_id, sub.status as "status: SubstitutionStatus",
+                sub.substituting_teacher_id AS substitute_id, s.username as "substitute_name?",
+                sub.starts_on, sub.ends_on
+            FROM substitutions sub
+            JOIN timetable_entries te ON te.id = sub.timetable_entry_id
+            JOIN courses c ON c.id = te.course_id
+            LEFT JOIN users s ON s.id = sub.substituting_teacher_id
+            WHERE c.workspace_id = $1 AND sub.status IN ('Pending', 'Accepted')
+                AND sub.starts_on <= $3 AND sub.ends_on >= $2
+            "#,
+            workspace_id,
+            from,
+            to
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(substitutions)
+    }
+
+    /// Lesson exceptions on any date in the range.
+    pub async fn find_exceptions(&self, workspace_id: Uuid, from: NaiveDate, to: NaiveDate) -> AppResult<Vec<DatedException>> {
+        let exceptions = sqlx::query_as!(
+            DatedException,
+            r#"
+            SELECT x.id, x.date, x.kind as "kind: LessonExceptionKind", x.timetable_entry_id, x.course_id,
+                c.name AS course_name, x.teacher_id, u.username AS teacher_name, x.room_id, r.name as "room_name?",
+                x.start_time, x.end_time
+            FROM lesson_exceptions x
+            JOIN courses c ON c.id = x.course_id
+            JOIN users u ON u.id = x.teacher_id
+            LEFT JOIN rooms r ON r.id = x.room_id
+            WHERE x.workspace_id = $1 AND x.date BETWEEN $2 AND $3
+            "#,
+            workspace_id,
+            from,
+            to
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(exceptions)
+    }
+
+    pub async fn create_feed(&self, feed: CalendarFeed) -> AppResult<CalendarFeed> {
+        let feed = sqlx::query_as!(
+            CalendarFeed,
+            r#"
+            INSERT INTO calendar_feeds (token, workspace_id, user_id, created_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING token, workspace_id, user_id, created_at
+            "#,
+            feed.token,
+            feed.workspace_id,
+            feed.user_id,
+            feed.created_at
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(feed)
+    }
+
+    /// The feed with this token, as long as its owner still belongs to the
+    /// workspace.
+    pub async fn find_feed(&self, token: &str) -> AppResult<Option<CalendarFeed>> {
+        let feed = sqlx::query_as!(
+            CalendarFeed,
+            r#"
+            SELECT f.token, f.workspace_id, f.user_id, f.created_at
+            FROM calendar_feeds f
+            JOIN workspace_members m ON m.workspace_id = f.workspace_id AND m.user_id = f.user_id
+            WHERE f.token = $1
+            "#,
+            token
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(feed)
+    }
+
+    pub async fn delete_feeds(&self, workspace_id: Uuid, user_id: Uuid) -> AppResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM calendar_feeds
+            WHERE workspace_id = $1 AND user_id = $2
+            "#,
+            workspace_id,
+            user_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,222 @@
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use crate::error::AppResult;
+use crate::models::outbox::{OutboxChannel, OutboxMessage, WebhookEndpoint};
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn begin(&self) -> AppResult<Transaction<'static, Postgres>> {
+        Ok(self.db_pool.begin().await?)
+    }
+
+    /// Queues a message unless one with the same channel and dedupe key exists.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn enqueue(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Option<Uuid>,
+        channel: OutboxChannel,
+        event_type: &str,
+        payload: &serde_json::Value,
+        target: Option<&str>,
+        dedupe_key: &str,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO outbox (id, workspace_id, channel, event_type, payload, target, dedupe_key)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (channel, dedupe_key) DO NOTHING
+            "#,
+            Uuid::new_v4(),
+            workspace_id,
+            channel as OutboxChannel,
+            event_type,
+            payload,
+            target,
+            dedupe_key
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Queues one webhook delivery per active endpoint of the workspace.
+    pub async fn enqueue_webhooks(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        event_type: &str,
+        payload: &serde_json::Value,
+        dedupe_key: &str,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO outbox (id, workspace_id, channel, event_type, payload, target, webhook_endpoint_id, dedupe_key)
+            SELECT gen_random_uuid(), workspace_id, 'Webhook', $2, $3, url, id, $4 || ':' || id::text
+            FROM webhook_endpoints
+            WHERE workspace_id = $1 AND is_active
+            ON CONFLICT (channel, dedupe_key) DO NOTHING
+            "#,
+            workspace_id,
+            event_type,
+            payload,
+            dedupe_key
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Leases due messages so that concurrent dispatchers skip them until
+    /// `lease_until`, when they become due again unless marked delivered.
+    pub async fn claim_due(&self, limit: i64, max_attempts: i32, lease_until: DateTime<Utc>) -> AppResult<Vec<OutboxMessage>> {
+        let messages = sqlx::query_as!(
+            OutboxMessage,
+            r#"
+            UPDATE outbox
+            SET attempts = attempts + 1, next_attempt_at = $3
+            WHERE id IN (
+                SELECT id FROM outbox
+                WHERE delivered_at IS NULL AND next_attempt_at <= NOW() AND attempts < $2
+                ORDER BY created_at ASC
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, workspace_id, channel as "channel: OutboxChannel", event_type, payload, target, webhook_endpoint_id, dedupe_key,
+                attempts, next_attempt_at, last_error, delivered_at, created_at
+            "#,
+            limit,
+            max_attempts,
+            lease_until
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(messages)
+    }
+
+    pub async fn mark_delivered(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE outbox
+            SET delivered_at = NOW(), last_error = NULL
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn mark_failed(&self, id: Uuid, error: &str, next_attempt_at: DateTime<Utc>) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE outbox
+            SET last_error = $2, next_attempt_at = $3
+            WHERE id = $1
+            "#,
+            id,
+            error,
+            next_attempt_at
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_delivered_before(&self, cutoff: DateTime<Utc>) -> AppResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM outbox
+            WHERE delivered_at < $1
+            "#,
+            cutoff
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn create_webhook_endpoint(&self, endpoint: WebhookEndpoint) -> AppResult<WebhookEndpoint> {
+        let row = sqlx::query_as!(
+            WebhookEndpoint,
+            r#"
+            INSERT INTO webhook_endpoints (id, workspace_id, url, secret, is_active, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, workspace_id, url, secret, is_active, created_at, updated_at
+            "#,
+            endpoint.id,
+            endpoint.workspace_id,
+            endpoint.url,
+            endpoint.secret,
+            endpoint.is_active,
+            endpoint.created_at,
+            endpoint.updated_at
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn get_webhook_endpoints(&self, workspace_id: Uuid) -> AppResult<Vec<WebhookEndpoint>> {
+        let rows = sqlx::query_as!(
+            WebhookEndpoint,
+            r#"
+            SELECT id, workspace_id, url, secret, is_active, created_at, updated_at
+            FROM webhook_endpoints
+            WHERE workspace_id = $1
+            ORDER BY created_at ASC
+            "#,
+            workspace_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    pub async fn find_webhook_secret(&self, id: Uuid) -> AppResult<Option<String>> {
+        let secret = sqlx::query_scalar!(
+            r#"
+            SELECT secret FROM webhook_endpoints
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(secret)
+    }
+
+    pub async fn delete_webhook_endpoint(&self, workspace_id: Uuid, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM webhook_endpoints
+            WHERE id = $1 AND workspace_id = $2
+            "#,
+            id,
+            workspace_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -1,7 +1,8 @@
-use sqlx::PgPool;
+use chrono::NaiveDate;
+use sqlx::{PgConnection, PgPool};
 use uuid::Uuid;
 use crate::error::AppResult;
-use crate::models::published_timetables::PublishedTimetable;
+use crate::models::published_timetables::{PublishedLesson, PublishedTimetable};
 
 #[derive(Clone)]
 pub struct Repository {
@@ -13,13 +14,14 @@ impl Repository {
         Self { db_pool }
     }
 
-    pub async fn create(&self, timetable: PublishedTimetable) -> AppResult<PublishedTimetable> {
+    pub async fn create(&self, conn: &mut PgConnection, timetable: PublishedTimetable) -> AppResult<PublishedTimetable> {
         let record = sqlx::query_as::<_, PublishedTimetable>(
             r#"
             INSERT INTO published_timetables (
-                id, workspace_id, draft_timetable_id, published_at, valid_from, valid_to, created_at, updated_at
+                id, workspace_id, draft_timetable_id, published_at, valid_from, valid_to, created_at, updated_at,
+                previous_published_timetable_id
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             RETURNING *
             "#,
         )
@@ -31,7 +33,8 @@ impl Repository {
         .bind(timetable.valid_to)
         .bind(timetable.created_at)
         .bind(timetable.updated_at)
-        .fetch_one(&self.db_pool)
+        .bind(timetable.previous_published_timetable_id)
+        .fetch_one(conn)
         .await?;
 
         Ok(record)
@@ -63,4 +66,45 @@ impl Repository {
 
         Ok(record)
     }
+
+    /// The most recently published timetable of the workspace that is valid
+    /// on `on`.
+    pub async fn find_current(&self, conn: &mut PgConnection, workspace_id: Uuid, on: NaiveDate) -> AppResult<Option<PublishedTimetable>> {
+        let record = sqlx::query_as::<_, PublishedTimetable>(
+            r#"
+            SELECT * FROM published_timetables
+            WHERE workspace_id = $1 AND valid_from <= $2 AND valid_to >= $2
+            ORDER BY published_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(on)
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(record)
+    }
+
+    pub async fn find_lessons(&self, conn: &mut PgConnection, published_timetable_id: Uuid) -> AppResult<Vec<PublishedLesson>> {
+        let lessons = sqlx::query_as::<_, PublishedLesson>(
+            r#"
+            SELECT de.course_id, c.name AS course_name, de.teacher_id, u.username AS teacher_name,
+                de.room_id, r.name AS room_name, de.time_slot_id, ts.day_of_week, ts.start_time, ts.end_time
+            FROM published_timetables pt
+            JOIN draft_entries de ON de.draft_timetable_id = pt.draft_timetable_id
+            JOIN courses c ON c.id = de.course_id
+            JOIN users u ON u.id = de.teacher_id
+            JOIN rooms r ON r.id = de.room_id
+            JOIN time_slots ts ON ts.id = de.time_slot_id
+            WHERE pt.id = $1
+            ORDER BY ts.day_of_week, ts.start_time, c.name
+            "#,
+        )
+        .bind(published_timetable_id)
+        .fetch_all(conn)
+        .await?;
+
+        Ok(lessons)
+    }
 }
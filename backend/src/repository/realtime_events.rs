@@ -0,0 +1,149 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use crate::error::AppResult;
+use crate::models::realtime_events::RealtimeEvent;
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    /// Stores an event under the next sequence number of its workspace. An
+    /// event whose dedupe key was already stored is returned as is.
+    pub async fn append(
+        &self,
+        workspace_id: Uuid,
+        event_type: &str,
+        payload: &serde_json::Value,
+        topics: &[String],
+        dedupe_key: Option<&str>,
+    ) -> AppResult<RealtimeEvent> {
+        let mut tx = self.db_pool.begin().await?;
+
+        if let Some(dedupe_key) = dedupe_key {
+            let existing = sqlx::query_as!(
+                RealtimeEvent,
+                r#"
+                SELECT workspace_id, seq, event_type, payload, topics, created_at
+                FROM realtime_events
+                WHERE workspace_id = $1 AND dedupe_key = $2
+                "#,
+                workspace_id,
+                dedupe_key
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if let Some(existing) = existing {
+                return Ok(existing);
+            }
+        }
+
+        // The upsert takes a row lock, serialising writers of the same workspace
+        let seq = sqlx::query_scalar!(
+            r#"
+            INSERT INTO workspace_event_sequences (workspace_id, last_seq)
+            VALUES ($1, 1)
+            ON CONFLICT (workspace_id) DO UPDATE SET last_seq = workspace_event_sequences.last_seq + 1
+            RETURNING last_seq
+            "#,
+            workspace_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let event = sqlx::query_as!(
+            RealtimeEvent,
+            r#"
+            INSERT INTO realtime_events (workspace_id, seq, event_type, payload, topics, dedupe_key)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING workspace_id, seq, event_type, payload, topics, created_at
+            "#,
+            workspace_id,
+            seq,
+            event_type,
+            payload,
+            topics,
+            dedupe_key
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(event)
+    }
+
+    pub async fn list_since(&self, workspace_id: Uuid, since: i64, limit: i64) -> AppResult<Vec<RealtimeEvent>> {
+        let events = sqlx::query_as!(
+            RealtimeEvent,
+            r#"
+            SELECT workspace_id, seq, event_type, payload, topics, created_at
+            FROM realtime_events
+            WHERE workspace_id = $1 AND seq > $2
+            ORDER BY seq ASC
+            LIMIT $3
+            "#,
+            workspace_id,
+            since,
+            limit
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    pub async fn find(&self, workspace_id: Uuid, seq: i64) -> AppResult<Option<RealtimeEvent>> {
+        let event = sqlx::query_as!(
+            RealtimeEvent,
+            r#"
+            SELECT workspace_id, seq, event_type, payload, topics, created_at
+            FROM realtime_events
+            WHERE workspace_id = $1 AND seq = $2
+            "#,
+            workspace_id,
+            seq
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(event)
+    }
+
+    /// Returns the oldest retained and the latest assigned sequence numbers.
+    pub async fn bounds(&self, workspace_id: Uuid) -> AppResult<(Option<i64>, i64)> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                (SELECT MIN(seq) FROM realtime_events WHERE workspace_id = $1) AS "oldest",
+                COALESCE((SELECT last_seq FROM workspace_event_sequences WHERE workspace_id = $1), 0) AS "latest!"
+            "#,
+            workspace_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok((row.oldest, row.latest))
+    }
+
+    pub async fn delete_older_than(&self, cutoff: DateTime<Utc>) -> AppResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM realtime_events
+            WHERE created_at < $1
+            "#,
+            cutoff
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
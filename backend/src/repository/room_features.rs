@@ -0,0 +1,262 @@
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+use crate::error::AppResult;
+use crate::models::{RoomFeature, RoomFeatureNeed, RoomFeatureRequirement};
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn begin(&self) -> AppResult<Transaction<'static, Postgres>> {
+        Ok(self.db_pool.begin().await?)
+    }
+
+    pub async fn create(&self, feature: RoomFeature) -> AppResult<RoomFeature> {
+        let feature = sqlx::query_as!(
+            RoomFeature,
+            r#"
+            INSERT INTO room_features (id, workspace_id, name, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, workspace_id, name, created_at, updated_at
+            "#,
+            feature.id,
+            feature.workspace_id,
+            feature.name,
+            feature.created_at,
+            feature.updated_at
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(feature)
+    }
+
+    pub async fn rename(&self, id: Uuid, name: &str) -> AppResult<RoomFeature> {
+        let feature = sqlx::query_as!(
+            RoomFeature,
+            r#"
+            UPDATE room_features
+            SET name = $2
+            WHERE id = $1
+            RETURNING id, workspace_id, name, created_at, updated_at
+            "#,
+            id,
+            name
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(feature)
+    }
+
+    /// Deletes the feature, taking it off rooms, courses and groups.
+    pub async fn delete(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query!("DELETE FROM room_features WHERE id = $1", id)
+            .execute(&self.db_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_id(&self, workspace_id: Uuid, id: Uuid) -> AppResult<Option<RoomFeature>> {
+        let feature = sqlx::query_as!(
+            RoomFeature,
+            r#"
+            SELECT id, workspace_id, name, created_at, updated_at
+            FROM room_features
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(feature)
+    }
+
+    /// The catalogue, or only the features of a room.
+    pub async fn find_all(&self, workspace_id: Uuid, room_id: Option<Uuid>) -> AppResult<Vec<RoomFeature>> {
+        let features = sqlx::query_as!(
+            RoomFeature,
+            r#"
+            SELECT f.id, f.workspace_id, f.name, f.created_at, f.updated_at
+            FROM room_features f
+            WHERE f.workspace_id = $1
+                AND ($2::uuid IS NULL OR EXISTS (
+                    SELECT 1 FROM room_feature_assignments a
+                    WHERE a.room_feature_id = f.id AND a.room_id = $2
+                ))
+            ORDER BY f.name
+            "#,
+            workspace_id,
+            room_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(features)
+    }
+
+    /// How many of the features belong to the workspace.
+    pub async fn count_in_workspace(&self, workspace_id: Uuid, ids: &[Uuid]) -> AppResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM room_features WHERE workspace_id = $1 AND id = ANY($2)"#,
+            workspace_id,
+            ids
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    pub async fn room_in_workspace(&self, workspace_id: Uuid, room_id: Uuid) -> AppResult<bool> {
+        let found = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM rooms WHERE workspace_id = $1 AND id = $2) AS "found!""#,
+            workspace_id,
+            room_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(found)
+    }
+
+    pub async fn course_in_workspace(&self, workspace_id: Uuid, course_id: Uuid) -> AppResult<bool> {
+        let found = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM courses WHERE workspace_id = $1 AND id = $2) AS "found!""#,
+            workspace_id,
+            course_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(found)
+    }
+
+    pub async fn group_in_workspace(&self, workspace_id: Uuid, student_group_id: Uuid) -> AppResult<bool> {
+        let found = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM student_groups WHERE workspace_id = $1 AND id = $2) AS "found!""#,
+            workspace_id,
+            student_group_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(found)
+    }
+
+    pub async fn set_room_features(&self, conn: &mut PgConnection, room_id: Uuid, feature_ids: &[Uuid]) -> AppResult<()> {
+        sqlx::query!("DELETE FROM room_feature_assignments WHERE room_id = $1", room_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO room_feature_assignments (room_id, room_feature_id)
+            SELECT $1, unnest($2::uuid[])
+            ON CONFLICT DO NOTHING
+            "#,
+            room_id,
+            feature_ids
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_course_requirements(
+        &self,
+        conn: &mut PgConnection,
+        course_id: Uuid,
+        feature_ids: &[Uuid],
+        needs: &[RoomFeatureNeed],
+    ) -> AppResult<()> {
+        sqlx::query!("DELETE FROM course_room_features WHERE course_id = $1", course_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO course_room_features (course_id, room_feature_id, need)
+            SELECT $1, f.id, f.need
+            FROM unnest($2::uuid[], $3::room_feature_need[]) AS f (id, need)
+            "#,
+            course_id,
+            feature_ids,
+            needs as &[RoomFeatureNeed]
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_group_requirements(
+        &self,
+        conn: &mut PgConnection,
+        student_group_id: Uuid,
+        feature_ids: &[Uuid],
+        needs: &[RoomFeatureNeed],
+    ) -> AppResult<()> {
+        sqlx::query!("DELETE FROM student_group_room_features WHERE student_group_id = $1", student_group_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO student_group_room_features (student_group_id, room_feature_id, need)
+            SELECT $1, f.id, f.need
+            FROM unnest($2::uuid[], $3::room_feature_need[]) AS f (id, need)
+            "#,
+            student_group_id,
+            feature_ids,
+            needs as &[RoomFeatureNeed]
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_course_requirements(&self, course_id: Uuid) -> AppResult<Vec<RoomFeatureRequirement>> {
+        let requirements = sqlx::query_as!(
+            RoomFeatureRequirement,
+            r#"
+            SELECT f.id AS room_feature_id, f.name, cf.need as "need: RoomFeatureNeed"
+            FROM course_room_features cf
+            JOIN room_features f ON f.id = cf.room_feature_id
+            WHERE cf.course_id = $1
+            ORDER BY f.name
+            "#,
+            course_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(requirements)
+    }
+
+    pub async fn find_group_requirements(&self, student_group_id: Uuid) -> AppResult<Vec<RoomFeatureRequirement>> {
+        let requirements = sqlx::query_as!(
+            RoomFeatureRequirement,
+            r#"
+            SELECT f.id AS room_feature_id, f.name, gf.need as "need: RoomFeatureNeed"
+            FROM student_group_room_features gf
+            JOIN room_features f ON f.id = gf.room_feature_id
+            WHERE gf.student_group_id = $1
+            ORDER BY f.name
+            "#,
+            student_group_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(requirements)
+    }
+}
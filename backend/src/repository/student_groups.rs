@@ -0,0 +1,251 @@
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+use chrono::NaiveDate;
+use crate::error::AppResult;
+use crate::models::{DraftEntryGroup, PublishedLesson, StudentGroup, StudentGroupKind};
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn begin(&self) -> AppResult<Transaction<'static, Postgres>> {
+        Ok(self.db_pool.begin().await?)
+    }
+
+    pub async fn create(&self, group: StudentGroup) -> AppResult<StudentGroup> {
+        let group = sqlx::query_as!(
+            StudentGroup,
+            r#"
+            INSERT INTO student_groups (id, workspace_id, name, kind, parent_id, size, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, workspace_id, name, kind as "kind: StudentGroupKind", parent_id, size, created_at, updated_at
+            "#,
+            group.id,
+            group.workspace_id,
+            group.name,
+            group.kind as StudentGroupKind,
+            group.parent_id,
+            group.size,
+            group.created_at,
+            group.updated_at
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(group)
+    }
+
+    pub async fn update(&self, group: &StudentGroup) -> AppResult<StudentGroup> {
+        let group = sqlx::query_as!(
+            StudentGroup,
+            r#"
+            UPDATE student_groups
+            SET name = $2, kind = $3, parent_id = $4, size = $5
+            WHERE id = $1
+            RETURNING id, workspace_id, name, kind as "kind: StudentGroupKind", parent_id, size, created_at, updated_at
+            "#,
+            group.id,
+            group.name,
+            group.kind as StudentGroupKind,
+            group.parent_id,
+            group.size
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(group)
+    }
+
+    /// Deletes the group along with the groups within it.
+    pub async fn delete(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query!("DELETE FROM student_groups WHERE id = $1", id)
+            .execute(&self.db_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_id(&self, workspace_id: Uuid, id: Uuid) -> AppResult<Option<StudentGroup>> {
+        let group = sqlx::query_as!(
+            StudentGroup,
+            r#"
+            SELECT id, workspace_id, name, kind as "kind: StudentGroupKind", parent_id, size, created_at, updated_at
+            FROM student_groups
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(group)
+    }
+
+    pub async fn find_all(&self, workspace_id: Uuid) -> AppResult<Vec<StudentGroup>> {
+        let groups = sqlx::query_as!(
+            StudentGroup,
+            r#"
+            SELECT id, workspace_id, name, kind as "kind: StudentGroupKind", parent_id, size, created_at, updated_at
+            FROM student_groups
+            WHERE workspace_id = $1
+            ORDER BY name
+            "#,
+            workspace_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(groups)
+    }
+
+    pub async fn find_by_course(&self, course_id: Uuid) -> AppResult<Vec<StudentGroup>> {
+        let groups = sqlx::query_as!(
+            StudentGroup,
+            r#"
+            SELECT g.id, g.workspace_id, g.name, g.kind as "kind: StudentGroupKind", g.parent_id, g.size, g.created_at, g.updated_at
+            FROM course_student_groups cg
+            JOIN student_groups g ON g.id = cg.student_group_id
+            WHERE cg.course_id = $1
+            ORDER BY g.name
+            "#,
+            course_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(groups)
+    }
+
+    /// How many of the groups belong to the workspace.
+    pub async fn count_in_workspace(&self, workspace_id: Uuid, ids: &[Uuid]) -> AppResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM student_groups WHERE workspace_id = $1 AND id = ANY($2)"#,
+            workspace_id,
+            ids
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    pub async fn course_in_workspace(&self, workspace_id: Uuid, course_id: Uuid) -> AppResult<bool> {
+        let found = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM courses WHERE workspace_id = $1 AND id = $2) AS "found!""#,
+            workspace_id,
+            course_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(found)
+    }
+
+    pub async fn set_course_groups(&self, conn: &mut PgConnection, course_id: Uuid, group_ids: &[Uuid]) -> AppResult<()> {
+        sqlx::query!("DELETE FROM course_student_groups WHERE course_id = $1", course_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO course_student_groups (course_id, student_group_id)
+            SELECT $1, unnest($2::uuid[])
+            ON CONFLICT DO NOTHING
+            "#,
+            course_id,
+            group_ids
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_draft_entry_groups(&self, conn: &mut PgConnection, draft_entry_id: Uuid, group_ids: &[Uuid]) -> AppResult<()> {
+        sqlx::query!("DELETE FROM draft_entry_student_groups WHERE draft_entry_id = $1", draft_entry_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO draft_entry_student_groups (draft_entry_id, student_group_id)
+            SELECT $1, unnest($2::uuid[])
+            ON CONFLICT DO NOTHING
+            "#,
+            draft_entry_id,
+            group_ids
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The groups attending each lesson of a draft.
+    pub async fn find_draft_entry_groups(&self, draft_timetable_id: Uuid) -> AppResult<Vec<DraftEntryGroup>> {
+        let groups = sqlx::query_as!(
+            DraftEntryGroup,
+            r#"
+            SELECT draft_entry_id AS "draft_entry_id!", student_group_id AS "student_group_id!"
+            FROM draft_entry_groups
+            WHERE draft_timetable_id = $1
+            "#,
+            draft_timetable_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(groups)
+    }
+
+    /// The draft of the timetable in force on the date.
+    pub async fn find_current_draft(&self, workspace_id: Uuid, on: NaiveDate) -> AppResult<Option<Uuid>> {
+        let draft_timetable_id = sqlx::query_scalar!(
+            r#"
+            SELECT draft_timetable_id
+            FROM published_timetables
+            WHERE workspace_id = $1 AND valid_from <= $2 AND valid_to >= $2
+            ORDER BY published_at DESC
+            LIMIT 1
+            "#,
+            workspace_id,
+            on
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(draft_timetable_id)
+    }
+
+    /// The lessons of a draft attended by any of the groups.
+    pub async fn find_lessons(&self, draft_timetable_id: Uuid, group_ids: &[Uuid]) -> AppResult<Vec<PublishedLesson>> {
+        let lessons = sqlx::query_as::<_, PublishedLesson>(
+            r#"
+            SELECT de.course_id, c.name AS course_name, de.teacher_id, u.username AS teacher_name,
+                de.room_id, r.name AS room_name, de.time_slot_id, ts.day_of_week, ts.start_time, ts.end_time
+            FROM draft_entries de
+            JOIN courses c ON c.id = de.course_id
+            JOIN users u ON u.id = de.teacher_id
+            JOIN rooms r ON r.id = de.room_id
+            JOIN time_slots ts ON ts.id = de.time_slot_id
+            WHERE de.draft_timetable_id = $1
+                AND EXISTS (
+                    SELECT 1 FROM draft_entry_groups g
+                    WHERE g.draft_entry_id = de.id AND g.student_group_id = ANY($2)
+                )
+            ORDER BY ts.day_of_week, ts.start_time, c.name
+            "#,
+        )
+        .bind(draft_timetable_id)
+        .bind(group_ids)
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(lessons)
+    }
+}
@@ -0,0 +1,77 @@
+use chrono::NaiveDate;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::error::AppResult;
+use crate::models::TeacherSubstitutionStats;
+
+#[derive(Clone)]
+pub struct Repository {
+    pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Substitution figures of every teacher of the workspace, or of one,
+    /// for the lessons held from `from` through `to`. Retracted
+    /// substitutions are left out.
+    pub async fn find_stats(
+        &self,
+        workspace_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+        teacher_id: Option<Uuid>,
+    ) -> AppResult<Vec<TeacherSubstitutionStats>> {
+        let stats = sqlx::query_as!(
+            TeacherSubstitutionStats,
+            r#"
+            WITH lessons AS (
+                SELECT te.teacher_id, sub.substituting_teacher_id, sub.status,
+                    EXTRACT(EPOCH FROM ts.end_time - ts.start_time)::float8 / 3600 AS hours
+                FROM substitutions sub
+                JOIN timetable_entries te ON te.id = sub.timetable_entry_id
+                JOIN time_slots ts ON ts.id = te.time_slot_id
+                JOIN courses c ON c.id = te.course_id
+                JOIN generate_series(GREATEST(sub.starts_on, $2::date), LEAST(sub.ends_on, $3::date), INTERVAL '1 day') day
+                    ON EXTRACT(DOW FROM day)::int = ts.day_of_week
+                WHERE c.workspace_id = $1 AND sub.status <> 'Cancelled'
+                    AND sub.starts_on <= $3 AND sub.ends_on >= $2
+            )
+            SELECT u.id AS teacher_id, u.username AS teacher_name,
+                (SELECT COUNT(*) FROM lessons l WHERE l.teacher_id = u.id) AS "requested!",
+                cover.lessons AS "covered!",
+                (
+                    SELECT COUNT(*)
+                    FROM substitution_offers o
+                    JOIN substitutions sub ON sub.id = o.substitution_id
+                    JOIN timetable_entries te ON te.id = sub.timetable_entry_id
+                    JOIN courses c ON c.id = te.course_id
+                    WHERE c.workspace_id = $1 AND o.teacher_id = u.id AND o.status = 'Declined'
+                        AND o.responded_at >= $2::date AND o.responded_at < $3::date + 1
+                ) AS "declined!",
+                cover.hours AS "cover_hours!",
+                m.contract_hours * ($3::date - $2::date + 1) / 7.0 AS contract_hours,
+                cover.hours / (m.contract_hours * ($3::date - $2::date + 1) / 7.0) AS cover_share
+            FROM workspace_members m
+            JOIN users u ON u.id = m.user_id
+            CROSS JOIN LATERAL (
+                SELECT COUNT(*) AS lessons, COALESCE(SUM(l.hours), 0) AS hours
+                FROM lessons l
+                WHERE l.substituting_teacher_id = u.id AND l.status = 'Accepted'
+            ) cover
+            WHERE m.workspace_id = $1 AND u.role = 'Teacher' AND ($4::uuid IS NULL OR u.id = $4)
+            ORDER BY u.username
+            "#,
+            workspace_id,
+            from,
+            to,
+            teacher_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(stats)
+    }
+}
@@ -1,7 +1,10 @@
-use sqlx::PgPool;
+use chrono::NaiveDate;
+use sqlx::{PgConnection, PgPool};
 use uuid::Uuid;
-#[allow(unused_imports)]
-use crate::models::{Substitution, SubstitutionStatus};
+use crate::models::{
+    CandidateFacts, ExpiredOffer, QualificationPreference, Substitution, SubstitutionLesson, SubstitutionOffer, SubstitutionOfferStatus,
+    SubstitutionStatus,
+};
 use crate::error::AppResult;
 
 #[derive(Clone)]
@@ -14,20 +17,25 @@ impl SubstitutionRepository {
         Self { pool }
     }
 
-    pub async fn create(&self, substitution: Substitution) -> AppResult<Substitution> {
+    pub async fn create(&self, conn: &mut PgConnection, substitution: Substitution) -> AppResult<Substitution> {
         sqlx::query!(
             r#"
-            INSERT INTO substitutions (id, timetable_entry_id, substituting_teacher_id, status, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6)
+            INSERT INTO substitutions (
+                id, timetable_entry_id, substituting_teacher_id, status, starts_on, ends_on, absence_id, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             "#,
             substitution.id,
             substitution.timetable_entry_id,
             substitution.substituting_teacher_id,
             substitution.status as SubstitutionStatus,
+            substitution.starts_on,
+            substitution.ends_on,
+            substitution.absence_id,
             substitution.created_at,
             substitution.updated_at
         )
-        .execute(&self.pool)
+        .execute(conn)
         .await?;
 
         Ok(substitution)
@@ -37,7 +45,8 @@ impl SubstitutionRepository {
         let substitution = sqlx::query_as!(
             Substitution,
             r#"
-            SELECT id, timetable_entry_id, substituting_teacher_id, status as "status: SubstitutionStatus", created_at, updated_at
+            SELECT id, timetable_entry_id, substituting_teacher_id, status as "status: SubstitutionStatus", starts_on, ends_on,
+                absence_id, created_at, updated_at
             FROM substitutions
             WHERE id = $1
             "#,
@@ -49,7 +58,7 @@ impl SubstitutionRepository {
         Ok(substitution)
     }
 
-    pub async fn update(&self, substitution: Substitution) -> AppResult<Substitution> {
+    pub async fn update(&self, conn: &mut PgConnection, substitution: Substitution) -> AppResult<Substitution> {
         sqlx::query!(
             r#"
             UPDATE substitutions
@@ -60,17 +69,546 @@ impl SubstitutionRepository {
             substitution.substituting_teacher_id,
             substitution.status as SubstitutionStatus
         )
-        .execute(&self.pool)
+        .execute(conn)
         .await?;
 
         Ok(substitution)
     }
 
+    /// Gives a pending substitution to the teacher in a single conditional
+    /// update, so that of several teachers accepting at once only one wins.
+    /// While the substitution has open offers only a teacher holding a sent,
+    /// unexpired offer may take it; otherwise any teacher but the lesson's
+    /// own, and never a teacher absent on one of its dates. Returns `None`
+    /// when the teacher may not take it (any more).
+    pub async fn accept(&self, conn: &mut PgConnection, workspace_id: Uuid, id: Uuid, teacher_id: Uuid) -> AppResult<Option<Substitution>> {
+        let substitution = sqlx::query_as!(
+            Substitution,
+            r#"
+            UPDATE substitutions sub
+            SET status = 'Accepted', substituting_teacher_id = $3, updated_at = NOW()
+            FROM timetable_entries te, courses c, time_slots ts
+            WHERE sub.id = $2 AND te.id = sub.timetable_entry_id AND c.id = te.course_id AND c.workspace_id = $1
+                AND ts.id = te.time_slot_id
+                AND sub.status = 'Pending' AND te.teacher_id <> $3
+                AND NOT teacher_absent($1, $3, sub.starts_on, sub.ends_on, ts.day_of_week)
+                AND (
+                    EXISTS (
+                        SELECT 1 FROM substitution_offers o
+                        WHERE o.substitution_id = sub.id AND o.teacher_id = $3
+                            AND o.status = 'Pending' AND o.expires_at > NOW()
+                    )
+                    OR NOT EXISTS (
+                        SELECT 1 FROM substitution_offers o
+                        WHERE o.substitution_id = sub.id AND o.status IN ('Waiting', 'Pending')
+                    )
+                )
+            RETURNING sub.id, sub.timetable_entry_id, sub.substituting_teacher_id, sub.status as "status: SubstitutionStatus",
+                sub.starts_on, sub.ends_on, sub.absence_id, sub.created_at, sub.updated_at
+            "#,
+            workspace_id,
+            id,
+            teacher_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(substitution)
+    }
+
+    /// Locks the substitution of the workspace and returns its status.
+    pub async fn lock_status(&self, conn: &mut PgConnection, workspace_id: Uuid, id: Uuid) -> AppResult<Option<SubstitutionStatus>> {
+        let status = sqlx::query_scalar!(
+            r#"
+            SELECT sub.status as "status: SubstitutionStatus"
+            FROM substitutions sub
+            JOIN timetable_entries te ON te.id = sub.timetable_entry_id
+            JOIN courses c ON c.id = te.course_id
+            WHERE sub.id = $2 AND c.workspace_id = $1
+            FOR UPDATE OF sub
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(status)
+    }
+
+    pub async fn create_offer(&self, conn: &mut PgConnection, offer: SubstitutionOffer) -> AppResult<SubstitutionOffer> {
+        let offer = sqlx::query_as!(
+            SubstitutionOffer,
+            r#"
+            INSERT INTO substitution_offers (
+                id, substitution_id, teacher_id, position, status, timeout_minutes, sent_at, expires_at, created_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id, substitution_id, teacher_id, position, status as "status: SubstitutionOfferStatus",
+                timeout_minutes, sent_at, expires_at, responded_at, created_at
+            "#,
+            offer.id,
+            offer.substitution_id,
+            offer.teacher_id,
+            offer.position,
+            offer.status as SubstitutionOfferStatus,
+            offer.timeout_minutes,
+            offer.sent_at,
+            offer.expires_at,
+            offer.created_at
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(offer)
+    }
+
+    pub async fn find_offers(&self, workspace_id: Uuid, substitution_id: Uuid) -> AppResult<Vec<SubstitutionOffer>> {
+        let offers = sqlx::query_as!(
+            SubstitutionOffer,
+            r#"
+            SELECT o.id, o.substitution_id, o.teacher_id, o.position, o.status as "status: SubstitutionOfferStatus",
+                o.timeout_minutes, o.sent_at, o.expires_at, o.responded_at, o.created_at
+            FROM substitution_offers o
+            JOIN substitutions sub ON sub.id = o.substitution_id
+            JOIN timetable_entries te ON te.id = sub.timetable_entry_id
+            JOIN courses c ON c.id = te.course_id
+            WHERE o.substitution_id = $2 AND c.workspace_id = $1
+            ORDER BY o.created_at, o.position
+            "#,
+            workspace_id,
+            substitution_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(offers)
+    }
+
+    /// Of the given users, those who may be offered the substitution:
+    /// teachers of the workspace other than the lesson's own who are not
+    /// absent on its dates.
+    pub async fn find_offerable(&self, conn: &mut PgConnection, workspace_id: Uuid, id: Uuid, teacher_ids: &[Uuid]) -> AppResult<Vec<Uuid>> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            SELECT u.id
+            FROM workspace_members m
+            JOIN users u ON u.id = m.user_id
+            JOIN substitutions sub ON sub.id = $2
+            JOIN timetable_entries te ON te.id = sub.timetable_entry_id
+            JOIN time_slots ts ON ts.id = te.time_slot_id
+            WHERE m.workspace_id = $1 AND u.role = 'Teacher' AND u.id = ANY($3)
+                AND u.id <> te.teacher_id
+                AND NOT teacher_absent($1, u.id, sub.starts_on, sub.ends_on, ts.day_of_week)
+            "#,
+            workspace_id,
+            id,
+            teacher_ids
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Marks the teacher's sent offer as accepted, if they had one.
+    pub async fn mark_offer_accepted(&self, conn: &mut PgConnection, substitution_id: Uuid, teacher_id: Uuid) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE substitution_offers
+            SET status = 'Accepted', responded_at = NOW()
+            WHERE substitution_id = $1 AND teacher_id = $2 AND status = 'Pending'
+            "#,
+            substitution_id,
+            teacher_id
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn decline_offer(&self, conn: &mut PgConnection, workspace_id: Uuid, substitution_id: Uuid, teacher_id: Uuid) -> AppResult<Option<SubstitutionOffer>> {
+        let offer = sqlx::query_as!(
+            SubstitutionOffer,
+            r#"
+            UPDATE substitution_offers o
+            SET status = 'Declined', responded_at = NOW()
+            FROM substitutions sub, timetable_entries te, courses c
+            WHERE o.substitution_id = $2 AND o.teacher_id = $3 AND o.status = 'Pending'
+                AND sub.id = o.substitution_id AND te.id = sub.timetable_entry_id AND c.id = te.course_id
+                AND c.workspace_id = $1
+            RETURNING o.id, o.substitution_id, o.teacher_id, o.position, o.status as "status: SubstitutionOfferStatus",
+                o.timeout_minutes, o.sent_at, o.expires_at, o.responded_at, o.created_at
+            "#,
+            workspace_id,
+            substitution_id,
+            teacher_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(offer)
+    }
+
+    /// Closes every open offer of the substitution and returns the teachers
+    /// whose offer had already been sent.
+    pub async fn withdraw_offers(&self, conn: &mut PgConnection, substitution_id: Uuid) -> AppResult<Vec<Uuid>> {
+        let teacher_ids = sqlx::query_scalar!(
+            r#"
+            WITH open AS (
+                SELECT id, status FROM substitution_offers
+                WHERE substitution_id = $1 AND status IN ('Waiting', 'Pending')
+                FOR UPDATE
+            ),
+            closed AS (
+                UPDATE substitution_offers o
+                SET status = 'Withdrawn', responded_at = NOW()
+                FROM open
+                WHERE o.id = open.id
+                RETURNING o.teacher_id, open.status AS sent_status
+            )
+            SELECT teacher_id FROM closed WHERE sent_status = 'Pending'
+            "#,
+            substitution_id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(teacher_ids)
+    }
+
+    /// Sends the next queued offer of a cascade, unless the substitution is
+    /// no longer pending or another offer is still out.
+    pub async fn send_next_offer(&self, conn: &mut PgConnection, substitution_id: Uuid) -> AppResult<Option<SubstitutionOffer>> {
+        let offer = sqlx::query_as!(
+            SubstitutionOffer,
+            r#"
+            UPDATE substitution_offers
+            SET status = 'Pending', sent_at = NOW(), expires_at = NOW() + make_interval(mins => timeout_minutes)
+            WHERE id = (
+                SELECT o.id
+                FROM substitution_offers o
+                JOIN substitutions sub ON sub.id = o.substitution_id
+                WHERE o.substitution_id = $1 AND o.status = 'Waiting' AND sub.status = 'Pending'
+                    AND NOT EXISTS (
+                        SELECT 1 FROM substitution_offers p
+                        WHERE p.substitution_id = $1 AND p.status = 'Pending'
+                    )
+                ORDER BY o.position
+                LIMIT 1
+                FOR UPDATE OF o
+            )
+            RETURNING id, substitution_id, teacher_id, position, status as "status: SubstitutionOfferStatus",
+                timeout_minutes, sent_at, expires_at, responded_at, created_at
+            "#,
+            substitution_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(offer)
+    }
+
+    pub async fn has_open_offers(&self, conn: &mut PgConnection, substitution_id: Uuid) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM substitution_offers
+                WHERE substitution_id = $1 AND status IN ('Waiting', 'Pending')
+            ) AS "exists!"
+            "#,
+            substitution_id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(exists)
+    }
+
+    /// Expires sent offers whose time is up. Rows locked by a concurrent
+    /// accept are skipped and picked up on the next run.
+    pub async fn expire_offers(&self, conn: &mut PgConnection) -> AppResult<Vec<ExpiredOffer>> {
+        let expired = sqlx::query_as!(
+            ExpiredOffer,
+            r#"
+            WITH due AS (
+                SELECT id FROM substitution_offers
+                WHERE status = 'Pending' AND expires_at <= NOW()
+                FOR UPDATE SKIP LOCKED
+            ),
+            expired AS (
+                UPDATE substitution_offers o
+                SET status = 'Expired'
+                FROM due
+                WHERE o.id = due.id
+                RETURNING o.substitution_id, o.teacher_id
+            )
+            SELECT c.workspace_id, expired.substitution_id, expired.teacher_id
+            FROM expired
+            JOIN substitutions sub ON sub.id = expired.substitution_id
+            JOIN timetable_entries te ON te.id = sub.timetable_entry_id
+            JOIN courses c ON c.id = te.course_id
+            "#
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(expired)
+    }
+
+    /// Admins of the workspace, who hear about offers nobody took.
+    pub async fn find_admins(&self, conn: &mut PgConnection, workspace_id: Uuid) -> AppResult<Vec<Uuid>> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            SELECT u.id
+            FROM workspace_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.workspace_id = $1 AND u.role = 'Admin'
+            "#,
+            workspace_id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Retracts the substitutions of an absence that nobody has accepted
+    /// yet and returns their ids.
+    pub async fn cancel_pending_for_absence(&self, conn: &mut PgConnection, absence_id: Uuid) -> AppResult<Vec<Uuid>> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            UPDATE substitutions
+            SET status = 'Cancelled', updated_at = NOW()
+            WHERE absence_id = $1 AND status = 'Pending'
+            RETURNING id
+            "#,
+            absence_id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// The weekday of a timetable entry of the workspace.
+    pub async fn find_entry_day(&self, conn: &mut PgConnection, workspace_id: Uuid, timetable_entry_id: Uuid) -> AppResult<Option<i32>> {
+        let day = sqlx::query_scalar!(
+            r#"
+            SELECT ts.day_of_week
+            FROM timetable_entries te
+            JOIN time_slots ts ON ts.id = te.time_slot_id
+            JOIN courses c ON c.id = te.course_id
+            WHERE te.id = $1 AND c.workspace_id = $2
+            "#,
+            timetable_entry_id,
+            workspace_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(day)
+    }
+
+    /// Whether a pending or accepted substitution already covers some of the
+    /// dates for this entry.
+    /// Locks a lesson's timetable entry until the transaction ends, so that
+    /// checking for overlapping substitutions and inserting one cannot
+    /// interleave with another request for the same lesson.
+    pub async fn lock_entry(&self, conn: &mut PgConnection, timetable_entry_id: Uuid) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            SELECT id FROM timetable_entries
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+            timetable_entry_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn has_overlapping(
+        &self,
+        conn: &mut PgConnection,
+        timetable_entry_id: Uuid,
+        starts_on: NaiveDate,
+        ends_on: NaiveDate,
+    ) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM substitutions
+                WHERE timetable_entry_id = $1 AND status IN ('Pending', 'Accepted')
+                    AND starts_on <= $3 AND ends_on >= $2
+            ) AS "exists!"
+            "#,
+            timetable_entry_id,
+            starts_on,
+            ends_on
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(exists)
+    }
+
+    pub async fn find_lesson(&self, conn: &mut PgConnection, id: Uuid) -> AppResult<Option<SubstitutionLesson>> {
+        let lesson = sqlx::query_as!(
+            SubstitutionLesson,
+            r#"
+            SELECT t.id as teacher_id, t.username as teacher_name, s.username as "substitute_name?",
+                c.name as course_name, ts.day_of_week, ts.start_time, ts.end_time, sub.starts_on, sub.ends_on
+            FROM substitutions sub
+            JOIN timetable_entries te ON te.id = sub.timetable_entry_id
+            JOIN users t ON t.id = te.teacher_id
+            JOIN courses c ON c.id = te.course_id
+            JOIN time_slots ts ON ts.id = te.time_slot_id
+            LEFT JOIN users s ON s.id = sub.substituting_teacher_id
+            WHERE sub.id = $1
+            "#,
+            id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(lesson)
+    }
+
+    /// Teachers of the workspace who could cover the substitution, i.e.
+    /// everyone but the teacher of the lesson and those absent on its dates.
+    pub async fn find_offer_recipients(&self, conn: &mut PgConnection, workspace_id: Uuid, id: Uuid) -> AppResult<Vec<Uuid>> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            SELECT u.id
+            FROM workspace_members m
+            JOIN users u ON u.id = m.user_id
+            JOIN substitutions sub ON sub.id = $2
+            JOIN timetable_entries te ON te.id = sub.timetable_entry_id
+            JOIN time_slots ts ON ts.id = te.time_slot_id
+            WHERE m.workspace_id = $1 AND u.role = 'Teacher'
+                AND u.id <> te.teacher_id
+                AND NOT teacher_absent($1, u.id, sub.starts_on, sub.ends_on, ts.day_of_week)
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Facts about every teacher of the workspace, other than the lesson's
+    /// own teacher and those absent on its dates, for ranking them as
+    /// substitutes.
+    pub async fn find_candidates(&self, workspace_id: Uuid, id: Uuid) -> AppResult<Vec<CandidateFacts>> {
+        let candidates = sqlx::query_as!(
+            CandidateFacts,
+            r#"
+            WITH lesson AS (
+                SELECT te.teacher_id, te.course_id, ts.day_of_week, ts.start_time, ts.end_time, sub.starts_on, sub.ends_on,
+                    COALESCE(c.department, owner.department) AS department
+                FROM substitutions sub
+                JOIN timetable_entries te ON te.id = sub.timetable_entry_id
+                JOIN time_slots ts ON ts.id = te.time_slot_id
+                JOIN courses c ON c.id = te.course_id
+                LEFT JOIN workspace_members owner ON owner.workspace_id = c.workspace_id AND owner.user_id = te.teacher_id
+                WHERE sub.id = $2 AND c.workspace_id = $1
+            )
+            SELECT u.id AS teacher_id, u.username AS teacher_name,
+                (
+                    EXISTS (
+                        SELECT 1
+                        FROM timetable_entries e
+                        JOIN time_slots s ON s.id = e.time_slot_id
+                        CROSS JOIN generate_series(lesson.starts_on, lesson.ends_on, INTERVAL '1 day') day
+                        WHERE e.teacher_id = u.id AND s.day_of_week = lesson.day_of_week
+                            AND EXTRACT(DOW FROM day)::int = lesson.day_of_week
+                            AND s.start_time < lesson.end_time AND s.end_time > lesson.start_time
+                            AND NOT EXISTS (
+                                SELECT 1 FROM lesson_exceptions x
+                                WHERE x.timetable_entry_id = e.id AND x.date = day::date
+                                    AND x.kind IN ('Cancellation', 'TeacherChange')
+                            )
+                    )
+                    OR EXISTS (
+                        SELECT 1
+                        FROM lesson_exceptions x
+                        WHERE x.workspace_id = $1 AND x.teacher_id = u.id AND x.kind IN ('ExtraLesson', 'TeacherChange')
+                            AND x.date BETWEEN lesson.starts_on AND lesson.ends_on
+                            AND EXTRACT(DOW FROM x.date)::int = lesson.day_of_week
+                            AND x.start_time < lesson.end_time AND x.end_time > lesson.start_time
+                    )
+                    OR EXISTS (
+                        SELECT 1
+                        FROM substitutions other
+                        JOIN timetable_entries e ON e.id = other.timetable_entry_id
+                        JOIN time_slots s ON s.id = e.time_slot_id
+                        WHERE other.substituting_teacher_id = u.id AND other.status = 'Accepted'
+                            AND other.starts_on <= lesson.ends_on AND other.ends_on >= lesson.starts_on
+                            AND s.day_of_week = lesson.day_of_week
+                            AND s.start_time < lesson.end_time AND s.end_time > lesson.start_time
+                    )
+                ) AS "is_busy!",
+                (
+                    EXISTS (SELECT 1 FROM availability a WHERE a.workspace_id = $1 AND a.teacher_id = u.id)
+                    OR EXISTS (
+                        SELECT 1 FROM availability_overrides o
+                        WHERE o.workspace_id = $1 AND o.teacher_id = u.id
+                            AND o.starts_on <= lesson.ends_on AND o.ends_on >= lesson.starts_on
+                    )
+                ) AS "has_availability!",
+                available.all_dates AS "is_available!",
+                available.all_dates AND COALESCE(covering.is_preferred, false) AS "is_preferred!",
+                (
+                    SELECT q.preference FROM teacher_qualifications q
+                    WHERE q.teacher_id = u.id AND q.course_id = lesson.course_id
+                ) AS "qualification: QualificationPreference",
+                EXISTS (
+                    SELECT 1 FROM teacher_qualifications q WHERE q.course_id = lesson.course_id
+                ) AS "course_has_qualifications!",
+                COALESCE(m.department = lesson.department, false) AS "same_department!"
+            FROM lesson
+            JOIN workspace_members m ON m.workspace_id = $1
+            JOIN users u ON u.id = m.user_id
+            LEFT JOIN LATERAL (
+                SELECT a.teacher_id, a.is_preferred
+                FROM availability a
+                WHERE a.workspace_id = $1 AND a.teacher_id = u.id AND a.day_of_week = lesson.day_of_week
+                    AND a.start_time <= lesson.start_time AND a.end_time >= lesson.end_time
+                ORDER BY a.is_preferred DESC
+                LIMIT 1
+            ) covering ON true
+            CROSS JOIN LATERAL (
+                SELECT NOT EXISTS (
+                    SELECT 1
+                    FROM generate_series(lesson.starts_on, lesson.ends_on, INTERVAL '1 day') day
+                    WHERE EXTRACT(DOW FROM day)::int = lesson.day_of_week
+                        AND NOT teacher_available($1, u.id, day::date, lesson.start_time, lesson.end_time)
+                ) AS all_dates
+            ) available
+            WHERE u.role = 'Teacher' AND u.id <> lesson.teacher_id
+                AND NOT teacher_absent($1, u.id, lesson.starts_on, lesson.ends_on, lesson.day_of_week)
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(candidates)
+    }
+
     pub async fn find_all(&self) -> AppResult<Vec<Substitution>> {
         let substitutions = sqlx::query_as!(
             Substitution,
             r#"
-            SELECT id, timetable_entry_id, substituting_teacher_id, status as "status: SubstitutionStatus", created_at, updated_at
+            SELECT id, timetable_entry_id, substituting_teacher_id, status as "status: SubstitutionStatus", starts_on, ends_on,
+                absence_id, created_at, updated_at
             FROM substitutions
             ORDER BY created_at DESC
             "#
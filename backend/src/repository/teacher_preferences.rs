@@ -0,0 +1,170 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::error::AppResult;
+use crate::models::{ScheduledLesson, TeacherPreference, TeacherPreferenceKind};
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+/// A lesson of a draft with the name of its teacher.
+pub struct DraftLesson {
+    pub teacher_name: String,
+    pub lesson: ScheduledLesson,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn create(&self, preference: TeacherPreference) -> AppResult<TeacherPreference> {
+        let preference = sqlx::query_as!(
+            TeacherPreference,
+            r#"
+            INSERT INTO teacher_preferences
+                (id, workspace_id, teacher_id, kind, weight, day_of_week, start_time, end_time, max_count, note, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            RETURNING id, workspace_id, teacher_id, kind as "kind: TeacherPreferenceKind", weight, day_of_week, start_time,
+                end_time, max_count, note, created_at, updated_at
+            "#,
+            preference.id,
+            preference.workspace_id,
+            preference.teacher_id,
+            preference.kind as TeacherPreferenceKind,
+            preference.weight,
+            preference.day_of_week,
+            preference.start_time,
+            preference.end_time,
+            preference.max_count,
+            preference.note,
+            preference.created_at,
+            preference.updated_at
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(preference)
+    }
+
+    pub async fn update(&self, preference: &TeacherPreference) -> AppResult<TeacherPreference> {
+        let preference = sqlx::query_as!(
+            TeacherPreference,
+            r#"
+            UPDATE teacher_preferences
+            SET kind = $2, weight = $3, day_of_week = $4, start_time = $5, end_time = $6, max_count = $7, note = $8
+            WHERE id = $1
+            RETURNING id, workspace_id, teacher_id, kind as "kind: TeacherPreferenceKind", weight, day_of_week, start_time,
+                end_time, max_count, note, created_at, updated_at
+            "#,
+            preference.id,
+            preference.kind as TeacherPreferenceKind,
+            preference.weight,
+            preference.day_of_week,
+            preference.start_time,
+            preference.end_time,
+            preference.max_count,
+            preference.note
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(preference)
+    }
+
+    pub async fn delete(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query!("DELETE FROM teacher_preferences WHERE id = $1", id)
+            .execute(&self.db_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_id(&self, workspace_id: Uuid, id: Uuid) -> AppResult<Option<TeacherPreference>> {
+        let preference = sqlx::query_as!(
+            TeacherPreference,
+            r#"
+            SELECT id, workspace_id, teacher_id, kind as "kind: TeacherPreferenceKind", weight, day_of_week, start_time,
+                end_time, max_count, note, created_at, updated_at
+            FROM teacher_preferences
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+            workspace_id,
+            id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(preference)
+    }
+
+    /// Preferences of one teacher or of all, strongest first.
+    pub async fn find_all(&self, workspace_id: Uuid, teacher_id: Option<Uuid>) -> AppResult<Vec<TeacherPreference>> {
+        let preferences = sqlx::query_as!(
+            TeacherPreference,
+            r#"
+            SELECT id, workspace_id, teacher_id, kind as "kind: TeacherPreferenceKind", weight, day_of_week, start_time,
+                end_time, max_count, note, created_at, updated_at
+            FROM teacher_preferences
+            WHERE workspace_id = $1 AND ($2::uuid IS NULL OR teacher_id = $2)
+            ORDER BY teacher_id, ABS(weight) DESC, created_at
+            "#,
+            workspace_id,
+            teacher_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(preferences)
+    }
+
+    pub async fn is_member(&self, workspace_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM workspace_members WHERE workspace_id = $1 AND user_id = $2
+            ) AS "exists!"
+            "#,
+            workspace_id,
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    /// The lessons of a draft with their times, by teacher.
+    pub async fn find_draft_lessons(&self, workspace_id: Uuid, draft_timetable_id: Uuid) -> AppResult<Vec<DraftLesson>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT de.teacher_id, u.username AS teacher_name, de.time_slot_id, ts.day_of_week, ts.start_time, ts.end_time
+            FROM draft_entries de
+            JOIN draft_timetables d ON d.id = de.draft_timetable_id
+            JOIN time_slots ts ON ts.id = de.time_slot_id
+            JOIN users u ON u.id = de.teacher_id
+            WHERE d.workspace_id = $1 AND d.id = $2
+            ORDER BY u.username, ts.day_of_week, ts.start_time
+            "#,
+            workspace_id,
+            draft_timetable_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| DraftLesson {
+                teacher_name: row.teacher_name,
+                lesson: ScheduledLesson {
+                    teacher_id: row.teacher_id,
+                    time_slot_id: row.time_slot_id,
+                    day_of_week: row.day_of_week,
+                    start_time: row.start_time,
+                    end_time: row.end_time,
+                },
+            })
+            .collect())
+    }
+}
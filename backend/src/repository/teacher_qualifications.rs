@@ -0,0 +1,144 @@
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+use crate::error::AppResult;
+use crate::models::{QualificationPreference, TeacherQualification};
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn begin(&self) -> AppResult<Transaction<'static, Postgres>> {
+        Ok(self.db_pool.begin().await?)
+    }
+
+    /// Records the qualification or changes its preference. Returns whether
+    /// it is new.
+    pub async fn set(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        teacher_id: Uuid,
+        course_id: Uuid,
+        preference: QualificationPreference,
+    ) -> AppResult<bool> {
+        let created = sqlx::query_scalar!(
+            r#"
+            INSERT INTO teacher_qualifications (workspace_id, teacher_id, course_id, preference)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (teacher_id, course_id) DO UPDATE SET preference = EXCLUDED.preference
+            RETURNING (xmax = 0) AS "created!"
+            "#,
+            workspace_id,
+            teacher_id,
+            course_id,
+            preference as QualificationPreference
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(created)
+    }
+
+    /// Returns whether there was such a qualification.
+    pub async fn delete(&self, workspace_id: Uuid, teacher_id: Uuid, course_id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM teacher_qualifications WHERE workspace_id = $1 AND teacher_id = $2 AND course_id = $3",
+            workspace_id,
+            teacher_id,
+            course_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn find_all(
+        &self,
+        workspace_id: Uuid,
+        teacher_id: Option<Uuid>,
+        course_id: Option<Uuid>,
+    ) -> AppResult<Vec<TeacherQualification>> {
+        let qualifications = sqlx::query_as!(
+            TeacherQualification,
+            r#"
+            SELECT workspace_id, teacher_id, course_id, preference as "preference: QualificationPreference", created_at, updated_at
+            FROM teacher_qualifications
+            WHERE workspace_id = $1
+                AND ($2::uuid IS NULL OR teacher_id = $2)
+                AND ($3::uuid IS NULL OR course_id = $3)
+            ORDER BY course_id, preference, teacher_id
+            "#,
+            workspace_id,
+            teacher_id,
+            course_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(qualifications)
+    }
+
+    pub async fn is_member(&self, workspace_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM workspace_members WHERE workspace_id = $1 AND user_id = $2
+            ) AS "exists!"
+            "#,
+            workspace_id,
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    pub async fn course_in_workspace(&self, workspace_id: Uuid, course_id: Uuid) -> AppResult<bool> {
+        let found = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM courses WHERE workspace_id = $1 AND id = $2) AS "found!""#,
+            workspace_id,
+            course_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(found)
+    }
+
+    pub async fn find_member_by_email(&self, conn: &mut PgConnection, workspace_id: Uuid, email: &str) -> AppResult<Option<Uuid>> {
+        let user_id = sqlx::query_scalar!(
+            r#"
+            SELECT u.id
+            FROM users u
+            JOIN workspace_members wm ON wm.user_id = u.id
+            WHERE wm.workspace_id = $1 AND lower(u.email) = lower($2)
+            "#,
+            workspace_id,
+            email
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    pub async fn find_course_by_code(&self, conn: &mut PgConnection, workspace_id: Uuid, code: &str) -> AppResult<Option<Uuid>> {
+        let course_id = sqlx::query_scalar!(
+            "SELECT id FROM courses WHERE workspace_id = $1 AND code = $2",
+            workspace_id,
+            code
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(course_id)
+    }
+}
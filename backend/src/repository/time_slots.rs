@@ -64,6 +64,23 @@ impl TimeSlotRepository {
         Ok(time_slots)
     }
 
+    pub async fn find_by_workspace(&self, workspace_id: Uuid) -> AppResult<Vec<TimeSlot>> {
+        let time_slots = sqlx::query_as!(
+            TimeSlot,
+            r#"
+            SELECT id, workspace_id, day_of_week, start_time, end_time, created_at, updated_at
+            FROM time_slots
+            WHERE workspace_id = $1
+            ORDER BY day_of_week ASC, start_time ASC
+            "#,
+            workspace_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(time_slots)
+    }
+
     pub async fn update(&self, time_slot: TimeSlot) -> AppResult<TimeSlot> {
         sqlx::query!(
             r#"
@@ -1,4 +1,4 @@
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 use uuid::Uuid;
 use crate::models::TimetableEntry;
 use crate::error::AppResult;
@@ -13,7 +13,7 @@ impl TimetableEntryRepository {
         Self { pool }
     }
 
-    pub async fn create(&self, entry: TimetableEntry) -> AppResult<TimetableEntry> {
+    pub async fn create(&self, conn: &mut PgConnection, entry: TimetableEntry) -> AppResult<TimetableEntry> {
         sqlx::query!(
             r#"
             INSERT INTO timetable_entries (id, course_id, room_id, time_slot_id, teacher_id, created_at, updated_at)
@@ -27,7 +27,7 @@ impl TimetableEntryRepository {
             entry.created_at,
             entry.updated_at
         )
-        .execute(&self.pool)
+        .execute(conn)
         .await?;
 
         Ok(entry)
@@ -64,7 +64,7 @@ impl TimetableEntryRepository {
         Ok(entries)
     }
 
-    pub async fn update(&self, entry: TimetableEntry) -> AppResult<TimetableEntry> {
+    pub async fn update(&self, conn: &mut PgConnection, entry: TimetableEntry) -> AppResult<TimetableEntry> {
         sqlx::query!(
             r#"
             UPDATE timetable_entries
@@ -77,13 +77,13 @@ impl TimetableEntryRepository {
             entry.time_slot_id,
             entry.teacher_id
         )
-        .execute(&self.pool)
+        .execute(conn)
         .await?;
 
         Ok(entry)
     }
 
-    pub async fn delete(&self, id: Uuid) -> AppResult<()> {
+    pub async fn delete(&self, conn: &mut PgConnection, id: Uuid) -> AppResult<()> {
         sqlx::query!(
             r#"
             DELETE FROM timetable_entries
@@ -91,7 +91,7 @@ impl TimetableEntryRepository {
             "#,
             id
         )
-        .execute(&self.pool)
+        .execute(conn)
         .await?;
 
         Ok(())
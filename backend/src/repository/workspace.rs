@@ -1,4 +1,4 @@
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 use uuid::Uuid;
 use crate::models::workspace::{Workspace, WorkspaceInvite, WorkspaceRole};
 use crate::error::AppResult;
@@ -16,12 +16,13 @@ impl WorkspaceRepository {
     pub async fn create(&self, workspace: Workspace) -> AppResult<Workspace> {
         sqlx::query!(
             r#"
-            INSERT INTO workspaces (id, name, domain_restriction, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5)
+            INSERT INTO workspaces (id, name, domain_restriction, room_capacity_tolerance, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
             "#,
             workspace.id,
             workspace.name,
             workspace.domain_restriction,
+            workspace.room_capacity_tolerance,
             workspace.created_at,
             workspace.updated_at
         )
@@ -35,7 +36,7 @@ impl WorkspaceRepository {
         let workspace = sqlx::query_as!(
             Workspace,
             r#"
-            SELECT id, name, domain_restriction, created_at, updated_at
+            SELECT id, name, domain_restriction, room_capacity_tolerance, created_at, updated_at
             FROM workspaces
             WHERE id = $1
             "#,
@@ -51,7 +52,7 @@ impl WorkspaceRepository {
         let workspaces = sqlx::query_as!(
             Workspace,
             r#"
-            SELECT w.id, w.name, w.domain_restriction, w.created_at, w.updated_at
+            SELECT w.id, w.name, w.domain_restriction, w.room_capacity_tolerance, w.created_at, w.updated_at
             FROM workspaces w
             JOIN workspace_members wm ON w.id = wm.workspace_id
             WHERE wm.user_id = $1
@@ -82,6 +83,60 @@ impl WorkspaceRepository {
         Ok(())
     }
 
+    /// Returns false if the user is not a member of the workspace.
+    pub async fn set_member_department(&self, workspace_id: Uuid, user_id: Uuid, department: Option<String>) -> AppResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE workspace_members
+            SET department = $3
+            WHERE workspace_id = $1 AND user_id = $2
+            "#,
+            workspace_id,
+            user_id,
+            department
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns false if the user is not a member of the workspace.
+    pub async fn set_member_contract_hours(&self, workspace_id: Uuid, user_id: Uuid, contract_hours: Option<f64>) -> AppResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE workspace_members
+            SET contract_hours = $3
+            WHERE workspace_id = $1 AND user_id = $2
+            "#,
+            workspace_id,
+            user_id,
+            contract_hours
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn set_room_capacity_tolerance(&self, id: Uuid, percent: i32) -> AppResult<Option<Workspace>> {
+        let workspace = sqlx::query_as!(
+            Workspace,
+            r#"
+            UPDATE workspaces
+            SET room_capacity_tolerance = $2
+            WHERE id = $1
+            RETURNING id, name, domain_restriction, room_capacity_tolerance, created_at, updated_at
+            "#,
+            id,
+            percent
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(workspace)
+    }
+
     pub async fn check_membership(&self, workspace_id: Uuid, user_id: Uuid) -> AppResult<Option<WorkspaceRole>> {
         let member = sqlx::query!(
             r#"
@@ -98,7 +153,7 @@ impl WorkspaceRepository {
         Ok(member.map(|m| m.role))
     }
 
-    pub async fn create_invite(&self, invite: WorkspaceInvite) -> AppResult<WorkspaceInvite> {
+    pub async fn create_invite(&self, conn: &mut PgConnection, invite: WorkspaceInvite) -> AppResult<WorkspaceInvite> {
         sqlx::query!(
             r#"
             INSERT INTO workspace_invites (token_hash, workspace_id, email, role, expires_at, created_by, created_at)
@@ -112,7 +167,7 @@ impl WorkspaceRepository {
             invite.created_by,
             invite.created_at
         )
-        .execute(&self.pool)
+        .execute(conn)
         .await?;
 
         Ok(invite)
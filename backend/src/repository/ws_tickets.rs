@@ -0,0 +1,54 @@
+use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use crate::error::AppResult;
+
+#[derive(Clone)]
+pub struct Repository {
+    db_pool: PgPool,
+}
+
+impl Repository {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    /// Stores a ticket, dropping those that have expired on the way.
+    pub async fn create(&self, ticket: &str, claims: &serde_json::Value, expires_at: DateTime<Utc>) -> AppResult<()> {
+        let mut tx = self.db_pool.begin().await?;
+
+        sqlx::query!("DELETE FROM ws_tickets WHERE expires_at <= NOW()")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO ws_tickets (ticket, claims, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            ticket,
+            claims,
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Removes a ticket and returns its claims if it has not expired. Of
+    /// two concurrent redemptions only one gets the row.
+    pub async fn take(&self, ticket: &str) -> AppResult<Option<serde_json::Value>> {
+        let row = sqlx::query!(
+            r#"
+            DELETE FROM ws_tickets
+            WHERE ticket = $1
+            RETURNING claims, expires_at > NOW() AS "valid!"
+            "#,
+            ticket
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(row.filter(|row| row.valid).map(|row| row.claims))
+    }
+}
@@ -3,6 +3,7 @@ use crate::error::{AppError, AppResult};
 use crate::models::magic_link::MagicLink;
 use crate::models::{User, UserRole};
 use crate::oidc::OidcClient;
+use crate::mail::EmailTemplate;
 use crate::repository::{AuthRepository, UserRepository};
 use argon2::{
     Argon2,
@@ -16,6 +17,7 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::service::workspace::WorkspaceService;
+use crate::service::OutboxService;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
@@ -33,6 +35,7 @@ pub struct AuthService {
     config: Arc<Config>,
     oidc_client: Arc<OidcClient>,
     workspace_service: Arc<WorkspaceService>,
+    outbox: Arc<OutboxService>,
 }
 
 impl AuthService {
@@ -42,6 +45,7 @@ impl AuthService {
         config: Arc<Config>,
         oidc_client: Arc<OidcClient>,
         workspace_service: Arc<WorkspaceService>,
+        outbox: Arc<OutboxService>,
     ) -> Self {
         Self {
             auth_repo,
@@ -49,6 +53,7 @@ impl AuthService {
             config,
             oidc_client,
             workspace_service,
+            outbox,
         }
     }
 
@@ -87,12 +92,21 @@ impl AuthService {
             created_at: Utc::now(),
         };
 
-        // Save the MagicLink to the database
-        self.auth_repo.create_magic_link(magic_link).await?;
-
-        // Simulate sending the email: log the raw token to the console
-        tracing::info!("MAGIC LINK TOKEN: {}", token);
-        println!("MAGIC LINK TOKEN: {}", token);
+        // Save the MagicLink and queue the email carrying the raw token
+        let mut tx = self.outbox.begin().await?;
+        self.auth_repo.create_magic_link(&mut tx, magic_link).await?;
+
+        let link = format!("{}/magic-link-callback?token={}", self.config.frontend_url.trim_end_matches('/'), token);
+        self.outbox
+            .send_email(
+                &mut tx,
+                None,
+                &user.email,
+                EmailTemplate::MagicLink,
+                serde_json::json!({ "link": link, "expires_in_minutes": 15 }),
+            )
+            .await?;
+        self.outbox.commit(tx).await?;
 
         Ok("Magic link sent to your email".to_string())
     }
@@ -1,9 +1,13 @@
 use crate::repository::AvailabilityRepository;
-use crate::error::AppResult;
-use crate::graphql::types::AvailabilityInput;
-use crate::models::availability::Availability;
+use crate::error::{AppError, AppResult};
+use crate::graphql::types::{AvailabilityInput, AvailabilityOverrideInput, AvailabilityWindowInput};
+use crate::models::availability::{Availability, AvailabilityOverride, AvailabilityOverrideKind, AvailabilityWindow};
+use sqlx::PgConnection;
 use uuid::Uuid;
-use chrono::Utc;
+use chrono::{Datelike, NaiveDate, NaiveTime, Utc};
+
+/// Longest period an override may span.
+const MAX_OVERRIDE_DAYS: i64 = 366;
 
 pub struct AvailabilityService {
     repo: AvailabilityRepository,
@@ -14,23 +18,473 @@ impl AvailabilityService {
         Self { repo }
     }
 
+    /// Adds a window to a teacher's weekly pattern, merging it with the
+    /// windows of the same preference it overlaps or touches.
     pub async fn submit_availability(&self, workspace_id: Uuid, input: AvailabilityInput) -> AppResult<Availability> {
-        let availability = Availability {
-            id: Uuid::new_v4(),
-            workspace_id,
-            teacher_id: input.teacher_id,
+        let window = AvailabilityWindowInput {
             day_of_week: input.day_of_week,
             start_time: input.start_time,
             end_time: input.end_time,
             is_preferred: input.is_preferred,
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
         };
+        validate(&window)?;
+
+        let mut tx = self.repo.begin().await?;
+        let saved = self.save_window(&mut tx, workspace_id, input.teacher_id, None, window).await?;
+        tx.commit().await?;
+
+        Ok(saved)
+    }
+
+    /// Changes one window; it is merged with its neighbours like a new one.
+    /// Teachers can only change their own windows.
+    pub async fn update_availability(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        user_id: Uuid,
+        is_admin: bool,
+        window: AvailabilityWindowInput,
+    ) -> AppResult<Availability> {
+        validate(&window)?;
+        let existing = self.find_own(workspace_id, id, user_id, is_admin).await?;
+
+        let mut tx = self.repo.begin().await?;
+        let saved = self.save_window(&mut tx, workspace_id, existing.teacher_id, Some(id), window).await?;
+        tx.commit().await?;
+
+        Ok(saved)
+    }
+
+    pub async fn delete_availability(&self, workspace_id: Uuid, id: Uuid, user_id: Uuid, is_admin: bool) -> AppResult<()> {
+        let existing = self.find_own(workspace_id, id, user_id, is_admin).await?;
+
+        let mut tx = self.repo.begin().await?;
+        self.lock_teacher(&mut tx, workspace_id, existing.teacher_id).await?;
+        self.repo.delete(&mut tx, &[existing.id]).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Replaces a teacher's whole weekly pattern in one go. The windows are
+    /// validated first and merged where they overlap or touch.
+    pub async fn set_weekly_availability(
+        &self,
+        workspace_id: Uuid,
+        teacher_id: Uuid,
+        windows: Vec<AvailabilityWindowInput>,
+    ) -> AppResult<Vec<Availability>> {
+        let mut normalized: Vec<AvailabilityWindowInput> = Vec::with_capacity(windows.len());
+        for window in windows {
+            validate(&window)?;
+            let (merged, mut absorbed) = merge(window, &normalized)?;
+            absorbed.sort_unstable();
+            for i in absorbed.into_iter().rev() {
+                normalized.remove(i);
+            }
+            normalized.push(merged);
+        }
+        normalized.sort_by_key(|w| (w.day_of_week, w.start_time));
+
+        let mut tx = self.repo.begin().await?;
+        self.lock_teacher(&mut tx, workspace_id, teacher_id).await?;
+        self.repo.delete_by_teacher(&mut tx, workspace_id, teacher_id).await?;
+
+        let mut saved = Vec::with_capacity(normalized.len());
+        for window in normalized {
+            saved.push(self.repo.create(&mut tx, new_availability(workspace_id, teacher_id, window)).await?);
+        }
+        tx.commit().await?;
+
+        Ok(saved)
+    }
+
+    /// When a teacher is available on a date: the weekly windows of its
+    /// weekday less any unavailability, plus extra availability. A teacher
+    /// without a weekly pattern is available all day, as in
+    /// `teacher_available`.
+    pub async fn get_availability(&self, workspace_id: Uuid, teacher_id: Uuid, date: NaiveDate) -> AppResult<Vec<AvailabilityWindow>> {
+        let weekly = self.repo.get_by_teacher_and_date(workspace_id, teacher_id, date).await?;
+        let overrides: Vec<AvailabilityOverride> = self
+            .repo
+            .find_overrides(workspace_id, Some(teacher_id), date, date)
+            .await?
+            .into_iter()
+            .filter(|o| o.day_of_week.is_none_or(|day| day == date.weekday().num_days_from_sunday() as i32))
+            .collect();
+
+        let cuts: Vec<(NaiveTime, NaiveTime)> = overrides
+            .iter()
+            .filter(|o| o.kind == AvailabilityOverrideKind::Unavailable)
+            .map(override_times)
+            .collect();
+
+        let mut windows: Vec<AvailabilityWindow> = Vec::new();
+        for a in &weekly {
+            for (start_time, end_time) in subtract((a.start_time, a.end_time), &cuts) {
+                windows.push(AvailabilityWindow { date, start_time, end_time, is_preferred: a.is_preferred, override_id: None });
+            }
+        }
+        if weekly.is_empty() && !self.repo.has_weekly_pattern(workspace_id, teacher_id).await? {
+            for (start_time, end_time) in subtract(whole_day(), &cuts) {
+                windows.push(AvailabilityWindow { date, start_time, end_time, is_preferred: false, override_id: None });
+            }
+        }
+
+        // Extra availability only fills what the pattern leaves open
+        for o in overrides.iter().filter(|o| o.kind == AvailabilityOverrideKind::Available) {
+            let mut taken = cuts.clone();
+            taken.extend(windows.iter().map(|w| (w.start_time, w.end_time)));
+            for (start_time, end_time) in subtract(override_times(o), &taken) {
+                windows.push(AvailabilityWindow { date, start_time, end_time, is_preferred: false, override_id: Some(o.id) });
+            }
+        }
+        windows.sort_by_key(|w| w.start_time);
+
+        Ok(windows)
+    }
+
+    pub async fn add_override(&self, workspace_id: Uuid, created_by: Uuid, input: AvailabilityOverrideInput) -> AppResult<AvailabilityOverride> {
+        let ends_on = input.ends_on.unwrap_or(input.starts_on);
+        if ends_on < input.starts_on {
+            return Err(AppError::BadRequest("The end date must not be before the start date".to_string()));
+        }
+        if (ends_on - input.starts_on).num_days() >= MAX_OVERRIDE_DAYS {
+            return Err(AppError::BadRequest(format!("An override can span at most {} days", MAX_OVERRIDE_DAYS)));
+        }
+        if input.day_of_week.is_some_and(|day| !(0..=6).contains(&day)) {
+            return Err(AppError::BadRequest("Day of week must be between 0 (Sunday) and 6 (Saturday)".to_string()));
+        }
+        match (input.start_time, input.end_time) {
+            (None, None) => {}
+            (Some(start_time), Some(end_time)) if start_time < end_time => {}
+            (Some(_), Some(_)) => {
+                return Err(AppError::BadRequest("An availability window must start before it ends".to_string()));
+            }
+            _ => return Err(AppError::BadRequest("Give both a start and an end time, or neither for the whole day".to_string())),
+        }
+
+        let mut tx = self.repo.begin().await?;
+        if !self.repo.lock_teacher(&mut tx, workspace_id, input.teacher_id).await? {
+            return Err(AppError::NotFound);
+        }
+
+        let created = self
+            .repo
+            .create_override(&mut tx, AvailabilityOverride {
+                id: Uuid::new_v4(),
+                workspace_id,
+                teacher_id: input.teacher_id,
+                kind: input.kind,
+                starts_on: input.starts_on,
+                ends_on,
+                day_of_week: input.day_of_week,
+                start_time: input.start_time,
+                end_time: input.end_time,
+                reason: input.reason,
+                created_by: Some(created_by),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .await?;
+        tx.commit().await?;
+
+        Ok(created)
+    }
+
+    pub async fn delete_override(&self, workspace_id: Uuid, id: Uuid, user_id: Uuid, is_admin: bool) -> AppResult<()> {
+        let item = self.repo.find_override(workspace_id, id).await?.ok_or(AppError::NotFound)?;
+        if !is_admin && item.teacher_id != user_id {
+            return Err(AppError::Forbidden("Teachers can only change their own availability".to_string()));
+        }
+        self.repo.delete_override(id).await
+    }
+
+    pub async fn get_overrides(
+        &self,
+        workspace_id: Uuid,
+        teacher_id: Option<Uuid>,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> AppResult<Vec<AvailabilityOverride>> {
+        self.repo.find_overrides(workspace_id, teacher_id, from, to).await
+    }
+
+    pub async fn get_weekly_availability(&self, workspace_id: Uuid, teacher_id: Uuid) -> AppResult<Vec<Availability>> {
+        let mut tx = self.repo.begin().await?;
+        self.repo.find_by_teacher(&mut tx, workspace_id, teacher_id).await
+    }
+
+    /// The weekly pattern a draft is checked against, frozen if an
+    /// availability round for its term has closed.
+    pub async fn get_draft_availability(&self, workspace_id: Uuid, draft_timetable_id: Uuid, teacher_id: Uuid) -> AppResult<Vec<Availability>> {
+        self.repo.find_for_draft(workspace_id, draft_timetable_id, teacher_id).await
+    }
+
+    /// Serialises changes to the teacher's weekly pattern and refuses them
+    /// once the teacher has submitted it for an open round.
+    async fn lock_teacher(&self, conn: &mut PgConnection, workspace_id: Uuid, teacher_id: Uuid) -> AppResult<()> {
+        if !self.repo.lock_teacher(&mut *conn, workspace_id, teacher_id).await? {
+            return Err(AppError::NotFound);
+        }
+        if self.repo.is_locked(conn, workspace_id, teacher_id).await? {
+            return Err(AppError::Conflict(
+                "This availability has been submitted and is locked until the round closes or a coordinator unlocks it".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn find_own(&self, workspace_id: Uuid, id: Uuid, user_id: Uuid, is_admin: bool) -> AppResult<Availability> {
+        let availability = self.repo.find_by_id(workspace_id, id).await?.ok_or(AppError::NotFound)?;
+        if !is_admin && availability.teacher_id != user_id {
+            return Err(AppError::Forbidden("Teachers can only change their own availability".to_string()));
+        }
+        Ok(availability)
+    }
+
+    /// Stores a window in place of `replacing`, or as a new one, and drops
+    /// the windows of the same day it absorbs.
+    async fn save_window(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        teacher_id: Uuid,
+        replacing: Option<Uuid>,
+        window: AvailabilityWindowInput,
+    ) -> AppResult<Availability> {
+        self.lock_teacher(&mut *conn, workspace_id, teacher_id).await?;
+
+        let others: Vec<Availability> = self
+            .repo
+            .find_by_teacher(&mut *conn, workspace_id, teacher_id)
+            .await?
+            .into_iter()
+            .filter(|a| Some(a.id) != replacing)
+            .collect();
+        let windows: Vec<AvailabilityWindowInput> = others.iter().map(to_window).collect();
+
+        let (merged, absorbed) = merge(window, &windows)?;
+        let absorbed: Vec<Uuid> = absorbed.into_iter().map(|i| others[i].id).collect();
+        self.repo.delete(&mut *conn, &absorbed).await?;
+
+        let mut availability = new_availability(workspace_id, teacher_id, merged);
+        match replacing {
+            Some(id) => {
+                availability.id = id;
+                self.repo.update(conn, &availability).await
+            }
+            None => self.repo.create(conn, availability).await,
+        }
+    }
+}
 
-        self.repo.create(availability).await
+fn validate(window: &AvailabilityWindowInput) -> AppResult<()> {
+    if !(0..=6).contains(&window.day_of_week) {
+        return Err(AppError::BadRequest("Day of week must be between 0 (Sunday) and 6 (Saturday)".to_string()));
     }
+    if window.start_time >= window.end_time {
+        return Err(AppError::BadRequest("An availability window must start before it ends".to_string()));
+    }
+    Ok(())
+}
+
+/// Grows `window` over the windows of `others` on the same day with the
+/// same preference that overlap or touch it, returning the merged window
+/// and the indexes of those it absorbed. Windows with a different
+/// preference may touch it but not overlap.
+fn merge(mut window: AvailabilityWindowInput, others: &[AvailabilityWindowInput]) -> AppResult<(AvailabilityWindowInput, Vec<usize>)> {
+    let mut absorbed = Vec::new();
+
+    // Growing the window can bring it up to further windows
+    loop {
+        let mut grown = false;
+        for (i, other) in others.iter().enumerate() {
+            if other.day_of_week != window.day_of_week || absorbed.contains(&i) {
+                continue;
+            }
+            if other.is_preferred != window.is_preferred {
+                if other.start_time < window.end_time && window.start_time < other.end_time {
+                    return Err(AppError::BadRequest(
+                        "Preferred and non-preferred availability must not overlap".to_string(),
+                    ));
+                }
+                continue;
+            }
+            if other.start_time <= window.end_time && window.start_time <= other.end_time {
+                window.start_time = window.start_time.min(other.start_time);
+                window.end_time = window.end_time.max(other.end_time);
+                absorbed.push(i);
+                grown = true;
+            }
+        }
+        if !grown {
+            return Ok((window, absorbed));
+        }
+    }
+}
+
+fn to_window(availability: &Availability) -> AvailabilityWindowInput {
+    AvailabilityWindowInput {
+        day_of_week: availability.day_of_week,
+        start_time: availability.start_time,
+        end_time: availability.end_time,
+        is_preferred: availability.is_preferred,
+    }
+}
+
+fn new_availability(workspace_id: Uuid, teacher_id: Uuid, window: AvailabilityWindowInput) -> Availability {
+    Availability {
+        id: Uuid::new_v4(),
+        workspace_id,
+        teacher_id,
+        day_of_week: window.day_of_week,
+        start_time: window.start_time,
+        end_time: window.end_time,
+        is_preferred: window.is_preferred,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    }
+}
+
+/// The times an override covers, the whole day when it has none.
+fn override_times(item: &AvailabilityOverride) -> (NaiveTime, NaiveTime) {
+    match (item.start_time, item.end_time) {
+        (Some(start_time), Some(end_time)) => (start_time, end_time),
+        _ => whole_day(),
+    }
+}
+
+/// The times an override without times, or a teacher without a weekly
+/// pattern, covers.
+fn whole_day() -> (NaiveTime, NaiveTime) {
+    (NaiveTime::MIN, NaiveTime::from_hms_opt(23, 59, 59).unwrap_or(NaiveTime::MIN))
+}
+
+/// What remains of a window after taking out the given times.
+fn subtract(window: (NaiveTime, NaiveTime), cuts: &[(NaiveTime, NaiveTime)]) -> Vec<(NaiveTime, NaiveTime)> {
+    let mut parts = vec![window];
+    for &(cut_start, cut_end) in cuts {
+        parts = parts
+            .into_iter()
+            .flat_map(|(start, end)| {
+                if cut_end <= start || cut_start >= end {
+                    return vec![(start, end)];
+                }
+                let mut rest = Vec::new();
+                if start < cut_start {
+                    rest.push((start, cut_start));
+                }
+                if cut_end < end {
+                    rest.push((cut_end, end));
+                }
+                rest
+            })
+            .collect();
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_support;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn window(day_of_week: i32, start: NaiveTime, end: NaiveTime, is_preferred: bool) -> AvailabilityWindowInput {
+        AvailabilityWindowInput { day_of_week, start_time: start, end_time: end, is_preferred }
+    }
+
+    #[test]
+    fn merge_joins_adjacent_and_overlapping_windows() {
+        let others = [
+            window(1, time(10, 0), time(11, 0), false),
+            window(1, time(10, 30), time(12, 0), false),
+            window(2, time(8, 0), time(9, 0), false),
+        ];
+        let (merged, absorbed) = merge(window(1, time(8, 0), time(10, 0), false), &others).unwrap();
+
+        assert_eq!((merged.start_time, merged.end_time), (time(8, 0), time(12, 0)));
+        assert_eq!(absorbed, vec![0, 1]);
+    }
+
+    #[test]
+    fn merge_follows_windows_reached_by_growing() {
+        // The first window only touches once the second has been absorbed
+        let others = [window(1, time(12, 0), time(13, 0), false), window(1, time(10, 0), time(12, 0), false)];
+        let (merged, absorbed) = merge(window(1, time(9, 0), time(10, 0), false), &others).unwrap();
+
+        assert_eq!((merged.start_time, merged.end_time), (time(9, 0), time(13, 0)));
+        assert_eq!(absorbed, vec![1, 0]);
+    }
+
+    #[test]
+    fn merge_keeps_apart_windows_with_a_gap() {
+        let others = [window(1, time(10, 1), time(11, 0), false)];
+        let (merged, absorbed) = merge(window(1, time(9, 0), time(10, 0), false), &others).unwrap();
+
+        assert_eq!((merged.start_time, merged.end_time), (time(9, 0), time(10, 0)));
+        assert!(absorbed.is_empty());
+    }
+
+    #[test]
+    fn merge_lets_other_preferences_touch_but_not_overlap() {
+        let adjacent = [window(1, time(10, 0), time(11, 0), true)];
+        let (merged, absorbed) = merge(window(1, time(9, 0), time(10, 0), false), &adjacent).unwrap();
+        assert_eq!((merged.start_time, merged.end_time), (time(9, 0), time(10, 0)));
+        assert!(absorbed.is_empty());
+
+        let overlapping = [window(1, time(9, 30), time(11, 0), true)];
+        assert!(merge(window(1, time(9, 0), time(10, 0), false), &overlapping).is_err());
+    }
+
+    #[test]
+    fn subtract_leaves_windows_only_touching_a_cut_whole() {
+        let parts = subtract((time(9, 0), time(12, 0)), &[(time(8, 0), time(9, 0)), (time(12, 0), time(13, 0))]);
+        assert_eq!(parts, vec![(time(9, 0), time(12, 0))]);
+    }
+
+    #[test]
+    fn subtract_splits_and_trims_overlapping_windows() {
+        let parts = subtract((time(9, 0), time(12, 0)), &[(time(10, 0), time(10, 30)), (time(11, 30), time(13, 0))]);
+        assert_eq!(parts, vec![(time(9, 0), time(10, 0)), (time(10, 30), time(11, 30))]);
+
+        assert!(subtract((time(9, 0), time(12, 0)), &[(time(8, 0), time(12, 0))]).is_empty());
+    }
+
+    #[sqlx::test]
+    async fn teachers_without_a_weekly_pattern_are_available_all_day(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let teacher_id = test_support::teacher(&pool, workspace_id).await;
+        let service = test_support::availability_service(&pool);
+        let monday = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let lunch = AvailabilityOverrideInput {
+            teacher_id,
+            kind: AvailabilityOverrideKind::Unavailable,
+            starts_on: monday,
+            ends_on: None,
+            day_of_week: None,
+            start_time: Some(time(12, 0)),
+            end_time: Some(time(13, 0)),
+            reason: None,
+        };
+        service.add_override(workspace_id, teacher_id, lunch).await.unwrap();
+
+        let windows = service.get_availability(workspace_id, teacher_id, monday).await.unwrap();
+        let times: Vec<_> = windows.iter().map(|w| (w.start_time, w.end_time)).collect();
+        assert_eq!(times, vec![(NaiveTime::MIN, time(12, 0)), (time(13, 0), whole_day().1)]);
 
-    pub async fn get_availability(&self, teacher_id: Uuid, date: chrono::NaiveDate) -> AppResult<Vec<Availability>> {
-        self.repo.get_by_teacher_and_date(teacher_id, date).await
+        // With a pattern, only its days count
+        service
+            .set_weekly_availability(workspace_id, teacher_id, vec![window(2, time(8, 0), time(12, 0), false)])
+            .await
+            .unwrap();
+        assert!(service.get_availability(workspace_id, teacher_id, monday).await.unwrap().is_empty());
     }
 }
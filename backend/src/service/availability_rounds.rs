@@ -0,0 +1,284 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgConnection;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{AvailabilityRound, AvailabilityRoundStatus, AvailabilitySubmission, NotificationType};
+use crate::repository::{AvailabilityRepository, AvailabilityRoundRepository};
+use crate::service::notifications::NewNotification;
+use crate::service::{NotificationService, OutboxService};
+
+/// Default lead time of the automatic reminder.
+const DEFAULT_REMIND_HOURS_BEFORE: i32 = 48;
+/// Longest lead time a reminder may have, 30 days.
+const MAX_REMIND_HOURS_BEFORE: i32 = 720;
+/// How often due reminders and deadlines are checked.
+const ROUND_CHECK_INTERVAL: StdDuration = StdDuration::from_secs(15 * 60);
+
+/// Availability collection: coordinators open a round for a term, teachers
+/// submit and thereby lock their weekly availability, and closing the
+/// round freezes everyone's availability for drafting.
+pub struct AvailabilityRoundService {
+    repo: AvailabilityRoundRepository,
+    availability_repo: AvailabilityRepository,
+    outbox: Arc<OutboxService>,
+    notifications: Arc<NotificationService>,
+}
+
+impl AvailabilityRoundService {
+    pub fn new(
+        repo: AvailabilityRoundRepository,
+        availability_repo: AvailabilityRepository,
+        outbox: Arc<OutboxService>,
+        notifications: Arc<NotificationService>,
+    ) -> Self {
+        Self {
+            repo,
+            availability_repo,
+            outbox,
+            notifications,
+        }
+    }
+
+    /// Opens a round and asks every teacher to submit their availability.
+    pub async fn open_round(
+        &self,
+        workspace_id: Uuid,
+        created_by: Uuid,
+        term: String,
+        year: i32,
+        deadline: DateTime<Utc>,
+        remind_hours_before: Option<i32>,
+    ) -> AppResult<AvailabilityRound> {
+        let term = term.trim().to_string();
+        if term.is_empty() {
+            return Err(AppError::BadRequest("The term must not be empty".to_string()));
+        }
+        if deadline <= Utc::now() {
+            return Err(AppError::BadRequest("The deadline must be in the future".to_string()));
+        }
+        let remind_hours_before = remind_hours_before.unwrap_or(DEFAULT_REMIND_HOURS_BEFORE);
+        if !(1..=MAX_REMIND_HOURS_BEFORE).contains(&remind_hours_before) {
+            return Err(AppError::BadRequest(format!(
+                "Reminders go out between 1 and {} hours before the deadline",
+                MAX_REMIND_HOURS_BEFORE
+            )));
+        }
+
+        let mut tx = self.outbox.begin().await?;
+        if self.repo.is_open_for(&mut tx, workspace_id, &term, year).await? {
+            return Err(AppError::Conflict(format!("An availability round for {} {} is already open", term, year)));
+        }
+        let round = self.repo.create(&mut tx, AvailabilityRound {
+            id: Uuid::new_v4(),
+            workspace_id,
+            term,
+            year,
+            deadline,
+            remind_hours_before,
+            status: AvailabilityRoundStatus::Open,
+            reminded_at: None,
+            closed_at: None,
+            created_by: Some(created_by),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }).await?;
+
+        let teachers = self.repo.find_submissions(&mut tx, workspace_id, round.id, None).await?;
+        for teacher in &teachers {
+            self.notify(
+                &mut tx,
+                &round,
+                teacher.teacher_id,
+                format!("Submit your availability for {} {}", round.term, round.year),
+            ).await?;
+        }
+        self.outbox.commit(tx).await?;
+
+        Ok(round)
+    }
+
+    /// Submits the teacher's current weekly availability for the round,
+    /// which locks it until the round closes.
+    pub async fn submit(&self, workspace_id: Uuid, round_id: Uuid, teacher_id: Uuid) -> AppResult<AvailabilitySubmission> {
+        let mut tx = self.outbox.begin().await?;
+        let round = self.repo.lock(&mut tx, workspace_id, round_id).await?.ok_or(AppError::NotFound)?;
+        if round.status != AvailabilityRoundStatus::Open {
+            return Err(AppError::Conflict("The availability round is closed".to_string()));
+        }
+        if round.deadline <= Utc::now() {
+            return Err(AppError::Conflict("The deadline of the availability round has passed".to_string()));
+        }
+        // Waits for changes to the teacher's availability in flight
+        if !self.availability_repo.lock_teacher(&mut tx, workspace_id, teacher_id).await? {
+            return Err(AppError::NotFound);
+        }
+        self.repo.submit(&mut tx, round_id, teacher_id).await?;
+
+        let submission = self.repo.find_submissions(&mut tx, workspace_id, round_id, Some(true)).await?
+            .into_iter()
+            .find(|s| s.teacher_id == teacher_id)
+            .ok_or(AppError::NotFound)?;
+        self.outbox.commit(tx).await?;
+
+        Ok(submission)
+    }
+
+    /// Lets a teacher change their availability again while the round is
+    /// open; they have to submit it anew.
+    pub async fn unlock(&self, workspace_id: Uuid, round_id: Uuid, teacher_id: Uuid) -> AppResult<()> {
+        let mut tx = self.outbox.begin().await?;
+        let round = self.repo.lock(&mut tx, workspace_id, round_id).await?.ok_or(AppError::NotFound)?;
+        if round.status != AvailabilityRoundStatus::Open {
+            return Err(AppError::Conflict("The availability round is closed".to_string()));
+        }
+        if !self.repo.unlock(&mut tx, round_id, teacher_id).await? {
+            return Err(AppError::NotFound);
+        }
+        self.notify(
+            &mut tx,
+            &round,
+            teacher_id,
+            format!("Your availability for {} {} has been unlocked; submit it again when done", round.term, round.year),
+        ).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(())
+    }
+
+    /// Closes the round ahead of or at its deadline and freezes the
+    /// availability of every teacher.
+    pub async fn close(&self, workspace_id: Uuid, round_id: Uuid) -> AppResult<AvailabilityRound> {
+        let mut tx = self.outbox.begin().await?;
+        let round = self.repo.lock(&mut tx, workspace_id, round_id).await?.ok_or(AppError::NotFound)?;
+        if round.status != AvailabilityRoundStatus::Open {
+            return Err(AppError::Conflict("The availability round is already closed".to_string()));
+        }
+        let round = self.repo.close(&mut tx, &round).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(round)
+    }
+
+    /// Reminds the teachers who have not submitted yet; returns how many
+    /// were reminded.
+    pub async fn remind(&self, workspace_id: Uuid, round_id: Uuid) -> AppResult<usize> {
+        let mut tx = self.outbox.begin().await?;
+        let round = self.repo.lock(&mut tx, workspace_id, round_id).await?.ok_or(AppError::NotFound)?;
+        if round.status != AvailabilityRoundStatus::Open {
+            return Err(AppError::Conflict("The availability round is closed".to_string()));
+        }
+        let reminded = self.send_reminders(&mut tx, &round).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(reminded)
+    }
+
+    pub async fn get_rounds(&self, workspace_id: Uuid, status: Option<AvailabilityRoundStatus>) -> AppResult<Vec<AvailabilityRound>> {
+        self.repo.find_all(workspace_id, status).await
+    }
+
+    /// Every teacher's submission for the round, or only those who have or
+    /// have not submitted.
+    pub async fn get_submissions(
+        &self,
+        workspace_id: Uuid,
+        round_id: Uuid,
+        submitted: Option<bool>,
+    ) -> AppResult<Vec<AvailabilitySubmission>> {
+        if self.repo.find(workspace_id, round_id).await?.is_none() {
+            return Err(AppError::NotFound);
+        }
+        let mut tx = self.outbox.begin().await?;
+        self.repo.find_submissions(&mut tx, workspace_id, round_id, submitted).await
+    }
+
+    /// Sends the reminders that have come due and closes the rounds whose
+    /// deadline has passed. Returns how many rounds were reminded and
+    /// closed.
+    pub async fn process_due_rounds(&self) -> AppResult<(usize, usize)> {
+        let now = Utc::now();
+
+        let mut reminded = 0;
+        for round in self.repo.find_due_reminders(now).await? {
+            let mut tx = self.outbox.begin().await?;
+            // Skip rounds handled elsewhere meanwhile
+            match self.repo.lock(&mut tx, round.workspace_id, round.id).await? {
+                Some(round) if round.status == AvailabilityRoundStatus::Open && round.reminded_at.is_none() => {
+                    self.send_reminders(&mut tx, &round).await?;
+                    self.outbox.commit(tx).await?;
+                    reminded += 1;
+                }
+                _ => continue,
+            }
+        }
+
+        let mut closed = 0;
+        for round in self.repo.find_overdue(now).await? {
+            let mut tx = self.outbox.begin().await?;
+            match self.repo.lock(&mut tx, round.workspace_id, round.id).await? {
+                Some(round) if round.status == AvailabilityRoundStatus::Open => {
+                    self.repo.close(&mut tx, &round).await?;
+                    self.outbox.commit(tx).await?;
+                    closed += 1;
+                }
+                _ => continue,
+            }
+        }
+
+        Ok((reminded, closed))
+    }
+
+    async fn send_reminders(&self, conn: &mut PgConnection, round: &AvailabilityRound) -> AppResult<usize> {
+        let missing = self.repo.find_submissions(&mut *conn, round.workspace_id, round.id, Some(false)).await?;
+        let left = round.deadline - Utc::now();
+        let title = if left < Duration::hours(1) {
+            format!("Your availability for {} {} is due within the hour", round.term, round.year)
+        } else {
+            format!("Your availability for {} {} is due in {} hours", round.term, round.year, left.num_hours())
+        };
+        for teacher in &missing {
+            self.notify(&mut *conn, round, teacher.teacher_id, title.clone()).await?;
+        }
+        self.repo.mark_reminded(conn, round.id).await?;
+
+        Ok(missing.len())
+    }
+
+    async fn notify(&self, conn: &mut PgConnection, round: &AvailabilityRound, user_id: Uuid, title: String) -> AppResult<()> {
+        self.notifications
+            .notify(conn, round.workspace_id, NewNotification {
+                user_id,
+                notification_type: NotificationType::AvailabilityReminder,
+                title,
+                body: Some(format!("The deadline is {}.", round.deadline.format("%Y-%m-%d %H:%M UTC"))),
+                entity_id: Some(round.id),
+                link: Some(format!("/availability-rounds/{}", round.id)),
+                email: None,
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Periodically sends due reminders and closes rounds past their deadline.
+pub fn spawn_round_task(service: Arc<AvailabilityRoundService>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ROUND_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            match service.process_due_rounds().await {
+                Ok((0, 0)) => {}
+                Ok((reminded, closed)) => tracing::info!(
+                    "Sent reminders for {} availability rounds and closed {}",
+                    reminded,
+                    closed
+                ),
+                Err(e) => tracing::error!("Failed to process availability rounds: {:?}", e),
+            }
+        }
+    });
+}
@@ -1,16 +1,25 @@
-use crate::repository::{ConflictRepository, TimeSlotRepository};
-use crate::error::AppResult;
-use crate::models::conflicts::{Conflict, ConflictStatus};
-use crate::service::{DraftEntryService, AvailabilityService};
+use crate::repository::{ConflictRepository, StudentGroupRepository, TeacherPreferenceRepository, TimeSlotRepository};
+use crate::error::{AppError, AppResult};
+use crate::models::conflicts::{Conflict, ConflictKind, ConflictStatus, LessonSize, RoomSuggestion};
+use crate::models::LessonFeature;
+use crate::models::TeacherPreferenceScore;
+use crate::service::{DraftEntryService, AvailabilityService, OutboxService};
+use crate::service::teacher_preferences::score_lessons;
+use crate::service::student_groups;
+use crate::ws::{self, Topic, WebSocketMessage};
 use uuid::Uuid;
 use std::sync::Arc;
 use chrono::Utc;
+use serde_json::json;
 
 pub struct ConflictService {
     repo: ConflictRepository,
     draft_entry_service: Arc<DraftEntryService>,
     availability_service: Arc<AvailabilityService>,
     time_slot_repo: TimeSlotRepository,
+    preference_repo: TeacherPreferenceRepository,
+    group_repo: StudentGroupRepository,
+    outbox: Arc<OutboxService>,
 }
 
 impl ConflictService {
@@ -19,18 +28,24 @@ impl ConflictService {
         draft_entry_service: Arc<DraftEntryService>,
         availability_service: Arc<AvailabilityService>,
         time_slot_repo: TimeSlotRepository,
+        preference_repo: TeacherPreferenceRepository,
+        group_repo: StudentGroupRepository,
+        outbox: Arc<OutboxService>,
     ) -> Self {
         Self {
             repo,
             draft_entry_service,
             availability_service,
             time_slot_repo,
+            preference_repo,
+            group_repo,
+            outbox,
         }
     }
 
-    pub async fn get_conflicts(&self, draft_timetable_id: Uuid) -> AppResult<Vec<Conflict>> {
+    pub async fn get_conflicts(&self, workspace_id: Uuid, draft_timetable_id: Uuid) -> AppResult<Vec<Conflict>> {
         let entries = self.draft_entry_service.get_entries_for_draft(draft_timetable_id).await?;
-        let mut conflicts = Vec::new();
+        let mut detected = Vec::new();
 
         // 1. Teacher Double-Booking
         for (i, entry1) in entries.iter().enumerate() {
@@ -43,15 +58,17 @@ impl ConflictService {
                     let conflict = Conflict {
                         id: Uuid::new_v4(),
                         draft_timetable_id,
+                        kind: ConflictKind::TeacherDoubleBooking,
                         description,
                         teacher_id: Some(entry1.teacher_id),
                         room_id: None,
                         time_slot_id: Some(entry1.time_slot_id),
+                        student_group_id: None,
                         status: ConflictStatus::Open,
                         created_at: Utc::now(),
                         updated_at: Utc::now(),
                     };
-                    conflicts.push(self.repo.create(conflict).await?);
+                    detected.push(conflict);
                 }
             }
         }
@@ -67,38 +84,155 @@ impl ConflictService {
                     let conflict = Conflict {
                         id: Uuid::new_v4(),
                         draft_timetable_id,
+                        kind: ConflictKind::RoomDoubleBooking,
                         description,
                         teacher_id: None,
                         room_id: Some(entry1.room_id),
                         time_slot_id: Some(entry1.time_slot_id),
+                        student_group_id: None,
                         status: ConflictStatus::Open,
                         created_at: Utc::now(),
                         updated_at: Utc::now(),
                     };
-                    conflicts.push(self.repo.create(conflict).await?);
+                    detected.push(conflict);
                 }
             }
         }
 
-        // 3. Availability Check (Optional Bonus)
+        // 3. Student Group Double-Booking, counting a year group or class
+        // as booked whenever a group within it is
+        let groups = self.group_repo.find_all(workspace_id).await?;
+        let entry_groups = self.group_repo.find_draft_entry_groups(draft_timetable_id).await?;
+        let time_slots = self.time_slot_repo.find_by_workspace(workspace_id).await?;
+        let slot = |id: Uuid| time_slots.iter().find(|s| s.id == id);
+        for (i, entry1) in entries.iter().enumerate() {
+            for entry2 in entries.iter().skip(i + 1) {
+                let (Some(slot1), Some(slot2)) = (slot(entry1.time_slot_id), slot(entry2.time_slot_id)) else {
+                    continue;
+                };
+                if slot1.day_of_week != slot2.day_of_week || slot1.start_time >= slot2.end_time || slot2.start_time >= slot1.end_time {
+                    continue;
+                }
+
+                // Report the innermost group that is booked twice
+                let mut clashing: Vec<Uuid> = Vec::new();
+                for group1 in entry_groups.iter().filter(|g| g.draft_entry_id == entry1.id) {
+                    for group2 in entry_groups.iter().filter(|g| g.draft_entry_id == entry2.id) {
+                        let (a, b) = (group1.student_group_id, group2.student_group_id);
+                        let group_id = if student_groups::ancestors(&groups, a).contains(&b) {
+                            a
+                        } else if student_groups::ancestors(&groups, b).contains(&a) {
+                            b
+                        } else {
+                            continue;
+                        };
+                        if !clashing.contains(&group_id) {
+                            clashing.push(group_id);
+                        }
+                    }
+                }
+
+                for group_id in clashing {
+                    let group_name = groups.iter().find(|g| g.id == group_id).map_or("", |g| g.name.as_str());
+                    let description = format!(
+                        "Group {} is double-booked for courses {} and {} at overlapping times",
+                        group_name, entry1.course_id, entry2.course_id
+                    );
+                    let conflict = Conflict {
+                        id: Uuid::new_v4(),
+                        draft_timetable_id,
+                        kind: ConflictKind::GroupDoubleBooking,
+                        description,
+                        teacher_id: None,
+                        room_id: None,
+                        time_slot_id: Some(entry1.time_slot_id),
+                        student_group_id: Some(group_id),
+                        status: ConflictStatus::Open,
+                        created_at: Utc::now(),
+                        updated_at: Utc::now(),
+                    };
+                    detected.push(conflict);
+                }
+            }
+        }
+
+        // 4. Room Capacity, beyond the workspace's tolerance
+        let sizes = self.repo.find_lesson_sizes(workspace_id, draft_timetable_id).await?;
+        let features = self.repo.find_lesson_features(workspace_id, draft_timetable_id).await?;
+        detected.extend(capacity_conflicts(&self.repo, workspace_id, draft_timetable_id, &sizes, &features).await?);
+
+        // 5. Room Features required by the course or its groups
+        for lesson in &sizes {
+            let missing: Vec<&str> = features
+                .iter()
+                .filter(|f| f.draft_entry_id == lesson.draft_entry_id && f.required && !f.in_room)
+                .map(|f| f.name.as_str())
+                .collect();
+            if missing.is_empty() {
+                continue;
+            }
+            let rooms = free_rooms(&self.repo, workspace_id, draft_timetable_id, lesson, &features).await?;
+            let suggestion = if rooms.is_empty() {
+                "no free room has them at that time".to_string()
+            } else {
+                format!("free rooms that have them: {}", room_list(&rooms))
+            };
+            let description = format!(
+                "Room {} lacks {} required by course {}; {}",
+                lesson.room_name, missing.join(", "), lesson.course_id, suggestion
+            );
+            let conflict = Conflict {
+                id: Uuid::new_v4(),
+                draft_timetable_id,
+                kind: ConflictKind::RoomFeatures,
+                description,
+                teacher_id: None,
+                room_id: Some(lesson.room_id),
+                time_slot_id: Some(lesson.time_slot_id),
+                student_group_id: None,
+                status: ConflictStatus::Open,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            };
+            detected.push(conflict);
+        }
+
+        // 6. Qualification for the course
+        let unqualified = self.repo.find_unqualified_entries(workspace_id, draft_timetable_id).await?;
+        for entry in entries.iter().filter(|e| unqualified.contains(&e.id)) {
+            let description = format!("Teacher is not qualified to teach course {}", entry.course_id);
+            let conflict = Conflict {
+                id: Uuid::new_v4(),
+                draft_timetable_id,
+                kind: ConflictKind::Qualification,
+                description,
+                teacher_id: Some(entry.teacher_id),
+                room_id: None,
+                time_slot_id: Some(entry.time_slot_id),
+                student_group_id: None,
+                status: ConflictStatus::Open,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            };
+            detected.push(conflict);
+        }
+
+        // 7. Availability Check (Optional Bonus)
+        let mut weekly_unavailable = Vec::new();
         for entry in &entries {
             if let Some(time_slot) = self.time_slot_repo.find_by_id(entry.time_slot_id).await? {
-                // Mock a date based on day_of_week for the availability service
-                // Day 0 is Sunday in chrono weekday() but we need to check what AvailabilityRepository expects.
-                // AvailabilityRepository::get_by_teacher_and_date calculates day_of_week = date.weekday().num_days_from_sunday() as i32;
-                // So 0 is Sunday, 1 is Monday, ..., 6 is Saturday.
-                
-                // We can use a reference date that we know is a Sunday, e.g., 2026-01-04 (which is a Sunday)
-                let base_date = chrono::NaiveDate::from_ymd_opt(2026, 1, 4).unwrap();
-                let date = base_date + chrono::Duration::days(time_slot.day_of_week as i64);
-                
-                let availabilities = self.availability_service.get_availability(entry.teacher_id, date).await?;
-                
-                let is_available = availabilities.iter().any(|a| {
-                    a.start_time <= time_slot.start_time && a.end_time >= time_slot.end_time
+                // The weekly pattern; the dated rule below covers overrides.
+                // A teacher without one is available throughout.
+                let availabilities = self.availability_service
+                    .get_draft_availability(workspace_id, draft_timetable_id, entry.teacher_id)
+                    .await?;
+
+                let is_available = availabilities.is_empty() || availabilities.iter().any(|a| {
+                    a.day_of_week == time_slot.day_of_week && a.start_time <= time_slot.start_time && a.end_time >= time_slot.end_time
                 });
 
                 if !is_available {
+                    weekly_unavailable.push((entry.teacher_id, entry.time_slot_id));
                     let description = format!(
                         "Teacher is not available during time slot {}",
                         entry.time_slot_id
@@ -106,27 +240,246 @@ impl ConflictService {
                     let conflict = Conflict {
                         id: Uuid::new_v4(),
                         draft_timetable_id,
+                        kind: ConflictKind::Availability,
                         description,
                         teacher_id: Some(entry.teacher_id),
                         room_id: None,
                         time_slot_id: Some(entry.time_slot_id),
+                        student_group_id: None,
                         status: ConflictStatus::Open,
                         created_at: Utc::now(),
                         updated_at: Utc::now(),
                     };
-                    conflicts.push(self.repo.create(conflict).await?);
+                    detected.push(conflict);
                 }
             }
         }
 
+        // 8. Dated Availability: once the draft is published its lessons have
+        // dates, so overrides, absences and lesson exceptions apply too.
+        // Lessons already outside the weekly pattern were reported above.
+        for lesson in self.repo.find_unavailable_lessons(workspace_id, draft_timetable_id).await? {
+            let reported = lesson
+                .time_slot_id
+                .is_some_and(|slot| weekly_unavailable.contains(&(lesson.teacher_id, slot)));
+            if reported {
+                continue;
+            }
+            let description = format!(
+                "Teacher is not available for course {} on {} ({} date(s) in total)",
+                lesson.course_id, lesson.first_date, lesson.dates
+            );
+            let conflict = Conflict {
+                id: Uuid::new_v4(),
+                draft_timetable_id,
+                kind: ConflictKind::DatedAvailability,
+                description,
+                teacher_id: Some(lesson.teacher_id),
+                room_id: None,
+                time_slot_id: lesson.time_slot_id,
+                student_group_id: None,
+                status: ConflictStatus::Open,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            };
+            detected.push(conflict);
+        }
+
+        // Conflicts found before keep their status, except resolved ones
+        // that are back; only new and reopened ones are announced
+        let mut tx = self.outbox.begin().await?;
+        for conflict in &detected {
+            if let Some(opened) = self.repo.create_if_new(&mut tx, conflict.clone()).await? {
+                self.outbox.publish(&mut tx, conflict_message(workspace_id, ws::CONFLICT_OPENED, &opened)).await?;
+            }
+        }
+        self.outbox.commit(tx).await?;
+
+        let keys: Vec<_> = detected.iter().map(Conflict::key).collect();
+        let mut conflicts = self.repo.get_by_draft_timetable(draft_timetable_id).await?;
+        conflicts.retain(|c| keys.contains(&c.key()));
+
         Ok(conflicts)
     }
 
-    pub async fn resolve_conflict(&self, conflict_id: Uuid, status: ConflictStatus) -> AppResult<Conflict> {
-        self.repo.update_status(conflict_id, status).await
+    pub async fn resolve_conflict(&self, workspace_id: Uuid, conflict_id: Uuid, status: ConflictStatus) -> AppResult<Conflict> {
+        let mut tx = self.outbox.begin().await?;
+        let conflict = self.repo.update_status(&mut tx, conflict_id, status).await?;
+
+        self.outbox.publish(&mut tx, conflict_message(workspace_id, ws::CONFLICT_RESOLVED, &conflict)).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(conflict)
+    }
+
+    /// Free rooms at the time of a draft lesson that hold all its
+    /// students and have the features it requires, those with the most
+    /// preferred features first, then the smallest.
+    pub async fn get_room_suggestions(&self, workspace_id: Uuid, draft_timetable_id: Uuid, draft_entry_id: Uuid) -> AppResult<Vec<RoomSuggestion>> {
+        let sizes = self.repo.find_lesson_sizes(workspace_id, draft_timetable_id).await?;
+        let lesson = sizes.iter().find(|l| l.draft_entry_id == draft_entry_id).ok_or(AppError::NotFound)?;
+        let features = self.repo.find_lesson_features(workspace_id, draft_timetable_id).await?;
+
+        free_rooms(&self.repo, workspace_id, draft_timetable_id, lesson, &features).await
+    }
+
+    /// Soft constraints: how far each teacher's lessons in the draft go
+    /// against their preferences, worst first. Unlike conflicts these are
+    /// not stored; they change with every edit of the draft.
+    pub async fn get_preference_scores(&self, workspace_id: Uuid, draft_timetable_id: Uuid) -> AppResult<Vec<TeacherPreferenceScore>> {
+        let lessons = self.preference_repo.find_draft_lessons(workspace_id, draft_timetable_id).await?;
+        let preferences = self.preference_repo.find_all(workspace_id, None).await?;
+        let time_slots = self.time_slot_repo.find_by_workspace(workspace_id).await?;
+
+        let mut scores: Vec<TeacherPreferenceScore> = Vec::new();
+        for lesson in &lessons {
+            if scores.iter().any(|s| s.teacher_id == lesson.lesson.teacher_id) {
+                continue;
+            }
+            let teacher_id = lesson.lesson.teacher_id;
+            let teacher_lessons: Vec<_> = lessons.iter().filter(|l| l.lesson.teacher_id == teacher_id).map(|l| l.lesson.clone()).collect();
+            let teacher_preferences: Vec<_> = preferences.iter().filter(|p| p.teacher_id == teacher_id).cloned().collect();
+            let violations = score_lessons(&teacher_preferences, &teacher_lessons, &time_slots);
+
+            scores.push(TeacherPreferenceScore {
+                teacher_id,
+                teacher_name: lesson.teacher_name.clone(),
+                penalty: violations.iter().map(|v| v.penalty).sum(),
+                violations,
+            });
+        }
+        scores.sort_by(|a, b| b.penalty.total_cmp(&a.penalty));
+
+        Ok(scores)
     }
 
     pub async fn get_conflicts_for_draft(&self, draft_id: Uuid) -> AppResult<Vec<Conflict>> {
         self.repo.get_by_draft_timetable(draft_id).await
     }
 }
+
+pub(crate) fn conflict_message(workspace_id: Uuid, event_type: &str, conflict: &Conflict) -> WebSocketMessage {
+    let mut msg = WebSocketMessage::new(
+        workspace_id,
+        event_type,
+        json!({ "id": conflict.id, "draft_timetable_id": conflict.draft_timetable_id, "status": conflict.status }),
+    )
+    .with_topic(Topic::Draft(conflict.draft_timetable_id));
+
+    if let Some(teacher_id) = conflict.teacher_id {
+        msg = msg.with_topic(Topic::Teacher(teacher_id));
+    }
+    if let Some(room_id) = conflict.room_id {
+        msg = msg.with_topic(Topic::Room(room_id));
+    }
+
+    msg
+}
+
+/// Room capacity conflicts for the lessons among `sizes` that exceed their
+/// room, each naming free rooms that would hold them.
+pub(crate) async fn capacity_conflicts(
+    repo: &ConflictRepository,
+    workspace_id: Uuid,
+    draft_timetable_id: Uuid,
+    sizes: &[LessonSize],
+    features: &[LessonFeature],
+) -> AppResult<Vec<Conflict>> {
+    let mut conflicts = Vec::new();
+    for lesson in sizes.iter().filter(|l| l.overflows()) {
+        let rooms = free_rooms(repo, workspace_id, draft_timetable_id, lesson, features).await?;
+        let suggestion = if rooms.is_empty() {
+            "no free room holds them at that time".to_string()
+        } else {
+            format!("free rooms that fit: {}", room_list(&rooms))
+        };
+        let description = format!(
+            "Room {} holds {} but course {} has {} students; {}",
+            lesson.room_name, lesson.capacity, lesson.course_id, lesson.size, suggestion
+        );
+        conflicts.push(Conflict {
+            id: Uuid::new_v4(),
+            draft_timetable_id,
+            kind: ConflictKind::RoomCapacity,
+            description,
+            teacher_id: None,
+            room_id: Some(lesson.room_id),
+            time_slot_id: Some(lesson.time_slot_id),
+            student_group_id: None,
+            status: ConflictStatus::Open,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        });
+    }
+
+    Ok(conflicts)
+}
+
+async fn free_rooms(
+    repo: &ConflictRepository,
+    workspace_id: Uuid,
+    draft_timetable_id: Uuid,
+    lesson: &LessonSize,
+    features: &[LessonFeature],
+) -> AppResult<Vec<RoomSuggestion>> {
+    let needs: Vec<&LessonFeature> = features.iter().filter(|f| f.draft_entry_id == lesson.draft_entry_id).collect();
+    let required: Vec<Uuid> = needs.iter().filter(|f| f.required).map(|f| f.room_feature_id).collect();
+    let preferred: Vec<Uuid> = needs.iter().filter(|f| !f.required).map(|f| f.room_feature_id).collect();
+
+    repo.find_free_rooms(workspace_id, draft_timetable_id, lesson, &required, &preferred).await
+}
+
+/// The first few suggested rooms with their capacities, for a conflict
+/// description.
+fn room_list(rooms: &[RoomSuggestion]) -> String {
+    let names: Vec<String> = rooms.iter().take(3).map(|r| format!("{} ({})", r.room_name, r.capacity)).collect();
+    names.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::graphql::types::AvailabilityWindowInput;
+    use crate::test_support;
+
+    #[sqlx::test]
+    async fn teachers_without_a_weekly_pattern_have_no_availability_conflicts(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let teacher_id = test_support::teacher(&pool, workspace_id).await;
+        let entry_id = test_support::lesson(&pool, workspace_id, teacher_id, 1).await;
+        let published_id = test_support::publish(&pool, workspace_id, &[entry_id]).await;
+        let draft_id: Uuid = sqlx::query_scalar("SELECT draft_timetable_id FROM published_timetables WHERE id = $1")
+            .bind(published_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let service = test_support::conflict_service(&pool);
+        let availability_kinds = |conflicts: Vec<Conflict>| -> Vec<ConflictKind> {
+            conflicts
+                .into_iter()
+                .map(|c| c.kind)
+                .filter(|kind| matches!(kind, ConflictKind::Availability | ConflictKind::DatedAvailability))
+                .collect()
+        };
+
+        let conflicts = service.get_conflicts(workspace_id, draft_id).await.unwrap();
+        assert!(availability_kinds(conflicts).is_empty());
+
+        // A pattern leaving out Monday makes the lesson a conflict, reported once
+        let tuesday = AvailabilityWindowInput {
+            day_of_week: 2,
+            start_time: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            end_time: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            is_preferred: false,
+        };
+        test_support::availability_service(&pool)
+            .set_weekly_availability(workspace_id, teacher_id, vec![tuesday])
+            .await
+            .unwrap();
+        let conflicts = service.get_conflicts(workspace_id, draft_id).await.unwrap();
+        assert_eq!(availability_kinds(conflicts), vec![ConflictKind::Availability]);
+    }
+}
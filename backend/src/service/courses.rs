@@ -19,13 +19,20 @@ impl CourseService {
         code: String,
         name: String,
         description: Option<String>,
+        department: Option<String>,
+        expected_size: Option<i32>,
     ) -> AppResult<Course> {
+        if expected_size.is_some_and(|size| size < 0) {
+            return Err(crate::error::AppError::BadRequest("The expected size cannot be negative".to_string()));
+        }
         let course = Course {
             id: Uuid::new_v4(),
             workspace_id,
             code,
             name,
             description,
+            department,
+            expected_size,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
@@ -47,7 +54,12 @@ impl CourseService {
         code: Option<String>,
         name: Option<String>,
         description: Option<String>,
+        department: Option<String>,
+        expected_size: Option<i32>,
     ) -> AppResult<Course> {
+        if expected_size.is_some_and(|size| size < 0) {
+            return Err(crate::error::AppError::BadRequest("The expected size cannot be negative".to_string()));
+        }
         let mut course = self.repo.find_by_id(id).await?.ok_or(crate::error::AppError::NotFound)?;
         
         if let Some(c) = code {
@@ -59,6 +71,12 @@ impl CourseService {
         if let Some(d) = description {
             course.description = Some(d);
         }
+        if let Some(d) = department {
+            course.department = Some(d);
+        }
+        if let Some(s) = expected_size {
+            course.expected_size = Some(s);
+        }
         
         course.updated_at = Utc::now();
         self.repo.update(course).await
@@ -1,20 +1,47 @@
-use crate::repository::DraftEntryRepository;
-use crate::error::AppResult;
+use crate::repository::{ConflictRepository, DraftEntryRepository, StudentGroupRepository};
+use crate::error::{AppError, AppResult};
 use crate::models::draft_entries::DraftEntry;
+use crate::models::ConflictKind;
 use crate::graphql::types::DraftEntryInput;
+use crate::service::OutboxService;
+use crate::service::conflicts::{capacity_conflicts, conflict_message};
+use crate::ws::{self, Topic, WebSocketMessage};
 use uuid::Uuid;
 use chrono::Utc;
+use serde_json::json;
+use std::sync::Arc;
 
 pub struct DraftEntryService {
     repo: DraftEntryRepository,
+    group_repo: StudentGroupRepository,
+    conflict_repo: ConflictRepository,
+    outbox: Arc<OutboxService>,
 }
 
 impl DraftEntryService {
-    pub fn new(repo: DraftEntryRepository) -> Self {
-        Self { repo }
+    pub fn new(
+        repo: DraftEntryRepository,
+        group_repo: StudentGroupRepository,
+        conflict_repo: ConflictRepository,
+        outbox: Arc<OutboxService>,
+    ) -> Self {
+        Self { repo, group_repo, conflict_repo, outbox }
     }
 
-    pub async fn add_entries_to_draft(&self, draft_timetable_id: Uuid, entries: Vec<DraftEntryInput>) -> AppResult<Vec<DraftEntry>> {
+    pub async fn add_entries_to_draft(&self, workspace_id: Uuid, draft_timetable_id: Uuid, entries: Vec<DraftEntryInput>) -> AppResult<Vec<DraftEntry>> {
+        let mut group_ids: Vec<Uuid> = entries.iter().flat_map(|e| e.student_group_ids.iter().flatten().copied()).collect();
+        group_ids.sort();
+        group_ids.dedup();
+        if self.group_repo.count_in_workspace(workspace_id, &group_ids).await? != group_ids.len() as i64 {
+            return Err(AppError::BadRequest("Unknown student group".to_string()));
+        }
+        let (section_ids, course_ids): (Vec<Uuid>, Vec<Uuid>) =
+            entries.iter().filter_map(|e| e.course_section_id.map(|s| (s, e.course_id))).unzip();
+        if !self.repo.sections_match_courses(workspace_id, &section_ids, &course_ids).await? {
+            return Err(AppError::BadRequest("A course section does not belong to the course of its lesson".to_string()));
+        }
+        let entry_groups: Vec<Vec<Uuid>> = entries.iter().map(|e| e.student_group_ids.clone().unwrap_or_default()).collect();
+
         let now = Utc::now();
         let draft_entries = entries.into_iter().map(|input| DraftEntry {
             id: Uuid::new_v4(),
@@ -23,11 +50,83 @@ impl DraftEntryService {
             teacher_id: input.teacher_id,
             room_id: input.room_id,
             time_slot_id: input.time_slot_id,
+            course_section_id: input.course_section_id,
             created_at: now,
             updated_at: now,
         }).collect();
 
-        self.repo.create_many(draft_entries).await
+        let mut tx = self.outbox.begin().await?;
+        let created = self.repo.create_many(&mut tx, draft_entries).await?;
+        for (entry, groups) in created.iter().zip(&entry_groups).filter(|(_, groups)| !groups.is_empty()) {
+            self.group_repo.set_draft_entry_groups(&mut tx, entry.id, groups).await?;
+        }
+
+        self.outbox.publish(&mut tx, WebSocketMessage::new(
+            workspace_id,
+            ws::DRAFT_UPDATED,
+            json!({ "id": draft_timetable_id }),
+        )
+        .with_topic(Topic::Draft(draft_timetable_id))).await?;
+        self.outbox.commit(tx).await?;
+
+        let entry_ids: Vec<Uuid> = created.iter().map(|e| e.id).collect();
+        self.check_capacity(workspace_id, draft_timetable_id, &entry_ids).await?;
+
+        Ok(created)
+    }
+
+    /// Replaces the groups of a single draft lesson. With no groups the
+    /// lesson goes back to those of its course.
+    pub async fn set_entry_groups(&self, workspace_id: Uuid, draft_entry_id: Uuid, group_ids: Vec<Uuid>) -> AppResult<()> {
+        let draft_timetable_id = self.repo.find_draft_timetable_id(workspace_id, draft_entry_id).await?
+            .ok_or(AppError::NotFound)?;
+        let mut unique = group_ids.clone();
+        unique.sort();
+        unique.dedup();
+        if self.group_repo.count_in_workspace(workspace_id, &unique).await? != unique.len() as i64 {
+            return Err(AppError::BadRequest("Unknown student group".to_string()));
+        }
+
+        let mut tx = self.outbox.begin().await?;
+        self.group_repo.set_draft_entry_groups(&mut tx, draft_entry_id, &group_ids).await?;
+        self.outbox.commit(tx).await?;
+
+        self.check_capacity(workspace_id, draft_timetable_id, &[draft_entry_id]).await
+    }
+
+    /// Runs the room capacity rule for the given lessons as soon as they
+    /// change: a lesson that outgrows its room opens a conflict, and one
+    /// that fits again resolves it unless another lesson in the same room
+    /// and slot still overflows.
+    async fn check_capacity(&self, workspace_id: Uuid, draft_timetable_id: Uuid, entry_ids: &[Uuid]) -> AppResult<()> {
+        let sizes = self.conflict_repo.find_lesson_sizes(workspace_id, draft_timetable_id).await?;
+        let features = self.conflict_repo.find_lesson_features(workspace_id, draft_timetable_id).await?;
+        let affected: Vec<_> = sizes.iter().filter(|l| entry_ids.contains(&l.draft_entry_id)).cloned().collect();
+        let raised = capacity_conflicts(&self.conflict_repo, workspace_id, draft_timetable_id, &affected, &features).await?;
+
+        let mut tx = self.outbox.begin().await?;
+        for conflict in raised {
+            if let Some(opened) = self.conflict_repo.create_if_new(&mut tx, conflict).await? {
+                self.outbox.publish(&mut tx, conflict_message(workspace_id, ws::CONFLICT_OPENED, &opened)).await?;
+            }
+        }
+        for lesson in affected.iter().filter(|l| !l.overflows()) {
+            let still_full = sizes
+                .iter()
+                .any(|l| l.room_id == lesson.room_id && l.time_slot_id == lesson.time_slot_id && l.overflows());
+            if still_full {
+                continue;
+            }
+            let resolved = self.conflict_repo
+                .resolve_open(&mut tx, draft_timetable_id, ConflictKind::RoomCapacity, lesson.room_id, lesson.time_slot_id)
+                .await?;
+            if let Some(resolved) = resolved {
+                self.outbox.publish(&mut tx, conflict_message(workspace_id, ws::CONFLICT_RESOLVED, &resolved)).await?;
+            }
+        }
+        self.outbox.commit(tx).await?;
+
+        Ok(())
     }
 
     pub async fn get_entries_for_draft(&self, draft_timetable_id: Uuid) -> AppResult<Vec<DraftEntry>> {
@@ -1,16 +1,22 @@
 use crate::error::AppResult;
 use crate::models::draft_timetables::{DraftTimetable, DraftTimetableStatus};
 use crate::repository::DraftTimetableRepository;
+use crate::service::OutboxService;
+use crate::ws::{self, Topic, WebSocketMessage};
 use chrono::Utc;
+use serde_json::json;
+use sqlx::PgConnection;
+use std::sync::Arc;
 use uuid::Uuid;
 
 pub struct DraftTimetableService {
     repo: DraftTimetableRepository,
+    outbox: Arc<OutboxService>,
 }
 
 impl DraftTimetableService {
-    pub fn new(repo: DraftTimetableRepository) -> Self {
-        Self { repo }
+    pub fn new(repo: DraftTimetableRepository, outbox: Arc<OutboxService>) -> Self {
+        Self { repo, outbox }
     }
 
     pub async fn create_draft(
@@ -49,6 +55,30 @@ impl DraftTimetableService {
         id: Uuid,
         status: DraftTimetableStatus,
     ) -> AppResult<DraftTimetable> {
-        self.repo.update_status(workspace_id, id, status).await
+        let mut tx = self.outbox.begin().await?;
+        let draft = self.update_draft_status_in(&mut tx, workspace_id, id, status).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(draft)
+    }
+
+    /// Updates the status as part of a larger transaction owned by the caller.
+    pub async fn update_draft_status_in(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        id: Uuid,
+        status: DraftTimetableStatus,
+    ) -> AppResult<DraftTimetable> {
+        let draft = self.repo.update_status(&mut *conn, workspace_id, id, status).await?;
+
+        self.outbox.publish(conn, WebSocketMessage::new(
+            workspace_id,
+            ws::DRAFT_UPDATED,
+            json!({ "id": draft.id }),
+        )
+        .with_topic(Topic::Draft(draft.id))).await?;
+
+        Ok(draft)
     }
 }
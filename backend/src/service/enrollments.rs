@@ -0,0 +1,479 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::graphql::types::StudentInput;
+use crate::models::{CourseSection, Enrollment, EnrollmentClash, EnrollmentImport, Student, StudentLesson};
+use crate::repository::{EnrollmentRepository, StudentGroupRepository};
+
+/// Errors listed back to the importer before giving up.
+const MAX_IMPORT_ERRORS: usize = 20;
+
+/// Students, the course sections they choose and the lessons that follow
+/// from it.
+pub struct EnrollmentService {
+    repo: EnrollmentRepository,
+    group_repo: StudentGroupRepository,
+}
+
+impl EnrollmentService {
+    pub fn new(repo: EnrollmentRepository, group_repo: StudentGroupRepository) -> Self {
+        Self { repo, group_repo }
+    }
+
+    pub async fn create_student(&self, workspace_id: Uuid, input: StudentInput) -> AppResult<Student> {
+        self.validate_student(workspace_id, None, &input).await?;
+
+        let mut tx = self.repo.begin().await?;
+        let student = self
+            .repo
+            .create_student(
+                &mut tx,
+                Student {
+                    id: Uuid::new_v4(),
+                    workspace_id,
+                    student_number: input.student_number.trim().to_string(),
+                    name: input.name.trim().to_string(),
+                    email: input.email,
+                    user_id: input.user_id,
+                    student_group_id: input.student_group_id,
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                },
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(student)
+    }
+
+    pub async fn update_student(&self, workspace_id: Uuid, id: Uuid, input: StudentInput) -> AppResult<Student> {
+        let mut student = self.repo.find_student(workspace_id, id).await?.ok_or(AppError::NotFound)?;
+        self.validate_student(workspace_id, Some(id), &input).await?;
+        student.student_number = input.student_number.trim().to_string();
+        student.name = input.name.trim().to_string();
+        student.email = input.email;
+        student.user_id = input.user_id;
+        student.student_group_id = input.student_group_id;
+
+        self.repo.update_student(&student).await
+    }
+
+    pub async fn delete_student(&self, workspace_id: Uuid, id: Uuid) -> AppResult<()> {
+        self.repo.find_student(workspace_id, id).await?.ok_or(AppError::NotFound)?;
+        self.repo.delete_student(id).await
+    }
+
+    pub async fn get_student(&self, workspace_id: Uuid, id: Uuid) -> AppResult<Option<Student>> {
+        self.repo.find_student(workspace_id, id).await
+    }
+
+    pub async fn get_students(&self, workspace_id: Uuid, student_group_id: Option<Uuid>) -> AppResult<Vec<Student>> {
+        self.repo.find_students(workspace_id, student_group_id).await
+    }
+
+    pub async fn create_section(&self, workspace_id: Uuid, course_id: Uuid, name: String) -> AppResult<CourseSection> {
+        if !self.repo.course_in_workspace(workspace_id, course_id).await? {
+            return Err(AppError::NotFound);
+        }
+        let name = self.check_section_name(workspace_id, course_id, None, &name).await?;
+
+        self.repo
+            .create_section(CourseSection {
+                id: Uuid::new_v4(),
+                workspace_id,
+                course_id,
+                name,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .await
+    }
+
+    pub async fn rename_section(&self, workspace_id: Uuid, id: Uuid, name: String) -> AppResult<CourseSection> {
+        let section = self.repo.find_section(workspace_id, id).await?.ok_or(AppError::NotFound)?;
+        let name = self.check_section_name(workspace_id, section.course_id, Some(id), &name).await?;
+        self.repo.rename_section(id, &name).await
+    }
+
+    /// Deletes the section with its enrolments. Its draft lessons stay, for
+    /// every section of the course.
+    pub async fn delete_section(&self, workspace_id: Uuid, id: Uuid) -> AppResult<()> {
+        self.repo.find_section(workspace_id, id).await?.ok_or(AppError::NotFound)?;
+        self.repo.delete_section(id).await
+    }
+
+    pub async fn get_sections(&self, workspace_id: Uuid, course_id: Option<Uuid>) -> AppResult<Vec<CourseSection>> {
+        self.repo.find_sections(workspace_id, course_id).await
+    }
+
+    /// Enrols the student in the section, moving them out of any other
+    /// section of the same course.
+    pub async fn enroll(&self, workspace_id: Uuid, student_id: Uuid, course_section_id: Uuid) -> AppResult<()> {
+        self.repo.find_student(workspace_id, student_id).await?.ok_or(AppError::NotFound)?;
+        self.repo.find_section(workspace_id, course_section_id).await?.ok_or(AppError::NotFound)?;
+
+        let mut tx = self.repo.begin().await?;
+        self.repo.enroll(&mut tx, student_id, course_section_id).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn unenroll(&self, workspace_id: Uuid, student_id: Uuid, course_section_id: Uuid) -> AppResult<()> {
+        self.repo.find_student(workspace_id, student_id).await?.ok_or(AppError::NotFound)?;
+        if !self.repo.unenroll(student_id, course_section_id).await? {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    pub async fn get_enrollments(
+        &self,
+        workspace_id: Uuid,
+        student_id: Option<Uuid>,
+        course_section_id: Option<Uuid>,
+    ) -> AppResult<Vec<Enrollment>> {
+        self.repo.find_enrollments(workspace_id, student_id, course_section_id).await
+    }
+
+    /// Imports enrolments from CSV with the columns `student_number`,
+    /// `name`, `email`, `course_code` and `section`, in any order after a
+    /// header row. Unknown students are added to the roster; sections must
+    /// exist. Nothing is imported if any line is wrong.
+    pub async fn import_csv(&self, workspace_id: Uuid, csv: &str) -> AppResult<EnrollmentImport> {
+        let csv = CsvImport::parse(csv)?;
+        let [number_col, course_col, section_col] = csv.require(["student_number", "course_code", "section"])?;
+        let (name_col, email_col) = (csv.column("name"), csv.column("email"));
+
+        let mut tx = self.repo.begin().await?;
+        let mut errors = ImportErrors::default();
+        let mut sections: HashMap<(String, String), Option<Uuid>> = HashMap::new();
+        let mut result = EnrollmentImport { students_created: 0, enrollments_created: 0 };
+
+        for row in csv.rows() {
+            let (Some(student_number), Some(course_code), Some(section_name)) =
+                (row.field(number_col), row.field(course_col), row.field(section_col))
+            else {
+                errors.reject(row, "student_number, course_code and section are required");
+                continue;
+            };
+
+            let key = (course_code.to_string(), section_name.to_string());
+            let section_id = match sections.get(&key) {
+                Some(id) => *id,
+                None => {
+                    let section = self.repo.find_section_by_code(&mut tx, workspace_id, course_code, section_name).await?;
+                    let id = section.map(|s| s.id);
+                    sections.insert(key, id);
+                    id
+                }
+            };
+            let Some(section_id) = section_id else {
+                errors.reject(row, format!("course {} has no section {}", course_code, section_name));
+                continue;
+            };
+
+            let student = match self.repo.find_student_by_number(&mut tx, workspace_id, student_number).await? {
+                Some(student) => student,
+                None => {
+                    let Some(name) = row.field(name_col) else {
+                        errors.reject(row, format!("new student {} needs a name", student_number));
+                        continue;
+                    };
+                    let email = row.field(email_col).map(str::to_string);
+                    let user_id = match &email {
+                        Some(email) => self.repo.find_member_by_email(&mut tx, workspace_id, email).await?,
+                        None => None,
+                    };
+                    result.students_created += 1;
+                    self.repo
+                        .create_student(
+                            &mut tx,
+                            Student {
+                                id: Uuid::new_v4(),
+                                workspace_id,
+                                student_number: student_number.to_string(),
+                                name: name.to_string(),
+                                email,
+                                user_id,
+                                student_group_id: None,
+                                created_at: Utc::now(),
+                                updated_at: Utc::now(),
+                            },
+                        )
+                        .await?
+                }
+            };
+
+            if self.repo.enroll(&mut tx, student.id, section_id).await? {
+                result.enrollments_created += 1;
+            }
+        }
+
+        errors.check()?;
+        tx.commit().await?;
+
+        Ok(result)
+    }
+
+    /// A student's weekly lessons in a draft, or in the timetable in force
+    /// today when no draft is given.
+    pub async fn get_timetable(
+        &self,
+        workspace_id: Uuid,
+        student_id: Uuid,
+        draft_timetable_id: Option<Uuid>,
+    ) -> AppResult<Vec<StudentLesson>> {
+        self.repo.find_student(workspace_id, student_id).await?.ok_or(AppError::NotFound)?;
+        let Some(draft_timetable_id) = self.draft_or_current(workspace_id, draft_timetable_id).await? else {
+            return Ok(Vec::new());
+        };
+
+        let rows = self.repo.find_student_lessons(workspace_id, draft_timetable_id, Some(student_id)).await?;
+        Ok(rows.into_iter().map(|row| row.lesson).collect())
+    }
+
+    /// Every pair of lessons of the same student that overlap, in a draft
+    /// or in the timetable in force today.
+    pub async fn get_clashes(&self, workspace_id: Uuid, draft_timetable_id: Option<Uuid>) -> AppResult<Vec<EnrollmentClash>> {
+        let Some(draft_timetable_id) = self.draft_or_current(workspace_id, draft_timetable_id).await? else {
+            return Ok(Vec::new());
+        };
+        let rows = self.repo.find_student_lessons(workspace_id, draft_timetable_id, None).await?;
+
+        let mut clashes = Vec::new();
+        for (i, first) in rows.iter().enumerate() {
+            for second in rows.iter().skip(i + 1).take_while(|row| row.student_id == first.student_id) {
+                let (a, b) = (&first.lesson.lesson, &second.lesson.lesson);
+                if a.day_of_week == b.day_of_week && a.start_time < b.end_time && b.start_time < a.end_time {
+                    clashes.push(EnrollmentClash {
+                        student_id: first.student_id,
+                        student_name: first.student_name.clone(),
+                        first: first.lesson.clone(),
+                        second: second.lesson.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(clashes)
+    }
+
+    async fn draft_or_current(&self, workspace_id: Uuid, draft_timetable_id: Option<Uuid>) -> AppResult<Option<Uuid>> {
+        match draft_timetable_id {
+            Some(id) => Ok(Some(id)),
+            None => self.group_repo.find_current_draft(workspace_id, Utc::now().date_naive()).await,
+        }
+    }
+
+    async fn validate_student(&self, workspace_id: Uuid, id: Option<Uuid>, input: &StudentInput) -> AppResult<()> {
+        if input.student_number.trim().is_empty() || input.name.trim().is_empty() {
+            return Err(AppError::BadRequest("A student needs a student number and a name".to_string()));
+        }
+        let group_ids: Vec<Uuid> = input.student_group_id.into_iter().collect();
+        if self.group_repo.count_in_workspace(workspace_id, &group_ids).await? != group_ids.len() as i64 {
+            return Err(AppError::BadRequest("Unknown student group".to_string()));
+        }
+
+        let mut tx = self.repo.begin().await?;
+        let existing = self.repo.find_student_by_number(&mut tx, workspace_id, input.student_number.trim()).await?;
+        if existing.is_some_and(|s| Some(s.id) != id) {
+            return Err(AppError::Conflict(format!("Student number {} is already taken", input.student_number.trim())));
+        }
+        Ok(())
+    }
+
+    async fn check_section_name(&self, workspace_id: Uuid, course_id: Uuid, id: Option<Uuid>, name: &str) -> AppResult<String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::BadRequest("A section needs a name".to_string()));
+        }
+        let sections = self.repo.find_sections(workspace_id, Some(course_id)).await?;
+        if sections.iter().any(|s| s.name == name && Some(s.id) != id) {
+            return Err(AppError::Conflict(format!("The course already has a section {}", name)));
+        }
+        Ok(name.to_string())
+    }
+}
+
+/// A CSV file being imported: a header row naming the columns, in any
+/// order, and the data rows below it.
+pub(crate) struct CsvImport {
+    header: Vec<String>,
+    rows: Vec<CsvRow>,
+}
+
+impl CsvImport {
+    pub fn parse(text: &str) -> AppResult<Self> {
+        let mut rows = parse_csv(text).into_iter();
+        let (_, header) = rows.next().ok_or_else(|| AppError::BadRequest("The file is empty".to_string()))?;
+        let rows = rows
+            .map(|(line, fields)| CsvRow { line, fields })
+            .filter(|row| row.fields.iter().any(|v| !v.trim().is_empty()))
+            .collect();
+
+        Ok(Self { header, rows })
+    }
+
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.header.iter().position(|h| h.trim().eq_ignore_ascii_case(name))
+    }
+
+    /// The columns the header must name.
+    pub fn require<const N: usize>(&self, names: [&str; N]) -> AppResult<[usize; N]> {
+        let mut columns = [0; N];
+        for (column, name) in columns.iter_mut().zip(names) {
+            *column = self.column(name).ok_or_else(|| {
+                AppError::BadRequest(format!("The header must name the {} columns", name_list(&names)))
+            })?;
+        }
+        Ok(columns)
+    }
+
+    /// The data rows, leaving out blank ones.
+    pub fn rows(&self) -> &[CsvRow] {
+        &self.rows
+    }
+}
+
+pub(crate) struct CsvRow {
+    /// The line of the file the row starts on.
+    pub line: usize,
+    fields: Vec<String>,
+}
+
+impl CsvRow {
+    /// The trimmed value in a column, unless it is empty.
+    pub fn field(&self, column: impl Into<Option<usize>>) -> Option<&str> {
+        column.into().and_then(|c| self.fields.get(c)).map(|v| v.trim()).filter(|v| !v.is_empty())
+    }
+}
+
+/// The lines an import rejected.
+#[derive(Default)]
+pub(crate) struct ImportErrors(Vec<String>);
+
+impl ImportErrors {
+    pub fn reject(&mut self, row: &CsvRow, reason: impl std::fmt::Display) {
+        self.0.push(format!("Line {}: {}", row.line, reason));
+    }
+
+    /// Fails with the rejected lines if there are any.
+    pub fn check(self) -> AppResult<()> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        Err(import_error(self.0))
+    }
+}
+
+/// Reports the lines an import rejected, the first few of them in full.
+fn import_error(mut errors: Vec<String>) -> AppError {
+    let more = errors.len().saturating_sub(MAX_IMPORT_ERRORS);
+    errors.truncate(MAX_IMPORT_ERRORS);
+    if more > 0 {
+        errors.push(format!("and {} more", more));
+    }
+    AppError::UnprocessableEntity(errors.join("; "))
+}
+
+/// "a", "a and b", "a, b and c".
+fn name_list(names: &[&str]) -> String {
+    match names {
+        [] => String::new(),
+        [name] => name.to_string(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+/// Splits CSV text into rows of fields, each with the line it starts on.
+/// Fields may be quoted, with `""` for a quote inside, and may then span
+/// lines; line breaks may be `\n` or `\r\n`.
+fn parse_csv(text: &str) -> Vec<(usize, Vec<String>)> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push((start, std::mem::take(&mut row)));
+                line += 1;
+                start = line;
+            }
+            ('\n', true) => {
+                line += 1;
+                field.push(c);
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push((start, row));
+    }
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(line: usize, fields: &[&str]) -> (usize, Vec<String>) {
+        (line, fields.iter().map(|f| f.to_string()).collect())
+    }
+
+    #[test]
+    fn keeps_commas_and_quotes_inside_quoted_fields() {
+        assert_eq!(
+            parse_csv("name,note\n\"Doe, Jane\",\"said \"\"hi\"\"\"\n"),
+            vec![row(1, &["name", "note"]), row(2, &["Doe, Jane", "said \"hi\""])]
+        );
+    }
+
+    #[test]
+    fn reads_crlf_line_breaks_and_a_last_line_without_one() {
+        assert_eq!(
+            parse_csv("\u{feff}a,b\r\n1,2\r\n3,\r\n4,5"),
+            vec![row(1, &["a", "b"]), row(2, &["1", "2"]), row(3, &["3", ""]), row(4, &["4", "5"])]
+        );
+    }
+
+    #[test]
+    fn numbers_rows_by_the_line_they_start_on() {
+        let rows = parse_csv("a,b\r\n1,\"two\r\nlines\"\r\n\r\n2,x\n");
+
+        assert_eq!(rows[1], row(2, &["1", "two\r\nlines"]));
+        assert_eq!(rows[2], row(4, &[""]));
+        assert_eq!(rows[3], row(5, &["2", "x"]));
+    }
+
+    #[test]
+    fn import_skips_blank_rows_and_names_missing_columns() {
+        let csv = CsvImport::parse("Student_Number, course_code\n\n,\n42,MATH\n").unwrap();
+
+        assert_eq!(csv.rows().iter().map(|r| r.line).collect::<Vec<_>>(), vec![4]);
+        assert_eq!(csv.require(["student_number", "course_code"]).unwrap(), [0, 1]);
+        let Err(AppError::BadRequest(message)) = csv.require(["student_number", "course_code", "section"]) else {
+            panic!("the section column is missing");
+        };
+        assert_eq!(message, "The header must name the student_number, course_code and section columns");
+    }
+}
@@ -0,0 +1,350 @@
+use chrono::{Datelike, NaiveDate, NaiveTime, Utc};
+use serde_json::json;
+use sqlx::PgConnection;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{DailyChange, ExceptionLesson, LessonException, LessonExceptionKind};
+use crate::repository::LessonExceptionRepository;
+use crate::service::OutboxService;
+use crate::ws::{self, Topic, WebSocketMessage};
+
+/// An extra lesson to add on a single date.
+pub struct ExtraLesson {
+    pub date: NaiveDate,
+    pub course_id: Uuid,
+    pub teacher_id: Uuid,
+    pub room_id: Uuid,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub reason: Option<String>,
+}
+
+/// One-off changes to single dates: cancelled lessons, room changes,
+/// extra lessons and teacher changes from lesson swaps, plus the daily
+/// changes board built from them and from substitutions.
+pub struct LessonExceptionService {
+    repo: LessonExceptionRepository,
+    outbox: Arc<OutboxService>,
+}
+
+impl LessonExceptionService {
+    pub fn new(repo: LessonExceptionRepository, outbox: Arc<OutboxService>) -> Self {
+        Self { repo, outbox }
+    }
+
+    pub async fn cancel_lesson(
+        &self,
+        workspace_id: Uuid,
+        created_by: Uuid,
+        timetable_entry_id: Uuid,
+        date: NaiveDate,
+        reason: Option<String>,
+    ) -> AppResult<LessonException> {
+        let mut tx = self.outbox.begin().await?;
+        let (published_timetable_id, lesson) = self.find_lesson(&mut tx, workspace_id, timetable_entry_id, date).await?;
+
+        let exception = self.repo.create(&mut tx, LessonException {
+            id: Uuid::new_v4(),
+            workspace_id,
+            published_timetable_id,
+            date,
+            kind: LessonExceptionKind::Cancellation,
+            timetable_entry_id: Some(timetable_entry_id),
+            course_id: lesson.course_id,
+            teacher_id: lesson.teacher_id,
+            room_id: None,
+            start_time: lesson.start_time,
+            end_time: lesson.end_time,
+            reason: clean_reason(reason),
+            created_by: Some(created_by),
+            created_at: Utc::now(),
+        }).await?;
+        self.publish(&mut tx, ws::LESSON_EXCEPTION_CREATED, &exception, Some(lesson.room_id)).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(exception)
+    }
+
+    /// Moves a lesson to another room for one date. The room must be free
+    /// at that time.
+    pub async fn change_room(
+        &self,
+        workspace_id: Uuid,
+        created_by: Uuid,
+        timetable_entry_id: Uuid,
+        date: NaiveDate,
+        room_id: Uuid,
+        reason: Option<String>,
+    ) -> AppResult<LessonException> {
+        let mut tx = self.outbox.begin().await?;
+        let (published_timetable_id, lesson) = self.find_lesson(&mut tx, workspace_id, timetable_entry_id, date).await?;
+        if room_id == lesson.room_id {
+            return Err(AppError::BadRequest("The lesson is already held in that room".to_string()));
+        }
+        if !self.repo.room_exists(&mut tx, workspace_id, room_id).await? {
+            return Err(AppError::NotFound);
+        }
+        if self.repo.is_room_busy(&mut tx, workspace_id, room_id, date, lesson.start_time, lesson.end_time).await? {
+            return Err(AppError::Conflict("The room is taken at that time".to_string()));
+        }
+
+        let exception = self.repo.create(&mut tx, LessonException {
+            id: Uuid::new_v4(),
+            workspace_id,
+            published_timetable_id,
+            date,
+            kind: LessonExceptionKind::RoomChange,
+            timetable_entry_id: Some(timetable_entry_id),
+            course_id: lesson.course_id,
+            teacher_id: lesson.teacher_id,
+            room_id: Some(room_id),
+            start_time: lesson.start_time,
+            end_time: lesson.end_time,
+            reason: clean_reason(reason),
+            created_by: Some(created_by),
+            created_at: Utc::now(),
+        }).await?;
+        self.publish(&mut tx, ws::LESSON_EXCEPTION_CREATED, &exception, Some(lesson.room_id)).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(exception)
+    }
+
+    /// Adds a lesson on one date. Both the teacher and the room must be free,
+    /// and the teacher available.
+    pub async fn add_extra_lesson(&self, workspace_id: Uuid, created_by: Uuid, lesson: ExtraLesson) -> AppResult<LessonException> {
+        if lesson.end_time <= lesson.start_time {
+            return Err(AppError::BadRequest("The lesson must end after it starts".to_string()));
+        }
+
+        let mut tx = self.outbox.begin().await?;
+        let published_timetable_id = self.repo.find_published_for(&mut tx, workspace_id, lesson.date).await?
+            .ok_or_else(|| AppError::BadRequest(format!("No timetable is published for {}", lesson.date)))?;
+        if !self.repo.course_exists(&mut tx, workspace_id, lesson.course_id).await?
+            || !self.repo.room_exists(&mut tx, workspace_id, lesson.room_id).await?
+        {
+            return Err(AppError::NotFound);
+        }
+        if !self.repo.is_teacher(&mut tx, workspace_id, lesson.teacher_id).await? {
+            return Err(AppError::BadRequest("Extra lessons must be taught by a teacher of the workspace".to_string()));
+        }
+        if self.repo.count_teacher_lessons(&mut tx, workspace_id, lesson.teacher_id, lesson.date, lesson.start_time, lesson.end_time).await? > 0 {
+            return Err(AppError::Conflict("The teacher already teaches at that time".to_string()));
+        }
+        if !self.repo.is_available(&mut tx, workspace_id, lesson.teacher_id, lesson.date, lesson.start_time, lesson.end_time).await? {
+            return Err(AppError::Conflict("The teacher is not available at that time".to_string()));
+        }
+        if self.repo.is_room_busy(&mut tx, workspace_id, lesson.room_id, lesson.date, lesson.start_time, lesson.end_time).await? {
+            return Err(AppError::Conflict("The room is taken at that time".to_string()));
+        }
+
+        let exception = self.repo.create(&mut tx, LessonException {
+            id: Uuid::new_v4(),
+            workspace_id,
+            published_timetable_id,
+            date: lesson.date,
+            kind: LessonExceptionKind::ExtraLesson,
+            timetable_entry_id: None,
+            course_id: lesson.course_id,
+            teacher_id: lesson.teacher_id,
+            room_id: Some(lesson.room_id),
+            start_time: lesson.start_time,
+            end_time: lesson.end_time,
+            reason: clean_reason(lesson.reason),
+            created_by: Some(created_by),
+            created_at: Utc::now(),
+        }).await?;
+        self.publish(&mut tx, ws::LESSON_EXCEPTION_CREATED, &exception, None).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(exception)
+    }
+
+    /// Undoes a change, restoring the lesson as published. Teacher changes
+    /// belong to a lesson swap and are undone with it.
+    pub async fn delete_exception(&self, workspace_id: Uuid, id: Uuid) -> AppResult<LessonException> {
+        let mut tx = self.outbox.begin().await?;
+        let exception = self.repo.delete(&mut tx, workspace_id, id).await?
+            .ok_or(AppError::NotFound)?;
+        if exception.kind == LessonExceptionKind::TeacherChange {
+            return Err(AppError::BadRequest("Teacher changes are undone by cancelling their lesson swap".to_string()));
+        }
+        self.publish(&mut tx, ws::LESSON_EXCEPTION_DELETED, &exception, None).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(exception)
+    }
+
+    /// Everything that differs from the timetable on the date, one line per
+    /// lesson, in order of start time. A cancelled lesson hides its
+    /// substitution, since nobody needs to cover it.
+    pub async fn daily_changes(&self, workspace_id: Uuid, date: NaiveDate) -> AppResult<Vec<DailyChange>> {
+        let exceptions = self.repo.find_changes(workspace_id, date).await?;
+        let substitutions = self.repo.find_substitution_changes(workspace_id, date).await?;
+
+        let mut lessons: HashMap<Uuid, DailyChange> = HashMap::new();
+        let mut changes = Vec::new();
+        for exception in exceptions {
+            let Some(timetable_entry_id) = exception.timetable_entry_id else {
+                changes.push(DailyChange {
+                    timetable_entry_id: None,
+                    course_id: exception.course_id,
+                    course_name: exception.course_name,
+                    teacher_id: exception.teacher_id,
+                    teacher_name: exception.teacher_name,
+                    start_time: exception.start_time,
+                    end_time: exception.end_time,
+                    room_id: exception.room_id,
+                    room_name: exception.room_name,
+                    cancelled: false,
+                    extra_lesson: true,
+                    new_room_id: None,
+                    new_room_name: None,
+                    new_teacher_id: None,
+                    new_teacher_name: None,
+                    substitution_id: None,
+                    substitution_status: None,
+                    substitute_id: None,
+                    substitute_name: None,
+                    exception_ids: vec![exception.id],
+                    reason: exception.reason,
+                });
+                continue;
+            };
+
+            let change = lessons.entry(timetable_entry_id).or_insert_with(|| DailyChange {
+                timetable_entry_id: Some(timetable_entry_id),
+                course_id: exception.course_id,
+                course_name: exception.course_name.clone(),
+                teacher_id: exception.scheduled_teacher_id.unwrap_or(exception.teacher_id),
+                teacher_name: exception.scheduled_teacher_name.clone().unwrap_or_else(|| exception.teacher_name.clone()),
+                start_time: exception.start_time,
+                end_time: exception.end_time,
+                room_id: exception.scheduled_room_id,
+                room_name: exception.scheduled_room_name.clone(),
+                cancelled: false,
+                extra_lesson: false,
+                new_room_id: None,
+                new_room_name: None,
+                new_teacher_id: None,
+                new_teacher_name: None,
+                substitution_id: None,
+                substitution_status: None,
+                substitute_id: None,
+                substitute_name: None,
+                exception_ids: Vec::new(),
+                reason: None,
+            });
+            match exception.kind {
+                LessonExceptionKind::Cancellation => change.cancelled = true,
+                LessonExceptionKind::RoomChange => {
+                    change.new_room_id = exception.room_id;
+                    change.new_room_name = exception.room_name;
+                }
+                LessonExceptionKind::TeacherChange => {
+                    change.new_teacher_id = Some(exception.teacher_id);
+                    change.new_teacher_name = Some(exception.teacher_name);
+                }
+                LessonExceptionKind::ExtraLesson => {}
+            }
+            change.exception_ids.push(exception.id);
+            change.reason = change.reason.take().or(exception.reason);
+        }
+
+        for substitution in substitutions {
+            let change = lessons.entry(substitution.timetable_entry_id).or_insert_with(|| DailyChange {
+                timetable_entry_id: Some(substitution.timetable_entry_id),
+                course_id: substitution.course_id,
+                course_name: substitution.course_name.clone(),
+                teacher_id: substitution.teacher_id,
+                teacher_name: substitution.teacher_name.clone(),
+                start_time: substitution.start_time,
+                end_time: substitution.end_time,
+                room_id: Some(substitution.room_id),
+                room_name: Some(substitution.room_name.clone()),
+                cancelled: false,
+                extra_lesson: false,
+                new_room_id: None,
+                new_room_name: None,
+                new_teacher_id: None,
+                new_teacher_name: None,
+                substitution_id: None,
+                substitution_status: None,
+                substitute_id: None,
+                substitute_name: None,
+                exception_ids: Vec::new(),
+                reason: None,
+            });
+            if !change.cancelled {
+                change.substitution_id = Some(substitution.id);
+                change.substitution_status = Some(substitution.status);
+                change.substitute_id = substitution.substitute_id;
+                change.substitute_name = substitution.substitute_name;
+            }
+        }
+
+        changes.extend(lessons.into_values());
+        changes.sort_by(|a, b| {
+            a.start_time
+                .cmp(&b.start_time)
+                .then_with(|| a.course_name.cmp(&b.course_name))
+        });
+        Ok(changes)
+    }
+
+    /// The weekly lesson to change, which must be held on the date in the
+    /// timetable published for it and not be changed already.
+    pub(crate) async fn find_lesson(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        timetable_entry_id: Uuid,
+        date: NaiveDate,
+    ) -> AppResult<(Uuid, ExceptionLesson)> {
+        let published_timetable_id = self.repo.find_published_for(&mut *conn, workspace_id, date).await?
+            .ok_or_else(|| AppError::BadRequest(format!("No timetable is published for {}", date)))?;
+        let lesson = self.repo.find_lesson(&mut *conn, workspace_id, timetable_entry_id, published_timetable_id).await?
+            .ok_or(AppError::NotFound)?;
+        if lesson.day_of_week != date.weekday().num_days_from_sunday() as i32 || !lesson.is_published {
+            return Err(AppError::BadRequest(format!("The lesson is not held on {}", date)));
+        }
+        if self.repo.has_exception(&mut *conn, timetable_entry_id, date).await? {
+            return Err(AppError::Conflict("The lesson is already changed on that date".to_string()));
+        }
+
+        Ok((published_timetable_id, lesson))
+    }
+
+    /// Tells boards about the change, tagged for the teacher and for every
+    /// room involved.
+    pub(crate) async fn publish(
+        &self,
+        conn: &mut PgConnection,
+        event_type: &str,
+        exception: &LessonException,
+        scheduled_room_id: Option<Uuid>,
+    ) -> AppResult<()> {
+        let mut message = WebSocketMessage::new(
+            exception.workspace_id,
+            event_type,
+            json!({
+                "id": exception.id,
+                "date": exception.date,
+                "kind": exception.kind,
+                "timetable_entry_id": exception.timetable_entry_id,
+            }),
+        )
+        .with_topic(Topic::Teacher(exception.teacher_id));
+        for room_id in scheduled_room_id.into_iter().chain(exception.room_id) {
+            message = message.with_topic(Topic::Room(room_id));
+        }
+        self.outbox.publish(conn, message).await
+    }
+}
+
+fn clean_reason(reason: Option<String>) -> Option<String> {
+    reason.map(|r| r.trim().to_string()).filter(|r| !r.is_empty())
+}
@@ -0,0 +1,436 @@
+use chrono::{NaiveDate, NaiveTime, Utc};
+use serde_json::json;
+use sqlx::PgConnection;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    ExceptionLesson, LessonException, LessonExceptionKind, LessonSwap, LessonSwapStatus, NotificationType, SwapSummary,
+};
+use crate::repository::{LessonExceptionRepository, LessonSwapRepository};
+use crate::service::notifications::NewNotification;
+use crate::service::{LessonExceptionService, NotificationService, OutboxService};
+use crate::ws::{self, Topic, WebSocketMessage};
+
+/// A teacher's proposal to swap one of their lessons with a colleague's.
+pub struct SwapProposal {
+    pub timetable_entry_id: Uuid,
+    pub date: NaiveDate,
+    pub colleague_entry_id: Uuid,
+    pub colleague_date: NaiveDate,
+    pub reason: Option<String>,
+}
+
+/// Lesson swaps between two teachers: proposed by one, accepted by the
+/// other and approved by a coordinator, at which point each lesson gets a
+/// teacher change for its date.
+pub struct LessonSwapService {
+    repo: LessonSwapRepository,
+    exception_repo: LessonExceptionRepository,
+    exceptions: Arc<LessonExceptionService>,
+    outbox: Arc<OutboxService>,
+    notifications: Arc<NotificationService>,
+}
+
+impl LessonSwapService {
+    pub fn new(
+        repo: LessonSwapRepository,
+        exception_repo: LessonExceptionRepository,
+        exceptions: Arc<LessonExceptionService>,
+        outbox: Arc<OutboxService>,
+        notifications: Arc<NotificationService>,
+    ) -> Self {
+        Self {
+            repo,
+            exception_repo,
+            exceptions,
+            outbox,
+            notifications,
+        }
+    }
+
+    /// Proposes swapping the requester's lesson on `date` with the lesson
+    /// of a colleague on `colleague_date`. The colleague is whoever teaches
+    /// that lesson.
+    pub async fn propose(&self, workspace_id: Uuid, requester_id: Uuid, proposal: SwapProposal) -> AppResult<LessonSwap> {
+        let today = Utc::now().date_naive();
+        if proposal.date < today || proposal.colleague_date < today {
+            return Err(AppError::BadRequest("Lessons in the past cannot be swapped".to_string()));
+        }
+        if proposal.timetable_entry_id == proposal.colleague_entry_id && proposal.date == proposal.colleague_date {
+            return Err(AppError::BadRequest("A lesson cannot be swapped with itself".to_string()));
+        }
+
+        let mut tx = self.outbox.begin().await?;
+        let (_, lesson) = self.exceptions
+            .find_lesson(&mut tx, workspace_id, proposal.timetable_entry_id, proposal.date)
+            .await?;
+        if lesson.teacher_id != requester_id {
+            return Err(AppError::Forbidden("You can only swap your own lessons".to_string()));
+        }
+        let (_, colleague_lesson) = self.exceptions
+            .find_lesson(&mut tx, workspace_id, proposal.colleague_entry_id, proposal.colleague_date)
+            .await?;
+        if colleague_lesson.teacher_id == requester_id {
+            return Err(AppError::BadRequest("Both lessons are yours already".to_string()));
+        }
+        self.check_swappable(&mut tx, proposal.timetable_entry_id, proposal.date).await?;
+        self.check_swappable(&mut tx, proposal.colleague_entry_id, proposal.colleague_date).await?;
+
+        let swap = self.repo.create(&mut tx, LessonSwap {
+            id: Uuid::new_v4(),
+            workspace_id,
+            requester_id,
+            requester_entry_id: proposal.timetable_entry_id,
+            requester_date: proposal.date,
+            colleague_id: colleague_lesson.teacher_id,
+            colleague_entry_id: proposal.colleague_entry_id,
+            colleague_date: proposal.colleague_date,
+            status: LessonSwapStatus::Proposed,
+            reason: proposal.reason.map(|r| r.trim().to_string()).filter(|r| !r.is_empty()),
+            decided_by: None,
+            decision_note: None,
+            requester_exception_id: None,
+            colleague_exception_id: None,
+            responded_at: None,
+            decided_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }).await?;
+
+        let summary = self.summary(&mut tx, swap.id).await?;
+        self.notify(&mut tx, &swap, swap.colleague_id, format!(
+            "{} would like to swap {} for your {}",
+            summary.requester_name,
+            lesson_label(&summary.requester_course, swap.requester_date, summary.requester_start),
+            lesson_label(&summary.colleague_course, swap.colleague_date, summary.colleague_start),
+        ), swap.reason.clone()).await?;
+        self.publish(&mut tx, ws::LESSON_SWAP_PROPOSED, &swap).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(swap)
+    }
+
+    /// The colleague's answer. Once accepted, the swap waits for a
+    /// coordinator.
+    pub async fn respond(&self, workspace_id: Uuid, id: Uuid, colleague_id: Uuid, accept: bool) -> AppResult<LessonSwap> {
+        let mut tx = self.outbox.begin().await?;
+        let mut swap = self.repo.lock(&mut tx, workspace_id, id).await?
+            .ok_or(AppError::NotFound)?;
+        if swap.colleague_id != colleague_id {
+            return Err(AppError::Forbidden("This swap was not proposed to you".to_string()));
+        }
+        if swap.status != LessonSwapStatus::Proposed {
+            return Err(AppError::Conflict("The swap is no longer open".to_string()));
+        }
+
+        swap.status = if accept { LessonSwapStatus::Accepted } else { LessonSwapStatus::Declined };
+        swap.responded_at = Some(Utc::now());
+        let swap = self.repo.update(&mut tx, &swap).await?;
+
+        let summary = self.summary(&mut tx, swap.id).await?;
+        let lessons = swap_label(&swap, &summary);
+        if accept {
+            self.notify(&mut tx, &swap, swap.requester_id, format!(
+                "{} agreed to swap {}; waiting for approval",
+                summary.colleague_name, lessons
+            ), None).await?;
+            for admin_id in self.repo.find_admins(&mut tx, workspace_id).await? {
+                self.notify(&mut tx, &swap, admin_id, format!(
+                    "{} and {} want to swap {}",
+                    summary.requester_name, summary.colleague_name, lessons
+                ), swap.reason.clone()).await?;
+            }
+        } else {
+            self.notify(&mut tx, &swap, swap.requester_id, format!(
+                "{} declined to swap {}",
+                summary.colleague_name, lessons
+            ), None).await?;
+        }
+        self.publish(&mut tx, ws::LESSON_SWAP_UPDATED, &swap).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(swap)
+    }
+
+    /// Approves an accepted swap and applies it as a teacher change on each
+    /// lesson. Both lessons must still be held as proposed, and each
+    /// teacher must be available and otherwise free at the lesson they take
+    /// over.
+    pub async fn approve(&self, workspace_id: Uuid, id: Uuid, decided_by: Uuid, note: Option<String>) -> AppResult<LessonSwap> {
+        let mut tx = self.outbox.begin().await?;
+        let mut swap = self.repo.lock(&mut tx, workspace_id, id).await?
+            .ok_or(AppError::NotFound)?;
+        if swap.status != LessonSwapStatus::Accepted {
+            return Err(AppError::Conflict("Only swaps the colleague accepted can be approved".to_string()));
+        }
+
+        let summary = self.summary(&mut tx, swap.id).await?;
+        let (requester_timetable_id, lesson) = self.exceptions
+            .find_lesson(&mut tx, workspace_id, swap.requester_entry_id, swap.requester_date)
+            .await?;
+        let (colleague_timetable_id, colleague_lesson) = self.exceptions
+            .find_lesson(&mut tx, workspace_id, swap.colleague_entry_id, swap.colleague_date)
+            .await?;
+        if lesson.teacher_id != swap.requester_id || colleague_lesson.teacher_id != swap.colleague_id {
+            return Err(AppError::Conflict("The lessons changed since the swap was proposed".to_string()));
+        }
+        self.check_covered(&mut tx, swap.requester_entry_id, swap.requester_date).await?;
+        self.check_covered(&mut tx, swap.colleague_entry_id, swap.colleague_date).await?;
+        self.check_available(&mut tx, workspace_id, swap.colleague_id, &summary.colleague_name, swap.requester_date, &lesson).await?;
+        self.check_available(&mut tx, workspace_id, swap.requester_id, &summary.requester_name, swap.colleague_date, &colleague_lesson).await?;
+
+        let reason = swap.reason.clone().or_else(|| Some("Lesson swap".to_string()));
+        let requester_exception = self.exception_repo.create(&mut tx, LessonException {
+            id: Uuid::new_v4(),
+            workspace_id,
+            published_timetable_id: requester_timetable_id,
+            date: swap.requester_date,
+            kind: LessonExceptionKind::TeacherChange,
+            timetable_entry_id: Some(swap.requester_entry_id),
+            course_id: lesson.course_id,
+            teacher_id: swap.colleague_id,
+            room_id: None,
+            start_time: lesson.start_time,
+            end_time: lesson.end_time,
+            reason: reason.clone(),
+            created_by: Some(decided_by),
+            created_at: Utc::now(),
+        }).await?;
+        let colleague_exception = self.exception_repo.create(&mut tx, LessonException {
+            id: Uuid::new_v4(),
+            workspace_id,
+            published_timetable_id: colleague_timetable_id,
+            date: swap.colleague_date,
+            kind: LessonExceptionKind::TeacherChange,
+            timetable_entry_id: Some(swap.colleague_entry_id),
+            course_id: colleague_lesson.course_id,
+            teacher_id: swap.requester_id,
+            room_id: None,
+            start_time: colleague_lesson.start_time,
+            end_time: colleague_lesson.end_time,
+            reason,
+            created_by: Some(decided_by),
+            created_at: Utc::now(),
+        }).await?;
+
+        // With both changes in place, the lesson each teacher takes over
+        // must be the only one they teach at that time
+        self.check_free(&mut tx, workspace_id, &summary.colleague_name, &requester_exception).await?;
+        self.check_free(&mut tx, workspace_id, &summary.requester_name, &colleague_exception).await?;
+
+        swap.status = LessonSwapStatus::Approved;
+        swap.decided_by = Some(decided_by);
+        swap.decision_note = note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+        swap.decided_at = Some(Utc::now());
+        swap.requester_exception_id = Some(requester_exception.id);
+        swap.colleague_exception_id = Some(colleague_exception.id);
+        let swap = self.repo.update(&mut tx, &swap).await?;
+
+        self.exceptions.publish(&mut tx, ws::LESSON_EXCEPTION_CREATED, &requester_exception, Some(lesson.room_id)).await?;
+        self.exceptions.publish(&mut tx, ws::LESSON_EXCEPTION_CREATED, &colleague_exception, Some(colleague_lesson.room_id)).await?;
+        let title = format!("Your swap of {} was approved", swap_label(&swap, &summary));
+        for user_id in [swap.requester_id, swap.colleague_id] {
+            self.notify(&mut tx, &swap, user_id, title.clone(), swap.decision_note.clone()).await?;
+        }
+        self.publish(&mut tx, ws::LESSON_SWAP_UPDATED, &swap).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(swap)
+    }
+
+    /// Turns down a swap that has not been applied.
+    pub async fn reject(&self, workspace_id: Uuid, id: Uuid, decided_by: Uuid, note: Option<String>) -> AppResult<LessonSwap> {
+        let mut tx = self.outbox.begin().await?;
+        let mut swap = self.repo.lock(&mut tx, workspace_id, id).await?
+            .ok_or(AppError::NotFound)?;
+        if !matches!(swap.status, LessonSwapStatus::Proposed | LessonSwapStatus::Accepted) {
+            return Err(AppError::Conflict("The swap is no longer open".to_string()));
+        }
+
+        swap.status = LessonSwapStatus::Rejected;
+        swap.decided_by = Some(decided_by);
+        swap.decision_note = note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+        swap.decided_at = Some(Utc::now());
+        let swap = self.repo.update(&mut tx, &swap).await?;
+
+        let summary = self.summary(&mut tx, swap.id).await?;
+        let title = format!("Your swap of {} was not approved", swap_label(&swap, &summary));
+        for user_id in [swap.requester_id, swap.colleague_id] {
+            self.notify(&mut tx, &swap, user_id, title.clone(), swap.decision_note.clone()).await?;
+        }
+        self.publish(&mut tx, ws::LESSON_SWAP_UPDATED, &swap).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(swap)
+    }
+
+    /// Withdraws a swap. The requester can withdraw it until it is
+    /// approved; admins can also undo an approved swap, which removes its
+    /// teacher changes.
+    pub async fn cancel(&self, workspace_id: Uuid, id: Uuid, user_id: Uuid, is_admin: bool) -> AppResult<LessonSwap> {
+        let mut tx = self.outbox.begin().await?;
+        let mut swap = self.repo.lock(&mut tx, workspace_id, id).await?
+            .ok_or(AppError::NotFound)?;
+        if !is_admin && swap.requester_id != user_id {
+            return Err(AppError::Forbidden("Only the teacher who proposed the swap can withdraw it".to_string()));
+        }
+        match swap.status {
+            LessonSwapStatus::Proposed | LessonSwapStatus::Accepted => {}
+            LessonSwapStatus::Approved if is_admin => {
+                for exception_id in [swap.requester_exception_id, swap.colleague_exception_id].into_iter().flatten() {
+                    if let Some(exception) = self.exception_repo.delete(&mut tx, workspace_id, exception_id).await? {
+                        self.exceptions.publish(&mut tx, ws::LESSON_EXCEPTION_DELETED, &exception, None).await?;
+                    }
+                }
+                swap.requester_exception_id = None;
+                swap.colleague_exception_id = None;
+            }
+            LessonSwapStatus::Approved => {
+                return Err(AppError::Forbidden("Only admins can undo an approved swap".to_string()));
+            }
+            _ => return Err(AppError::Conflict("The swap is no longer open".to_string())),
+        }
+
+        let was_approved = swap.status == LessonSwapStatus::Approved;
+        swap.status = LessonSwapStatus::Cancelled;
+        let swap = self.repo.update(&mut tx, &swap).await?;
+
+        let summary = self.summary(&mut tx, swap.id).await?;
+        let lessons = swap_label(&swap, &summary);
+        let (title, recipients) = if was_approved {
+            (format!("Your swap of {} was undone", lessons), vec![swap.requester_id, swap.colleague_id])
+        } else {
+            (format!("{} withdrew the swap of {}", summary.requester_name, lessons), vec![swap.colleague_id])
+        };
+        for recipient in recipients {
+            self.notify(&mut tx, &swap, recipient, title.clone(), None).await?;
+        }
+        self.publish(&mut tx, ws::LESSON_SWAP_UPDATED, &swap).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(swap)
+    }
+
+    /// Swaps of the workspace; teachers only see the ones they are part of.
+    pub async fn get_swaps(
+        &self,
+        workspace_id: Uuid,
+        status: Option<LessonSwapStatus>,
+        teacher_id: Option<Uuid>,
+    ) -> AppResult<Vec<LessonSwap>> {
+        self.repo.find_all(workspace_id, status, teacher_id).await
+    }
+
+    /// A lesson can only be offered in a swap when no substitute is
+    /// arranged for it and no other swap is pending for it.
+    async fn check_swappable(&self, conn: &mut PgConnection, timetable_entry_id: Uuid, date: NaiveDate) -> AppResult<()> {
+        self.check_covered(&mut *conn, timetable_entry_id, date).await?;
+        if self.repo.is_in_open_swap(&mut *conn, timetable_entry_id, date).await? {
+            return Err(AppError::Conflict(format!("The lesson on {} is already part of an open swap", date)));
+        }
+
+        Ok(())
+    }
+
+    async fn check_covered(&self, conn: &mut PgConnection, timetable_entry_id: Uuid, date: NaiveDate) -> AppResult<()> {
+        if self.repo.has_substitution(conn, timetable_entry_id, date).await? {
+            return Err(AppError::Conflict(format!("A substitute is already arranged for the lesson on {}", date)));
+        }
+
+        Ok(())
+    }
+
+    async fn check_available(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        teacher_id: Uuid,
+        teacher_name: &str,
+        date: NaiveDate,
+        lesson: &ExceptionLesson,
+    ) -> AppResult<()> {
+        if !self.exception_repo.is_available(conn, workspace_id, teacher_id, date, lesson.start_time, lesson.end_time).await? {
+            return Err(AppError::Conflict(format!(
+                "{} is not available at {} on {}",
+                teacher_name,
+                lesson.start_time.format("%H:%M"),
+                date
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn check_free(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        teacher_name: &str,
+        exception: &LessonException,
+    ) -> AppResult<()> {
+        let lessons = self.exception_repo
+            .count_teacher_lessons(conn, workspace_id, exception.teacher_id, exception.date, exception.start_time, exception.end_time)
+            .await?;
+        if lessons > 1 {
+            return Err(AppError::Conflict(format!(
+                "{} already teaches at {} on {}",
+                teacher_name,
+                exception.start_time.format("%H:%M"),
+                exception.date
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn summary(&self, conn: &mut PgConnection, id: Uuid) -> AppResult<SwapSummary> {
+        self.repo.find_summary(conn, id).await?.ok_or(AppError::NotFound)
+    }
+
+    async fn notify(
+        &self,
+        conn: &mut PgConnection,
+        swap: &LessonSwap,
+        user_id: Uuid,
+        title: String,
+        body: Option<String>,
+    ) -> AppResult<()> {
+        self.notifications
+            .notify(conn, swap.workspace_id, NewNotification {
+                user_id,
+                notification_type: NotificationType::LessonSwap,
+                title,
+                body,
+                entity_id: Some(swap.id),
+                link: Some(format!("/lesson-swaps/{}", swap.id)),
+                email: None,
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn publish(&self, conn: &mut PgConnection, event_type: &str, swap: &LessonSwap) -> AppResult<()> {
+        self.outbox.publish(conn, WebSocketMessage::new(
+            swap.workspace_id,
+            event_type,
+            json!({ "id": swap.id, "status": swap.status }),
+        )
+        .with_topic(Topic::Teacher(swap.requester_id))
+        .with_topic(Topic::Teacher(swap.colleague_id))).await
+    }
+}
+
+fn lesson_label(course_name: &str, date: NaiveDate, start_time: NaiveTime) -> String {
+    format!("{} on {} at {}", course_name, date.format("%a %Y-%m-%d"), start_time.format("%H:%M"))
+}
+
+fn swap_label(swap: &LessonSwap, summary: &SwapSummary) -> String {
+    format!(
+        "{} and {}",
+        lesson_label(&summary.requester_course, swap.requester_date, summary.requester_start),
+        lesson_label(&summary.colleague_course, swap.colleague_date, summary.colleague_start),
+    )
+}
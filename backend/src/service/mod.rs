@@ -6,14 +6,25 @@ pub mod rooms;
 pub mod time_slots;
 pub mod timetable_entries;
 pub mod substitutions;
+pub mod substitution_stats;
+pub mod teacher_preferences;
+pub mod teacher_qualifications;
+pub mod student_groups;
+pub mod enrollments;
+pub mod room_features;
 pub mod notifications;
+pub mod occurrences;
+pub mod lesson_exceptions;
+pub mod lesson_swaps;
 pub mod snapshots;
 pub mod availability;
+pub mod availability_rounds;
 pub mod conflicts;
 pub mod draft_timetables;
 pub mod published_timetables;
 pub mod draft_entries;
 pub mod workspace;
+pub mod outbox;
 
 pub use auth::AuthService;
 pub use users::UserService;
@@ -23,11 +34,22 @@ pub use rooms::RoomService;
 pub use time_slots::TimeSlotService;
 pub use timetable_entries::TimetableEntryService;
 pub use substitutions::SubstitutionService;
+pub use substitution_stats::SubstitutionStatsService;
+pub use teacher_preferences::TeacherPreferenceService;
+pub use teacher_qualifications::TeacherQualificationService;
+pub use student_groups::StudentGroupService;
+pub use enrollments::EnrollmentService;
+pub use room_features::RoomFeatureService;
 pub use notifications::NotificationService;
+pub use occurrences::OccurrenceService;
+pub use lesson_exceptions::LessonExceptionService;
+pub use lesson_swaps::LessonSwapService;
 pub use snapshots::SnapshotService;
 pub use availability::AvailabilityService;
+pub use availability_rounds::AvailabilityRoundService;
 pub use conflicts::ConflictService;
 pub use draft_timetables::DraftTimetableService;
 pub use published_timetables::PublishedTimetableService;
 pub use draft_entries::DraftEntryService;
 pub use workspace::WorkspaceService;
+pub use outbox::OutboxService;
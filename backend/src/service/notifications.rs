@@ -1,23 +1,414 @@
+use chrono::{DateTime, Duration as ChronoDuration, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde_json::{json, Value};
+use sqlx::PgConnection;
+use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
-use tracing::info;
 
-#[derive(Clone, Default)]
-pub struct NotificationService;
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::mail::{escape_html, EmailTemplate, QueuedEmail};
+use crate::models::{
+    Notification, NotificationDelivery, NotificationPreference, NotificationSettings, NotificationType,
+};
+use crate::repository::{NotificationRepository, UserRepository};
+use crate::service::outbox::validate_webhook_url;
+use crate::service::OutboxService;
+use crate::ws::{self, Topic, WebSocketMessage};
+
+const MAX_PAGE_SIZE: i64 = 100;
+const DIGEST_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Webhook event types for notifications sent to a user's own webhook.
+const NOTIFICATION_EVENT: &str = "NOTIFICATION";
+const NOTIFICATION_DIGEST_EVENT: &str = "NOTIFICATION_DIGEST";
+
+/// A notification about to be added to a user's inbox.
+pub struct NewNotification {
+    pub user_id: Uuid,
+    pub notification_type: NotificationType,
+    pub title: String,
+    pub body: Option<String>,
+    pub entity_id: Option<Uuid>,
+    pub link: Option<String>,
+    /// Email sent when the user wants this notification by email right away;
+    /// a generic one built from the title and body is used otherwise.
+    pub email: Option<QueuedEmail>,
+}
+
+/// The per-user in-app inbox. Every change pushes the new unread count to the
+/// user over the realtime channel. Notifications also go out by email and to
+/// the user's webhook, immediately or in a daily digest, as each user chose
+/// per notification type.
+pub struct NotificationService {
+    repo: NotificationRepository,
+    users: UserRepository,
+    outbox: Arc<OutboxService>,
+    config: Arc<Config>,
+}
 
 impl NotificationService {
-    pub fn new() -> Self {
-        Self
+    pub fn new(repo: NotificationRepository, users: UserRepository, outbox: Arc<OutboxService>, config: Arc<Config>) -> Self {
+        Self {
+            repo,
+            users,
+            outbox,
+            config,
+        }
+    }
+
+    /// Delivers a notification as part of the caller's transaction, following
+    /// the recipient's preferences. Returns `None` if they turned this type off.
+    pub async fn notify(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        notification: NewNotification,
+    ) -> AppResult<Option<Notification>> {
+        let preference = self
+            .repo
+            .find_preference(&mut *conn, workspace_id, notification.user_id, notification.notification_type)
+            .await?
+            .unwrap_or_else(|| {
+                NotificationPreference::default_for(workspace_id, notification.user_id, notification.notification_type)
+            });
+
+        let digest = match preference.delivery {
+            NotificationDelivery::Off => return Ok(None),
+            NotificationDelivery::Immediate => false,
+            NotificationDelivery::Digest => true,
+        };
+
+        let created = self
+            .repo
+            .create(
+                &mut *conn,
+                Notification {
+                    id: Uuid::new_v4(),
+                    workspace_id,
+                    user_id: notification.user_id,
+                    notification_type: notification.notification_type,
+                    title: notification.title,
+                    body: notification.body,
+                    entity_id: notification.entity_id,
+                    link: notification.link,
+                    read_at: None,
+                    created_at: Utc::now(),
+                    in_app: preference.in_app,
+                    digest_email: digest && preference.email,
+                    digest_webhook: digest && preference.webhook,
+                },
+            )
+            .await?;
+
+        if created.in_app {
+            self.publish_unread_count(&mut *conn, workspace_id, created.user_id, Some(created.id)).await?;
+        }
+        if digest {
+            return Ok(Some(created));
+        }
+
+        if preference.email {
+            let user = self.users.find_by_id(created.user_id).await?.ok_or(AppError::NotFound)?;
+            let email = notification.email.unwrap_or_else(|| {
+                QueuedEmail::new(EmailTemplate::Notification, json!({
+                    "title": created.title,
+                    "body": created.body.clone().unwrap_or_default(),
+                    "link": self.absolute_link(created.link.as_deref()),
+                }))
+            });
+            self.outbox
+                .queue_email(&mut *conn, Some(workspace_id), &user.email, email)
+                .await?;
+        }
+        if preference.webhook {
+            let settings = self.repo.find_settings(&mut *conn, workspace_id, created.user_id).await?;
+            if let Some(url) = settings.and_then(|s| s.webhook_url) {
+                self.outbox
+                    .send_webhook(conn, workspace_id, &url, NOTIFICATION_EVENT, self.webhook_payload(&created))
+                    .await?;
+            }
+        }
+
+        Ok(Some(created))
+    }
+
+    /// The user's preference for every notification type, with defaults for
+    /// the types they never configured.
+    pub async fn get_preferences(&self, workspace_id: Uuid, user_id: Uuid) -> AppResult<Vec<NotificationPreference>> {
+        let saved = self.repo.find_preferences(workspace_id, user_id).await?;
+
+        Ok(NotificationType::ALL
+            .into_iter()
+            .map(|notification_type| {
+                saved
+                    .iter()
+                    .find(|p| p.notification_type == notification_type)
+                    .cloned()
+                    .unwrap_or_else(|| NotificationPreference::default_for(workspace_id, user_id, notification_type))
+            })
+            .collect())
+    }
+
+    pub async fn set_preference(&self, preference: NotificationPreference) -> AppResult<NotificationPreference> {
+        self.repo.upsert_preference(preference).await
+    }
+
+    pub async fn get_settings(&self, workspace_id: Uuid, user_id: Uuid) -> AppResult<NotificationSettings> {
+        let mut tx = self.outbox.begin().await?;
+        let settings = self.repo.find_settings(&mut tx, workspace_id, user_id).await?;
+        tx.commit().await?;
+
+        Ok(settings.unwrap_or_else(|| NotificationSettings {
+            workspace_id,
+            user_id,
+            timezone: self.config.digest_timezone.clone(),
+            digest_time: self.config.digest_time,
+            webhook_url: None,
+            last_digest_at: None,
+        }))
     }
 
-    pub fn send_substitution_request_notification(&self, substitution_id: Uuid) {
-        info!("Substitution request notification sent for ID: {}", substitution_id);
+    pub async fn update_settings(
+        &self,
+        workspace_id: Uuid,
+        user_id: Uuid,
+        timezone: String,
+        digest_time: NaiveTime,
+        webhook_url: Option<String>,
+    ) -> AppResult<NotificationSettings> {
+        if timezone.parse::<Tz>().is_err() {
+            return Err(AppError::BadRequest(format!("Unknown time zone {}", timezone)));
+        }
+        if let Some(url) = &webhook_url {
+            validate_webhook_url(url).await?;
+        }
+
+        self.repo
+            .upsert_settings(NotificationSettings {
+                workspace_id,
+                user_id,
+                timezone,
+                digest_time,
+                webhook_url,
+                last_digest_at: None,
+            })
+            .await
     }
 
-    pub fn send_substitution_accepted_notification(&self, substitution_id: Uuid) {
-        info!("Substitution accepted notification sent for ID: {}", substitution_id);
+    /// Sends the digests that are due. Returns how many users got one.
+    pub async fn send_due_digests(&self) -> AppResult<usize> {
+        let mut sent = 0;
+        for (workspace_id, user_id) in self.repo.find_digest_recipients().await? {
+            match self.send_digest(workspace_id, user_id, Utc::now()).await {
+                Ok(true) => sent += 1,
+                Ok(false) => {}
+                Err(e) => tracing::error!("Failed to send notification digest to user {}: {:?}", user_id, e),
+            }
+        }
+        Ok(sent)
     }
 
-    pub fn send_substitution_rejected_notification(&self, substitution_id: Uuid) {
-        info!("Substitution rejected notification sent for ID: {}", substitution_id);
+    /// Batches the user's pending notifications into one email and one
+    /// webhook call, once their digest time has passed.
+    async fn send_digest(&self, workspace_id: Uuid, user_id: Uuid, now: DateTime<Utc>) -> AppResult<bool> {
+        let mut tx = self.outbox.begin().await?;
+        let settings = self
+            .repo
+            .lock_settings(&mut tx, workspace_id, user_id, &self.config.digest_timezone, self.config.digest_time)
+            .await?;
+
+        // The first digest goes out at the next scheduled time rather than right away
+        let Some(last_digest_at) = settings.last_digest_at else {
+            self.repo.set_last_digest_at(&mut tx, workspace_id, user_id, now).await?;
+            tx.commit().await?;
+            return Ok(false);
+        };
+        if last_digest_at >= last_scheduled_digest(&settings, now) {
+            return Ok(false);
+        }
+
+        let items = self.repo.take_digest_items(&mut tx, workspace_id, user_id).await?;
+        let (emailed, webhooked): (Vec<_>, Vec<_>) = (
+            items.iter().filter(|n| n.digest_email).collect(),
+            items.iter().filter(|n| n.digest_webhook).collect(),
+        );
+
+        if !emailed.is_empty() {
+            let user = self.users.find_by_id(user_id).await?.ok_or(AppError::NotFound)?;
+            let items_text = emailed
+                .iter()
+                .map(|n| format!("- {}", n.title))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let items_html = emailed
+                .iter()
+                .map(|n| {
+                    format!(
+                        "    <li><a href=\"{}\">{}</a></li>",
+                        escape_html(&self.absolute_link(n.link.as_deref())),
+                        escape_html(&n.title),
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            self.outbox
+                .send_email(&mut tx, Some(workspace_id), &user.email, EmailTemplate::NotificationDigest, json!({
+                    "count": emailed.len(),
+                    "items_text": items_text,
+                    "items_html": items_html,
+                    "link": self.absolute_link(None),
+                }))
+                .await?;
+        }
+        if let (false, Some(url)) = (webhooked.is_empty(), &settings.webhook_url) {
+            let payload = json!({
+                "user_id": user_id,
+                "notifications": webhooked.iter().map(|n| self.webhook_payload(n)).collect::<Vec<_>>(),
+            });
+            self.outbox
+                .send_webhook(&mut tx, workspace_id, url, NOTIFICATION_DIGEST_EVENT, payload)
+                .await?;
+        }
+
+        self.repo.set_last_digest_at(&mut tx, workspace_id, user_id, now).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(!items.is_empty())
     }
+
+    pub fn absolute_link(&self, link: Option<&str>) -> String {
+        format!("{}{}", self.config.frontend_url.trim_end_matches('/'), link.unwrap_or("/notifications"))
+    }
+
+    fn webhook_payload(&self, notification: &Notification) -> Value {
+        json!({
+            "id": notification.id,
+            "user_id": notification.user_id,
+            "notification_type": notification.notification_type,
+            "title": notification.title,
+            "body": notification.body,
+            "entity_id": notification.entity_id,
+            "link": notification.link.as_deref().map(|link| self.absolute_link(Some(link))),
+            "created_at": notification.created_at,
+        })
+    }
+
+    pub async fn get_notifications(
+        &self,
+        workspace_id: Uuid,
+        user_id: Uuid,
+        unread_only: bool,
+        before: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> AppResult<Vec<Notification>> {
+        self.repo
+            .find_for_user(workspace_id, user_id, unread_only, before, limit.clamp(1, MAX_PAGE_SIZE))
+            .await
+    }
+
+    pub async fn get_unread_count(&self, workspace_id: Uuid, user_id: Uuid) -> AppResult<i64> {
+        let mut tx = self.outbox.begin().await?;
+        let count = self.repo.count_unread(&mut tx, workspace_id, user_id).await?;
+        tx.commit().await?;
+        Ok(count)
+    }
+
+    pub async fn mark_read(&self, workspace_id: Uuid, user_id: Uuid, id: Uuid) -> AppResult<Notification> {
+        let mut tx = self.outbox.begin().await?;
+        let notification = self
+            .repo
+            .mark_read(&mut tx, workspace_id, user_id, id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        self.publish_unread_count(&mut tx, workspace_id, user_id, None).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(notification)
+    }
+
+    pub async fn mark_all_read(&self, workspace_id: Uuid, user_id: Uuid) -> AppResult<u64> {
+        let mut tx = self.outbox.begin().await?;
+        let updated = self.repo.mark_all_read(&mut tx, workspace_id, user_id).await?;
+
+        if updated > 0 {
+            self.publish_unread_count(&mut tx, workspace_id, user_id, None).await?;
+        }
+        self.outbox.commit(tx).await?;
+
+        Ok(updated)
+    }
+
+    async fn publish_unread_count(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        user_id: Uuid,
+        notification_id: Option<Uuid>,
+    ) -> AppResult<()> {
+        let unread_count = self.repo.count_unread(&mut *conn, workspace_id, user_id).await?;
+
+        self.outbox.publish(conn, WebSocketMessage::new(
+            workspace_id,
+            ws::NOTIFICATIONS_UNREAD,
+            json!({ "user_id": user_id, "unread_count": unread_count, "notification_id": notification_id }),
+        )
+        .with_topic(Topic::User(user_id))).await
+    }
+}
+
+/// The most recent moment, at or before `now`, at which the user's digest
+/// was scheduled in their time zone.
+fn last_scheduled_digest(settings: &NotificationSettings, now: DateTime<Utc>) -> DateTime<Utc> {
+    let tz: Tz = settings.timezone.parse().unwrap_or(Tz::UTC);
+    let scheduled_on = |date: chrono::NaiveDate| {
+        let local = date.and_time(settings.digest_time);
+        // A digest time skipped by a DST change falls back to its UTC reading
+        tz.from_local_datetime(&local)
+            .earliest()
+            .map(|at| at.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+    };
+
+    let today = now.with_timezone(&tz).date_naive();
+    let scheduled = scheduled_on(today);
+    if scheduled <= now {
+        scheduled
+    } else {
+        scheduled_on(today.pred_opt().unwrap_or(today))
+    }
+}
+
+/// Periodically sends the daily digests whose time has come.
+pub fn spawn_digest_task(service: Arc<NotificationService>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(DIGEST_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            match service.send_due_digests().await {
+                Ok(0) => {}
+                Ok(sent) => tracing::info!("Sent {} notification digests", sent),
+                Err(e) => tracing::error!("Failed to send notification digests: {:?}", e),
+            }
+        }
+    });
+}
+
+/// Periodically drops notifications older than the retention window.
+pub fn spawn_retention_task(repo: NotificationRepository, retention_days: i64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            let cutoff = Utc::now() - ChronoDuration::days(retention_days);
+            match repo.delete_older_than(cutoff).await {
+                Ok(0) => {}
+                Ok(deleted) => tracing::info!("Pruned {} old notifications", deleted),
+                Err(e) => tracing::error!("Failed to prune notifications: {:?}", e),
+            }
+        }
+    });
 }
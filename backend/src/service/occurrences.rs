@@ -0,0 +1,223 @@
+use std::sync::Arc;
+use uuid::Uuid;
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use rand::{RngCore, rng};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use crate::calendar::{self, CalendarEvent};
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::models::{CalendarFeed, DatedException, LessonExceptionKind, LessonOccurrence, SubstitutionStatus};
+use crate::repository::OccurrenceRepository;
+
+/// Longest range a single occurrence query may span.
+const MAX_RANGE_DAYS: i64 = 120;
+/// How far back and ahead iCal feeds reach.
+const FEED_PAST_DAYS: i64 = 28;
+const FEED_FUTURE_DAYS: i64 = 120;
+
+/// Expands the weekly timetable into dated lessons and lays substitutions
+/// and lesson exceptions over the dates they cover.
+pub struct OccurrenceService {
+    repo: OccurrenceRepository,
+    config: Arc<Config>,
+}
+
+impl OccurrenceService {
+    pub fn new(repo: OccurrenceRepository, config: Arc<Config>) -> Self {
+        Self { repo, config }
+    }
+
+    /// Lessons between `from` and `to`, inclusive. With a teacher, only the
+    /// lessons they teach or cover on each date; with a room, only the lessons
+    /// held there.
+    pub async fn get_occurrences(
+        &self,
+        workspace_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+        teacher_id: Option<Uuid>,
+        room_id: Option<Uuid>,
+    ) -> AppResult<Vec<LessonOccurrence>> {
+        if to < from {
+            return Err(AppError::BadRequest("The end date must not be before the start date".to_string()));
+        }
+        if (to - from).num_days() >= MAX_RANGE_DAYS {
+            return Err(AppError::BadRequest(format!("Query at most {} days at a time", MAX_RANGE_DAYS)));
+        }
+
+        self.expand(workspace_id, from, to, teacher_id, room_id).await
+    }
+
+    async fn expand(
+        &self,
+        workspace_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+        teacher_id: Option<Uuid>,
+        room_id: Option<Uuid>,
+    ) -> AppResult<Vec<LessonOccurrence>> {
+        let lessons = self.repo.find_weekly_lessons(workspace_id).await?;
+        let substitutions = self.repo.find_substitutions(workspace_id, from, to).await?;
+        let exceptions = self.repo.find_exceptions(workspace_id, from, to).await?;
+
+        let mut occurrences = Vec::new();
+        for date in from.iter_days().take_while(|date| *date <= to) {
+            let day_of_week = date.weekday().num_days_from_sunday() as i32;
+            for lesson in lessons.iter().filter(|l| l.day_of_week == day_of_week) {
+                let exception = exceptions
+                    .iter()
+                    .find(|x| x.date == date && x.timetable_entry_id == Some(lesson.timetable_entry_id));
+                let (held_in, room_name) = match exception {
+                    Some(x) if x.kind == LessonExceptionKind::Cancellation => continue,
+                    Some(DatedException { kind: LessonExceptionKind::RoomChange, room_id: Some(id), room_name: Some(name), .. }) => {
+                        (*id, name.clone())
+                    }
+                    _ => (lesson.room_id, lesson.room_name.clone()),
+                };
+                // An approved swap hands the lesson to another teacher for the date
+                let (taught_by, teacher_name) = match exception {
+                    Some(x) if x.kind == LessonExceptionKind::TeacherChange => (x.teacher_id, x.teacher_name.clone()),
+                    _ => (lesson.teacher_id, lesson.teacher_name.clone()),
+                };
+
+                // An accepted substitution wins over one still looking for a substitute
+                let substitution = substitutions
+                    .iter()
+                    .filter(|s| {
+                        s.timetable_entry_id == lesson.timetable_entry_id && s.starts_on <= date && s.ends_on >= date
+                    })
+                    .min_by_key(|s| s.status != SubstitutionStatus::Accepted);
+                let accepted = substitution.filter(|s| s.status == SubstitutionStatus::Accepted);
+
+                occurrences.push(LessonOccurrence {
+                    date,
+                    timetable_entry_id: Some(lesson.timetable_entry_id),
+                    course_id: lesson.course_id,
+                    course_name: lesson.course_name.clone(),
+                    teacher_id: taught_by,
+                    teacher_name,
+                    room_id: held_in,
+                    room_name,
+                    start_time: lesson.start_time,
+                    end_time: lesson.end_time,
+                    substitution_id: substitution.map(|s| s.id),
+                    substitution_status: substitution.map(|s| s.status),
+                    substitute_id: accepted.and_then(|s| s.substitute_id),
+                    substitute_name: accepted.and_then(|s| s.substitute_name.clone()),
+                    lesson_exception_id: exception.map(|x| x.id),
+                    exception_kind: exception.map(|x| x.kind),
+                });
+            }
+
+            for extra in exceptions.iter().filter(|x| x.date == date && x.kind == LessonExceptionKind::ExtraLesson) {
+                let (Some(room_id), Some(room_name)) = (extra.room_id, &extra.room_name) else {
+                    continue;
+                };
+                occurrences.push(LessonOccurrence {
+                    date,
+                    timetable_entry_id: None,
+                    course_id: extra.course_id,
+                    course_name: extra.course_name.clone(),
+                    teacher_id: extra.teacher_id,
+                    teacher_name: extra.teacher_name.clone(),
+                    room_id,
+                    room_name: room_name.clone(),
+                    start_time: extra.start_time,
+                    end_time: extra.end_time,
+                    substitution_id: None,
+                    substitution_status: None,
+                    substitute_id: None,
+                    substitute_name: None,
+                    lesson_exception_id: Some(extra.id),
+                    exception_kind: Some(extra.kind),
+                });
+            }
+        }
+
+        occurrences.retain(|o| {
+            teacher_id.is_none_or(|t| o.teacher_id == t || o.substitute_id == Some(t)) && room_id.is_none_or(|r| o.room_id == r)
+        });
+        occurrences.sort_by(|a, b| {
+            (a.date, a.start_time, &a.course_name).cmp(&(b.date, b.start_time, &b.course_name))
+        });
+        Ok(occurrences)
+    }
+
+    /// The teacher's lessons as an iCal calendar. Lessons someone else covers
+    /// show up as cancelled on those dates, and lessons the teacher covers
+    /// for a colleague are included.
+    pub async fn teacher_calendar(&self, workspace_id: Uuid, teacher_id: Uuid) -> AppResult<String> {
+        let today = Utc::now().date_naive();
+        let occurrences = self
+            .expand(
+                workspace_id,
+                today - Duration::days(FEED_PAST_DAYS),
+                today + Duration::days(FEED_FUTURE_DAYS),
+                Some(teacher_id),
+                None,
+            )
+            .await?;
+
+        let events: Vec<CalendarEvent> = occurrences
+            .iter()
+            .map(|occurrence| {
+                let covered_by_other = occurrence.taught_by() != teacher_id;
+                let (summary, description) = match (&occurrence.substitute_name, covered_by_other) {
+                    (Some(substitute), true) => {
+                        (occurrence.course_name.clone(), Some(format!("Covered by {}", substitute)))
+                    }
+                    (Some(_), false) => (
+                        format!("{} (substitution)", occurrence.course_name),
+                        Some(format!("Covering for {}", occurrence.teacher_name)),
+                    ),
+                    (None, _) => (occurrence.course_name.clone(), None),
+                };
+
+                CalendarEvent {
+                    uid: format!(
+                        "{}-{}@nullslot",
+                        occurrence.timetable_entry_id.or(occurrence.lesson_exception_id).unwrap_or_default(),
+                        occurrence.date.format("%Y%m%d")
+                    ),
+                    summary,
+                    location: Some(occurrence.room_name.clone()),
+                    description,
+                    start: occurrence.date.and_time(occurrence.start_time),
+                    end: occurrence.date.and_time(occurrence.end_time),
+                    repeat_until: None,
+                    cancelled: covered_by_other,
+                }
+            })
+            .collect();
+
+        Ok(calendar::to_ics("Timetable", &events, Utc::now()))
+    }
+
+    /// Creates a secret feed URL for the user's calendar app.
+    pub async fn create_feed(&self, workspace_id: Uuid, user_id: Uuid) -> AppResult<String> {
+        let mut token_bytes = [0u8; 32];
+        rng().fill_bytes(&mut token_bytes);
+
+        let feed = self
+            .repo
+            .create_feed(CalendarFeed {
+                token: URL_SAFE_NO_PAD.encode(token_bytes),
+                workspace_id,
+                user_id,
+                created_at: Utc::now(),
+            })
+            .await?;
+
+        Ok(format!("{}/calendar/{}.ics", self.config.public_url.trim_end_matches('/'), feed.token))
+    }
+
+    pub async fn revoke_feeds(&self, workspace_id: Uuid, user_id: Uuid) -> AppResult<u64> {
+        self.repo.delete_feeds(workspace_id, user_id).await
+    }
+
+    /// The calendar behind a feed token.
+    pub async fn feed_calendar(&self, token: &str) -> AppResult<String> {
+        let feed = self.repo.find_feed(token).await?.ok_or(AppError::NotFound)?;
+        self.teacher_calendar(feed.workspace_id, feed.user_id).await
+    }
+}
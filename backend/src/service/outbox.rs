@@ -0,0 +1,462 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{Duration as ChronoDuration, Utc};
+use hmac::{Hmac, Mac};
+use rand::{RngCore, rng};
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::{PgConnection, Postgres, Transaction};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::Url;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::mail::{EmailSender, EmailTemplate, QueuedEmail};
+use crate::models::{CreatedWebhookEndpoint, OutboxChannel, OutboxMessage, WebhookEndpoint};
+use crate::repository::OutboxRepository;
+use crate::ws::{Broadcaster, Topic, WebSocketMessage};
+
+const BATCH_SIZE: i64 = 50;
+const MAX_ATTEMPTS: i32 = 10;
+const LEASE: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_BACKOFF_SECS: i64 = 3600;
+const RETENTION_DAYS: i64 = 7;
+
+/// Records side effects (realtime events, webhooks, emails) in the same
+/// transaction as the change that caused them. A dispatcher delivers them
+/// after commit, retrying until they succeed.
+pub struct OutboxService {
+    repo: OutboxRepository,
+    wake: Notify,
+}
+
+impl OutboxService {
+    pub fn new(repo: OutboxRepository) -> Self {
+        Self {
+            repo,
+            wake: Notify::new(),
+        }
+    }
+
+    pub async fn begin(&self) -> AppResult<Transaction<'static, Postgres>> {
+        self.repo.begin().await
+    }
+
+    /// Commits the transaction and wakes the dispatcher so the queued side
+    /// effects go out right away.
+    pub async fn commit(&self, tx: Transaction<'static, Postgres>) -> AppResult<()> {
+        tx.commit().await?;
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Queues a realtime event along with a copy for every webhook endpoint of
+    /// the workspace, unless the event is addressed to particular users.
+    pub async fn publish(&self, conn: &mut PgConnection, msg: WebSocketMessage) -> AppResult<()> {
+        let dedupe_key = Uuid::new_v4().to_string();
+        let payload = serde_json::to_value(&msg)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize event: {}", e))?;
+
+        self.repo
+            .enqueue(&mut *conn, Some(msg.workspace_id), OutboxChannel::WebSocket, &msg.event_type, &payload, None, &dedupe_key)
+            .await?;
+
+        // Events addressed to single users are not for third parties
+        if msg.topics.iter().any(|topic| matches!(topic.parse(), Ok(Topic::User(_)))) {
+            return Ok(());
+        }
+        self.repo
+            .enqueue_webhooks(conn, msg.workspace_id, &msg.event_type, &msg.payload, &dedupe_key)
+            .await
+    }
+
+    /// Queues an email; it is rendered from `template` in the default
+    /// locale when the dispatcher sends it.
+    pub async fn send_email(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Option<Uuid>,
+        to: &str,
+        template: EmailTemplate,
+        vars: serde_json::Value,
+    ) -> AppResult<()> {
+        self.queue_email(conn, workspace_id, to, QueuedEmail::new(template, vars)).await
+    }
+
+    pub async fn queue_email(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Option<Uuid>,
+        to: &str,
+        email: QueuedEmail,
+    ) -> AppResult<()> {
+        let dedupe_key = Uuid::new_v4().to_string();
+        let payload = serde_json::to_value(&email)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize email: {}", e))?;
+
+        self.repo
+            .enqueue(conn, workspace_id, OutboxChannel::Email, email.template.name(), &payload, Some(to), &dedupe_key)
+            .await
+    }
+
+    /// Queues a single webhook call to `url`, outside the workspace's
+    /// registered endpoints.
+    pub async fn send_webhook(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        url: &str,
+        event_type: &str,
+        payload: serde_json::Value,
+    ) -> AppResult<()> {
+        let dedupe_key = Uuid::new_v4().to_string();
+
+        self.repo
+            .enqueue(conn, Some(workspace_id), OutboxChannel::Webhook, event_type, &payload, Some(url), &dedupe_key)
+            .await
+    }
+
+    pub async fn create_webhook_endpoint(&self, workspace_id: Uuid, url: String) -> AppResult<CreatedWebhookEndpoint> {
+        validate_webhook_url(&url).await?;
+
+        let mut secret = [0u8; 32];
+        rng().fill_bytes(&mut secret);
+        let endpoint = WebhookEndpoint {
+            id: Uuid::new_v4(),
+            workspace_id,
+            url,
+            secret: URL_SAFE_NO_PAD.encode(secret),
+            is_active: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+
+        let endpoint = self.repo.create_webhook_endpoint(endpoint).await?;
+        Ok(CreatedWebhookEndpoint {
+            secret: endpoint.secret.clone(),
+            endpoint,
+        })
+    }
+
+    pub async fn get_webhook_endpoints(&self, workspace_id: Uuid) -> AppResult<Vec<WebhookEndpoint>> {
+        self.repo.get_webhook_endpoints(workspace_id).await
+    }
+
+    pub async fn delete_webhook_endpoint(&self, workspace_id: Uuid, id: Uuid) -> AppResult<bool> {
+        self.repo.delete_webhook_endpoint(workspace_id, id).await
+    }
+}
+
+/// Checks that a webhook URL is http(s) and that its host resolves only to
+/// public addresses, so webhooks cannot be aimed at the server's own
+/// network.
+pub(crate) async fn validate_webhook_url(url: &str) -> AppResult<()> {
+    let parsed = Url::parse(url).map_err(|_| AppError::BadRequest("Invalid webhook URL".to_string()))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(AppError::BadRequest("Webhook URL must use http or https".to_string()));
+    }
+    let port = parsed.port_or_known_default().unwrap_or(443);
+    let host = parsed.host_str().ok_or_else(|| AppError::BadRequest("Webhook URL needs a host".to_string()))?;
+    let public = match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) => is_public_address(ip),
+        Err(_) => public_addrs(host, port).await.is_ok(),
+    };
+    if !public {
+        return Err(AppError::BadRequest("Webhook URL must point to a public address".to_string()));
+    }
+    Ok(())
+}
+
+/// Resolves `host`, failing unless every address it resolves to is public.
+async fn public_addrs(host: &str, port: u16) -> anyhow::Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    if addrs.is_empty() {
+        anyhow::bail!("{} does not resolve", host);
+    }
+    if addrs.iter().any(|addr| !is_public_address(addr.ip())) {
+        anyhow::bail!("{} resolves to a non-public address", host);
+    }
+    Ok(addrs)
+}
+
+/// Loopback, private, link-local, unique-local and other internal ranges
+/// are off limits for webhooks.
+fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // Shared address space used for carrier-grade NAT
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public_address(IpAddr::V4(v4)),
+            None => !(ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local()),
+        },
+    }
+}
+
+/// `sha256=` followed by the hex HMAC-SHA256 of a webhook body under the
+/// endpoint's secret.
+fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Resolves hosts for webhook deliveries, refusing internal addresses so a
+/// name that passed validation cannot be pointed at one later.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = public_addrs(&host, 0).await.map_err(Into::<Box<dyn std::error::Error + Send + Sync>>::into)?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Delivers queued outbox messages. Several instances may run the dispatcher
+/// side by side; claimed messages are leased so each goes out once per attempt.
+pub fn spawn_dispatcher(outbox: Arc<OutboxService>, broadcaster: Arc<Broadcaster>, emails: EmailSender) {
+    let dispatcher = Dispatcher::new(outbox.repo.clone(), broadcaster, emails);
+
+    let prune_repo = outbox.repo.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let cutoff = Utc::now() - ChronoDuration::days(RETENTION_DAYS);
+            match prune_repo.delete_delivered_before(cutoff).await {
+                Ok(0) => {}
+                Ok(deleted) => tracing::info!("Pruned {} delivered outbox messages", deleted),
+                Err(e) => tracing::error!("Failed to prune outbox: {:?}", e),
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        loop {
+            match dispatcher.dispatch_due().await {
+                // A full batch means more may be waiting
+                Ok(claimed) if claimed as i64 == BATCH_SIZE => continue,
+                Ok(_) => {}
+                Err(e) => tracing::error!("Outbox dispatch failed: {:?}", e),
+            }
+
+            tokio::select! {
+                _ = outbox.wake.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+struct Dispatcher {
+    repo: OutboxRepository,
+    broadcaster: Arc<Broadcaster>,
+    emails: EmailSender,
+    http: reqwest::Client,
+}
+
+impl Dispatcher {
+    fn new(repo: OutboxRepository, broadcaster: Arc<Broadcaster>, emails: EmailSender) -> Self {
+        Self {
+            repo,
+            broadcaster,
+            emails,
+            http: reqwest::Client::builder()
+                .dns_resolver(PublicResolver)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("Failed to build the webhook client"),
+        }
+    }
+
+    async fn dispatch_due(&self) -> AppResult<usize> {
+        let lease_until = Utc::now() + ChronoDuration::from_std(LEASE).unwrap_or_default();
+        let messages = self.repo.claim_due(BATCH_SIZE, MAX_ATTEMPTS, lease_until).await?;
+        let claimed = messages.len();
+
+        for msg in messages {
+            match self.deliver(&msg).await {
+                Ok(()) => self.repo.mark_delivered(msg.id).await?,
+                Err(e) => {
+                    let backoff = (1i64 << msg.attempts.clamp(0, 12)).min(MAX_BACKOFF_SECS);
+                    if msg.attempts >= MAX_ATTEMPTS {
+                        tracing::error!("Giving up on outbox message {} after {} attempts: {:#}", msg.id, msg.attempts, e);
+                    } else {
+                        tracing::warn!("Outbox message {} failed (attempt {}): {:#}", msg.id, msg.attempts, e);
+                    }
+                    self.repo
+                        .mark_failed(msg.id, &format!("{:#}", e), Utc::now() + ChronoDuration::seconds(backoff))
+                        .await?;
+                }
+            }
+        }
+
+        Ok(claimed)
+    }
+
+    async fn deliver(&self, msg: &OutboxMessage) -> anyhow::Result<()> {
+        match msg.channel {
+            OutboxChannel::WebSocket => {
+                let event: WebSocketMessage = serde_json::from_value(msg.payload.clone())
+                    .map_err(|e| anyhow::anyhow!("Malformed outbox event: {}", e))?;
+                self.broadcaster.broadcast_once(event, &msg.dedupe_key).await;
+            }
+            OutboxChannel::Webhook => {
+                let url = msg.target.as_deref().ok_or_else(|| anyhow::anyhow!("Webhook message without a URL"))?;
+                validate_webhook_url(url).await.map_err(|e| anyhow::anyhow!("Refusing webhook to {}: {}", url, e))?;
+                let request = self.webhook_request(msg, url).await?;
+                self.http
+                    .execute(request)
+                    .await
+                    .and_then(|res| res.error_for_status())
+                    .map_err(|e| anyhow::anyhow!("Webhook delivery failed: {}", e))?;
+            }
+            OutboxChannel::Email => self.emails.deliver(msg).await?,
+        }
+
+        Ok(())
+    }
+
+    async fn webhook_request(&self, msg: &OutboxMessage, url: &str) -> anyhow::Result<reqwest::Request> {
+        let body = serde_json::to_vec(&json!({
+            "id": msg.dedupe_key,
+            "workspace_id": msg.workspace_id,
+            "event_type": msg.event_type,
+            "payload": msg.payload,
+            "created_at": msg.created_at,
+        }))?;
+        let mut request = self
+            .http
+            .post(url)
+            .header("X-Nullslot-Delivery", &msg.dedupe_key)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .timeout(Duration::from_secs(10));
+        // Endpoints sign the body so receivers can check it came from us
+        if let Some(endpoint_id) = msg.webhook_endpoint_id {
+            let secret = self
+                .repo
+                .find_webhook_secret(endpoint_id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Webhook endpoint {} no longer exists", endpoint_id))?;
+            request = request.header("X-Nullslot-Signature", signature(&secret, &body));
+        }
+        Ok(request.body(body).build()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::mail::{MemoryMailer, Templates};
+    use crate::test_support;
+    use crate::ws;
+
+    fn dispatcher(pool: &PgPool, broadcaster: Arc<Broadcaster>) -> Dispatcher {
+        let emails = EmailSender::new(Arc::new(MemoryMailer::new()), Templates::new("en"));
+        Dispatcher::new(OutboxRepository::new(pool.clone()), broadcaster, emails)
+    }
+
+    fn draft_updated(workspace_id: Uuid) -> WebSocketMessage {
+        WebSocketMessage::new(workspace_id, ws::DRAFT_UPDATED, json!({ "id": Uuid::new_v4() }))
+    }
+
+    #[test]
+    fn signature_is_the_hmac_sha256_of_the_body() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[sqlx::test]
+    async fn committed_events_are_broadcast_once(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let outbox = test_support::outbox(&pool);
+        let broadcaster = Arc::new(Broadcaster::new(16));
+        let mut rx = broadcaster.subscribe(workspace_id);
+        let dispatcher = dispatcher(&pool, broadcaster.clone());
+
+        let mut tx = pool.begin().await.unwrap();
+        outbox.publish(&mut tx, draft_updated(workspace_id)).await.unwrap();
+        // Nothing goes out until the change commits
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+        tx.commit().await.unwrap();
+
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+        assert_eq!(rx.try_recv().unwrap().event_type, ws::DRAFT_UPDATED);
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[sqlx::test]
+    async fn webhooks_are_signed_with_the_endpoint_secret(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let outbox = test_support::outbox(&pool);
+        let dispatcher = dispatcher(&pool, Arc::new(Broadcaster::new(16)));
+        let endpoint = outbox
+            .create_webhook_endpoint(workspace_id, "https://93.184.216.34/hooks".to_string())
+            .await
+            .unwrap();
+
+        let mut tx = pool.begin().await.unwrap();
+        outbox.publish(&mut tx, draft_updated(workspace_id)).await.unwrap();
+        tx.commit().await.unwrap();
+
+        let messages = dispatcher.repo.claim_due(BATCH_SIZE, MAX_ATTEMPTS, Utc::now()).await.unwrap();
+        let webhook = messages.iter().find(|msg| msg.channel == OutboxChannel::Webhook).unwrap();
+        let request = dispatcher.webhook_request(webhook, &endpoint.endpoint.url).await.unwrap();
+
+        let body = request.body().and_then(|body| body.as_bytes()).unwrap();
+        let header = request.headers()["X-Nullslot-Signature"].to_str().unwrap();
+        let sent = hex::decode(header.strip_prefix("sha256=").unwrap()).unwrap();
+        let mut mac = Hmac::<Sha256>::new_from_slice(endpoint.secret.as_bytes()).unwrap();
+        mac.update(body);
+        assert!(mac.verify_slice(&sent).is_ok());
+
+        assert_eq!(request.headers()["X-Nullslot-Delivery"], webhook.dedupe_key.as_str());
+        let body: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(body["event_type"], ws::DRAFT_UPDATED);
+    }
+
+    #[sqlx::test]
+    async fn failed_deliveries_are_retried_later(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let outbox = test_support::outbox(&pool);
+        let dispatcher = dispatcher(&pool, Arc::new(Broadcaster::new(16)));
+
+        let mut conn = pool.acquire().await.unwrap();
+        outbox
+            .send_webhook(&mut conn, workspace_id, "http://127.0.0.1:9/hooks", "digest", json!({}))
+            .await
+            .unwrap();
+
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+        let (delivered_at, last_error, next_attempt_at): (Option<DateTime<Utc>>, Option<String>, DateTime<Utc>) =
+            sqlx::query_as("SELECT delivered_at, last_error, next_attempt_at FROM outbox")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert!(delivered_at.is_none());
+        assert!(last_error.unwrap().starts_with("Refusing webhook"));
+        assert!(next_attempt_at > Utc::now());
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+    }
+}
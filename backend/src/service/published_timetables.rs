@@ -1,17 +1,31 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use sqlx::PgConnection;
 use uuid::Uuid;
 use chrono::Utc;
+use crate::calendar::{self, weekday_name, CalendarEvent};
 use crate::repository::PublishedTimetableRepository;
 use crate::error::{AppResult, AppError};
-use crate::models::published_timetables::PublishedTimetable;
+use crate::mail::{escape_html, Attachment, EmailTemplate, QueuedEmail};
+use crate::models::published_timetables::{
+    LessonChange, LessonChangeKind, PublishedLesson, PublishedTimetable, RoomTimetableChanges,
+    TeacherTimetableChanges, TimetableChanges,
+};
 use crate::models::draft_timetables::DraftTimetableStatus;
 use crate::models::conflicts::ConflictStatus;
-use crate::service::{DraftTimetableService, ConflictService};
+use crate::models::NotificationType;
+use crate::service::notifications::NewNotification;
+use crate::service::{DraftTimetableService, ConflictService, NotificationService};
+use crate::service::OutboxService;
+use crate::ws::{self, WebSocketMessage};
+use serde_json::json;
 
 pub struct PublishedTimetableService {
     repo: PublishedTimetableRepository,
     draft_timetable_service: Arc<DraftTimetableService>,
     conflict_service: Arc<ConflictService>,
+    outbox: Arc<OutboxService>,
+    notifications: Arc<NotificationService>,
 }
 
 impl PublishedTimetableService {
@@ -19,11 +33,15 @@ impl PublishedTimetableService {
         repo: PublishedTimetableRepository,
         draft_timetable_service: Arc<DraftTimetableService>,
         conflict_service: Arc<ConflictService>,
+        outbox: Arc<OutboxService>,
+        notifications: Arc<NotificationService>,
     ) -> Self {
         Self {
             repo,
             draft_timetable_service,
             conflict_service,
+            outbox,
+            notifications,
         }
     }
 
@@ -50,18 +68,146 @@ impl PublishedTimetableService {
             valid_to: now.date_naive() + chrono::Duration::days(180), // Default to 6 months
             created_at: now,
             updated_at: now,
+            previous_published_timetable_id: None,
         };
 
-        let published = self.repo.create(published).await?;
+        let mut tx = self.outbox.begin().await?;
+        let previous = self.repo.find_current(&mut tx, workspace_id, published.valid_from).await?;
+        let published = self.repo
+            .create(&mut tx, PublishedTimetable {
+                previous_published_timetable_id: previous.map(|p| p.id),
+                ..published
+            })
+            .await?;
+        let changes = self.diff(&mut tx, &published).await?;
 
         // 4. Update the status of the original DraftTimetable
         self.draft_timetable_service
-            .update_draft_status(workspace_id, draft_timetable_id, DraftTimetableStatus::Published)
+            .update_draft_status_in(&mut tx, workspace_id, draft_timetable_id, DraftTimetableStatus::Published)
             .await?;
 
+        self.outbox.publish(&mut tx, WebSocketMessage::new(
+            workspace_id,
+            ws::TIMETABLE_PUBLISHED,
+            json!({
+                "id": published.id,
+                "draft_timetable_id": published.draft_timetable_id,
+                "changed_teachers": changes.teachers.len(),
+                "changed_rooms": changes.rooms.len(),
+            }),
+        )).await?;
+
+        for teacher in &changes.teachers {
+            self.notify_teacher(&mut tx, &published, teacher).await?;
+        }
+        self.outbox.commit(tx).await?;
+
         Ok(published)
     }
 
+    /// What the published timetable changed, per teacher and per room,
+    /// compared to the timetable it replaced.
+    pub async fn get_changes(&self, workspace_id: Uuid, published_timetable_id: Uuid) -> AppResult<TimetableChanges> {
+        let published = self.repo.get_by_id(published_timetable_id).await?
+            .filter(|p| p.workspace_id == workspace_id)
+            .ok_or(AppError::NotFound)?;
+
+        let mut tx = self.outbox.begin().await?;
+        let changes = self.diff(&mut tx, &published).await?;
+        tx.commit().await?;
+
+        Ok(changes)
+    }
+
+    async fn diff(&self, conn: &mut PgConnection, published: &PublishedTimetable) -> AppResult<TimetableChanges> {
+        let before = match published.previous_published_timetable_id {
+            Some(previous_id) => self.repo.find_lessons(&mut *conn, previous_id).await?,
+            None => Vec::new(),
+        };
+        let after = self.repo.find_lessons(conn, published.id).await?;
+
+        let teachers = diff_lessons(&before, &after, |l| l.teacher_id)
+            .into_iter()
+            .map(|(teacher_id, changes)| TeacherTimetableChanges {
+                teacher_id,
+                teacher_name: changes[0].lesson.teacher_name.clone(),
+                changes,
+            })
+            .collect();
+        let rooms = diff_lessons(&before, &after, |l| l.room_id)
+            .into_iter()
+            .map(|(room_id, changes)| RoomTimetableChanges {
+                room_id,
+                room_name: changes[0].lesson.room_name.clone(),
+                changes,
+            })
+            .collect();
+
+        Ok(TimetableChanges {
+            published_timetable_id: published.id,
+            previous_published_timetable_id: published.previous_published_timetable_id,
+            teachers,
+            rooms,
+        })
+    }
+
+    /// Sends the teacher one notification listing their changes, with a
+    /// calendar file that adds, moves and cancels the lessons concerned.
+    async fn notify_teacher(
+        &self,
+        conn: &mut PgConnection,
+        published: &PublishedTimetable,
+        teacher: &TeacherTimetableChanges,
+    ) -> AppResult<()> {
+        let lines: Vec<String> = teacher.changes.iter().map(describe_change).collect();
+        let link = format!("/published-timetables/{}/changes", published.id);
+        let changes_html = lines
+            .iter()
+            .map(|line| format!("    <li>{}</li>", escape_html(line)))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let email = QueuedEmail::new(EmailTemplate::TimetableChanged, json!({
+            "teacher_name": teacher.teacher_name,
+            "valid_from": published.valid_from.to_string(),
+            "changes_text": lines.iter().map(|line| format!("- {}", line)).collect::<Vec<_>>().join("\n"),
+            "changes_html": changes_html,
+            "link": self.notifications.absolute_link(Some(&link)),
+        }))
+        .with_attachment(Attachment {
+            filename: "timetable.ics".to_string(),
+            content_type: "text/calendar; charset=utf-8; method=PUBLISH".to_string(),
+            content: calendar::to_ics(
+                &format!("{} – timetable", teacher.teacher_name),
+                &calendar_events(published, &teacher.changes),
+                published.published_at,
+            ),
+        });
+
+        let (added, removed, moved) = teacher.changes.iter().fold((0, 0, 0), |(a, r, m), change| match change.kind {
+            LessonChangeKind::Added => (a + 1, r, m),
+            LessonChangeKind::Removed => (a, r + 1, m),
+            LessonChangeKind::Moved => (a, r, m + 1),
+        });
+
+        self.notifications
+            .notify(conn, published.workspace_id, NewNotification {
+                user_id: teacher.teacher_id,
+                notification_type: NotificationType::TimetablePublished,
+                title: format!(
+                    "Your timetable changes from {}: {} added, {} removed, {} moved",
+                    published.valid_from, added, removed, moved,
+                ),
+                body: Some(lines.join("\n")),
+                entity_id: Some(published.id),
+                link: Some(link),
+                email: Some(email),
+            })
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn get_published_timetable(&self, id: Uuid) -> AppResult<Option<PublishedTimetable>> {
         self.repo.get_by_id(id).await
     }
@@ -70,3 +216,215 @@ impl PublishedTimetableService {
         self.repo.get_latest().await
     }
 }
+
+/// Groups the differences between two timetables by `key` (teacher or room).
+/// Identical lessons are left out; a lesson of the same course that only
+/// changed time, room or teacher counts as moved. Groups without changes are
+/// not returned.
+fn diff_lessons(
+    before: &[PublishedLesson],
+    after: &[PublishedLesson],
+    key: impl Fn(&PublishedLesson) -> Uuid,
+) -> BTreeMap<Uuid, Vec<LessonChange>> {
+    let mut groups: BTreeMap<Uuid, (Vec<&PublishedLesson>, Vec<&PublishedLesson>)> = BTreeMap::new();
+    for lesson in before {
+        groups.entry(key(lesson)).or_default().0.push(lesson);
+    }
+    for lesson in after {
+        groups.entry(key(lesson)).or_default().1.push(lesson);
+    }
+
+    let mut result = BTreeMap::new();
+    for (id, (mut removed, mut added)) in groups {
+        // Drop lessons that did not change
+        added.retain(|new| match removed.iter().position(|old| old.is_same_as(new)) {
+            Some(i) => {
+                removed.remove(i);
+                false
+            }
+            None => true,
+        });
+
+        let mut changes = Vec::new();
+        for new in added {
+            match removed.iter().position(|old| old.course_id == new.course_id) {
+                Some(i) => changes.push(LessonChange {
+                    kind: LessonChangeKind::Moved,
+                    lesson: new.clone(),
+                    previous: Some(removed.remove(i).clone()),
+                }),
+                None => changes.push(LessonChange {
+                    kind: LessonChangeKind::Added,
+                    lesson: new.clone(),
+                    previous: None,
+                }),
+            }
+        }
+        changes.extend(removed.into_iter().map(|old| LessonChange {
+            kind: LessonChangeKind::Removed,
+            lesson: old.clone(),
+            previous: None,
+        }));
+
+        if !changes.is_empty() {
+            changes.sort_by_key(|c| (c.lesson.day_of_week, c.lesson.start_time));
+            result.insert(id, changes);
+        }
+    }
+    result
+}
+
+fn describe_lesson(lesson: &PublishedLesson) -> String {
+    format!(
+        "{} {}–{} in {}",
+        weekday_name(lesson.day_of_week),
+        lesson.start_time.format("%H:%M"),
+        lesson.end_time.format("%H:%M"),
+        lesson.room_name,
+    )
+}
+
+fn describe_change(change: &LessonChange) -> String {
+    match (change.kind, &change.previous) {
+        (LessonChangeKind::Moved, Some(previous)) => format!(
+            "Moved: {} from {} to {}",
+            change.lesson.course_name,
+            describe_lesson(previous),
+            describe_lesson(&change.lesson),
+        ),
+        (LessonChangeKind::Removed, _) => {
+            format!("Removed: {}, {}", change.lesson.course_name, describe_lesson(&change.lesson))
+        }
+        _ => format!("Added: {}, {}", change.lesson.course_name, describe_lesson(&change.lesson)),
+    }
+}
+
+/// New and moved lessons as weekly events over the validity of the
+/// timetable; removed lessons and the old place of moved ones as cancelled.
+fn calendar_events(published: &PublishedTimetable, changes: &[LessonChange]) -> Vec<CalendarEvent> {
+    let uid = |lesson: &PublishedLesson| {
+        format!("{}-{}-{}@nullslot", lesson.teacher_id, lesson.course_id, lesson.time_slot_id)
+    };
+    let event = |lesson: &PublishedLesson, cancelled: bool| CalendarEvent {
+        location: Some(lesson.room_name.clone()),
+        cancelled,
+        ..CalendarEvent::weekly(
+            uid(lesson),
+            lesson.course_name.clone(),
+            lesson.day_of_week,
+            lesson.start_time,
+            lesson.end_time,
+            published.valid_from,
+            published.valid_to,
+        )
+    };
+
+    changes
+        .iter()
+        .flat_map(|change| match change.kind {
+            LessonChangeKind::Removed => vec![event(&change.lesson, true)],
+            _ => std::iter::once(event(&change.lesson, false))
+                // A lesson that only changed room keeps its event
+                .chain(
+                    change.previous.iter()
+                        .filter(|previous| uid(previous) != uid(&change.lesson))
+                        .map(|previous| event(previous, true)),
+                )
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+
+    struct Ids {
+        teacher: Uuid,
+        rooms: [Uuid; 2],
+        slots: [Uuid; 3],
+        courses: [Uuid; 3],
+    }
+
+    fn ids() -> Ids {
+        Ids {
+            teacher: Uuid::new_v4(),
+            rooms: [Uuid::new_v4(), Uuid::new_v4()],
+            slots: [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()],
+            courses: [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()],
+        }
+    }
+
+    fn lesson(ids: &Ids, course: usize, room: usize, slot: usize) -> PublishedLesson {
+        let hour = 8 + slot as u32;
+        PublishedLesson {
+            course_id: ids.courses[course],
+            course_name: format!("Course {}", course),
+            teacher_id: ids.teacher,
+            teacher_name: "Teacher".to_string(),
+            room_id: ids.rooms[room],
+            room_name: format!("Room {}", room),
+            time_slot_id: ids.slots[slot],
+            day_of_week: 1,
+            start_time: NaiveTime::from_hms_opt(hour, 0, 0).unwrap(),
+            end_time: NaiveTime::from_hms_opt(hour + 1, 0, 0).unwrap(),
+        }
+    }
+
+    fn kinds(changes: &[LessonChange]) -> Vec<(LessonChangeKind, Uuid)> {
+        changes.iter().map(|c| (c.kind, c.lesson.course_id)).collect()
+    }
+
+    #[test]
+    fn tells_moved_lessons_from_added_ones() {
+        let ids = ids();
+        let before = [lesson(&ids, 0, 0, 0), lesson(&ids, 1, 0, 1)];
+        // Course 0 moves to another slot, course 1 stays, course 2 is new
+        let after = [lesson(&ids, 0, 0, 2), lesson(&ids, 1, 0, 1), lesson(&ids, 2, 1, 0)];
+
+        let by_teacher = diff_lessons(&before, &after, |l| l.teacher_id);
+
+        let changes = &by_teacher[&ids.teacher];
+        assert_eq!(
+            kinds(changes),
+            vec![(LessonChangeKind::Added, ids.courses[2]), (LessonChangeKind::Moved, ids.courses[0])]
+        );
+        assert_eq!(changes[1].previous.as_ref(), Some(&before[0]));
+        assert!(changes[0].previous.is_none());
+    }
+
+    #[test]
+    fn a_second_lesson_of_a_course_is_added_not_moved() {
+        let ids = ids();
+        let before = [lesson(&ids, 0, 0, 0)];
+        let after = [lesson(&ids, 0, 0, 0), lesson(&ids, 0, 0, 1)];
+
+        let by_teacher = diff_lessons(&before, &after, |l| l.teacher_id);
+
+        assert_eq!(kinds(&by_teacher[&ids.teacher]), vec![(LessonChangeKind::Added, ids.courses[0])]);
+        assert_eq!(by_teacher[&ids.teacher][0].lesson.time_slot_id, ids.slots[1]);
+    }
+
+    #[test]
+    fn a_lesson_moving_rooms_leaves_one_room_and_joins_the_other() {
+        let ids = ids();
+        let before = [lesson(&ids, 0, 0, 0)];
+        let after = [lesson(&ids, 0, 1, 0)];
+
+        let by_room = diff_lessons(&before, &after, |l| l.room_id);
+
+        assert_eq!(kinds(&by_room[&ids.rooms[0]]), vec![(LessonChangeKind::Removed, ids.courses[0])]);
+        assert_eq!(kinds(&by_room[&ids.rooms[1]]), vec![(LessonChangeKind::Added, ids.courses[0])]);
+        let by_teacher = diff_lessons(&before, &after, |l| l.teacher_id);
+        assert_eq!(kinds(&by_teacher[&ids.teacher]), vec![(LessonChangeKind::Moved, ids.courses[0])]);
+    }
+
+    #[test]
+    fn unchanged_timetables_have_no_changes() {
+        let ids = ids();
+        let lessons = [lesson(&ids, 0, 0, 0), lesson(&ids, 1, 1, 1)];
+
+        assert!(diff_lessons(&lessons, &lessons, |l| l.teacher_id).is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::graphql::types::RoomFeatureRequirementInput;
+use crate::models::{RoomFeature, RoomFeatureNeed, RoomFeatureRequirement};
+use crate::repository::RoomFeatureRepository;
+
+/// The workspace's catalogue of room features, which rooms have them and
+/// which courses and student groups need them.
+pub struct RoomFeatureService {
+    repo: RoomFeatureRepository,
+}
+
+impl RoomFeatureService {
+    pub fn new(repo: RoomFeatureRepository) -> Self {
+        Self { repo }
+    }
+
+    pub async fn create_feature(&self, workspace_id: Uuid, name: String) -> AppResult<RoomFeature> {
+        let name = self.check_name(workspace_id, None, &name).await?;
+
+        self.repo
+            .create(RoomFeature {
+                id: Uuid::new_v4(),
+                workspace_id,
+                name,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .await
+    }
+
+    pub async fn rename_feature(&self, workspace_id: Uuid, id: Uuid, name: String) -> AppResult<RoomFeature> {
+        self.repo.find_by_id(workspace_id, id).await?.ok_or(AppError::NotFound)?;
+        let name = self.check_name(workspace_id, Some(id), &name).await?;
+        self.repo.rename(id, &name).await
+    }
+
+    /// Deletes the feature from the catalogue and from every room, course
+    /// and group.
+    pub async fn delete_feature(&self, workspace_id: Uuid, id: Uuid) -> AppResult<()> {
+        self.repo.find_by_id(workspace_id, id).await?.ok_or(AppError::NotFound)?;
+        self.repo.delete(id).await
+    }
+
+    pub async fn get_features(&self, workspace_id: Uuid, room_id: Option<Uuid>) -> AppResult<Vec<RoomFeature>> {
+        self.repo.find_all(workspace_id, room_id).await
+    }
+
+    /// Replaces the features of a room.
+    pub async fn set_room_features(&self, workspace_id: Uuid, room_id: Uuid, feature_ids: Vec<Uuid>) -> AppResult<Vec<RoomFeature>> {
+        if !self.repo.room_in_workspace(workspace_id, room_id).await? {
+            return Err(AppError::NotFound);
+        }
+        let mut unique = feature_ids;
+        unique.sort();
+        unique.dedup();
+        self.check_features(workspace_id, &unique).await?;
+
+        let mut tx = self.repo.begin().await?;
+        self.repo.set_room_features(&mut tx, room_id, &unique).await?;
+        tx.commit().await?;
+
+        self.repo.find_all(workspace_id, Some(room_id)).await
+    }
+
+    pub async fn get_course_requirements(&self, course_id: Uuid) -> AppResult<Vec<RoomFeatureRequirement>> {
+        self.repo.find_course_requirements(course_id).await
+    }
+
+    /// Replaces the features the rooms of a course's lessons need.
+    pub async fn set_course_requirements(
+        &self,
+        workspace_id: Uuid,
+        course_id: Uuid,
+        requirements: Vec<RoomFeatureRequirementInput>,
+    ) -> AppResult<Vec<RoomFeatureRequirement>> {
+        if !self.repo.course_in_workspace(workspace_id, course_id).await? {
+            return Err(AppError::NotFound);
+        }
+        let (feature_ids, needs) = self.split_requirements(workspace_id, requirements).await?;
+
+        let mut tx = self.repo.begin().await?;
+        self.repo.set_course_requirements(&mut tx, course_id, &feature_ids, &needs).await?;
+        tx.commit().await?;
+
+        self.repo.find_course_requirements(course_id).await
+    }
+
+    pub async fn get_group_requirements(&self, student_group_id: Uuid) -> AppResult<Vec<RoomFeatureRequirement>> {
+        self.repo.find_group_requirements(student_group_id).await
+    }
+
+    /// Replaces the features a group needs in the rooms of its lessons and
+    /// of the lessons of every group it is part of.
+    pub async fn set_group_requirements(
+        &self,
+        workspace_id: Uuid,
+        student_group_id: Uuid,
+        requirements: Vec<RoomFeatureRequirementInput>,
+    ) -> AppResult<Vec<RoomFeatureRequirement>> {
+        if !self.repo.group_in_workspace(workspace_id, student_group_id).await? {
+            return Err(AppError::NotFound);
+        }
+        let (feature_ids, needs) = self.split_requirements(workspace_id, requirements).await?;
+
+        let mut tx = self.repo.begin().await?;
+        self.repo.set_group_requirements(&mut tx, student_group_id, &feature_ids, &needs).await?;
+        tx.commit().await?;
+
+        self.repo.find_group_requirements(student_group_id).await
+    }
+
+    async fn split_requirements(
+        &self,
+        workspace_id: Uuid,
+        requirements: Vec<RoomFeatureRequirementInput>,
+    ) -> AppResult<(Vec<Uuid>, Vec<RoomFeatureNeed>)> {
+        let (feature_ids, needs): (Vec<Uuid>, Vec<RoomFeatureNeed>) =
+            requirements.into_iter().map(|r| (r.room_feature_id, r.need)).unzip();
+        let mut unique = feature_ids.clone();
+        unique.sort();
+        unique.dedup();
+        if unique.len() != feature_ids.len() {
+            return Err(AppError::BadRequest("A feature can only be listed once".to_string()));
+        }
+        self.check_features(workspace_id, &unique).await?;
+        Ok((feature_ids, needs))
+    }
+
+    async fn check_features(&self, workspace_id: Uuid, feature_ids: &[Uuid]) -> AppResult<()> {
+        if self.repo.count_in_workspace(workspace_id, feature_ids).await? != feature_ids.len() as i64 {
+            return Err(AppError::BadRequest("Unknown room feature".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn check_name(&self, workspace_id: Uuid, id: Option<Uuid>, name: &str) -> AppResult<String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::BadRequest("A room feature needs a name".to_string()));
+        }
+        let features = self.repo.find_all(workspace_id, None).await?;
+        if features.iter().any(|f| f.name == name && Some(f.id) != id) {
+            return Err(AppError::Conflict(format!("There is already a room feature {}", name)));
+        }
+        Ok(name.to_string())
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::graphql::types::StudentGroupInput;
+use crate::models::{PublishedLesson, StudentGroup, StudentGroupKind};
+use crate::repository::StudentGroupRepository;
+
+/// Classes, year groups and subgroups, and which lessons they attend.
+/// A course's groups attend all its lessons unless a draft lesson names
+/// its own.
+pub struct StudentGroupService {
+    repo: StudentGroupRepository,
+}
+
+impl StudentGroupService {
+    pub fn new(repo: StudentGroupRepository) -> Self {
+        Self { repo }
+    }
+
+    pub async fn create_group(&self, workspace_id: Uuid, input: StudentGroupInput) -> AppResult<StudentGroup> {
+        let id = Uuid::new_v4();
+        self.validate(workspace_id, id, &input).await?;
+
+        self.repo
+            .create(StudentGroup {
+                id,
+                workspace_id,
+                name: input.name,
+                kind: input.kind,
+                parent_id: input.parent_id,
+                size: input.size,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .await
+    }
+
+    pub async fn update_group(&self, workspace_id: Uuid, id: Uuid, input: StudentGroupInput) -> AppResult<StudentGroup> {
+        let mut group = self.repo.find_by_id(workspace_id, id).await?.ok_or(AppError::NotFound)?;
+        self.validate(workspace_id, id, &input).await?;
+        group.name = input.name;
+        group.kind = input.kind;
+        group.parent_id = input.parent_id;
+        group.size = input.size;
+
+        self.repo.update(&group).await
+    }
+
+    /// Deletes the group and every group within it.
+    pub async fn delete_group(&self, workspace_id: Uuid, id: Uuid) -> AppResult<()> {
+        self.repo.find_by_id(workspace_id, id).await?.ok_or(AppError::NotFound)?;
+        self.repo.delete(id).await
+    }
+
+    pub async fn get_groups(&self, workspace_id: Uuid) -> AppResult<Vec<StudentGroup>> {
+        self.repo.find_all(workspace_id).await
+    }
+
+    pub async fn get_course_groups(&self, course_id: Uuid) -> AppResult<Vec<StudentGroup>> {
+        self.repo.find_by_course(course_id).await
+    }
+
+    /// Replaces the groups a course is taught to.
+    pub async fn set_course_groups(&self, workspace_id: Uuid, course_id: Uuid, group_ids: Vec<Uuid>) -> AppResult<Vec<StudentGroup>> {
+        if !self.repo.course_in_workspace(workspace_id, course_id).await? {
+            return Err(AppError::NotFound);
+        }
+        self.check_groups(workspace_id, &group_ids).await?;
+
+        let mut tx = self.repo.begin().await?;
+        self.repo.set_course_groups(&mut tx, course_id, &group_ids).await?;
+        tx.commit().await?;
+
+        self.repo.find_by_course(course_id).await
+    }
+
+    /// The weekly lessons of a group in a draft, or in the timetable in
+    /// force today when no draft is given. These include the lessons of the
+    /// groups it is part of and of the groups within it.
+    pub async fn get_timetable(
+        &self,
+        workspace_id: Uuid,
+        group_id: Uuid,
+        draft_timetable_id: Option<Uuid>,
+    ) -> AppResult<Vec<PublishedLesson>> {
+        let groups = self.repo.find_all(workspace_id).await?;
+        if !groups.iter().any(|g| g.id == group_id) {
+            return Err(AppError::NotFound);
+        }
+        let draft_timetable_id = match draft_timetable_id {
+            Some(id) => id,
+            None => match self.repo.find_current_draft(workspace_id, Utc::now().date_naive()).await? {
+                Some(id) => id,
+                None => return Ok(Vec::new()),
+            },
+        };
+
+        let related: Vec<Uuid> = groups.iter().map(|g| g.id).filter(|id| overlap(&groups, group_id, *id)).collect();
+        self.repo.find_lessons(draft_timetable_id, &related).await
+    }
+
+    async fn check_groups(&self, workspace_id: Uuid, group_ids: &[Uuid]) -> AppResult<()> {
+        let mut unique = group_ids.to_vec();
+        unique.sort();
+        unique.dedup();
+        if self.repo.count_in_workspace(workspace_id, &unique).await? != unique.len() as i64 {
+            return Err(AppError::BadRequest("Unknown student group".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn validate(&self, workspace_id: Uuid, id: Uuid, input: &StudentGroupInput) -> AppResult<()> {
+        if input.name.trim().is_empty() {
+            return Err(AppError::BadRequest("A student group needs a name".to_string()));
+        }
+        if input.size.is_some_and(|size| size < 0) {
+            return Err(AppError::BadRequest("The size of a group cannot be negative".to_string()));
+        }
+        match (input.kind, input.parent_id) {
+            (StudentGroupKind::YearGroup, Some(_)) => {
+                return Err(AppError::BadRequest("A year group cannot be part of another group".to_string()));
+            }
+            (StudentGroupKind::Subgroup, None) => {
+                return Err(AppError::BadRequest("A subgroup must be part of another group".to_string()));
+            }
+            _ => {}
+        }
+
+        if let Some(parent_id) = input.parent_id {
+            let groups = self.repo.find_all(workspace_id).await?;
+            if !groups.iter().any(|g| g.id == parent_id) {
+                return Err(AppError::BadRequest("Unknown parent group".to_string()));
+            }
+            if ancestors(&groups, parent_id).contains(&id) {
+                return Err(AppError::BadRequest("A group cannot be part of itself".to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The group followed by the groups it is part of, innermost first.
+pub fn ancestors(groups: &[StudentGroup], group_id: Uuid) -> Vec<Uuid> {
+    let parents: HashMap<Uuid, Option<Uuid>> = groups.iter().map(|g| (g.id, g.parent_id)).collect();
+    let mut chain = vec![group_id];
+    let mut current = group_id;
+    while let Some(Some(parent_id)) = parents.get(&current) {
+        if chain.contains(parent_id) {
+            break;
+        }
+        chain.push(*parent_id);
+        current = *parent_id;
+    }
+    chain
+}
+
+/// Whether two groups share students: the same group, or one is part of
+/// the other. Subgroups of the same class do not overlap.
+pub fn overlap(groups: &[StudentGroup], a: Uuid, b: Uuid) -> bool {
+    ancestors(groups, a).contains(&b) || ancestors(groups, b).contains(&a)
+}
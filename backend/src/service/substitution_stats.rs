@@ -0,0 +1,94 @@
+use chrono::{Duration, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::models::TeacherSubstitutionStats;
+use crate::repository::SubstitutionStatsRepository;
+
+/// Longest period a report may span.
+const MAX_PERIOD_DAYS: i64 = 366;
+/// The ranking weighs cover from this many days before and after today, so
+/// covers already agreed for the coming weeks count too.
+pub const FAIRNESS_WINDOW_DAYS: i64 = 30;
+
+/// Who is covered for and who covers: per-teacher substitution figures
+/// for reports, and for keeping the candidate ranking fair.
+pub struct SubstitutionStatsService {
+    repo: SubstitutionStatsRepository,
+}
+
+impl SubstitutionStatsService {
+    pub fn new(repo: SubstitutionStatsRepository) -> Self {
+        Self { repo }
+    }
+
+    /// Figures for the lessons from `from` through `to`, for every teacher
+    /// or only one.
+    pub async fn get_stats(
+        &self,
+        workspace_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+        teacher_id: Option<Uuid>,
+    ) -> AppResult<Vec<TeacherSubstitutionStats>> {
+        if to < from {
+            return Err(AppError::BadRequest("The end date must not be before the start date".to_string()));
+        }
+        if (to - from).num_days() >= MAX_PERIOD_DAYS {
+            return Err(AppError::BadRequest(format!("A report can cover at most {} days", MAX_PERIOD_DAYS)));
+        }
+
+        self.repo.find_stats(workspace_id, from, to, teacher_id).await
+    }
+
+    /// The same figures as CSV, one line per teacher.
+    pub async fn export_csv(&self, workspace_id: Uuid, from: NaiveDate, to: NaiveDate) -> AppResult<String> {
+        let stats = self.get_stats(workspace_id, from, to, None).await?;
+
+        let mut csv = String::from("teacher_id,teacher_name,requested,covered,declined,cover_hours,contract_hours,cover_share\r\n");
+        for s in stats {
+            let _ = write!(
+                csv,
+                "{},{},{},{},{},{:.2},{},{}\r\n",
+                s.teacher_id,
+                csv_field(&s.teacher_name),
+                s.requested,
+                s.covered,
+                s.declined,
+                s.cover_hours,
+                s.contract_hours.map(|h| format!("{:.2}", h)).unwrap_or_default(),
+                s.cover_share.map(|share| format!("{:.4}", share)).unwrap_or_default(),
+            );
+        }
+
+        Ok(csv)
+    }
+
+    /// Each teacher's figures around today, as weighed by the candidate
+    /// ranking.
+    pub async fn fairness(&self, workspace_id: Uuid) -> AppResult<HashMap<Uuid, TeacherSubstitutionStats>> {
+        let today = Utc::now().date_naive();
+        let stats = self
+            .repo
+            .find_stats(
+                workspace_id,
+                today - Duration::days(FAIRNESS_WINDOW_DAYS),
+                today + Duration::days(FAIRNESS_WINDOW_DAYS),
+                None,
+            )
+            .await?;
+
+        Ok(stats.into_iter().map(|s| (s.teacher_id, s)).collect())
+    }
+}
+
+/// Quotes a value when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -1,102 +1,531 @@
-use chrono::Utc;
+use chrono::{Duration as ChronoDuration, NaiveDate, Utc};
 use serde_json::json;
+use sqlx::PgConnection;
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
-use crate::models::{Substitution, SubstitutionStatus};
-use crate::repository::SubstitutionRepository;
-use crate::service::NotificationService;
-use crate::ws::{Broadcaster, WebSocketMessage};
+use crate::calendar::{self, weekday_name};
+use crate::mail::{EmailTemplate, QueuedEmail};
+use crate::models::{
+    Absence, CandidateFacts, NotificationType, OfferMode, QualificationPreference, ReportedAbsence, ScoreComponent, Substitution,
+    SubstitutionCandidate, SubstitutionLesson, SubstitutionOffer, SubstitutionOfferStatus, SubstitutionStatus,
+    TeacherSubstitutionStats,
+};
+use crate::repository::{AbsenceRepository, SubstitutionRepository};
+use crate::service::notifications::NewNotification;
+use crate::service::substitution_stats::FAIRNESS_WINDOW_DAYS;
+use crate::service::{NotificationService, OutboxService, SubstitutionStatsService};
+use crate::ws::{self, Topic, WebSocketMessage};
+
+const MAX_SUBSTITUTION_DAYS: i64 = 366;
+
+const DEFAULT_OFFER_TIMEOUT_MINUTES: i32 = 60;
+const MAX_OFFER_TIMEOUT_MINUTES: i32 = 7 * 24 * 60;
+/// How far down the ranking a cascade without a list goes.
+const MAX_RANKED_OFFERS: usize = 10;
+const OFFER_EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+const PREFERRED_TIME_POINTS: f64 = 40.0;
+const AVAILABLE_POINTS: f64 = 30.0;
+const OUTSIDE_AVAILABILITY_POINTS: f64 = -40.0;
+const PREFERRED_COURSE_POINTS: f64 = 40.0;
+const QUALIFIED_POINTS: f64 = 30.0;
+const RELUCTANT_POINTS: f64 = 15.0;
+const UNQUALIFIED_POINTS: f64 = -30.0;
+const SAME_DEPARTMENT_POINTS: f64 = 15.0;
+/// Load penalty per percentage point of contract hours spent covering.
+const POINTS_PER_COVER_PERCENT: f64 = -2.5;
+/// Load penalty per covered lesson, for teachers without contract hours.
+const POINTS_PER_COVERED_LESSON: f64 = -5.0;
+const MAX_LOAD_PENALTY: f64 = -25.0;
 
 pub struct SubstitutionService {
     repo: SubstitutionRepository,
-    notifications: NotificationService,
-    broadcaster: Arc<Broadcaster>,
+    absences: AbsenceRepository,
+    outbox: Arc<OutboxService>,
+    notifications: Arc<NotificationService>,
+    stats: Arc<SubstitutionStatsService>,
 }
 
 impl SubstitutionService {
     pub fn new(
         repo: SubstitutionRepository,
-        notifications: NotificationService,
-        broadcaster: Arc<Broadcaster>,
+        absences: AbsenceRepository,
+        outbox: Arc<OutboxService>,
+        notifications: Arc<NotificationService>,
+        stats: Arc<SubstitutionStatsService>,
     ) -> Self {
         Self {
             repo,
+            absences,
+            outbox,
             notifications,
-            broadcaster,
+            stats,
         }
     }
 
-    pub async fn request_substitution(&self, timetable_entry_id: Uuid) -> AppResult<Substitution> {
+    /// Asks for a substitute for the lesson on `starts_on`, or for every
+    /// occurrence up to `ends_on`. The weekly timetable is left as it is.
+    pub async fn request_substitution(
+        &self,
+        workspace_id: Uuid,
+        timetable_entry_id: Uuid,
+        starts_on: NaiveDate,
+        ends_on: Option<NaiveDate>,
+    ) -> AppResult<Substitution> {
+        let ends_on = ends_on.unwrap_or(starts_on);
+        validate_range(starts_on, ends_on)?;
+
+        let mut tx = self.outbox.begin().await?;
+        let day_of_week = self.repo.find_entry_day(&mut tx, workspace_id, timetable_entry_id).await?
+            .ok_or(AppError::NotFound)?;
+        if calendar::first_occurrence(starts_on, day_of_week) > ends_on {
+            return Err(AppError::BadRequest(format!(
+                "The lesson is not held between {} and {}",
+                starts_on, ends_on
+            )));
+        }
+        self.repo.lock_entry(&mut tx, timetable_entry_id).await?;
+        if self.repo.has_overlapping(&mut tx, timetable_entry_id, starts_on, ends_on).await? {
+            return Err(AppError::Conflict("A substitution already covers some of these dates".to_string()));
+        }
+
         let substitution = Substitution {
             id: Uuid::new_v4(),
             timetable_entry_id,
             substituting_teacher_id: None,
             status: SubstitutionStatus::Pending,
+            starts_on,
+            ends_on,
+            absence_id: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
+        let result = self.repo.create(&mut tx, substitution).await?;
+        let lesson = self.notify_teacher(&mut tx, workspace_id, result.id, EmailTemplate::SubstitutionRequested).await?;
+        self.offer_to_teachers(&mut tx, workspace_id, result.id, &lesson).await?;
 
-        let result = self.repo.create(substitution).await?;
-        self.notifications
-            .send_substitution_request_notification(result.id);
-
-        self.broadcaster.broadcast(WebSocketMessage {
-            event_type: "SUBSTITUTION_REQUESTED".to_string(),
-            payload: json!({ "id": result.id }),
-        });
+        self.outbox.publish(&mut tx, WebSocketMessage::new(
+            workspace_id,
+            ws::SUBSTITUTION_REQUESTED,
+            json!({ "id": result.id }),
+        )).await?;
+        self.outbox.commit(tx).await?;
 
         Ok(result)
     }
 
-    pub async fn accept_substitution(&self, substitution_id: Uuid, teacher_id: Uuid) -> AppResult<Substitution> {
-        let mut substitution = self.repo.find_by_id(substitution_id).await?
+    /// Records that a teacher is away and requests a substitute for each
+    /// of their lessons in the published timetable on those days. Lessons
+    /// that already have a substitution are left alone.
+    pub async fn report_absence(
+        &self,
+        workspace_id: Uuid,
+        reported_by: Uuid,
+        teacher_id: Uuid,
+        starts_on: NaiveDate,
+        ends_on: NaiveDate,
+        reason: Option<String>,
+    ) -> AppResult<ReportedAbsence> {
+        validate_range(starts_on, ends_on)?;
+        let reason = reason.map(|r| r.trim().to_string()).filter(|r| !r.is_empty());
+
+        let mut tx = self.outbox.begin().await?;
+        let teacher_name = self.absences.find_teacher_name(&mut tx, workspace_id, teacher_id).await?
             .ok_or(AppError::NotFound)?;
 
-        if substitution.status != SubstitutionStatus::Pending {
-            return Err(AppError::BadRequest("Substitution request is not pending".to_string()));
-        }
+        let now = Utc::now();
+        let absence = self.absences.create(&mut tx, Absence {
+            id: Uuid::new_v4(),
+            workspace_id,
+            teacher_id,
+            starts_on,
+            ends_on,
+            reason,
+            reported_by: Some(reported_by),
+            cancelled_at: None,
+            created_at: now,
+            updated_at: now,
+        }).await?;
 
-        substitution.substituting_teacher_id = Some(teacher_id);
-        substitution.status = SubstitutionStatus::Accepted;
-        substitution.updated_at = Utc::now();
+        let lessons = self.absences.find_lessons(&mut tx, workspace_id, teacher_id, starts_on, ends_on).await?;
+        let mut substitutions = Vec::new();
+        let mut already_covered = 0;
+        let mut unmatched_lessons = 0;
+        for lesson in lessons {
+            let Some(timetable_entry_id) = lesson.timetable_entry_id else {
+                unmatched_lessons += 1;
+                continue;
+            };
+            self.repo.lock_entry(&mut tx, timetable_entry_id).await?;
+            if self.repo.has_overlapping(&mut tx, timetable_entry_id, lesson.date, lesson.date).await? {
+                already_covered += 1;
+                continue;
+            }
 
-        let result = self.repo.update(substitution).await?;
+            let substitution = self.repo.create(&mut tx, Substitution {
+                id: Uuid::new_v4(),
+                timetable_entry_id,
+                substituting_teacher_id: None,
+                status: SubstitutionStatus::Pending,
+                starts_on: lesson.date,
+                ends_on: lesson.date,
+                absence_id: Some(absence.id),
+                created_at: now,
+                updated_at: now,
+            }).await?;
+            self.outbox.publish(&mut tx, WebSocketMessage::new(
+                workspace_id,
+                ws::SUBSTITUTION_REQUESTED,
+                json!({ "id": substitution.id }),
+            )).await?;
+            substitutions.push(substitution);
+        }
+
+        let dates = date_range(starts_on, ends_on);
+        if !substitutions.is_empty() {
+            let title = format!(
+                "Substitutes needed: {} is absent {} ({} lessons)",
+                teacher_name,
+                dates,
+                substitutions.len()
+            );
+            self.notify_pool(&mut tx, workspace_id, &absence, title).await?;
+        }
         self.notifications
-            .send_substitution_accepted_notification(result.id);
+            .notify(&mut tx, workspace_id, NewNotification {
+                user_id: teacher_id,
+                notification_type: NotificationType::SubstitutionUpdate,
+                title: format!("Your absence {} was recorded", dates),
+                body: Some(format!("A substitute was requested for {} of your lessons.", substitutions.len())),
+                entity_id: Some(absence.id),
+                link: Some(format!("/absences/{}", absence.id)),
+                email: None,
+            })
+            .await?;
 
-        self.broadcaster.broadcast(WebSocketMessage {
-            event_type: "SUBSTITUTION_ACCEPTED".to_string(),
-            payload: json!({ "id": result.id }),
-        });
+        self.outbox.publish(&mut tx, WebSocketMessage::new(
+            workspace_id,
+            ws::ABSENCE_REPORTED,
+            json!({
+                "id": absence.id,
+                "teacher_id": teacher_id,
+                "substitution_ids": substitutions.iter().map(|s| s.id).collect::<Vec<_>>(),
+            }),
+        )
+        .with_topic(Topic::Teacher(teacher_id))).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(ReportedAbsence {
+            absence,
+            substitutions,
+            already_covered,
+            unmatched_lessons,
+        })
+    }
+
+    /// Cancels an absence and retracts its substitutions that nobody has
+    /// accepted yet. Accepted ones stay, since a colleague has already
+    /// planned for them.
+    pub async fn cancel_absence(&self, workspace_id: Uuid, absence_id: Uuid) -> AppResult<Absence> {
+        let mut tx = self.outbox.begin().await?;
+        let Some(absence) = self.absences.cancel(&mut tx, workspace_id, absence_id).await? else {
+            return match self.absences.find_by_id(workspace_id, absence_id).await? {
+                Some(_) => Err(AppError::BadRequest("The absence is already cancelled".to_string())),
+                None => Err(AppError::NotFound),
+            };
+        };
+
+        let retracted = self.repo.cancel_pending_for_absence(&mut tx, absence.id).await?;
+        for id in &retracted {
+            self.repo.withdraw_offers(&mut tx, *id).await?;
+            self.outbox.publish(&mut tx, WebSocketMessage::new(
+                workspace_id,
+                ws::SUBSTITUTION_CANCELLED,
+                json!({ "id": id }),
+            )).await?;
+        }
+
+        if !retracted.is_empty() {
+            let teacher_name = self.absences.find_teacher_name(&mut tx, workspace_id, absence.teacher_id).await?
+                .unwrap_or_else(|| "A colleague".to_string());
+            let title = format!(
+                "No longer needed: cover for {} {}",
+                teacher_name,
+                date_range(absence.starts_on, absence.ends_on)
+            );
+            self.notify_pool(&mut tx, workspace_id, &absence, title).await?;
+        }
+
+        self.outbox.publish(&mut tx, WebSocketMessage::new(
+            workspace_id,
+            ws::ABSENCE_CANCELLED,
+            json!({
+                "id": absence.id,
+                "teacher_id": absence.teacher_id,
+                "substitution_ids": retracted,
+            }),
+        )
+        .with_topic(Topic::Teacher(absence.teacher_id))).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(absence)
+    }
+
+    pub async fn get_absences(
+        &self,
+        workspace_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+        teacher_id: Option<Uuid>,
+    ) -> AppResult<Vec<Absence>> {
+        validate_range(from, to)?;
+        self.absences.find_in_range(workspace_id, from, to, teacher_id).await
+    }
+
+    /// Gives the substitution to the teacher. Acceptance is a single
+    /// conditional update, so when several teachers accept at once exactly
+    /// one wins; the others get a conflict. While targeted offers are open
+    /// only their recipients may accept.
+    pub async fn accept_substitution(&self, workspace_id: Uuid, substitution_id: Uuid, teacher_id: Uuid) -> AppResult<Substitution> {
+        let mut tx = self.outbox.begin().await?;
+        let Some(result) = self.repo.accept(&mut tx, workspace_id, substitution_id, teacher_id).await? else {
+            return Err(match self.repo.lock_status(&mut tx, workspace_id, substitution_id).await? {
+                None => AppError::NotFound,
+                Some(SubstitutionStatus::Pending) => {
+                    AppError::Forbidden("This substitution is not offered to you".to_string())
+                }
+                Some(_) => AppError::Conflict("This substitution is no longer open".to_string()),
+            });
+        };
+
+        self.repo.mark_offer_accepted(&mut tx, result.id, teacher_id).await?;
+        let others = self.repo.withdraw_offers(&mut tx, result.id).await?;
+        let lesson = self.notify_teacher(&mut tx, workspace_id, result.id, EmailTemplate::SubstitutionAccepted).await?;
+        self.notify_offerees(
+            &mut tx,
+            workspace_id,
+            result.id,
+            &others,
+            format!("{} has been covered by a colleague", lesson_summary(&lesson)),
+        ).await?;
+
+        self.outbox.publish(&mut tx, WebSocketMessage::new(
+            workspace_id,
+            ws::SUBSTITUTION_ACCEPTED,
+            json!({ "id": result.id }),
+        )
+        .with_topic(Topic::Teacher(teacher_id))).await?;
+        self.outbox.commit(tx).await?;
 
         Ok(result)
     }
 
-    pub async fn reject_substitution(&self, substitution_id: Uuid) -> AppResult<Substitution> {
-        let mut substitution = self.repo.find_by_id(substitution_id).await?
+    pub async fn reject_substitution(&self, workspace_id: Uuid, substitution_id: Uuid) -> AppResult<Substitution> {
+        let mut tx = self.outbox.begin().await?;
+        let status = self.repo.lock_status(&mut tx, workspace_id, substitution_id).await?
             .ok_or(AppError::NotFound)?;
-
-        if substitution.status != SubstitutionStatus::Pending {
+        if status != SubstitutionStatus::Pending {
             return Err(AppError::BadRequest("Substitution request is not pending".to_string()));
         }
 
+        let mut substitution = self.repo.find_by_id(substitution_id).await?
+            .ok_or(AppError::NotFound)?;
         substitution.status = SubstitutionStatus::Rejected;
         substitution.updated_at = Utc::now();
 
-        let result = self.repo.update(substitution).await?;
-        self.notifications
-            .send_substitution_rejected_notification(result.id);
+        let result = self.repo.update(&mut tx, substitution).await?;
+        let offerees = self.repo.withdraw_offers(&mut tx, result.id).await?;
+        let lesson = self.notify_teacher(&mut tx, workspace_id, result.id, EmailTemplate::SubstitutionRejected).await?;
+        self.notify_offerees(
+            &mut tx,
+            workspace_id,
+            result.id,
+            &offerees,
+            format!("The offer to cover {} was withdrawn", lesson_summary(&lesson)),
+        ).await?;
 
-        self.broadcaster.broadcast(WebSocketMessage {
-            event_type: "SUBSTITUTION_REJECTED".to_string(),
-            payload: json!({ "id": result.id }),
-        });
+        self.outbox.publish(&mut tx, WebSocketMessage::new(
+            workspace_id,
+            ws::SUBSTITUTION_REJECTED,
+            json!({ "id": result.id }),
+        )).await?;
+        self.outbox.commit(tx).await?;
 
         Ok(result)
     }
 
+    /// Offers a pending substitution to specific teachers, replacing any
+    /// earlier offers. With [`OfferMode::All`] everyone is asked at once;
+    /// with [`OfferMode::Cascade`] one teacher at a time, in order, moving
+    /// on when an offer is declined or expires. Without a list, a cascade
+    /// goes through the best ranked candidates.
+    pub async fn offer_substitution(
+        &self,
+        workspace_id: Uuid,
+        substitution_id: Uuid,
+        teacher_ids: Option<Vec<Uuid>>,
+        mode: OfferMode,
+        timeout_minutes: Option<i32>,
+    ) -> AppResult<Vec<SubstitutionOffer>> {
+        let timeout_minutes = timeout_minutes.unwrap_or(DEFAULT_OFFER_TIMEOUT_MINUTES);
+        if !(1..=MAX_OFFER_TIMEOUT_MINUTES).contains(&timeout_minutes) {
+            return Err(AppError::BadRequest(format!(
+                "The timeout must be between 1 and {} minutes",
+                MAX_OFFER_TIMEOUT_MINUTES
+            )));
+        }
+
+        let teacher_ids = match (teacher_ids, mode) {
+            (Some(ids), _) => {
+                let mut unique = Vec::with_capacity(ids.len());
+                for id in ids {
+                    if !unique.contains(&id) {
+                        unique.push(id);
+                    }
+                }
+                unique
+            }
+            (None, OfferMode::Cascade) => self
+                .get_candidates(workspace_id, substitution_id, false)
+                .await?
+                .into_iter()
+                .take(MAX_RANKED_OFFERS)
+                .map(|c| c.teacher_id)
+                .collect(),
+            (None, OfferMode::All) => {
+                return Err(AppError::BadRequest("Choose the teachers to offer the substitution to".to_string()));
+            }
+        };
+        if teacher_ids.is_empty() {
+            return Err(AppError::BadRequest("There is nobody to offer the substitution to".to_string()));
+        }
+
+        let mut tx = self.outbox.begin().await?;
+        let status = self.repo.lock_status(&mut tx, workspace_id, substitution_id).await?
+            .ok_or(AppError::NotFound)?;
+        if status != SubstitutionStatus::Pending {
+            return Err(AppError::BadRequest("Substitution request is not pending".to_string()));
+        }
+        let offerable = self.repo.find_offerable(&mut tx, workspace_id, substitution_id, &teacher_ids).await?;
+        if let Some(id) = teacher_ids.iter().find(|id| !offerable.contains(id)) {
+            return Err(AppError::BadRequest(format!(
+                "User {} is not a teacher who can cover this lesson",
+                id
+            )));
+        }
+
+        let lesson = self.repo.find_lesson(&mut tx, substitution_id).await?
+            .ok_or(AppError::NotFound)?;
+        let replaced: Vec<Uuid> = self.repo.withdraw_offers(&mut tx, substitution_id).await?
+            .into_iter()
+            .filter(|id| !teacher_ids.contains(id))
+            .collect();
+        self.notify_offerees(
+            &mut tx,
+            workspace_id,
+            substitution_id,
+            &replaced,
+            format!("The offer to cover {} was withdrawn", lesson_summary(&lesson)),
+        ).await?;
+
+        let now = Utc::now();
+        let mut offers = Vec::with_capacity(teacher_ids.len());
+        for (position, teacher_id) in teacher_ids.into_iter().enumerate() {
+            let send_now = mode == OfferMode::All || position == 0;
+            let offer = self.repo.create_offer(&mut tx, SubstitutionOffer {
+                id: Uuid::new_v4(),
+                substitution_id,
+                teacher_id,
+                position: if mode == OfferMode::All { 0 } else { position as i32 },
+                status: if send_now { SubstitutionOfferStatus::Pending } else { SubstitutionOfferStatus::Waiting },
+                timeout_minutes,
+                sent_at: send_now.then_some(now),
+                expires_at: send_now.then(|| now + ChronoDuration::minutes(timeout_minutes as i64)),
+                responded_at: None,
+                created_at: now,
+            }).await?;
+            if send_now {
+                self.send_offer(&mut tx, workspace_id, &offer, &lesson).await?;
+            }
+            offers.push(offer);
+        }
+        self.outbox.commit(tx).await?;
+
+        Ok(offers)
+    }
+
+    /// Turns down the teacher's open offer; a cascade moves on to the next
+    /// teacher.
+    pub async fn decline_offer(&self, workspace_id: Uuid, substitution_id: Uuid, teacher_id: Uuid) -> AppResult<SubstitutionOffer> {
+        let mut tx = self.outbox.begin().await?;
+        let offer = self.repo.decline_offer(&mut tx, workspace_id, substitution_id, teacher_id).await?
+            .ok_or(AppError::NotFound)?;
+        self.advance_offers(&mut tx, workspace_id, substitution_id).await?;
+        self.outbox.commit(tx).await?;
+
+        Ok(offer)
+    }
+
+    /// Expires offers nobody answered in time and moves their cascades on.
+    /// Returns how many offers expired.
+    pub async fn expire_offers(&self) -> AppResult<usize> {
+        let mut tx = self.outbox.begin().await?;
+        let expired = self.repo.expire_offers(&mut tx).await?;
+
+        let mut substitutions: Vec<(Uuid, Uuid)> = Vec::new();
+        for offer in &expired {
+            if !substitutions.contains(&(offer.workspace_id, offer.substitution_id)) {
+                substitutions.push((offer.workspace_id, offer.substitution_id));
+            }
+        }
+        for (workspace_id, substitution_id) in substitutions {
+            self.advance_offers(&mut tx, workspace_id, substitution_id).await?;
+        }
+        self.outbox.commit(tx).await?;
+
+        Ok(expired.len())
+    }
+
+    pub async fn get_offers(&self, workspace_id: Uuid, substitution_id: Uuid) -> AppResult<Vec<SubstitutionOffer>> {
+        self.repo.find_offers(workspace_id, substitution_id).await
+    }
+
+    /// Teachers who are free during the lesson, best suited first, or only
+    /// those qualified for its course. The load criterion uses the same
+    /// figures as the substitution report.
+    pub async fn get_candidates(
+        &self,
+        workspace_id: Uuid,
+        substitution_id: Uuid,
+        qualified_only: bool,
+    ) -> AppResult<Vec<SubstitutionCandidate>> {
+        self.repo.find_by_id(substitution_id).await?.ok_or(AppError::NotFound)?;
+
+        let mut fairness = self.stats.fairness(workspace_id).await?;
+        let mut candidates: Vec<SubstitutionCandidate> = self
+            .repo
+            .find_candidates(workspace_id, substitution_id)
+            .await?
+            .into_iter()
+            .filter(|facts| !facts.is_busy)
+            .filter(|facts| !qualified_only || facts.qualification.is_some() || !facts.course_has_qualifications)
+            .map(|facts| {
+                let stats = fairness.remove(&facts.teacher_id);
+                score_candidate(facts, stats)
+            })
+            .collect();
+
+        candidates.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.teacher_name.cmp(&b.teacher_name))
+        });
+        Ok(candidates)
+    }
+
     pub async fn get_substitution(&self, id: Uuid) -> AppResult<Option<Substitution>> {
         self.repo.find_by_id(id).await
     }
@@ -104,4 +533,316 @@ impl SubstitutionService {
     pub async fn get_all_substitutions(&self) -> AppResult<Vec<Substitution>> {
         self.repo.find_all().await
     }
+
+    /// Tells the teacher of the lesson about the substitution.
+    async fn notify_teacher(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        substitution_id: Uuid,
+        template: EmailTemplate,
+    ) -> AppResult<SubstitutionLesson> {
+        let lesson = self.repo.find_lesson(&mut *conn, substitution_id).await?
+            .ok_or(AppError::NotFound)?;
+
+        let dates = lesson_dates(&lesson);
+        let vars = json!({
+            "dates": dates,
+            "teacher_name": lesson.teacher_name,
+            "substitute_name": lesson.substitute_name,
+            "course_name": lesson.course_name,
+            "day_of_week": lesson.day_of_week,
+            "start_time": lesson.start_time.format("%H:%M").to_string(),
+            "end_time": lesson.end_time.format("%H:%M").to_string(),
+        });
+
+        let title = match (template, &lesson.substitute_name) {
+            (EmailTemplate::SubstitutionAccepted, Some(substitute)) => {
+                format!("{} will cover {} on {} {}", substitute, lesson.course_name, weekday_name(lesson.day_of_week), dates)
+            }
+            (EmailTemplate::SubstitutionRejected, _) => {
+                format!("Substitution for {} on {} {} was rejected", lesson.course_name, weekday_name(lesson.day_of_week), dates)
+            }
+            _ => format!("Substitute requested for {} on {} {}", lesson.course_name, weekday_name(lesson.day_of_week), dates),
+        };
+        self.notifications
+            .notify(conn, workspace_id, NewNotification {
+                user_id: lesson.teacher_id,
+                notification_type: NotificationType::SubstitutionUpdate,
+                title,
+                body: None,
+                entity_id: Some(substitution_id),
+                link: Some(format!("/substitutions/{}", substitution_id)),
+                email: Some(QueuedEmail::new(template, vars)),
+            })
+            .await?;
+
+        Ok(lesson)
+    }
+
+    /// Sends one notification about the whole absence to every teacher who
+    /// could cover, rather than one per lesson.
+    async fn notify_pool(&self, conn: &mut PgConnection, workspace_id: Uuid, absence: &Absence, title: String) -> AppResult<()> {
+        let recipients = self.absences.find_pool(&mut *conn, absence).await?;
+        for user_id in recipients {
+            self.notifications
+                .notify(&mut *conn, workspace_id, NewNotification {
+                    user_id,
+                    notification_type: NotificationType::SubstitutionOffer,
+                    title: title.clone(),
+                    body: None,
+                    entity_id: Some(absence.id),
+                    link: Some(format!("/absences/{}", absence.id)),
+                    email: None,
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// After an offer was declined or expired: sends the next offer of a
+    /// cascade, or tells the admins once every offer has run out while the
+    /// substitution is still open. From then on any teacher may accept it.
+    async fn advance_offers(&self, conn: &mut PgConnection, workspace_id: Uuid, substitution_id: Uuid) -> AppResult<()> {
+        if let Some(offer) = self.repo.send_next_offer(&mut *conn, substitution_id).await? {
+            let lesson = self.repo.find_lesson(&mut *conn, substitution_id).await?
+                .ok_or(AppError::NotFound)?;
+            return self.send_offer(conn, workspace_id, &offer, &lesson).await;
+        }
+        if self.repo.has_open_offers(&mut *conn, substitution_id).await?
+            || self.repo.lock_status(&mut *conn, workspace_id, substitution_id).await? != Some(SubstitutionStatus::Pending)
+        {
+            return Ok(());
+        }
+
+        let lesson = self.repo.find_lesson(&mut *conn, substitution_id).await?
+            .ok_or(AppError::NotFound)?;
+        let title = format!("Nobody took up the offers to cover {}", lesson_summary(&lesson));
+        for user_id in self.repo.find_admins(&mut *conn, workspace_id).await? {
+            self.notifications
+                .notify(&mut *conn, workspace_id, NewNotification {
+                    user_id,
+                    notification_type: NotificationType::SubstitutionUpdate,
+                    title: title.clone(),
+                    body: Some("Any teacher can now accept it, or you can offer it again.".to_string()),
+                    entity_id: Some(substitution_id),
+                    link: Some(format!("/substitutions/{}", substitution_id)),
+                    email: None,
+                })
+                .await?;
+        }
+        self.outbox.publish(&mut *conn, WebSocketMessage::new(
+            workspace_id,
+            ws::SUBSTITUTION_OFFERS_EXPIRED,
+            json!({ "id": substitution_id }),
+        )).await?;
+
+        Ok(())
+    }
+
+    async fn send_offer(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        offer: &SubstitutionOffer,
+        lesson: &SubstitutionLesson,
+    ) -> AppResult<()> {
+        self.notifications
+            .notify(&mut *conn, workspace_id, NewNotification {
+                user_id: offer.teacher_id,
+                notification_type: NotificationType::SubstitutionOffer,
+                title: format!("Can you cover {}?", lesson_summary(lesson)),
+                body: offer
+                    .expires_at
+                    .map(|at| format!("Please answer by {}.", at.format("%Y-%m-%d %H:%M UTC"))),
+                entity_id: Some(offer.substitution_id),
+                link: Some(format!("/substitutions/{}", offer.substitution_id)),
+                email: None,
+            })
+            .await?;
+        self.outbox.publish(&mut *conn, WebSocketMessage::new(
+            workspace_id,
+            ws::SUBSTITUTION_OFFERED,
+            json!({ "id": offer.substitution_id, "teacher_id": offer.teacher_id }),
+        )
+        .with_topic(Topic::Teacher(offer.teacher_id))).await?;
+
+        Ok(())
+    }
+
+    /// Tells teachers whose offer was closed why.
+    async fn notify_offerees(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        substitution_id: Uuid,
+        teacher_ids: &[Uuid],
+        title: String,
+    ) -> AppResult<()> {
+        for &user_id in teacher_ids {
+            self.notifications
+                .notify(&mut *conn, workspace_id, NewNotification {
+                    user_id,
+                    notification_type: NotificationType::SubstitutionUpdate,
+                    title: title.clone(),
+                    body: None,
+                    entity_id: Some(substitution_id),
+                    link: Some(format!("/substitutions/{}", substitution_id)),
+                    email: None,
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Offers the lesson to every teacher who could cover it.
+    async fn offer_to_teachers(
+        &self,
+        conn: &mut PgConnection,
+        workspace_id: Uuid,
+        substitution_id: Uuid,
+        lesson: &SubstitutionLesson,
+    ) -> AppResult<()> {
+        let recipients = self.repo.find_offer_recipients(&mut *conn, workspace_id, substitution_id).await?;
+        let title = format!(
+            "Substitute needed: {}, {} {} {}–{}",
+            lesson.course_name,
+            weekday_name(lesson.day_of_week),
+            lesson_dates(lesson),
+            lesson.start_time.format("%H:%M"),
+            lesson.end_time.format("%H:%M"),
+        );
+
+        for user_id in recipients {
+            self.notifications
+                .notify(&mut *conn, workspace_id, NewNotification {
+                    user_id,
+                    notification_type: NotificationType::SubstitutionOffer,
+                    title: title.clone(),
+                    body: None,
+                    entity_id: Some(substitution_id),
+                    link: Some(format!("/substitutions/{}", substitution_id)),
+                    email: None,
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_range(starts_on: NaiveDate, ends_on: NaiveDate) -> AppResult<()> {
+    if ends_on < starts_on {
+        return Err(AppError::BadRequest("The end date must not be before the start date".to_string()));
+    }
+    if (ends_on - starts_on).num_days() > MAX_SUBSTITUTION_DAYS {
+        return Err(AppError::BadRequest(format!(
+            "A date range can cover at most {} days",
+            MAX_SUBSTITUTION_DAYS
+        )));
+    }
+    Ok(())
+}
+
+fn date_range(starts_on: NaiveDate, ends_on: NaiveDate) -> String {
+    if starts_on == ends_on {
+        starts_on.to_string()
+    } else {
+        format!("{} – {}", starts_on, ends_on)
+    }
+}
+
+fn lesson_dates(lesson: &SubstitutionLesson) -> String {
+    date_range(lesson.starts_on, lesson.ends_on)
+}
+
+fn lesson_summary(lesson: &SubstitutionLesson) -> String {
+    format!(
+        "{} on {} {}, {}–{}",
+        lesson.course_name,
+        weekday_name(lesson.day_of_week),
+        lesson_dates(lesson),
+        lesson.start_time.format("%H:%M"),
+        lesson.end_time.format("%H:%M"),
+    )
+}
+
+fn score_candidate(facts: CandidateFacts, stats: Option<TeacherSubstitutionStats>) -> SubstitutionCandidate {
+    let component = |criterion: &str, points: f64, detail: String| ScoreComponent {
+        criterion: criterion.to_string(),
+        points,
+        detail,
+    };
+
+    let availability = match (facts.has_availability, facts.is_available, facts.is_preferred) {
+        (false, _, _) => component("availability", 0.0, "No availability entered".to_string()),
+        (true, true, true) => component("availability", PREFERRED_TIME_POINTS, "Prefers to work at this time".to_string()),
+        (true, true, false) => component("availability", AVAILABLE_POINTS, "Available at this time".to_string()),
+        (true, false, _) => component("availability", OUTSIDE_AVAILABILITY_POINTS, "Outside their availability".to_string()),
+    };
+    let qualification = match (facts.qualification, facts.course_has_qualifications) {
+        (Some(QualificationPreference::Preferred), _) => {
+            component("qualification", PREFERRED_COURSE_POINTS, "Qualified and keen to teach this course".to_string())
+        }
+        (Some(QualificationPreference::Neutral), _) => {
+            component("qualification", QUALIFIED_POINTS, "Qualified for this course".to_string())
+        }
+        (Some(QualificationPreference::Reluctant), _) => {
+            component("qualification", RELUCTANT_POINTS, "Qualified but would rather not teach this course".to_string())
+        }
+        (None, true) => component("qualification", UNQUALIFIED_POINTS, "Not qualified for this course".to_string()),
+        (None, false) => component("qualification", 0.0, "No qualifications recorded for this course".to_string()),
+    };
+    let department = if facts.same_department {
+        component("department", SAME_DEPARTMENT_POINTS, "Same department".to_string())
+    } else {
+        component("department", 0.0, "Other or no department".to_string())
+    };
+    let load = match &stats {
+        Some(TeacherSubstitutionStats { covered, cover_hours, cover_share: Some(share), .. }) => component(
+            "load",
+            (share * 100.0 * POINTS_PER_COVER_PERCENT).max(MAX_LOAD_PENALTY),
+            format!(
+                "Covers {} lessons ({:.1} h, {:.0}% of contract hours) within {} days of today",
+                covered,
+                cover_hours,
+                share * 100.0,
+                FAIRNESS_WINDOW_DAYS
+            ),
+        ),
+        _ => {
+            let covered = stats.as_ref().map_or(0, |s| s.covered);
+            component(
+                "load",
+                (covered as f64 * POINTS_PER_COVERED_LESSON).max(MAX_LOAD_PENALTY),
+                format!("Covers {} lessons within {} days of today", covered, FAIRNESS_WINDOW_DAYS),
+            )
+        }
+    };
+
+    let breakdown = vec![availability, qualification, department, load];
+    SubstitutionCandidate {
+        teacher_id: facts.teacher_id,
+        teacher_name: facts.teacher_name,
+        score: breakdown.iter().map(|c| c.points).sum(),
+        breakdown,
+        fairness: stats,
+    }
+}
+
+/// Expires unanswered offers every minute.
+pub fn spawn_offer_expiry_task(service: Arc<SubstitutionService>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(OFFER_EXPIRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            match service.expire_offers().await {
+                Ok(0) => {}
+                Ok(expired) => tracing::info!("Expired {} substitution offers", expired),
+                Err(e) => tracing::error!("Failed to expire substitution offers: {:?}", e),
+            }
+        }
+    });
 }
@@ -0,0 +1,378 @@
+use chrono::{NaiveTime, Utc};
+use uuid::Uuid;
+
+use crate::calendar::weekday_name;
+use crate::error::{AppError, AppResult};
+use crate::graphql::types::TeacherPreferenceInput;
+use crate::models::{PreferenceViolation, ScheduledLesson, TeacherPreference, TeacherPreferenceKind, TimeSlot};
+use crate::repository::TeacherPreferenceRepository;
+
+/// Largest weight a preference may carry, either way.
+const MAX_WEIGHT: f64 = 10.0;
+
+/// Teachers' weighted scheduling wishes. They never block a timetable;
+/// `score_lessons` turns them into penalties for the conflict engine or a
+/// scheduler to weigh.
+pub struct TeacherPreferenceService {
+    repo: TeacherPreferenceRepository,
+}
+
+impl TeacherPreferenceService {
+    pub fn new(repo: TeacherPreferenceRepository) -> Self {
+        Self { repo }
+    }
+
+    pub async fn create_preference(
+        &self,
+        workspace_id: Uuid,
+        teacher_id: Uuid,
+        input: TeacherPreferenceInput,
+    ) -> AppResult<TeacherPreference> {
+        validate(&input)?;
+        if !self.repo.is_member(workspace_id, teacher_id).await? {
+            return Err(AppError::NotFound);
+        }
+
+        self.repo
+            .create(TeacherPreference {
+                id: Uuid::new_v4(),
+                workspace_id,
+                teacher_id,
+                kind: input.kind,
+                weight: input.weight,
+                day_of_week: input.day_of_week,
+                start_time: input.start_time,
+                end_time: input.end_time,
+                max_count: input.max_count,
+                note: input.note,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .await
+    }
+
+    pub async fn update_preference(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        user_id: Uuid,
+        is_admin: bool,
+        input: TeacherPreferenceInput,
+    ) -> AppResult<TeacherPreference> {
+        validate(&input)?;
+        let mut preference = self.find_own(workspace_id, id, user_id, is_admin).await?;
+        preference.kind = input.kind;
+        preference.weight = input.weight;
+        preference.day_of_week = input.day_of_week;
+        preference.start_time = input.start_time;
+        preference.end_time = input.end_time;
+        preference.max_count = input.max_count;
+        preference.note = input.note;
+
+        self.repo.update(&preference).await
+    }
+
+    pub async fn delete_preference(&self, workspace_id: Uuid, id: Uuid, user_id: Uuid, is_admin: bool) -> AppResult<()> {
+        self.find_own(workspace_id, id, user_id, is_admin).await?;
+        self.repo.delete(id).await
+    }
+
+    pub async fn get_preferences(&self, workspace_id: Uuid, teacher_id: Option<Uuid>) -> AppResult<Vec<TeacherPreference>> {
+        self.repo.find_all(workspace_id, teacher_id).await
+    }
+
+    async fn find_own(&self, workspace_id: Uuid, id: Uuid, user_id: Uuid, is_admin: bool) -> AppResult<TeacherPreference> {
+        let preference = self.repo.find_by_id(workspace_id, id).await?.ok_or(AppError::NotFound)?;
+        if !is_admin && preference.teacher_id != user_id {
+            return Err(AppError::Forbidden("Teachers can only change their own preferences".to_string()));
+        }
+        Ok(preference)
+    }
+}
+
+fn validate(input: &TeacherPreferenceInput) -> AppResult<()> {
+    if !input.weight.is_finite() || input.weight == 0.0 || input.weight.abs() > MAX_WEIGHT {
+        return Err(AppError::BadRequest(format!("The weight must be between -{0} and {0} and not 0", MAX_WEIGHT)));
+    }
+    if input.day_of_week.is_some_and(|day| !(0..=6).contains(&day)) {
+        return Err(AppError::BadRequest("Day of week must be between 0 (Sunday) and 6 (Saturday)".to_string()));
+    }
+    if input.kind != TeacherPreferenceKind::TimeWindow && input.weight < 0.0 {
+        return Err(AppError::BadRequest("Only time windows take negative weights".to_string()));
+    }
+
+    let has_times = input.start_time.is_some() || input.end_time.is_some();
+    match input.kind {
+        TeacherPreferenceKind::TimeWindow => match (input.start_time, input.end_time) {
+            (Some(start_time), Some(end_time)) if start_time < end_time && input.max_count.is_none() => Ok(()),
+            (Some(start_time), Some(end_time)) if start_time >= end_time => {
+                Err(AppError::BadRequest("A time window must start before it ends".to_string()))
+            }
+            _ => Err(AppError::BadRequest("A time window needs a start and an end time and no maximum".to_string())),
+        },
+        TeacherPreferenceKind::DayOff if input.day_of_week.is_none() || has_times || input.max_count.is_some() => {
+            Err(AppError::BadRequest("A day off needs a day of week and nothing else".to_string()))
+        }
+        TeacherPreferenceKind::DayOff => Ok(()),
+        TeacherPreferenceKind::MaxGaps | TeacherPreferenceKind::MaxConsecutive => {
+            let min = if input.kind == TeacherPreferenceKind::MaxGaps { 0 } else { 1 };
+            if has_times || input.max_count.is_none_or(|max| max < min) {
+                return Err(AppError::BadRequest(format!("This preference needs a maximum of at least {} and no times", min)));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Scores one teacher's weekly lessons against their preferences. Free
+/// periods are the workspace's time slots between two lessons of a day;
+/// lessons with no time slot between them count as consecutive.
+pub fn score_lessons(
+    preferences: &[TeacherPreference],
+    lessons: &[ScheduledLesson],
+    time_slots: &[TimeSlot],
+) -> Vec<PreferenceViolation> {
+    let mut violations = Vec::new();
+
+    for preference in preferences {
+        let applies = |day: i32| preference.day_of_week.is_none_or(|d| d == day);
+        let violation = |penalty: f64, detail: String, time_slot_id: Option<Uuid>, day_of_week: Option<i32>| PreferenceViolation {
+            preference_id: preference.id,
+            kind: preference.kind,
+            penalty,
+            detail,
+            time_slot_id,
+            day_of_week,
+        };
+
+        match preference.kind {
+            TeacherPreferenceKind::TimeWindow => {
+                let (Some(start_time), Some(end_time)) = (preference.start_time, preference.end_time) else {
+                    continue;
+                };
+                let window = time_range(start_time, end_time);
+                for lesson in lessons.iter().filter(|l| applies(l.day_of_week)) {
+                    let inside = start_time <= lesson.start_time && lesson.end_time <= end_time;
+                    let overlaps = lesson.start_time < end_time && start_time < lesson.end_time;
+                    let detail = if preference.weight > 0.0 && !inside {
+                        format!("{} is outside the preferred {}", lesson_label(lesson), window)
+                    } else if preference.weight < 0.0 && overlaps {
+                        format!("{} falls in the avoided {}", lesson_label(lesson), window)
+                    } else {
+                        continue;
+                    };
+                    violations.push(violation(preference.weight.abs(), detail, Some(lesson.time_slot_id), Some(lesson.day_of_week)));
+                }
+            }
+            TeacherPreferenceKind::DayOff => {
+                let Some(day) = preference.day_of_week else { continue };
+                let count = lessons.iter().filter(|l| l.day_of_week == day).count();
+                if count > 0 {
+                    violations.push(violation(
+                        preference.weight,
+                        format!("{} lessons on {}, wanted off", count, weekday_name(day)),
+                        None,
+                        Some(day),
+                    ));
+                }
+            }
+            TeacherPreferenceKind::MaxGaps => {
+                let max = preference.max_count.unwrap_or(0) as usize;
+                for (day, day_lessons) in by_day(lessons).into_iter().filter(|(day, _)| applies(*day)) {
+                    let (Some(first), Some(last)) = (day_lessons.first(), day_lessons.last()) else { continue };
+                    let gaps = time_slots
+                        .iter()
+                        .filter(|s| s.day_of_week == day && s.start_time >= first.start_time && s.end_time <= last.end_time)
+                        .filter(|s| !day_lessons.iter().any(|l| l.start_time < s.end_time && s.start_time < l.end_time))
+                        .count();
+                    if gaps > max {
+                        violations.push(violation(
+                            preference.weight * (gaps - max) as f64,
+                            format!("{} free periods on {}, at most {} wanted", gaps, weekday_name(day), max),
+                            None,
+                            Some(day),
+                        ));
+                    }
+                }
+            }
+            TeacherPreferenceKind::MaxConsecutive => {
+                let max = preference.max_count.unwrap_or(1) as usize;
+                for (day, day_lessons) in by_day(lessons).into_iter().filter(|(day, _)| applies(*day)) {
+                    let mut run_start = 0;
+                    for i in 1..=day_lessons.len() {
+                        let continues = i < day_lessons.len() && !time_slots.iter().any(|s| {
+                            s.day_of_week == day
+                                && s.start_time >= day_lessons[i - 1].end_time
+                                && s.end_time <= day_lessons[i].start_time
+                        });
+                        if continues {
+                            continue;
+                        }
+                        let run = i - run_start;
+                        if run > max {
+                            violations.push(violation(
+                                preference.weight * (run - max) as f64,
+                                format!(
+                                    "{} lessons in a row on {} from {}, at most {} wanted",
+                                    run,
+                                    weekday_name(day),
+                                    day_lessons[run_start].start_time.format("%H:%M"),
+                                    max
+                                ),
+                                None,
+                                Some(day),
+                            ));
+                        }
+                        run_start = i;
+                    }
+                }
+            }
+        }
+    }
+
+    violations
+}
+
+/// The lessons of each day that has any, by start time.
+fn by_day(lessons: &[ScheduledLesson]) -> Vec<(i32, Vec<&ScheduledLesson>)> {
+    (0..7)
+        .map(|day| {
+            let mut day_lessons: Vec<&ScheduledLesson> = lessons.iter().filter(|l| l.day_of_week == day).collect();
+            day_lessons.sort_by_key(|l| l.start_time);
+            (day, day_lessons)
+        })
+        .filter(|(_, day_lessons)| !day_lessons.is_empty())
+        .collect()
+}
+
+fn time_range(start_time: NaiveTime, end_time: NaiveTime) -> String {
+    format!("{}–{}", start_time.format("%H:%M"), end_time.format("%H:%M"))
+}
+
+fn lesson_label(lesson: &ScheduledLesson) -> String {
+    format!("The lesson on {} {}", weekday_name(lesson.day_of_week), time_range(lesson.start_time, lesson.end_time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn preference(kind: TeacherPreferenceKind, weight: f64) -> TeacherPreference {
+        TeacherPreference {
+            id: Uuid::new_v4(),
+            workspace_id: Uuid::nil(),
+            teacher_id: Uuid::nil(),
+            kind,
+            weight,
+            day_of_week: None,
+            start_time: None,
+            end_time: None,
+            max_count: None,
+            note: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn window(weight: f64, day_of_week: Option<i32>, start: NaiveTime, end: NaiveTime) -> TeacherPreference {
+        TeacherPreference {
+            day_of_week,
+            start_time: Some(start),
+            end_time: Some(end),
+            ..preference(TeacherPreferenceKind::TimeWindow, weight)
+        }
+    }
+
+    fn slot(day_of_week: i32, start: NaiveTime, end: NaiveTime) -> TimeSlot {
+        TimeSlot {
+            id: Uuid::new_v4(),
+            workspace_id: Uuid::nil(),
+            day_of_week,
+            start_time: start,
+            end_time: end,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn lesson(slot: &TimeSlot) -> ScheduledLesson {
+        ScheduledLesson {
+            teacher_id: Uuid::nil(),
+            time_slot_id: slot.id,
+            day_of_week: slot.day_of_week,
+            start_time: slot.start_time,
+            end_time: slot.end_time,
+        }
+    }
+
+    fn penalties(violations: &[PreferenceViolation], preference: &TeacherPreference) -> Vec<f64> {
+        violations.iter().filter(|v| v.preference_id == preference.id).map(|v| v.penalty).collect()
+    }
+
+    #[test]
+    fn scores_each_of_mixed_preferences() {
+        let slots: Vec<TimeSlot> = (8..13).map(|hour| slot(1, time(hour, 0), time(hour + 1, 0))).collect();
+        let friday = slot(5, time(14, 0), time(15, 0));
+        // Monday 08–11 in a row, a free period, then 12–13; one lesson on Friday
+        let lessons = [lesson(&slots[0]), lesson(&slots[1]), lesson(&slots[2]), lesson(&slots[4]), lesson(&friday)];
+
+        let preferred = window(2.0, None, time(8, 0), time(12, 0));
+        let avoided = window(-3.0, Some(1), time(8, 0), time(9, 0));
+        let day_off = TeacherPreference { day_of_week: Some(5), ..preference(TeacherPreferenceKind::DayOff, 4.0) };
+        let max_gaps = TeacherPreference { max_count: Some(0), ..preference(TeacherPreferenceKind::MaxGaps, 1.0) };
+        let max_consecutive =
+            TeacherPreference { max_count: Some(2), ..preference(TeacherPreferenceKind::MaxConsecutive, 1.5) };
+        let preferences = [preferred.clone(), avoided.clone(), day_off.clone(), max_gaps.clone(), max_consecutive.clone()];
+
+        let mut all_slots = slots.clone();
+        all_slots.push(friday.clone());
+        let violations = score_lessons(&preferences, &lessons, &all_slots);
+
+        assert_eq!(violations.len(), 6);
+        assert_eq!(penalties(&violations, &preferred), vec![2.0, 2.0]);
+        assert_eq!(
+            violations.iter().filter(|v| v.preference_id == preferred.id).map(|v| v.time_slot_id).collect::<Vec<_>>(),
+            vec![Some(slots[4].id), Some(friday.id)]
+        );
+        assert_eq!(penalties(&violations, &avoided), vec![3.0]);
+        assert_eq!(penalties(&violations, &day_off), vec![4.0]);
+        assert_eq!(penalties(&violations, &max_gaps), vec![1.0]);
+        assert_eq!(penalties(&violations, &max_consecutive), vec![1.5]);
+    }
+
+    #[test]
+    fn met_preferences_score_nothing() {
+        let slots: Vec<TimeSlot> = (8..11).map(|hour| slot(2, time(hour, 0), time(hour + 1, 0))).collect();
+        let lessons = [lesson(&slots[0]), lesson(&slots[2])];
+        let preferences = [
+            window(5.0, Some(2), time(8, 0), time(11, 0)),
+            window(-5.0, Some(2), time(9, 0), time(10, 0)),
+            TeacherPreference { day_of_week: Some(3), ..preference(TeacherPreferenceKind::DayOff, 1.0) },
+            TeacherPreference { max_count: Some(1), ..preference(TeacherPreferenceKind::MaxGaps, 1.0) },
+            TeacherPreference { max_count: Some(1), ..preference(TeacherPreferenceKind::MaxConsecutive, 1.0) },
+        ];
+
+        assert!(score_lessons(&preferences, &lessons, &slots).is_empty());
+    }
+
+    #[test]
+    fn scores_wall_clock_times_across_daylight_saving_changes() {
+        // Lessons are weekly local times, so one in the hour skipped or
+        // repeated when clocks change is scored like any other
+        let slots = [slot(0, time(1, 30), time(2, 30)), slot(0, time(2, 30), time(3, 30))];
+        let lessons = [lesson(&slots[0]), lesson(&slots[1])];
+        let preferred = window(1.0, Some(0), time(1, 0), time(3, 0));
+        let max_consecutive =
+            TeacherPreference { max_count: Some(1), ..preference(TeacherPreferenceKind::MaxConsecutive, 2.0) };
+
+        let violations = score_lessons(&[preferred.clone(), max_consecutive.clone()], &lessons, &slots);
+
+        assert_eq!(penalties(&violations, &preferred), vec![1.0]);
+        assert_eq!(violations[0].time_slot_id, Some(slots[1].id));
+        assert_eq!(penalties(&violations, &max_consecutive), vec![2.0]);
+    }
+}
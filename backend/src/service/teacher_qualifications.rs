@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{QualificationImport, QualificationPreference, TeacherQualification};
+use crate::repository::TeacherQualificationRepository;
+use crate::service::enrollments::{CsvImport, ImportErrors};
+
+/// Which teachers may teach which courses. Draft lessons of a course with
+/// qualified teachers must go to one of them, and substitutes are ranked
+/// by how keen they are on the course.
+pub struct TeacherQualificationService {
+    repo: TeacherQualificationRepository,
+}
+
+impl TeacherQualificationService {
+    pub fn new(repo: TeacherQualificationRepository) -> Self {
+        Self { repo }
+    }
+
+    /// Qualifies the teacher for the course, or changes their preference
+    /// when they already are. The preference defaults to neutral.
+    pub async fn set_qualification(
+        &self,
+        workspace_id: Uuid,
+        teacher_id: Uuid,
+        course_id: Uuid,
+        preference: Option<QualificationPreference>,
+    ) -> AppResult<TeacherQualification> {
+        if !self.repo.is_member(workspace_id, teacher_id).await? || !self.repo.course_in_workspace(workspace_id, course_id).await? {
+            return Err(AppError::NotFound);
+        }
+
+        let mut tx = self.repo.begin().await?;
+        self.repo
+            .set(&mut tx, workspace_id, teacher_id, course_id, preference.unwrap_or(QualificationPreference::Neutral))
+            .await?;
+        tx.commit().await?;
+
+        let mut qualifications = self.repo.find_all(workspace_id, Some(teacher_id), Some(course_id)).await?;
+        qualifications.pop().ok_or(AppError::NotFound)
+    }
+
+    pub async fn remove_qualification(&self, workspace_id: Uuid, teacher_id: Uuid, course_id: Uuid) -> AppResult<()> {
+        if !self.repo.delete(workspace_id, teacher_id, course_id).await? {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    pub async fn get_qualifications(
+        &self,
+        workspace_id: Uuid,
+        teacher_id: Option<Uuid>,
+        course_id: Option<Uuid>,
+    ) -> AppResult<Vec<TeacherQualification>> {
+        self.repo.find_all(workspace_id, teacher_id, course_id).await
+    }
+
+    /// Imports CSV text whose header names the `teacher_email`,
+    /// `course_code` and optionally `preference` columns. Either every line
+    /// is imported or, when any is wrong, none are.
+    pub async fn import_csv(&self, workspace_id: Uuid, csv: &str) -> AppResult<QualificationImport> {
+        let csv = CsvImport::parse(csv)?;
+        let [email_col, course_col] = csv.require(["teacher_email", "course_code"])?;
+        let preference_col = csv.column("preference");
+
+        let mut tx = self.repo.begin().await?;
+        let mut errors = ImportErrors::default();
+        let mut teachers: HashMap<String, Option<Uuid>> = HashMap::new();
+        let mut courses: HashMap<String, Option<Uuid>> = HashMap::new();
+        let mut result = QualificationImport { qualifications_created: 0, qualifications_updated: 0 };
+
+        for row in csv.rows() {
+            let (Some(email), Some(course_code)) = (row.field(email_col), row.field(course_col)) else {
+                errors.reject(row, "teacher_email and course_code are required");
+                continue;
+            };
+            let preference = match row.field(preference_col).map(str::to_ascii_lowercase).as_deref() {
+                None | Some("neutral") => QualificationPreference::Neutral,
+                Some("preferred") => QualificationPreference::Preferred,
+                Some("reluctant") => QualificationPreference::Reluctant,
+                Some(other) => {
+                    errors.reject(row, format!("unknown preference {}", other));
+                    continue;
+                }
+            };
+
+            let teacher_id = match teachers.get(&email.to_lowercase()) {
+                Some(id) => *id,
+                None => {
+                    let id = self.repo.find_member_by_email(&mut tx, workspace_id, email).await?;
+                    teachers.insert(email.to_lowercase(), id);
+                    id
+                }
+            };
+            let Some(teacher_id) = teacher_id else {
+                errors.reject(row, format!("no member has the email {}", email));
+                continue;
+            };
+            let course_id = match courses.get(course_code) {
+                Some(id) => *id,
+                None => {
+                    let id = self.repo.find_course_by_code(&mut tx, workspace_id, course_code).await?;
+                    courses.insert(course_code.to_string(), id);
+                    id
+                }
+            };
+            let Some(course_id) = course_id else {
+                errors.reject(row, format!("there is no course {}", course_code));
+                continue;
+            };
+
+            if self.repo.set(&mut tx, workspace_id, teacher_id, course_id, preference).await? {
+                result.qualifications_created += 1;
+            } else {
+                result.qualifications_updated += 1;
+            }
+        }
+
+        errors.check()?;
+        tx.commit().await?;
+
+        Ok(result)
+    }
+}
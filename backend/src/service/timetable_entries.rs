@@ -6,20 +6,22 @@ use uuid::Uuid;
 use crate::error::AppResult;
 use crate::models::TimetableEntry;
 use crate::repository::TimetableEntryRepository;
-use crate::ws::{Broadcaster, WebSocketMessage};
+use crate::service::OutboxService;
+use crate::ws::{self, Topic, WebSocketMessage};
 
 pub struct TimetableEntryService {
     repo: TimetableEntryRepository,
-    broadcaster: Arc<Broadcaster>,
+    outbox: Arc<OutboxService>,
 }
 
 impl TimetableEntryService {
-    pub fn new(repo: TimetableEntryRepository, broadcaster: Arc<Broadcaster>) -> Self {
-        Self { repo, broadcaster }
+    pub fn new(repo: TimetableEntryRepository, outbox: Arc<OutboxService>) -> Self {
+        Self { repo, outbox }
     }
 
     pub async fn create_timetable_entry(
         &self,
+        workspace_id: Uuid,
         course_id: Uuid,
         room_id: Uuid,
         time_slot_id: Uuid,
@@ -35,12 +37,17 @@ impl TimetableEntryService {
             updated_at: Utc::now(),
         };
 
-        let result = self.repo.create(entry).await?;
+        let mut tx = self.outbox.begin().await?;
+        let result = self.repo.create(&mut tx, entry).await?;
 
-        self.broadcaster.broadcast(WebSocketMessage {
-            event_type: "TIMETABLE_CREATED".to_string(),
-            payload: json!({ "id": result.id }),
-        });
+        self.outbox.publish(&mut tx, WebSocketMessage::new(
+            workspace_id,
+            ws::TIMETABLE_CREATED,
+            json!({ "id": result.id }),
+        )
+        .with_topic(Topic::Teacher(result.teacher_id))
+        .with_topic(Topic::Room(result.room_id))).await?;
+        self.outbox.commit(tx).await?;
 
         Ok(result)
     }
@@ -55,6 +62,7 @@ impl TimetableEntryService {
 
     pub async fn update_timetable_entry(
         &self,
+        workspace_id: Uuid,
         id: Uuid,
         course_id: Option<Uuid>,
         room_id: Option<Uuid>,
@@ -77,23 +85,31 @@ impl TimetableEntryService {
         }
         
         entry.updated_at = Utc::now();
-        let result = self.repo.update(entry).await?;
+        let mut tx = self.outbox.begin().await?;
+        let result = self.repo.update(&mut tx, entry).await?;
 
-        self.broadcaster.broadcast(WebSocketMessage {
-            event_type: "TIMETABLE_UPDATED".to_string(),
-            payload: json!({ "id": result.id }),
-        });
+        self.outbox.publish(&mut tx, WebSocketMessage::new(
+            workspace_id,
+            ws::TIMETABLE_UPDATED,
+            json!({ "id": result.id }),
+        )
+        .with_topic(Topic::Teacher(result.teacher_id))
+        .with_topic(Topic::Room(result.room_id))).await?;
+        self.outbox.commit(tx).await?;
 
         Ok(result)
     }
 
-    pub async fn delete_timetable_entry(&self, id: Uuid) -> AppResult<()> {
-        self.repo.delete(id).await?;
+    pub async fn delete_timetable_entry(&self, workspace_id: Uuid, id: Uuid) -> AppResult<()> {
+        let mut tx = self.outbox.begin().await?;
+        self.repo.delete(&mut tx, id).await?;
 
-        self.broadcaster.broadcast(WebSocketMessage {
-            event_type: "TIMETABLE_DELETED".to_string(),
-            payload: json!({ "id": id }),
-        });
+        self.outbox.publish(&mut tx, WebSocketMessage::new(
+            workspace_id,
+            ws::TIMETABLE_DELETED,
+            json!({ "id": id }),
+        )).await?;
+        self.outbox.commit(tx).await?;
 
         Ok(())
     }
@@ -1,7 +1,11 @@
 use uuid::Uuid;
 use chrono::{Utc, Duration};
 use crate::models::workspace::{Workspace, WorkspaceInvite, WorkspaceRole};
+use crate::config::Config;
+use crate::mail::EmailTemplate;
 use crate::repository::workspace::WorkspaceRepository;
+use crate::repository::UserRepository;
+use crate::service::OutboxService;
 use crate::error::{AppError, AppResult};
 use std::sync::Arc;
 use rand::{RngCore, rng};
@@ -9,11 +13,24 @@ use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
 
 pub struct WorkspaceService {
     repo: Arc<WorkspaceRepository>,
+    user_repo: UserRepository,
+    outbox: Arc<OutboxService>,
+    config: Arc<Config>,
 }
 
 impl WorkspaceService {
-    pub fn new(repo: Arc<WorkspaceRepository>) -> Self {
-        Self { repo }
+    pub fn new(
+        repo: Arc<WorkspaceRepository>,
+        user_repo: UserRepository,
+        outbox: Arc<OutboxService>,
+        config: Arc<Config>,
+    ) -> Self {
+        Self {
+            repo,
+            user_repo,
+            outbox,
+            config,
+        }
     }
 
     pub async fn create_workspace(&self, user_id: Uuid, name: String) -> AppResult<Workspace> {
@@ -22,6 +39,7 @@ impl WorkspaceService {
             id: Uuid::new_v4(),
             name,
             domain_restriction: None,
+            room_capacity_tolerance: 0,
             created_at: now,
             updated_at: now,
         };
@@ -38,6 +56,36 @@ impl WorkspaceService {
         self.repo.find_by_user_id(user_id).await
     }
 
+    pub async fn get_member_role(&self, workspace_id: Uuid, user_id: Uuid) -> AppResult<Option<WorkspaceRole>> {
+        self.repo.check_membership(workspace_id, user_id).await
+    }
+
+    pub async fn set_member_department(&self, workspace_id: Uuid, user_id: Uuid, department: Option<String>) -> AppResult<()> {
+        let department = department.map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
+        if !self.repo.set_member_department(workspace_id, user_id, department).await? {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Weekly contract hours, at most 80; `None` clears them.
+    pub async fn set_member_contract_hours(&self, workspace_id: Uuid, user_id: Uuid, contract_hours: Option<f64>) -> AppResult<()> {
+        if contract_hours.is_some_and(|h| !(h > 0.0 && h <= 80.0)) {
+            return Err(AppError::BadRequest("Contract hours must be more than 0 and at most 80 a week".to_string()));
+        }
+        if !self.repo.set_member_contract_hours(workspace_id, user_id, contract_hours).await? {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    pub async fn set_room_capacity_tolerance(&self, workspace_id: Uuid, percent: i32) -> AppResult<Workspace> {
+        if !(0..=100).contains(&percent) {
+            return Err(AppError::BadRequest("The tolerance must be between 0 and 100 percent".to_string()));
+        }
+        self.repo.set_room_capacity_tolerance(workspace_id, percent).await?.ok_or(AppError::NotFound)
+    }
+
     pub async fn create_invite(
         &self,
         workspace_id: Uuid,
@@ -71,10 +119,28 @@ impl WorkspaceService {
             created_at: Utc::now(),
         };
 
-        self.repo.create_invite(invite).await?;
-
-        // Mock email sending
-        println!("MOCK: Sending workspace invite for {} to {} with token {}", workspace_id, email, token);
+        let workspace = self.repo.find_by_id(workspace_id).await?.ok_or(AppError::NotFound)?;
+        let inviter = self.user_repo.find_by_id(creator_id).await?.ok_or(AppError::NotFound)?;
+        let link = format!("{}/accept-invite?token={}", self.config.frontend_url.trim_end_matches('/'), token);
+
+        let mut tx = self.outbox.begin().await?;
+        self.repo.create_invite(&mut tx, invite).await?;
+        self.outbox
+            .send_email(
+                &mut tx,
+                Some(workspace_id),
+                &email,
+                EmailTemplate::WorkspaceInvite,
+                serde_json::json!({
+                    "workspace_name": workspace.name,
+                    "inviter_name": inviter.username,
+                    "role": format!("{:?}", role).to_lowercase(),
+                    "link": link,
+                    "expires_in_days": 7,
+                }),
+            )
+            .await?;
+        self.outbox.commit(tx).await?;
 
         Ok(token)
     }
@@ -0,0 +1,314 @@
+//! Fixtures for tests that run against a database. `#[sqlx::test]` gives
+//! each test a fresh database with every migration applied, so tests need
+//! `DATABASE_URL` pointing at a server where they may create databases.
+
+use std::sync::Arc;
+
+use async_graphql::{Schema, SchemaBuilder};
+use chrono::{Duration, NaiveTime, Utc};
+use jsonwebtoken::{EncodingKey, Header, encode};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::{BroadcastBackendKind, Config, MailTransportKind, SmtpSecurity};
+use crate::graphql::mutation::Mutation;
+use crate::graphql::query::Query;
+use crate::graphql::subscription::Subscription;
+use crate::models::{UserRole, WorkspaceRole};
+use crate::repository::{
+    AbsenceRepository, AvailabilityRepository, AvailabilityRoundRepository, ConflictRepository, DraftEntryRepository, EnrollmentRepository,
+    LessonExceptionRepository, LessonSwapRepository, NotificationRepository, OutboxRepository, RoomFeatureRepository, StudentGroupRepository,
+    SubstitutionRepository, SubstitutionStatsRepository, TeacherPreferenceRepository, TeacherQualificationRepository, TimeSlotRepository, UserRepository,
+    WorkspaceRepository,
+};
+use crate::service::auth::Claims;
+use crate::service::{
+    AvailabilityRoundService, AvailabilityService, ConflictService, DraftEntryService, EnrollmentService, LessonExceptionService, LessonSwapService,
+    NotificationService, OutboxService, RoomFeatureService, StudentGroupService, SubstitutionService, SubstitutionStatsService,
+    TeacherPreferenceService, TeacherQualificationService, WorkspaceService,
+};
+
+pub(crate) const JWT_SECRET: &str = "test-secret";
+
+pub(crate) fn config() -> Arc<Config> {
+    Arc::new(Config {
+        oidc_client_id: "nullslot".to_string(),
+        oidc_client_secret: "secret".to_string(),
+        oidc_issuer_url: "http://localhost:8081".to_string(),
+        oidc_redirect_uri: "http://localhost:8080/auth/oidc/callback".to_string(),
+        oidc_frontend_redirect_url: "http://localhost:5173/oidc-callback".to_string(),
+        jwt_secret: JWT_SECRET.to_string(),
+        event_retention_days: 7,
+        notification_retention_days: 90,
+        digest_time: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+        digest_timezone: "UTC".to_string(),
+        broadcast_backend: BroadcastBackendKind::Memory,
+        frontend_url: "http://localhost:5173".to_string(),
+        public_url: "http://localhost:8080".to_string(),
+        mail_transport: MailTransportKind::Memory,
+        mail_from: "Nullslot <no-reply@nullslot.local>".to_string(),
+        mail_dir: "mailbox".to_string(),
+        mail_locale: "en".to_string(),
+        smtp_host: "localhost".to_string(),
+        smtp_port: 1025,
+        smtp_username: None,
+        smtp_password: None,
+        smtp_security: SmtpSecurity::None,
+    })
+}
+
+pub(crate) async fn workspace(pool: &PgPool) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query("INSERT INTO workspaces (id, name) VALUES ($1, 'School')")
+        .bind(id)
+        .execute(pool)
+        .await
+        .unwrap();
+    id
+}
+
+/// A user who belongs to no workspace.
+pub(crate) async fn user(pool: &PgPool) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (id, username, email, hashed_password) VALUES ($1, $2, $3, '')")
+        .bind(id)
+        .bind(id.to_string())
+        .bind(format!("{}@school.test", id))
+        .execute(pool)
+        .await
+        .unwrap();
+    id
+}
+
+/// A new user who is a member of the workspace with `role`.
+pub(crate) async fn member(pool: &PgPool, workspace_id: Uuid, role: WorkspaceRole) -> Uuid {
+    let id = user(pool).await;
+    sqlx::query("INSERT INTO workspace_members (workspace_id, user_id, role) VALUES ($1, $2, $3)")
+        .bind(workspace_id)
+        .bind(id)
+        .bind(role)
+        .execute(pool)
+        .await
+        .unwrap();
+    id
+}
+
+pub(crate) async fn course(pool: &PgPool, workspace_id: Uuid) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query("INSERT INTO courses (id, workspace_id, code, name) VALUES ($1, $2, $3, 'Maths')")
+        .bind(id)
+        .bind(workspace_id)
+        .bind(id.to_string())
+        .execute(pool)
+        .await
+        .unwrap();
+    id
+}
+
+/// A weekly lesson in the published timetable taught by `teacher_id` on
+/// `day_of_week` (0 is Sunday), from 09:00 to 10:00. Returns the timetable
+/// entry.
+pub(crate) async fn lesson(pool: &PgPool, workspace_id: Uuid, teacher_id: Uuid, day_of_week: i32) -> Uuid {
+    let course_id = course(pool, workspace_id).await;
+    let (room_id, time_slot_id, entry_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    sqlx::query("INSERT INTO rooms (id, workspace_id, name, capacity) VALUES ($1, $2, $3, 30)")
+        .bind(room_id)
+        .bind(workspace_id)
+        .bind(room_id.to_string())
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO time_slots (id, workspace_id, day_of_week, start_time, end_time) VALUES ($1, $2, $3, '09:00', '10:00')")
+        .bind(time_slot_id)
+        .bind(workspace_id)
+        .bind(day_of_week)
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO timetable_entries (id, course_id, room_id, time_slot_id, teacher_id) VALUES ($1, $2, $3, $4, $5)")
+        .bind(entry_id)
+        .bind(course_id)
+        .bind(room_id)
+        .bind(time_slot_id)
+        .bind(teacher_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    entry_id
+}
+
+/// A new teacher who is a viewer in the workspace.
+pub(crate) async fn teacher(pool: &PgPool, workspace_id: Uuid) -> Uuid {
+    let id = member(pool, workspace_id, WorkspaceRole::Viewer).await;
+    sqlx::query("UPDATE users SET role = 'Teacher' WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await
+        .unwrap();
+    id
+}
+
+/// Publishes a timetable holding the given lessons for the school year
+/// 2026/27.
+pub(crate) async fn publish(pool: &PgPool, workspace_id: Uuid, entry_ids: &[Uuid]) -> Uuid {
+    let (draft_id, published_id) = (Uuid::new_v4(), Uuid::new_v4());
+    sqlx::query("INSERT INTO draft_timetables (id, workspace_id, name, term, year) VALUES ($1, $2, '2026/27', 'Year', 2026)")
+        .bind(draft_id)
+        .bind(workspace_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO draft_entries (id, draft_timetable_id, course_id, teacher_id, room_id, time_slot_id)
+        SELECT gen_random_uuid(), $1, course_id, teacher_id, room_id, time_slot_id FROM timetable_entries WHERE id = ANY($2)",
+    )
+    .bind(draft_id)
+    .bind(entry_ids)
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO published_timetables (id, workspace_id, draft_timetable_id, valid_from, valid_to)
+        VALUES ($1, $2, $3, '2026-08-01', '2027-07-31')",
+    )
+    .bind(published_id)
+    .bind(workspace_id)
+    .bind(draft_id)
+    .execute(pool)
+    .await
+    .unwrap();
+    published_id
+}
+
+pub(crate) fn claims(user_id: Uuid, workspace_id: Uuid) -> Claims {
+    Claims {
+        sub: user_id,
+        role: UserRole::User,
+        exp: (Utc::now() + Duration::hours(1)).timestamp(),
+        workspace_id,
+    }
+}
+
+/// The claims a user made with `teacher` logs in with.
+pub(crate) fn teacher_claims(user_id: Uuid, workspace_id: Uuid) -> Claims {
+    Claims {
+        role: UserRole::Teacher,
+        ..claims(user_id, workspace_id)
+    }
+}
+
+/// A signed JWT for `claims`, as the login flow would hand out.
+pub(crate) fn token(claims: &Claims) -> String {
+    encode(&Header::default(), claims, &EncodingKey::from_secret(JWT_SECRET.as_bytes())).unwrap()
+}
+
+pub(crate) fn outbox(pool: &PgPool) -> Arc<OutboxService> {
+    Arc::new(OutboxService::new(OutboxRepository::new(pool.clone())))
+}
+
+pub(crate) fn availability_service(pool: &PgPool) -> Arc<AvailabilityService> {
+    Arc::new(AvailabilityService::new(AvailabilityRepository::new(pool.clone())))
+}
+
+pub(crate) fn availability_round_service(pool: &PgPool) -> Arc<AvailabilityRoundService> {
+    Arc::new(AvailabilityRoundService::new(
+        AvailabilityRoundRepository::new(pool.clone()),
+        AvailabilityRepository::new(pool.clone()),
+        outbox(pool),
+        notification_service(pool),
+    ))
+}
+
+pub(crate) fn conflict_service(pool: &PgPool) -> ConflictService {
+    let draft_entry_service = DraftEntryService::new(
+        DraftEntryRepository::new(pool.clone()),
+        StudentGroupRepository::new(pool.clone()),
+        ConflictRepository::new(pool.clone()),
+        outbox(pool),
+    );
+    ConflictService::new(
+        ConflictRepository::new(pool.clone()),
+        Arc::new(draft_entry_service),
+        availability_service(pool),
+        TimeSlotRepository::new(pool.clone()),
+        TeacherPreferenceRepository::new(pool.clone()),
+        StudentGroupRepository::new(pool.clone()),
+        outbox(pool),
+    )
+}
+
+pub(crate) fn enrollment_service(pool: &PgPool) -> Arc<EnrollmentService> {
+    Arc::new(EnrollmentService::new(EnrollmentRepository::new(pool.clone()), StudentGroupRepository::new(pool.clone())))
+}
+
+pub(crate) fn lesson_exception_service(pool: &PgPool) -> Arc<LessonExceptionService> {
+    Arc::new(LessonExceptionService::new(LessonExceptionRepository::new(pool.clone()), outbox(pool)))
+}
+
+pub(crate) fn lesson_swap_service(pool: &PgPool) -> Arc<LessonSwapService> {
+    Arc::new(LessonSwapService::new(
+        LessonSwapRepository::new(pool.clone()),
+        LessonExceptionRepository::new(pool.clone()),
+        lesson_exception_service(pool),
+        outbox(pool),
+        notification_service(pool),
+    ))
+}
+
+pub(crate) fn notification_service(pool: &PgPool) -> Arc<NotificationService> {
+    Arc::new(NotificationService::new(
+        NotificationRepository::new(pool.clone()),
+        UserRepository::new(pool.clone()),
+        outbox(pool),
+        config(),
+    ))
+}
+
+pub(crate) fn room_feature_service(pool: &PgPool) -> Arc<RoomFeatureService> {
+    Arc::new(RoomFeatureService::new(RoomFeatureRepository::new(pool.clone())))
+}
+
+pub(crate) fn student_group_service(pool: &PgPool) -> Arc<StudentGroupService> {
+    Arc::new(StudentGroupService::new(StudentGroupRepository::new(pool.clone())))
+}
+
+pub(crate) fn substitution_stats_service(pool: &PgPool) -> Arc<SubstitutionStatsService> {
+    Arc::new(SubstitutionStatsService::new(SubstitutionStatsRepository::new(pool.clone())))
+}
+
+pub(crate) fn substitution_service(pool: &PgPool) -> SubstitutionService {
+    SubstitutionService::new(
+        SubstitutionRepository::new(pool.clone()),
+        AbsenceRepository::new(pool.clone()),
+        outbox(pool),
+        notification_service(pool),
+        substitution_stats_service(pool),
+    )
+}
+
+pub(crate) fn teacher_preference_service(pool: &PgPool) -> Arc<TeacherPreferenceService> {
+    Arc::new(TeacherPreferenceService::new(TeacherPreferenceRepository::new(pool.clone())))
+}
+
+pub(crate) fn teacher_qualification_service(pool: &PgPool) -> Arc<TeacherQualificationService> {
+    Arc::new(TeacherQualificationService::new(TeacherQualificationRepository::new(pool.clone())))
+}
+
+pub(crate) fn workspace_service(pool: &PgPool) -> Arc<WorkspaceService> {
+    Arc::new(WorkspaceService::new(
+        Arc::new(WorkspaceRepository::new(pool.clone())),
+        UserRepository::new(pool.clone()),
+        outbox(pool),
+        config(),
+    ))
+}
+
+/// A schema without any services; tests add the ones their resolvers use.
+pub(crate) fn schema() -> SchemaBuilder<Query, Mutation, Subscription> {
+    Schema::build(Query, Mutation, Subscription)
+}
+
+/// The messages of the errors in a GraphQL response.
+pub(crate) fn errors(response: &async_graphql::Response) -> Vec<String> {
+    response.errors.iter().map(|e| e.message.clone()).collect()
+}
@@ -0,0 +1,165 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::error::AppResult;
+use crate::ws::{Broadcaster, WebSocketMessage};
+
+const NOTIFY_CHANNEL: &str = "nullslot_events";
+// Postgres rejects NOTIFY payloads of 8000 bytes or more
+const MAX_NOTIFY_PAYLOAD: usize = 7900;
+
+/// The subscribers connected to this instance, with one broadcast channel per
+/// workspace.
+pub struct LocalHub {
+    capacity: usize,
+    channels: RwLock<HashMap<Uuid, broadcast::Sender<WebSocketMessage>>>,
+}
+
+impl LocalHub {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            channels: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn send(&self, msg: WebSocketMessage) {
+        let workspace_id = msg.workspace_id;
+        let no_receivers = match self.channels.read().unwrap().get(&workspace_id) {
+            Some(tx) => tx.send(msg).is_err(),
+            None => false,
+        };
+
+        // Drop channels nobody listens to anymore
+        if no_receivers {
+            let mut channels = self.channels.write().unwrap();
+            if channels.get(&workspace_id).is_some_and(|tx| tx.receiver_count() == 0) {
+                channels.remove(&workspace_id);
+            }
+        }
+    }
+
+    pub fn subscribe(&self, workspace_id: Uuid) -> broadcast::Receiver<WebSocketMessage> {
+        if let Some(tx) = self.channels.read().unwrap().get(&workspace_id) {
+            return tx.subscribe();
+        }
+
+        self.channels
+            .write()
+            .unwrap()
+            .entry(workspace_id)
+            .or_insert_with(|| broadcast::channel(self.capacity).0)
+            .subscribe()
+    }
+}
+
+/// Carries events to the subscribers of every backend instance, including
+/// the one that published them.
+#[async_trait]
+pub trait BroadcastBackend: Send + Sync {
+    async fn publish(&self, msg: WebSocketMessage, local: &LocalHub) -> AppResult<()>;
+}
+
+/// Delivers straight to this instance's subscribers. Suitable for a single
+/// node and for tests.
+#[derive(Default)]
+pub struct InMemoryBackend;
+
+#[async_trait]
+impl BroadcastBackend for InMemoryBackend {
+    async fn publish(&self, msg: WebSocketMessage, local: &LocalHub) -> AppResult<()> {
+        local.send(msg);
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Notification {
+    Event(WebSocketMessage),
+    // Too large for NOTIFY; listeners load it from the event store instead
+    Reference { workspace_id: Uuid, seq: i64 },
+}
+
+/// Publishes through Postgres `NOTIFY`. Every instance, this one included,
+/// picks events up through [`spawn_postgres_listener`].
+pub struct PostgresBackend {
+    pool: PgPool,
+}
+
+impl PostgresBackend {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl BroadcastBackend for PostgresBackend {
+    async fn publish(&self, msg: WebSocketMessage, _local: &LocalHub) -> AppResult<()> {
+        let mut payload = serde_json::to_string(&Notification::Event(msg.clone()))
+            .map_err(|e| anyhow::anyhow!("Failed to serialize event: {}", e))?;
+
+        if payload.len() > MAX_NOTIFY_PAYLOAD {
+            let seq = msg.seq.ok_or_else(|| {
+                anyhow::anyhow!("Event {} is too large for NOTIFY and was not persisted", msg.event_type)
+            })?;
+            payload = serde_json::to_string(&Notification::Reference {
+                workspace_id: msg.workspace_id,
+                seq,
+            })
+            .map_err(|e| anyhow::anyhow!("Failed to serialize event reference: {}", e))?;
+        }
+
+        sqlx::query!("SELECT pg_notify($1, $2)", NOTIFY_CHANNEL, payload)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Forwards events published by any instance to this instance's subscribers.
+pub fn spawn_postgres_listener(pool: PgPool, broadcaster: Arc<Broadcaster>) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = listen(&pool, &broadcaster).await {
+                tracing::error!("Event listener failed, reconnecting: {:?}", e);
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+}
+
+async fn listen(pool: &PgPool, broadcaster: &Broadcaster) -> AppResult<()> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(NOTIFY_CHANNEL).await?;
+
+    loop {
+        let notification = listener.recv().await?;
+        let msg = match serde_json::from_str::<Notification>(notification.payload()) {
+            Ok(Notification::Event(msg)) => msg,
+            Ok(Notification::Reference { workspace_id, seq }) => {
+                let Some(store) = broadcaster.store() else {
+                    continue;
+                };
+                match store.find(workspace_id, seq).await? {
+                    Some(event) => event.into(),
+                    None => continue,
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Ignoring malformed event notification: {}", e);
+                continue;
+            }
+        };
+
+        broadcaster.hub().send(msg);
+    }
+}
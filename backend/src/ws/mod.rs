@@ -1,66 +1,327 @@
+pub mod backend;
+pub mod ticket;
+
 use axum::{
-    extract::State,
+    extract::{Query, State},
     extract::ws::{Message, WebSocket, WebSocketUpgrade},
     response::IntoResponse,
+    Extension,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::AppResult;
+use crate::graphql::AppSchema;
+use crate::middleware::auth::decode_claims;
+use crate::models::RealtimeEvent;
+use crate::repository::RealtimeEventRepository;
+use crate::service::auth::Claims;
+use crate::service::WorkspaceService;
+
+pub use backend::{BroadcastBackend, InMemoryBackend, LocalHub, PostgresBackend};
+pub use ticket::TicketStore;
+
+pub const TIMETABLE_CREATED: &str = "TIMETABLE_CREATED";
+pub const TIMETABLE_UPDATED: &str = "TIMETABLE_UPDATED";
+pub const TIMETABLE_DELETED: &str = "TIMETABLE_DELETED";
+pub const SUBSTITUTION_REQUESTED: &str = "SUBSTITUTION_REQUESTED";
+pub const SUBSTITUTION_ACCEPTED: &str = "SUBSTITUTION_ACCEPTED";
+pub const SUBSTITUTION_REJECTED: &str = "SUBSTITUTION_REJECTED";
+pub const SUBSTITUTION_CANCELLED: &str = "SUBSTITUTION_CANCELLED";
+pub const SUBSTITUTION_OFFERED: &str = "SUBSTITUTION_OFFERED";
+pub const SUBSTITUTION_OFFERS_EXPIRED: &str = "SUBSTITUTION_OFFERS_EXPIRED";
+pub const ABSENCE_REPORTED: &str = "ABSENCE_REPORTED";
+pub const ABSENCE_CANCELLED: &str = "ABSENCE_CANCELLED";
+pub const LESSON_EXCEPTION_CREATED: &str = "LESSON_EXCEPTION_CREATED";
+pub const LESSON_EXCEPTION_DELETED: &str = "LESSON_EXCEPTION_DELETED";
+pub const LESSON_SWAP_PROPOSED: &str = "LESSON_SWAP_PROPOSED";
+pub const LESSON_SWAP_UPDATED: &str = "LESSON_SWAP_UPDATED";
+pub const DRAFT_UPDATED: &str = "DRAFT_UPDATED";
+pub const CONFLICT_OPENED: &str = "CONFLICT_OPENED";
+pub const CONFLICT_RESOLVED: &str = "CONFLICT_RESOLVED";
+pub const TIMETABLE_PUBLISHED: &str = "TIMETABLE_PUBLISHED";
+pub const NOTIFICATIONS_UNREAD: &str = "NOTIFICATIONS_UNREAD";
+
+// Control messages sent to a single socket, never broadcast.
+pub const CONNECTED: &str = "CONNECTED";
+pub const RESYNC_REQUIRED: &str = "RESYNC_REQUIRED";
+pub const SUBSCRIBED: &str = "SUBSCRIBED";
+pub const UNSUBSCRIBED: &str = "UNSUBSCRIBED";
+pub const ERROR: &str = "ERROR";
+
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+// Clients further behind than this must refetch instead of replaying
+const MAX_REPLAY: i64 = 1000;
+
+/// A narrower stream within a workspace that clients can opt into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Topic {
+    Draft(Uuid),
+    Teacher(Uuid),
+    Room(Uuid),
+    /// Private to one user; see [`WebSocketMessage::is_visible_to`].
+    User(Uuid),
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Topic::Draft(id) => write!(f, "draft:{}", id),
+            Topic::Teacher(id) => write!(f, "teacher:{}", id),
+            Topic::Room(id) => write!(f, "room:{}", id),
+            Topic::User(id) => write!(f, "user:{}", id),
+        }
+    }
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, id) = s.split_once(':').ok_or_else(|| format!("Invalid topic: {}", s))?;
+        let id = Uuid::parse_str(id).map_err(|_| format!("Invalid topic id: {}", id))?;
+        match kind {
+            "draft" => Ok(Topic::Draft(id)),
+            "teacher" => Ok(Topic::Teacher(id)),
+            "room" => Ok(Topic::Room(id)),
+            "user" => Ok(Topic::User(id)),
+            _ => Err(format!("Unknown topic kind: {}", kind)),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct WebSocketMessage {
+    pub workspace_id: Uuid,
     pub event_type: String,
     pub payload: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub topics: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<i64>,
 }
 
+impl WebSocketMessage {
+    pub fn new(workspace_id: Uuid, event_type: &str, payload: serde_json::Value) -> Self {
+        Self {
+            workspace_id,
+            event_type: event_type.to_string(),
+            payload,
+            topics: Vec::new(),
+            seq: None,
+        }
+    }
+
+    pub fn with_topic(mut self, topic: Topic) -> Self {
+        self.topics.push(topic.to_string());
+        self
+    }
+
+    /// Events tagged with `user:` topics are only meant for those users; every
+    /// other event is visible to the whole workspace.
+    pub fn is_visible_to(&self, user_id: Uuid) -> bool {
+        let mut recipients = self
+            .topics
+            .iter()
+            .filter_map(|topic| match topic.parse() {
+                Ok(Topic::User(id)) => Some(id),
+                _ => None,
+            })
+            .peekable();
+
+        recipients.peek().is_none() || recipients.any(|id| id == user_id)
+    }
+}
+
+impl From<RealtimeEvent> for WebSocketMessage {
+    fn from(event: RealtimeEvent) -> Self {
+        Self {
+            workspace_id: event.workspace_id,
+            event_type: event.event_type,
+            payload: event.payload,
+            topics: event.topics,
+            seq: Some(event.seq),
+        }
+    }
+}
+
+/// Publishes domain events to realtime subscribers. Events only ever reach
+/// subscribers of their own workspace. With a store attached, every event is
+/// persisted first and stamped with its sequence number so clients can
+/// resume after a disconnect. The backend decides how events travel between
+/// instances.
 pub struct Broadcaster {
-    tx: broadcast::Sender<WebSocketMessage>,
+    hub: LocalHub,
+    backend: Box<dyn BroadcastBackend>,
+    store: Option<RealtimeEventRepository>,
 }
 
 impl Broadcaster {
     pub fn new(capacity: usize) -> Self {
-        let (tx, _) = broadcast::channel(capacity);
-        Self { tx }
+        Self {
+            hub: LocalHub::new(capacity),
+            backend: Box::new(InMemoryBackend),
+            store: None,
+        }
     }
 
-    pub fn broadcast(&self, msg: WebSocketMessage) {
-        // We ignore the error if there are no subscribers
-        let _ = self.tx.send(msg);
+    pub fn with_store(self, store: RealtimeEventRepository) -> Self {
+        Self {
+            store: Some(store),
+            ..self
+        }
     }
 
-    pub fn subscribe(&self) -> broadcast::Receiver<WebSocketMessage> {
-        self.tx.subscribe()
+    pub fn with_backend(self, backend: impl BroadcastBackend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+            ..self
+        }
     }
+
+    pub fn store(&self) -> Option<&RealtimeEventRepository> {
+        self.store.as_ref()
+    }
+
+    pub fn hub(&self) -> &LocalHub {
+        &self.hub
+    }
+
+    pub async fn broadcast(&self, msg: WebSocketMessage) {
+        self.publish(msg, None).await
+    }
+
+    /// Broadcasts an event that may be handed over more than once; repeats
+    /// keep the sequence number of the first delivery, so clients drop them.
+    pub async fn broadcast_once(&self, msg: WebSocketMessage, dedupe_key: &str) {
+        self.publish(msg, Some(dedupe_key)).await
+    }
+
+    async fn publish(&self, mut msg: WebSocketMessage, dedupe_key: Option<&str>) {
+        if let Some(store) = &self.store {
+            match store.append(msg.workspace_id, &msg.event_type, &msg.payload, &msg.topics, dedupe_key).await {
+                Ok(event) => msg.seq = Some(event.seq),
+                Err(e) => tracing::error!("Failed to persist realtime event: {:?}", e),
+            }
+        }
+
+        if let Err(e) = self.backend.publish(msg.clone(), &self.hub).await {
+            // Other instances miss the event, but local subscribers still get it
+            tracing::error!("Failed to publish realtime event: {:?}", e);
+            self.hub.send(msg);
+        }
+    }
+
+    pub fn subscribe(&self, workspace_id: Uuid) -> broadcast::Receiver<WebSocketMessage> {
+        self.hub.subscribe(workspace_id)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WsParams {
+    pub ticket: Option<String>,
+    pub since: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Auth { token: String, since: Option<i64> },
+    Subscribe { topic: String },
+    Unsubscribe { topic: String },
 }
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<crate::AppState>,
+    Extension(schema): Extension<AppSchema>,
+    Query(params): Query<WsParams>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state.broadcaster))
+    let claims = match params.ticket {
+        Some(ticket) => state.tickets.redeem(&ticket).await,
+        None => None,
+    };
+    let since = params.since;
+
+    ws.on_upgrade(move |socket| async move {
+        let workspace_service = schema
+            .data::<Arc<WorkspaceService>>()
+            .expect("WorkspaceService not found in schema data")
+            .clone();
+        handle_socket(socket, state.broadcaster, state.config, workspace_service, claims, since).await
+    })
 }
 
-async fn handle_socket(mut socket: WebSocket, broadcaster: Arc<Broadcaster>) {
-    let mut rx = broadcaster.subscribe();
+async fn handle_socket(
+    mut socket: WebSocket,
+    broadcaster: Arc<Broadcaster>,
+    config: Arc<Config>,
+    workspace_service: Arc<WorkspaceService>,
+    claims: Option<Claims>,
+    since: Option<i64>,
+) {
+    // Without a ticket the first message must carry the JWT
+    let (claims, since) = match claims {
+        Some(claims) => (claims, since),
+        None => match authenticate_first_message(&mut socket, &config).await {
+            Some(auth) => auth,
+            None => {
+                let _ = socket.send(Message::Close(None)).await;
+                return;
+            }
+        },
+    };
+
+    let mut session = Session {
+        socket,
+        workspace_id: claims.workspace_id,
+        user_id: claims.sub,
+        topics: HashSet::new(),
+        store: broadcaster.store().cloned(),
+        last_seq: 0,
+    };
+
+    match workspace_service.get_member_role(claims.workspace_id, claims.sub).await {
+        Ok(Some(_)) => {}
+        _ => {
+            let _ = session.send_control(ERROR, json!({ "message": "Not a member of this workspace" })).await;
+            let _ = session.socket.send(Message::Close(None)).await;
+            return;
+        }
+    }
+
+    // Subscribe before replaying so nothing published in between is lost
+    let mut rx = broadcaster.subscribe(claims.workspace_id);
+    if session.start(since).await.is_err() {
+        return;
+    }
 
     loop {
         tokio::select! {
             msg = rx.recv() => {
-                match msg {
-                    Ok(ws_msg) => {
-                        if let Ok(json) = serde_json::to_string(&ws_msg) && socket.send(Message::Text(json.into())).await.is_err() {
-                            break;
-                        }
-                    }
-                    Err(broadcast::error::RecvError::Lagged(_)) => {
-                        // Client is too slow, we could send a message or just ignore
-                        continue;
+                let delivered = match msg {
+                    Ok(ws_msg) => session.deliver_live(ws_msg).await,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::debug!("WebSocket client lagged by {} events, replaying", skipped);
+                        session.catch_up().await
                     }
                     Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if delivered.is_err() {
+                    break;
                 }
             }
-            result = socket.recv() => {
+            result = session.socket.recv() => {
                 match result {
+                    Some(Ok(Message::Text(text))) => session.handle_client_message(&text).await,
                     Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
                     _ => continue,
                 }
@@ -68,3 +329,242 @@ async fn handle_socket(mut socket: WebSocket, broadcaster: Arc<Broadcaster>) {
         }
     }
 }
+
+async fn authenticate_first_message(socket: &mut WebSocket, config: &Config) -> Option<(Claims, Option<i64>)> {
+    let msg = tokio::time::timeout(AUTH_TIMEOUT, socket.recv()).await.ok()??.ok()?;
+    let Message::Text(text) = msg else {
+        return None;
+    };
+
+    match serde_json::from_str::<ClientMessage>(&text) {
+        Ok(ClientMessage::Auth { token, since }) => {
+            let token = token.strip_prefix("Bearer ").unwrap_or(&token);
+            decode_claims(token, &config.jwt_secret).ok().map(|claims| (claims, since))
+        }
+        _ => None,
+    }
+}
+
+// The socket was closed by the peer.
+struct Disconnected;
+
+struct Session {
+    socket: WebSocket,
+    workspace_id: Uuid,
+    user_id: Uuid,
+    topics: HashSet<String>,
+    store: Option<RealtimeEventRepository>,
+    last_seq: i64,
+}
+
+impl Session {
+    /// Replays everything after `since`, or tells the client where the live
+    /// stream starts when it did not ask for a replay.
+    async fn start(&mut self, since: Option<i64>) -> Result<(), Disconnected> {
+        let Some(store) = self.store.clone() else {
+            return self.send_control(CONNECTED, json!({})).await;
+        };
+
+        match since {
+            Some(since) => self.replay(since).await,
+            None => match store.bounds(self.workspace_id).await {
+                Ok((_, latest)) => {
+                    self.last_seq = latest;
+                    self.send_control(CONNECTED, json!({ "seq": latest })).await
+                }
+                Err(e) => {
+                    tracing::error!("Failed to read realtime event bounds: {:?}", e);
+                    self.send_control(CONNECTED, json!({})).await
+                }
+            },
+        }
+    }
+
+    async fn deliver_live(&mut self, msg: WebSocketMessage) -> Result<(), Disconnected> {
+        match (msg.seq, &self.store) {
+            // Already sent during a replay
+            (Some(seq), Some(_)) if seq <= self.last_seq => Ok(()),
+            // Concurrent publishers can arrive out of order; fill the gap from the store
+            (Some(seq), Some(_)) if seq > self.last_seq + 1 => self.catch_up().await,
+            (Some(seq), _) => {
+                self.last_seq = seq;
+                self.send_event(&msg).await
+            }
+            (None, _) => self.send_event(&msg).await,
+        }
+    }
+
+    async fn catch_up(&mut self) -> Result<(), Disconnected> {
+        if self.store.is_none() {
+            return self.send_control(RESYNC_REQUIRED, json!({})).await;
+        }
+        self.replay(self.last_seq).await
+    }
+
+    async fn replay(&mut self, since: i64) -> Result<(), Disconnected> {
+        let Some(store) = self.store.clone() else {
+            return self.send_control(RESYNC_REQUIRED, json!({})).await;
+        };
+
+        let events = match self.load_replay(&store, since).await {
+            Ok(Some(events)) => events,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                tracing::error!("Failed to replay realtime events: {:?}", e);
+                return self.send_control(RESYNC_REQUIRED, json!({ "seq": self.last_seq })).await;
+            }
+        };
+
+        self.last_seq = since;
+        for event in events {
+            self.last_seq = event.seq;
+            self.send_event(&event.into()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns `None` after telling the client to resync because the gap can
+    /// no longer be replayed.
+    async fn load_replay(&mut self, store: &RealtimeEventRepository, since: i64) -> AppResult<Option<Vec<RealtimeEvent>>> {
+        let (oldest, latest) = store.bounds(self.workspace_id).await?;
+
+        if !can_replay(since, oldest, latest) {
+            self.last_seq = latest;
+            let _ = self.send_control(RESYNC_REQUIRED, json!({ "seq": latest })).await;
+            return Ok(None);
+        }
+
+        Ok(Some(store.list_since(self.workspace_id, since, MAX_REPLAY).await?))
+    }
+
+    async fn handle_client_message(&mut self, text: &str) {
+        let (event_type, topic) = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(ClientMessage::Subscribe { topic }) => (SUBSCRIBED, topic),
+            Ok(ClientMessage::Unsubscribe { topic }) => (UNSUBSCRIBED, topic),
+            Ok(ClientMessage::Auth { .. }) => {
+                let _ = self.send_control(ERROR, json!({ "message": "Already authenticated" })).await;
+                return;
+            }
+            Err(e) => {
+                let _ = self.send_control(ERROR, json!({ "message": e.to_string() })).await;
+                return;
+            }
+        };
+
+        // Normalise through Topic so clients cannot subscribe to arbitrary strings
+        let topic = match topic.parse::<Topic>() {
+            Ok(topic) => topic.to_string(),
+            Err(message) => {
+                let _ = self.send_control(ERROR, json!({ "message": message })).await;
+                return;
+            }
+        };
+
+        if event_type == SUBSCRIBED {
+            self.topics.insert(topic.clone());
+        } else {
+            self.topics.remove(&topic);
+        }
+
+        let _ = self.send_control(event_type, json!({ "topic": topic })).await;
+    }
+
+    async fn send_event(&mut self, msg: &WebSocketMessage) -> Result<(), Disconnected> {
+        if !msg.is_visible_to(self.user_id) || !is_wanted(msg, &self.topics) {
+            return Ok(());
+        }
+        self.send(msg).await
+    }
+
+    async fn send_control(&mut self, event_type: &str, payload: serde_json::Value) -> Result<(), Disconnected> {
+        let msg = WebSocketMessage::new(self.workspace_id, event_type, payload);
+        self.send(&msg).await
+    }
+
+    async fn send(&mut self, msg: &WebSocketMessage) -> Result<(), Disconnected> {
+        let Ok(json) = serde_json::to_string(msg) else {
+            return Ok(());
+        };
+        self.socket.send(Message::Text(json.into())).await.map_err(|_| Disconnected)
+    }
+}
+
+/// Whether the events after `since` can still be replayed: they must not be
+/// from the future, pruned already or more than a replay can hold.
+fn can_replay(since: i64, oldest: Option<i64>, latest: i64) -> bool {
+    let pruned = since < latest && oldest.is_none_or(|oldest| oldest > since + 1);
+    since <= latest && !pruned && latest - since <= MAX_REPLAY
+}
+
+/// Clients without topic subscriptions receive every workspace event. Once
+/// subscribed, they only receive events tagged with one of their topics plus
+/// workspace-wide events that carry no topics at all.
+fn is_wanted(msg: &WebSocketMessage, topics: &HashSet<String>) -> bool {
+    topics.is_empty() || msg.topics.is_empty() || msg.topics.iter().any(|t| topics.contains(t))
+}
+
+/// Periodically drops persisted events older than the retention window.
+pub fn spawn_retention_task(store: RealtimeEventRepository, retention_days: i64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(retention_days);
+            match store.delete_older_than(cutoff).await {
+                Ok(0) => {}
+                Ok(deleted) => tracing::info!("Pruned {} realtime events", deleted),
+                Err(e) => tracing::error!("Failed to prune realtime events: {:?}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn replays_only_gaps_the_store_still_holds() {
+        // Up to date, or behind by what is retained
+        assert!(can_replay(10, Some(1), 10));
+        assert!(can_replay(4, Some(5), 10));
+        assert!(can_replay(0, None, 0));
+
+        // A sequence number the workspace never reached
+        assert!(!can_replay(11, Some(1), 10));
+        // Events after `since` were pruned by retention
+        assert!(!can_replay(3, Some(5), 10));
+        assert!(!can_replay(3, None, 10));
+        // Too far behind to replay
+        assert!(!can_replay(0, Some(1), MAX_REPLAY + 1));
+    }
+
+    #[sqlx::test]
+    async fn stored_events_replay_in_order_and_only_once(pool: PgPool) {
+        let workspace_id = test_support::workspace(&pool).await;
+        let store = RealtimeEventRepository::new(pool.clone());
+        let broadcaster = Broadcaster::new(16).with_store(store.clone());
+        let mut rx = broadcaster.subscribe(workspace_id);
+
+        for event_type in [DRAFT_UPDATED, CONFLICT_OPENED] {
+            broadcaster.broadcast(WebSocketMessage::new(workspace_id, event_type, json!({}))).await;
+        }
+        // A redelivery keeps the sequence number of the first
+        for _ in 0..2 {
+            let msg = WebSocketMessage::new(workspace_id, TIMETABLE_PUBLISHED, json!({}));
+            broadcaster.broadcast_once(msg, "outbox:1").await;
+        }
+
+        let live: Vec<_> = (0..4).map(|_| rx.try_recv().unwrap().seq).collect();
+        assert_eq!(live, [Some(1), Some(2), Some(3), Some(3)]);
+
+        let replayed = store.list_since(workspace_id, 1, MAX_REPLAY).await.unwrap();
+        let replayed: Vec<_> = replayed.iter().map(|e| (e.seq, e.event_type.as_str())).collect();
+        assert_eq!(replayed, [(2, CONFLICT_OPENED), (3, TIMETABLE_PUBLISHED)]);
+        assert_eq!(store.bounds(workspace_id).await.unwrap(), (Some(1), 3));
+    }
+}
@@ -0,0 +1,97 @@
+use axum::{extract::State, Extension, Json};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{Duration as ChronoDuration, Utc};
+use rand::{rng, RngCore};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::error::{AppError, AppResult};
+use crate::repository::TicketRepository;
+use crate::service::auth::Claims;
+use crate::AppState;
+
+const TICKET_TTL: Duration = Duration::from_secs(30);
+
+/// Short-lived, single-use tickets that let browsers open `/ws` without
+/// being able to set an `Authorization` header on the upgrade request.
+/// Tickets live in Postgres, so the replica that redeems a ticket need not
+/// be the one that issued it.
+pub struct TicketStore {
+    repo: TicketRepository,
+}
+
+impl TicketStore {
+    pub fn new(repo: TicketRepository) -> Self {
+        Self { repo }
+    }
+
+    pub async fn issue(&self, claims: &Claims) -> AppResult<String> {
+        let mut bytes = [0u8; 32];
+        rng().fill_bytes(&mut bytes);
+        let ticket = URL_SAFE_NO_PAD.encode(bytes);
+
+        let claims = serde_json::to_value(claims).map_err(|e| anyhow::anyhow!("Failed to serialize claims: {}", e))?;
+        let expires_at = Utc::now() + ChronoDuration::from_std(TICKET_TTL).unwrap_or_default();
+        self.repo.create(&ticket, &claims, expires_at).await?;
+
+        Ok(ticket)
+    }
+
+    pub async fn redeem(&self, ticket: &str) -> Option<Claims> {
+        match self.repo.take(ticket).await {
+            Ok(claims) => claims.and_then(|claims| serde_json::from_value(claims).ok()),
+            Err(e) => {
+                tracing::error!("Failed to redeem WebSocket ticket: {:?}", e);
+                None
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TicketResponse {
+    pub ticket: String,
+    pub expires_in: u64,
+}
+
+pub async fn ticket_handler(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<TicketResponse>, AppError> {
+    Ok(Json(TicketResponse {
+        ticket: state.tickets.issue(&claims).await?,
+        expires_in: TICKET_TTL.as_secs(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::test_support;
+
+    #[sqlx::test]
+    async fn tickets_are_redeemed_once(pool: PgPool) {
+        let store = TicketStore::new(TicketRepository::new(pool.clone()));
+        let claims = test_support::claims(Uuid::new_v4(), Uuid::new_v4());
+
+        let ticket = store.issue(&claims).await.unwrap();
+        let redeemed = store.redeem(&ticket).await.unwrap();
+        assert_eq!((redeemed.sub, redeemed.workspace_id), (claims.sub, claims.workspace_id));
+
+        assert!(store.redeem(&ticket).await.is_none());
+        assert!(store.redeem("not-a-ticket").await.is_none());
+    }
+
+    #[sqlx::test]
+    async fn expired_tickets_are_refused(pool: PgPool) {
+        let repo = TicketRepository::new(pool.clone());
+        let claims = serde_json::to_value(test_support::claims(Uuid::new_v4(), Uuid::new_v4())).unwrap();
+        repo.create("stale", &claims, Utc::now() - ChronoDuration::seconds(1)).await.unwrap();
+
+        let store = TicketStore::new(repo);
+        assert!(store.redeem("stale").await.is_none());
+    }
+}